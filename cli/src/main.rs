@@ -5,7 +5,8 @@
 //!
 //! Commands: create-mint  mint-to  create-market  expand  claim-seat
 //!           deposit  withdraw  place-order  cancel-order  delegate
-//!           crank-funding  liquidate  fetch-price  market-info  setup
+//!           crank-funding  create-funding-history  funding-history
+//!           liquidate  settle-pnl  fetch-price  market-info  setup
 //!           create-escrow  delegate-escrow  fund-escrow
 use anyhow::{anyhow, Result};
 use clap::{Parser, Subcommand};
@@ -15,16 +16,26 @@ use manifest::{
         batch_update::{CancelOrderParams, PlaceOrderParams},
         batch_update_instruction,
         claim_seat_instruction::claim_seat_instruction,
-        crank_funding_instruction, create_market_instructions,
-        deposit_instruction, deposit_instruction_with_vault, expand_market_n_instruction,
-        liquidate_instruction, release_seat_instruction,
-        withdraw_instruction, withdraw_instruction_with_vault,
+        crank_funding_with_history_instruction, crank_funding_with_oracles_instruction,
+        create_funding_history_instruction, create_market_instructions, deposit_instruction,
+        deposit_instruction_with_vault, expand_market_n_instruction,
+        liquidate::LiquidationMode,
+        liquidate_batch_instruction, release_seat_instruction, settle_pnl_instruction,
         swap_instruction::swap_instruction_with_vaults,
+        update_market::UpdateMarketParams,
+        update_market_instruction, withdraw_instruction, withdraw_instruction_with_vault,
         ManifestInstruction,
     },
     quantities::{BaseAtoms, WrapperU64},
-    state::{market::MarketFixed, OrderType, RestingOrder, MARKET_FIXED_SIZE},
-    validation::{get_market_address, get_vault_address},
+    state::{
+        market::MarketFixed, FundingHistory, OracleSource, OracleType, OrderType, RestingOrder,
+        RiskTier, SeatExtension, FUNDING_HISTORY_SIZE, FUNDING_REFERENCE_BASE_ATOMS, FUNDING_SCALE,
+        MARKET_FIXED_SIZE, PREMIUM_RATE_SCALE, SEAT_EXTENSION_SIZE,
+    },
+    validation::{
+        get_funding_history_address, get_market_address, get_seat_extension_address,
+        get_vault_address,
+    },
 };
 use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
 use solana_program::{pubkey::Pubkey, system_program};
@@ -189,7 +200,22 @@ enum Commands {
         /// Liquidation buffer above maintenance margin in bps
         #[arg(long, default_value = "200")]
         liquidation_buffer_bps: u64,
-        /// Number of blocks to pre-allocate (each block = 80 bytes for a seat or order)
+        /// Liquidator reward (and takeover discount) in bps of closed notional
+        #[arg(long, default_value = "250")]
+        liquidator_reward_bps: u64,
+        /// Smallest position in base atoms left after a partial liquidation
+        #[arg(long, default_value = "1000")]
+        min_position_size_atoms: u64,
+        /// Length of a funding period in seconds
+        #[arg(long, default_value = "3600")]
+        funding_period_secs: u64,
+        /// Largest funding rate per period, scaled by 1e9 (10000000 = 1%)
+        #[arg(long, default_value = "10000000")]
+        max_funding_rate: u64,
+        /// Fixed interest rate per period added to the premium, scaled by 1e9
+        #[arg(long, default_value = "0", allow_hyphen_values = true)]
+        funding_interest_rate: i64,
+        /// Number of blocks to pre-allocate (each block = 104 bytes for a seat or order)
         #[arg(long, default_value = "0")]
         num_blocks: u32,
     },
@@ -344,14 +370,104 @@ enum Commands {
         pyth_feed: Option<String>,
     },
 
-    /// Liquidate an underwater trader
+    /// Create a market's funding history account; funding cranks record
+    /// into it from then on
+    CreateFundingHistory {
+        /// Market PDA address
+        #[arg(long)]
+        market: String,
+    },
+
+    /// Show the funding cranks recorded in a market's funding history
+    FundingHistory {
+        /// Market PDA address
+        #[arg(long)]
+        market: String,
+    },
+
+    /// Update market parameters (market authority only)
+    UpdateMarket {
+        /// Market PDA address
+        #[arg(long)]
+        market: String,
+        /// New liquidator reward in bps
+        #[arg(long)]
+        liquidator_reward_bps: Option<u64>,
+        /// New minimum position size in base atoms after partial liquidation
+        #[arg(long)]
+        min_position_size_atoms: Option<u64>,
+        /// Risk tier as NOTIONAL_ATOMS:INITIAL_BPS:MAINTENANCE_BPS; repeat in
+        /// increasing notional order. Replaces the whole tier table
+        #[arg(long)]
+        risk_tier: Vec<String>,
+        /// Remove all risk tiers
+        #[arg(long, conflicts_with = "risk_tier")]
+        clear_risk_tiers: bool,
+        /// Largest oracle confidence interval accepted by the funding crank,
+        /// in bps of the oracle price
+        #[arg(long)]
+        max_oracle_conf_bps: Option<u64>,
+        /// Oldest oracle price, in seconds since publication, the market accepts
+        #[arg(long)]
+        max_oracle_staleness_secs: Option<u64>,
+        /// Pyth feed id (64 hex chars) that PriceUpdateV3 oracle accounts
+        /// must carry
+        #[arg(long)]
+        pyth_feed_id: Option<String>,
+        /// Oracle account format: pyth | switchboard
+        #[arg(long)]
+        oracle_type: Option<String>,
+        /// Oracle account the funding crank reads, when moving the market to
+        /// another oracle
+        #[arg(long)]
+        oracle_feed: Option<String>,
+        /// Extra oracle source as TYPE:PUBKEY (TYPE is pyth | switchboard);
+        /// repeat for several. Replaces all extra sources
+        #[arg(long)]
+        extra_oracle: Vec<String>,
+        /// Remove all extra oracle sources
+        #[arg(long, conflicts_with = "extra_oracle")]
+        clear_extra_oracles: bool,
+        /// Time constant, in seconds, of the book premium average in the
+        /// mark price
+        #[arg(long)]
+        mark_ema_window_secs: Option<u64>,
+        /// Length of a funding period in seconds
+        #[arg(long)]
+        funding_period_secs: Option<u64>,
+        /// Largest funding rate per period, scaled by 1e9 (10000000 = 1%)
+        #[arg(long)]
+        max_funding_rate: Option<u64>,
+        /// Fixed interest rate per period added to the premium, scaled by 1e9
+        #[arg(long, allow_hyphen_values = true)]
+        funding_interest_rate: Option<i64>,
+    },
+
+    /// Liquidate underwater traders
     Liquidate {
         /// Market PDA address
         #[arg(long)]
         market: String,
-        /// Address of the trader to liquidate
+        /// Address of a trader to liquidate; repeat for several. Leave out to
+        /// liquidate every underwater seat the compute budget allows
         #[arg(long)]
-        trader: String,
+        trader: Vec<String>,
+        /// Close the position against the orderbook instead of at mark price
+        #[arg(long)]
+        orderbook: bool,
+        /// Take the position onto the liquidator's seat at a discount to mark
+        #[arg(long, conflicts_with = "orderbook")]
+        takeover: bool,
+    },
+
+    /// Move a trader's realized PnL into their margin
+    SettlePnl {
+        /// Market PDA address
+        #[arg(long)]
+        market: String,
+        /// Trader to settle (defaults to the payer)
+        #[arg(long)]
+        trader: Option<String>,
     },
 
     /// Fetch and display the live Pyth oracle price
@@ -583,6 +699,53 @@ fn parse_pubkey(s: &str) -> Result<Pubkey> {
     Pubkey::from_str(s).map_err(|e| anyhow!("Invalid pubkey '{s}': {e}"))
 }
 
+fn parse_risk_tier(s: &str) -> Result<RiskTier> {
+    let parts = s
+        .split(':')
+        .map(|p| p.trim().parse::<u64>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("Invalid risk tier '{s}': {e}"))?;
+    match parts.as_slice() {
+        [notional, initial, maintenance] => Ok(RiskTier::new(*notional, *initial, *maintenance)),
+        _ => Err(anyhow!(
+            "Invalid risk tier '{s}'. Use NOTIONAL_ATOMS:INITIAL_BPS:MAINTENANCE_BPS"
+        )),
+    }
+}
+
+fn parse_feed_id(s: &str) -> Result<[u8; 32]> {
+    let hex = s.trim().trim_start_matches("0x");
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(anyhow!("Invalid feed id '{s}'. Use 64 hex characters"));
+    }
+    let mut feed_id = [0u8; 32];
+    for (i, byte) in feed_id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+            .map_err(|e| anyhow!("Invalid feed id '{s}': {e}"))?;
+    }
+    Ok(feed_id)
+}
+
+fn parse_oracle_type(s: &str) -> Result<OracleType> {
+    match s.to_lowercase().as_str() {
+        "pyth" => Ok(OracleType::Pyth),
+        "switchboard" | "switchboard-on-demand" => Ok(OracleType::SwitchboardOnDemand),
+        other => Err(anyhow!(
+            "Unknown oracle type '{other}'. Use: pyth | switchboard"
+        )),
+    }
+}
+
+fn parse_oracle_source(s: &str) -> Result<OracleSource> {
+    match s.split_once(':') {
+        Some((oracle_type, feed)) => Ok(OracleSource::new(
+            parse_pubkey(feed.trim())?,
+            parse_oracle_type(oracle_type.trim())?,
+        )),
+        None => Err(anyhow!("Invalid oracle source '{s}'. Use TYPE:PUBKEY")),
+    }
+}
+
 fn parse_order_type(s: &str) -> Result<OrderType> {
     match s.to_lowercase().as_str() {
         "limit" => Ok(OrderType::Limit),
@@ -723,10 +886,7 @@ fn ephemeral_spl_token_id() -> Pubkey {
 }
 
 fn get_ephemeral_ata(owner: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[owner.as_ref(), mint.as_ref()],
-        &ephemeral_spl_token_id(),
-    )
+    Pubkey::find_program_address(&[owner.as_ref(), mint.as_ref()], &ephemeral_spl_token_id())
 }
 
 fn delegate_market_ix(payer: &Pubkey, market: &Pubkey, quote_mint: &Pubkey) -> Instruction {
@@ -836,7 +996,6 @@ fn get_global_vault(mint: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[mint.as_ref()], &ephemeral_spl_token_id())
 }
 
-
 /// The SPL token account (ATA) that the global vault PDA owns — holds real USDC.
 fn get_vault_token_account(mint: &Pubkey) -> Pubkey {
     let (global_vault, _) = get_global_vault(mint);
@@ -976,6 +1135,11 @@ fn cmd_create_market(
     pyth_feed: Pubkey,
     taker_fee_bps: u64,
     liquidation_buffer_bps: u64,
+    liquidator_reward_bps: u64,
+    min_position_size_atoms: u64,
+    funding_period_secs: u64,
+    max_funding_rate: u64,
+    funding_interest_rate: i64,
     num_blocks: u32,
 ) -> Result<()> {
     let (market, _) = get_market_address(base_mint_index, quote_mint);
@@ -996,6 +1160,11 @@ fn cmd_create_market(
         pyth_feed,
         taker_fee_bps,
         liquidation_buffer_bps,
+        liquidator_reward_bps,
+        min_position_size_atoms,
+        funding_period_secs,
+        max_funding_rate,
+        funding_interest_rate,
         num_blocks,
     );
     let sig = send(client, &ixs, &[payer])?;
@@ -1020,17 +1189,16 @@ fn cmd_expand(
     escrow_slot: u64,
 ) -> Result<()> {
     let er_spl_program = Pubkey::from_str(EPHEMERAL_ROLLUPS_SPL_ID)?;
-    let (escrow_pda, _) =
-        manifest::program::expand_market_instruction::get_escrow_address(
-            &payer.pubkey(),
-            validator,
-            escrow_slot,
-        );
+    let (escrow_pda, _) = manifest::program::expand_market_instruction::get_escrow_address(
+        &payer.pubkey(),
+        validator,
+        escrow_slot,
+    );
     println!("Expanding market {market} by {blocks} block(s)…");
     println!("Escrow PDA  : {escrow_pda}");
-    // Solana realloc limit is 10 KB per instruction → max ~125 blocks (80 bytes each).
-    // Use chunks of 100 blocks per tx to stay safe.
-    const CHUNK: u32 = 100;
+    // Solana realloc limit is 10 KB per instruction → max ~98 blocks (104 bytes each).
+    // Use chunks of 90 blocks per tx to stay safe.
+    const CHUNK: u32 = 90;
     let mut remaining = blocks;
     let mut allocated = 0u32;
     let mut batch = 1u32;
@@ -1091,8 +1259,8 @@ fn cmd_create_escrow(
         er_spl,
         &create_data,
         vec![
-            AccountMeta::new(payer.pubkey(), true),  // authority (signer, writable)
-            AccountMeta::new(escrow_pda, false),     // escrow PDA
+            AccountMeta::new(payer.pubkey(), true), // authority (signer, writable)
+            AccountMeta::new(escrow_pda, false),    // escrow PDA
             AccountMeta::new_readonly(system_program::id(), false),
         ],
     );
@@ -1133,32 +1301,29 @@ fn cmd_delegate_escrow(
     // buffer PDA: seeds=[b"buffer", escrow_pda] derived from owner_program (er_spl)
     // record PDA: seeds=[b"delegation", escrow_pda] derived from delegation_program
     // metadata PDA: seeds=[b"delegation-metadata", escrow_pda] derived from delegation_program
-    let delegation_buffer_pda = Pubkey::find_program_address(
-        &[b"buffer", escrow_pda.as_ref()],
-        &er_spl,
-    ).0;
-    let delegation_record_pda = Pubkey::find_program_address(
-        &[b"delegation", escrow_pda.as_ref()],
-        &delegation_program,
-    ).0;
+    let delegation_buffer_pda =
+        Pubkey::find_program_address(&[b"buffer", escrow_pda.as_ref()], &er_spl).0;
+    let delegation_record_pda =
+        Pubkey::find_program_address(&[b"delegation", escrow_pda.as_ref()], &delegation_program).0;
     let delegation_metadata_pda = Pubkey::find_program_address(
         &[b"delegation-metadata", escrow_pda.as_ref()],
         &delegation_program,
-    ).0;
+    )
+    .0;
 
     let delegate_ix = Instruction::new_with_bytes(
         er_spl,
         &delegate_data,
         vec![
-            AccountMeta::new(payer.pubkey(), true),                   // payer (writable, signer)
-            AccountMeta::new_readonly(payer.pubkey(), true),          // authority (signer)
-            AccountMeta::new(escrow_pda, false),                      // escrow PDA
-            AccountMeta::new(delegation_buffer_pda, false),           // buffer PDA
-            AccountMeta::new(delegation_record_pda, false),           // delegation record
-            AccountMeta::new(delegation_metadata_pda, false),         // delegation metadata
-            AccountMeta::new_readonly(delegation_program, false),     // delegation program
-            AccountMeta::new_readonly(er_spl, false),                 // owner program
-            AccountMeta::new_readonly(system_program::id(), false),   // system program
+            AccountMeta::new(payer.pubkey(), true), // payer (writable, signer)
+            AccountMeta::new_readonly(payer.pubkey(), true), // authority (signer)
+            AccountMeta::new(escrow_pda, false),    // escrow PDA
+            AccountMeta::new(delegation_buffer_pda, false), // buffer PDA
+            AccountMeta::new(delegation_record_pda, false), // delegation record
+            AccountMeta::new(delegation_metadata_pda, false), // delegation metadata
+            AccountMeta::new_readonly(delegation_program, false), // delegation program
+            AccountMeta::new_readonly(er_spl, false), // owner program
+            AccountMeta::new_readonly(system_program::id(), false), // system program
         ],
     );
 
@@ -1343,7 +1508,11 @@ fn cmd_swap(
     min_out_atoms: u64,
     is_base_in: bool,
 ) -> Result<()> {
-    let direction = if is_base_in { "SHORT (sell base)" } else { "LONG (buy base)" };
+    let direction = if is_base_in {
+        "SHORT (sell base)"
+    } else {
+        "LONG (buy base)"
+    };
     println!("Swap {direction} on market {market}");
     println!("  in_atoms     : {in_atoms}");
     println!("  min_out_atoms: {min_out_atoms}");
@@ -1356,17 +1525,17 @@ fn cmd_swap(
     let ix = swap_instruction_with_vaults(
         market,
         &payer.pubkey(),
-        &Pubkey::default(),  // base_mint (virtual, unused)
+        &Pubkey::default(), // base_mint (virtual, unused)
         quote_mint,
-        &Pubkey::default(),  // trader_base (virtual, unused)
+        &Pubkey::default(), // trader_base (virtual, unused)
         &trader_ata,
-        &Pubkey::default(),  // vault_base (virtual, unused)
+        &Pubkey::default(), // vault_base (virtual, unused)
         &vault_ata,
         in_atoms,
         min_out_atoms,
         is_base_in,
-        true, // is_exact_in
-        Pubkey::default(),   // token_program_base (unused)
+        true,              // is_exact_in
+        Pubkey::default(), // token_program_base (unused)
         spl_token::id(),
         false,
     );
@@ -1398,7 +1567,12 @@ fn cmd_cancel_order(
     Ok(())
 }
 
-fn cmd_delegate(client: &RpcClient, payer: &Keypair, market: &Pubkey, quote_mint: &Pubkey) -> Result<()> {
+fn cmd_delegate(
+    client: &RpcClient,
+    payer: &Keypair,
+    market: &Pubkey,
+    quote_mint: &Pubkey,
+) -> Result<()> {
     println!("Delegating market {market} to MagicBlock ER…");
     let ix = delegate_market_ix(&payer.pubkey(), market, quote_mint);
     let sig = send(client, &[ix], &[payer])?;
@@ -1533,25 +1707,140 @@ fn cmd_crank_funding(
     pyth_feed: &Pubkey,
 ) -> Result<()> {
     println!("Cranking funding for market {market}…");
-    let ix = crank_funding_instruction(market, &payer.pubkey(), pyth_feed);
+    let account = client.get_account(market)?;
+    if account.data.len() < MARKET_FIXED_SIZE {
+        return Err(anyhow!("Account data too small for MarketFixed"));
+    }
+    let fixed: &MarketFixed = bytemuck::from_bytes(&account.data[..MARKET_FIXED_SIZE]);
+    // The market's extra oracle accounts follow the primary one.
+    let mut oracle_feeds: Vec<Pubkey> = vec![*pyth_feed];
+    oracle_feeds.extend(
+        fixed
+            .get_extra_oracle_sources()
+            .iter()
+            .filter(|source| source.is_active())
+            .map(|source| source.feed),
+    );
+    // Record the crank when the market has a funding history account.
+    let (funding_history, _) = get_funding_history_address(market);
+    let ix = if client.get_account(&funding_history).is_ok() {
+        crank_funding_with_history_instruction(market, &payer.pubkey(), &oracle_feeds)
+    } else {
+        crank_funding_with_oracles_instruction(market, &payer.pubkey(), &oracle_feeds)
+    };
     let sig = send(client, &[ix], &[payer])?;
     println!("Signature: {sig}");
     Ok(())
 }
 
+fn cmd_create_funding_history(client: &RpcClient, payer: &Keypair, market: &Pubkey) -> Result<()> {
+    let (funding_history, _) = get_funding_history_address(market);
+    println!("Creating funding history {funding_history} for market {market}…");
+    let ix = create_funding_history_instruction(market, &payer.pubkey());
+    let sig = send(client, &[ix], &[payer])?;
+    println!("Signature: {sig}");
+    Ok(())
+}
+
+fn cmd_funding_history(client: &RpcClient, market: &Pubkey) -> Result<()> {
+    let market_account = client.get_account(market)?;
+    if market_account.data.len() < MARKET_FIXED_SIZE {
+        return Err(anyhow!("Account data too small for MarketFixed"));
+    }
+    let fixed: &MarketFixed = bytemuck::from_bytes(&market_account.data[..MARKET_FIXED_SIZE]);
+    let (funding_history, _) = get_funding_history_address(market);
+    let account = client.get_account(&funding_history)?;
+    if account.data.len() < FUNDING_HISTORY_SIZE {
+        return Err(anyhow!("Account data too small for FundingHistory"));
+    }
+    let history: FundingHistory =
+        bytemuck::pod_read_unaligned(&account.data[..FUNDING_HISTORY_SIZE]);
+
+    // Prices are stored in quote atoms per FUNDING_REFERENCE_BASE_ATOMS.
+    let price_scale = 10f64.powi(fixed.get_base_mint_decimals() as i32)
+        / (10f64.powi(fixed.get_quote_mint_decimals() as i32)
+            * FUNDING_REFERENCE_BASE_ATOMS as f64);
+    let entries = history.get_entries();
+    println!("Funding history for {market} ({} cranks)", entries.len());
+    println!(
+        "  {:>12}  {:>8}  {:>10}  {:>11}  {:>12}  {:>12}",
+        "Timestamp", "Secs", "Rate", "Annualized", "Oracle", "Mark"
+    );
+    for entry in entries {
+        let rate = entry.funding_rate as f64 / FUNDING_SCALE as f64;
+        let annualized = if entry.elapsed_secs == 0 {
+            0.0
+        } else {
+            rate * 365.0 * 24.0 * 3600.0 / entry.elapsed_secs as f64
+        };
+        println!(
+            "  {:>12}  {:>8}  {:>+9.4}%  {:>+10.2}%  ${:>11.4}  ${:>11.4}",
+            entry.timestamp,
+            entry.elapsed_secs,
+            rate * 100.0,
+            annualized * 100.0,
+            entry.oracle_price as f64 * price_scale,
+            entry.mark_price as f64 * price_scale,
+        );
+    }
+    Ok(())
+}
+
+fn cmd_update_market(
+    client: &RpcClient,
+    authority: &Keypair,
+    market: &Pubkey,
+    params: UpdateMarketParams,
+) -> Result<()> {
+    println!("Updating market {market}…");
+    let ix = update_market_instruction(market, &authority.pubkey(), params);
+    let sig = send(client, &[ix], &[authority])?;
+    println!("Signature: {sig}");
+    Ok(())
+}
+
 fn cmd_liquidate(
     client: &RpcClient,
     liquidator: &Keypair,
     market: &Pubkey,
-    trader: &Pubkey,
+    traders: Vec<Pubkey>,
+    orderbook: bool,
+    takeover: bool,
 ) -> Result<()> {
-    println!("Liquidating {trader} on market {market}…");
-    let ix = liquidate_instruction(market, &liquidator.pubkey(), trader);
+    if traders.is_empty() {
+        println!("Liquidating all underwater traders on market {market}…");
+    } else {
+        println!(
+            "Liquidating {} trader(s) on market {market}…",
+            traders.len()
+        );
+    }
+    let mode = if orderbook {
+        LiquidationMode::Orderbook
+    } else if takeover {
+        LiquidationMode::Takeover
+    } else {
+        LiquidationMode::MarkPrice
+    };
+    let ix = liquidate_batch_instruction(market, &liquidator.pubkey(), traders, mode);
     let sig = send(client, &[ix], &[liquidator])?;
     println!("Signature: {sig}");
     Ok(())
 }
 
+fn cmd_settle_pnl(
+    client: &RpcClient,
+    payer: &Keypair,
+    market: &Pubkey,
+    trader: &Pubkey,
+) -> Result<()> {
+    println!("Settling PnL for {trader} on market {market}…");
+    let ix = settle_pnl_instruction(market, &payer.pubkey(), trader);
+    let sig = send(client, &[ix], &[payer])?;
+    println!("Signature: {sig}");
+    Ok(())
+}
+
 fn cmd_fetch_price(
     client: &RpcClient,
     feed: &Pubkey,
@@ -1602,7 +1891,10 @@ fn cmd_orderbook(client: &RpcClient, market_key: &Pubkey) -> Result<()> {
     // Helper: price per 1 base unit in USD
     let price_usd = |order: &RestingOrder| -> f64 {
         let one_base_unit = BaseAtoms::new(10u64.pow(base_decimals));
-        match order.get_price().checked_quote_for_base(one_base_unit, false) {
+        match order
+            .get_price()
+            .checked_quote_for_base(one_base_unit, false)
+        {
             Ok(quote) => quote.as_u64() as f64 / 10f64.powi(quote_decimals as i32),
             Err(_) => 0.0,
         }
@@ -1644,39 +1936,62 @@ fn cmd_orderbook(client: &RpcClient, market_key: &Pubkey) -> Result<()> {
         seat.trader.to_string()
     };
 
-    println!("── ASKS ({} orders) ────────────────────────────────", asks.len());
+    println!(
+        "── ASKS ({} orders) ────────────────────────────────",
+        asks.len()
+    );
     if asks.is_empty() {
         println!("  (none)");
     } else {
-        println!("  {:>4}  {:>12}  {:>14}  {:>12}  {:>10}  {}", "#", "Price (USD)", "Size (atoms)", "Size (base)", "Seq#", "Owner");
+        println!(
+            "  {:>4}  {:>12}  {:>14}  {:>12}  {:>10}  {}",
+            "#", "Price (USD)", "Size (atoms)", "Size (base)", "Seq#", "Owner"
+        );
         for (i, (base_atoms, pusd, seq, ti)) in asks.iter().enumerate() {
             let size_base = *base_atoms as f64 / base_factor;
             let owner = resolve_trader(*ti);
             println!(
                 "  {:>4}  ${:>11.4}  {:>14}  {:>12.6}  {:>10}  {}",
-                i + 1, pusd, base_atoms, size_base, seq, &owner[..8],
+                i + 1,
+                pusd,
+                base_atoms,
+                size_base,
+                seq,
+                &owner[..8],
             );
         }
     }
 
     println!();
-    println!("── BIDS ({} orders) ────────────────────────────────", bids.len());
+    println!(
+        "── BIDS ({} orders) ────────────────────────────────",
+        bids.len()
+    );
     if bids.is_empty() {
         println!("  (none)");
     } else {
-        println!("  {:>4}  {:>12}  {:>14}  {:>12}  {:>10}  {}", "#", "Price (USD)", "Size (atoms)", "Size (base)", "Seq#", "Owner");
+        println!(
+            "  {:>4}  {:>12}  {:>14}  {:>12}  {:>10}  {}",
+            "#", "Price (USD)", "Size (atoms)", "Size (base)", "Seq#", "Owner"
+        );
         for (i, (base_atoms, pusd, seq, ti)) in bids.iter().enumerate() {
             let size_base = *base_atoms as f64 / base_factor;
             let owner = resolve_trader(*ti);
             println!(
                 "  {:>4}  ${:>11.4}  {:>14}  {:>12.6}  {:>10}  {}",
-                i + 1, pusd, base_atoms, size_base, seq, &owner[..8],
+                i + 1,
+                pusd,
+                base_atoms,
+                size_base,
+                seq,
+                &owner[..8],
             );
         }
     }
 
     // Summarize owners
-    let mut owner_counts: std::collections::HashMap<String, (usize, usize)> = std::collections::HashMap::new();
+    let mut owner_counts: std::collections::HashMap<String, (usize, usize)> =
+        std::collections::HashMap::new();
     for (_, _, _, ti) in &bids {
         let owner = resolve_trader(*ti);
         let e = owner_counts.entry(owner).or_insert((0, 0));
@@ -1698,23 +2013,36 @@ fn cmd_orderbook(client: &RpcClient, market_key: &Pubkey) -> Result<()> {
 
     // List all claimed seats with positions
     {
-        use manifest::deps::hypertree::{RBNode, get_helper};
+        use manifest::deps::hypertree::{get_helper, RBNode};
         use manifest::state::claimed_seat::ClaimedSeat;
 
         println!();
         println!("── All Seats ──────────────────────────────────────");
-        println!("  {:>44}  {:>10}  {:>14}  {:>12}", "Trader", "Direction", "Position", "Margin");
+        println!(
+            "  {:>44}  {:>10}  {:>14}  {:>12}",
+            "Trader", "Direction", "Position", "Margin"
+        );
 
         let root = fixed.get_claimed_seats_root_index();
         if root != manifest::deps::hypertree::NIL {
             // Iterate the claimed seats tree
             let seats_tree: manifest::state::market::ClaimedSeatTreeReadOnly =
-                manifest::state::market::ClaimedSeatTreeReadOnly::new(dynamic, root, manifest::deps::hypertree::NIL);
+                manifest::state::market::ClaimedSeatTreeReadOnly::new(
+                    dynamic,
+                    root,
+                    manifest::deps::hypertree::NIL,
+                );
             let mut net_position: i64 = 0;
             for (_, seat) in seats_tree.iter::<ClaimedSeat>() {
                 let pos = seat.get_position_size();
                 let margin = seat.quote_withdrawable_balance.as_u64();
-                let dir = if pos > 0 { "LONG" } else if pos < 0 { "SHORT" } else { "FLAT" };
+                let dir = if pos > 0 {
+                    "LONG"
+                } else if pos < 0 {
+                    "SHORT"
+                } else {
+                    "FLAT"
+                };
                 let pos_base = pos.unsigned_abs() as f64 / base_factor;
                 let margin_usd = margin as f64 / 10f64.powi(quote_decimals as i32);
                 println!(
@@ -1724,9 +2052,18 @@ fn cmd_orderbook(client: &RpcClient, market_key: &Pubkey) -> Result<()> {
                 net_position += pos;
             }
             let net_base = net_position.unsigned_abs() as f64 / base_factor;
-            let net_dir = if net_position > 0 { "LONG" } else if net_position < 0 { "SHORT" } else { "ZERO" };
+            let net_dir = if net_position > 0 {
+                "LONG"
+            } else if net_position < 0 {
+                "SHORT"
+            } else {
+                "ZERO"
+            };
             println!("  ────────────────────────────────────────────────────────────────────────────────");
-            println!("  Net position: {} {:.6} base ({} atoms)", net_dir, net_base, net_position);
+            println!(
+                "  Net position: {} {:.6} base ({} atoms)",
+                net_dir, net_base, net_position
+            );
         }
     }
 
@@ -1751,6 +2088,11 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     let oracle_mantissa = fixed.get_oracle_price_mantissa();
     let oracle_expo = fixed.get_oracle_price_expo();
     let oracle_price = oracle_mantissa as f64 * 10f64.powi(oracle_expo);
+    let oracle_conf = fixed.get_oracle_conf() as f64 * 10f64.powi(oracle_expo);
+    let max_oracle_conf_bps = fixed.get_max_oracle_conf_bps();
+    let oracle_publish_time = fixed.get_oracle_publish_time();
+    let max_oracle_staleness_secs = fixed.get_max_oracle_staleness_secs();
+    let oracle_type = fixed.get_oracle_type();
     let initial_margin_bps = fixed.get_initial_margin_bps();
     let maintenance_margin_bps = fixed.get_maintenance_margin_bps();
     let max_leverage = 10_000.0 / initial_margin_bps as f64;
@@ -1758,6 +2100,7 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     let taker_fee_bps = fixed.get_taker_fee_bps();
     let insurance_fund = fixed.get_insurance_fund_balance();
     let liq_buffer_bps = fixed.get_liquidation_buffer_bps();
+    let liquidator_reward_bps = fixed.get_liquidator_reward_bps();
     let cumulative_funding = fixed.get_cumulative_funding();
 
     let base_decimals = fixed.get_base_mint_decimals() as u32;
@@ -1765,10 +2108,64 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     let base_factor = 10f64.powi(base_decimals as i32);
     let quote_factor = 10f64.powi(quote_decimals as i32);
 
+    // ── Mark price ──────────────────────────────────────────────────────
+    // Median of the oracle, the oracle moved by the average book premium,
+    // and the book mid (the oracle without a two-sided book).
+    let ema_premium = fixed.get_ema_premium_rate() as f64 / PREMIUM_RATE_SCALE as f64;
+    let mark_ema_window_secs = fixed.get_mark_ema_window_secs();
+    let price_usd = |order: &RestingOrder| -> f64 {
+        let one_base_unit = BaseAtoms::new(10u64.pow(base_decimals));
+        match order
+            .get_price()
+            .checked_quote_for_base(one_base_unit, false)
+        {
+            Ok(quote) => quote.as_u64() as f64 / quote_factor,
+            Err(_) => 0.0,
+        }
+    };
+    let best_bid = market
+        .get_bids()
+        .iter::<RestingOrder>()
+        .next()
+        .map(|(_, o)| price_usd(o));
+    let best_ask = market
+        .get_asks()
+        .iter::<RestingOrder>()
+        .next()
+        .map(|(_, o)| price_usd(o));
+    let book_mid = match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+        _ => None,
+    };
+    let mark_price = if oracle_price > 0.0 {
+        let mut candidates = [
+            oracle_price,
+            oracle_price * (1.0 + ema_premium),
+            book_mid.unwrap_or(oracle_price),
+        ];
+        candidates.sort_by(f64::total_cmp);
+        candidates[1]
+    } else {
+        book_mid.unwrap_or(0.0)
+    };
+
     // ── Trader position ─────────────────────────────────────────────────
     let (position_size, cost_basis) = market.get_trader_position(trader);
     let (_, quote_balance) = market.get_trader_balance(trader);
     let margin_atoms = quote_balance.as_u64();
+    // Lifetime stats live in the seat extension, zero until it is created.
+    let (seat_extension_key, _) = get_seat_extension_address(market_key, trader, 0);
+    let seat_extension: SeatExtension = client
+        .get_multiple_accounts(&[seat_extension_key])?
+        .into_iter()
+        .flatten()
+        .find(|account| account.data.len() >= SEAT_EXTENSION_SIZE)
+        .map(|account| bytemuck::pod_read_unaligned(&account.data[..SEAT_EXTENSION_SIZE]))
+        .unwrap_or_default();
+    let realized_pnl = seat_extension.get_realized_pnl() as f64 / quote_factor;
+    let funding_paid = seat_extension.get_cumulative_funding_paid() as f64 / quote_factor;
+    let fees_paid = seat_extension.get_fees_paid() as f64 / quote_factor;
+    let unsettled_pnl = market.get_trader_unsettled_pnl(trader) as f64 / quote_factor;
 
     let pos_base = position_size as f64 / base_factor;
     let is_long = position_size > 0;
@@ -1782,7 +2179,7 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     };
 
     let abs_pos = position_size.unsigned_abs() as f64 / base_factor;
-    let notional = abs_pos * oracle_price;
+    let notional = abs_pos * mark_price;
     let margin = margin_atoms as f64 / quote_factor;
     let cost_usd = cost_basis as f64 / quote_factor;
     let entry_price = if position_size != 0 {
@@ -1792,7 +2189,7 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     };
 
     // PnL: LONG = value - cost, SHORT = cost - value
-    let current_value = abs_pos * oracle_price;
+    let current_value = abs_pos * mark_price;
     let unrealized_pnl = if is_long {
         current_value - cost_usd
     } else if is_short {
@@ -1828,15 +2225,15 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
         0.0
     };
     let distance_to_liq = if position_size != 0 {
-        ((oracle_price - liq_price) / oracle_price * 100.0).abs()
+        ((mark_price - liq_price) / mark_price * 100.0).abs()
     } else {
         0.0
     };
 
     // ── Max position at current equity ──────────────────────────────────
     let max_notional = equity * max_leverage;
-    let max_position_base = if oracle_price > 0.0 {
-        max_notional / oracle_price
+    let max_position_base = if mark_price > 0.0 {
+        max_notional / mark_price
     } else {
         0.0
     };
@@ -1850,8 +2247,7 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     };
     let funding_delta = cumulative_funding - last_cumul;
     let pending_funding = if position_size != 0 && funding_delta != 0 {
-        (position_size as i128 * funding_delta as i128 / 1_000_000_000i128) as f64
-            / quote_factor
+        (position_size as i128 * funding_delta as i128 / 1_000_000_000i128) as f64 / quote_factor
     } else {
         0.0
     };
@@ -1863,15 +2259,21 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     println!("═══════════════════════════════════════════════════════");
     println!();
     println!("── Oracle ─────────────────────────────────────────────");
+    println!("  Source          : {oracle_type:?}");
     println!("  Price           : ${oracle_price:.4}");
     println!("  Mantissa        : {oracle_mantissa}");
     println!("  Exponent        : {oracle_expo}");
+    println!("  Confidence      : ±${oracle_conf:.4} (max {max_oracle_conf_bps} bps)");
+    println!("  Published At    : {oracle_publish_time} (max age {max_oracle_staleness_secs}s)");
+    println!(
+        "  EMA Premium     : {:+.4}% ({mark_ema_window_secs}s window)",
+        ema_premium * 100.0
+    );
+    println!("  Mark Price      : ${mark_price:.4}");
     println!();
     println!("── Position ───────────────────────────────────────────");
     println!("  Direction       : {direction}");
-    println!(
-        "  Size            : {abs_pos:.6} base ({position_size} atoms)"
-    );
+    println!("  Size            : {abs_pos:.6} base ({position_size} atoms)");
     println!("  Entry Price     : ${entry_price:.4}");
     println!("  Cost Basis      : ${cost_usd:.4}");
     println!("  Notional        : ${notional:.4}");
@@ -1879,6 +2281,9 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     println!("── Margin & Equity ────────────────────────────────────");
     println!("  Margin (deposit): ${margin:.4} ({margin_atoms} atoms)");
     println!("  Unrealized PnL  : ${unrealized_pnl:+.4}");
+    if unsettled_pnl != 0.0 {
+        println!("  Unsettled PnL   : ${unsettled_pnl:+.4} (realized, waiting on settle-pnl)");
+    }
     println!(
         "  Pending Funding : ${pending_funding:+.4}{}",
         if pending_funding > 0.0 {
@@ -1891,6 +2296,11 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     );
     println!("  Equity          : ${equity:.4}");
     println!();
+    println!("── Lifetime ───────────────────────────────────────────");
+    println!("  Realized PnL    : ${realized_pnl:+.4}");
+    println!("  Funding Paid    : ${funding_paid:+.4}");
+    println!("  Fees Paid       : ${fees_paid:.4}");
+    println!();
     println!("── Leverage & Liquidation ─────────────────────────────");
     println!("  Effective Leverage : {leverage:.2}x");
     println!(
@@ -1912,15 +2322,36 @@ fn cmd_position(client: &RpcClient, market_key: &Pubkey, trader: &Pubkey) -> Res
     println!("  Max Size         : {max_position_base:.6} base");
     println!();
     println!("── Market Parameters ──────────────────────────────────");
-    println!("  Taker Fee        : {} bps ({:.3}%)", taker_fee_bps, taker_fee_bps as f64 / 100.0);
+    println!(
+        "  Taker Fee        : {} bps ({:.3}%)",
+        taker_fee_bps,
+        taker_fee_bps as f64 / 100.0
+    );
     println!(
         "  Liq. Buffer      : {liq_buffer_bps} bps ({:.1}%)",
         liq_buffer_bps as f64 / 100.0
     );
-    println!("  Insurance Fund   : ${:.4} ({insurance_fund} atoms)", insurance_fund as f64 / quote_factor);
     println!(
-        "  Cumul. Funding   : {cumulative_funding} (scaled by 1e9)"
+        "  Liq. Reward      : {liquidator_reward_bps} bps ({:.2}%)",
+        liquidator_reward_bps as f64 / 100.0
+    );
+    for tier in fixed
+        .get_risk_tiers()
+        .iter()
+        .take_while(|tier| tier.is_active())
+    {
+        println!(
+            "  Risk Tier        : >= ${:.2} notional: initial {} bps, maintenance {} bps",
+            tier.notional_threshold_atoms as f64 / quote_factor,
+            tier.initial_margin_bps,
+            tier.maintenance_margin_bps,
+        );
+    }
+    println!(
+        "  Insurance Fund   : ${:.4} ({insurance_fund} atoms)",
+        insurance_fund as f64 / quote_factor
     );
+    println!("  Cumul. Funding   : {cumulative_funding} (scaled by 1e9)");
     println!();
 
     Ok(())
@@ -1976,7 +2407,13 @@ fn cmd_setup(
 
     // ── Step 3: Deposit SPL tokens into ephemeral ATA ───────────────────
     println!("\n── Step 3: Depositing {amount} atoms into ephemeral ATA…");
-    let ix = ix_deposit_spl_tokens(&payer.pubkey(), &payer.pubkey(), quote_mint, &user_ata, amount);
+    let ix = ix_deposit_spl_tokens(
+        &payer.pubkey(),
+        &payer.pubkey(),
+        quote_mint,
+        &user_ata,
+        amount,
+    );
     match send(devnet, &[ix], &[payer]) {
         Ok(sig) => println!("  Deposited: {sig}"),
         Err(_) => println!("  Already deposited or delegated (skipping)"),
@@ -2113,6 +2550,11 @@ fn main() -> Result<()> {
             pyth_feed,
             taker_fee_bps,
             liquidation_buffer_bps,
+            liquidator_reward_bps,
+            min_position_size_atoms,
+            funding_period_secs,
+            max_funding_rate,
+            funding_interest_rate,
             num_blocks,
         } => {
             let quote_mint = parse_pubkey(&quote_mint)?;
@@ -2132,10 +2574,77 @@ fn main() -> Result<()> {
                 pyth,
                 taker_fee_bps,
                 liquidation_buffer_bps,
+                liquidator_reward_bps,
+                min_position_size_atoms,
+                funding_period_secs,
+                max_funding_rate,
+                funding_interest_rate,
                 num_blocks,
             )?;
         }
 
+        Commands::UpdateMarket {
+            market,
+            liquidator_reward_bps,
+            min_position_size_atoms,
+            risk_tier,
+            clear_risk_tiers,
+            max_oracle_conf_bps,
+            max_oracle_staleness_secs,
+            pyth_feed_id,
+            oracle_type,
+            oracle_feed,
+            extra_oracle,
+            clear_extra_oracles,
+            mark_ema_window_secs,
+            funding_period_secs,
+            max_funding_rate,
+            funding_interest_rate,
+        } => {
+            let market = parse_pubkey(&market)?;
+            let risk_tiers = if clear_risk_tiers {
+                Some(vec![])
+            } else if risk_tier.is_empty() {
+                None
+            } else {
+                Some(
+                    risk_tier
+                        .iter()
+                        .map(String::as_str)
+                        .map(parse_risk_tier)
+                        .collect::<Result<Vec<_>>>()?,
+                )
+            };
+            let extra_oracle_sources = if clear_extra_oracles {
+                Some(vec![])
+            } else if extra_oracle.is_empty() {
+                None
+            } else {
+                Some(
+                    extra_oracle
+                        .iter()
+                        .map(String::as_str)
+                        .map(parse_oracle_source)
+                        .collect::<Result<Vec<_>>>()?,
+                )
+            };
+            let params = UpdateMarketParams {
+                risk_tiers,
+                max_oracle_conf_bps,
+                max_oracle_staleness_secs,
+                pyth_feed_id: pyth_feed_id.as_deref().map(parse_feed_id).transpose()?,
+                oracle_type: oracle_type.as_deref().map(parse_oracle_type).transpose()?,
+                oracle_feed_account: oracle_feed.as_deref().map(parse_pubkey).transpose()?,
+                extra_oracle_sources,
+                mark_ema_window_secs,
+                funding_period_secs,
+                max_funding_rate,
+                funding_interest_rate,
+                ..UpdateMarketParams::new(liquidator_reward_bps, min_position_size_atoms)
+            };
+            cmd_update_market(&client, &payer, &market, params)?;
+        }
+
         Commands::Expand {
             market,
             blocks,
@@ -2230,10 +2739,39 @@ fn main() -> Result<()> {
             cmd_crank_funding(&client, &payer, &market, &feed)?;
         }
 
-        Commands::Liquidate { market, trader } => {
+        Commands::CreateFundingHistory { market } => {
+            let market = parse_pubkey(&market)?;
+            cmd_create_funding_history(&client, &payer, &market)?;
+        }
+
+        Commands::FundingHistory { market } => {
             let market = parse_pubkey(&market)?;
-            let trader = parse_pubkey(&trader)?;
-            cmd_liquidate(&client, &payer, &market, &trader)?;
+            cmd_funding_history(&client, &market)?;
+        }
+
+        Commands::Liquidate {
+            market,
+            trader,
+            orderbook,
+            takeover,
+        } => {
+            let market = parse_pubkey(&market)?;
+            let traders = trader
+                .iter()
+                .map(String::as_str)
+                .map(parse_pubkey)
+                .collect::<Result<Vec<_>>>()?;
+            cmd_liquidate(&client, &payer, &market, traders, orderbook, takeover)?;
+        }
+
+        Commands::SettlePnl { market, trader } => {
+            let market = parse_pubkey(&market)?;
+            let trader = trader
+                .as_deref()
+                .map(parse_pubkey)
+                .transpose()?
+                .unwrap_or(payer.pubkey());
+            cmd_settle_pnl(&client, &payer, &market, &trader)?;
         }
 
         Commands::FetchPrice {
@@ -2277,7 +2815,15 @@ fn main() -> Result<()> {
         } => {
             let market = parse_pubkey(&market)?;
             let quote_mint = parse_pubkey(&quote_mint)?;
-            cmd_swap(&client, &payer, &market, &quote_mint, in_atoms, min_out_atoms, is_base_in)?;
+            cmd_swap(
+                &client,
+                &payer,
+                &market,
+                &quote_mint,
+                in_atoms,
+                min_out_atoms,
+                is_base_in,
+            )?;
         }
 
         Commands::MarketInfo { market } => {
@@ -2292,7 +2838,11 @@ fn main() -> Result<()> {
 
         Commands::Position { market, trader } => {
             let market = parse_pubkey(&market)?;
-            let trader = trader.as_deref().map(parse_pubkey).transpose()?.unwrap_or(payer.pubkey());
+            let trader = trader
+                .as_deref()
+                .map(parse_pubkey)
+                .transpose()?
+                .unwrap_or(payer.pubkey());
             cmd_position(&client, &market, &trader)?;
         }
 
@@ -2310,8 +2860,13 @@ fn main() -> Result<()> {
             } else {
                 mint_authority
             };
-            let mint_auth = read_keypair_file(&mint_auth_path)
-                .map_err(|e| anyhow!("Failed to load mint authority from {}: {}", mint_auth_path, e))?;
+            let mint_auth = read_keypair_file(&mint_auth_path).map_err(|e| {
+                anyhow!(
+                    "Failed to load mint authority from {}: {}",
+                    mint_auth_path,
+                    e
+                )
+            })?;
             cmd_setup(
                 &client,
                 &er,
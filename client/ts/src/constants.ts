@@ -1,4 +1,4 @@
-export const FIXED_MANIFEST_HEADER_SIZE: number = 256;
+export const FIXED_MANIFEST_HEADER_SIZE: number = 768;
 export const FIXED_GLOBAL_HEADER_SIZE: number = 96;
 export const FIXED_WRAPPER_HEADER_SIZE: number = 64;
 export const NIL: number = 4_294_967_295;
@@ -31,6 +31,8 @@ export type FillLogArgs = {
   quoteAtoms: QuoteAtoms
   makerSequenceNumber: beet.bignum
   takerSequenceNumber: beet.bignum
+  makerRealizedPnl: beet.bignum
+  takerRealizedPnl: beet.bignum
   takerIsBuy: boolean
   isMakerGlobal: boolean
   padding: number[] /* size: 14 */
@@ -54,6 +56,8 @@ export class FillLog implements FillLogArgs {
     readonly quoteAtoms: QuoteAtoms,
     readonly makerSequenceNumber: beet.bignum,
     readonly takerSequenceNumber: beet.bignum,
+    readonly makerRealizedPnl: beet.bignum,
+    readonly takerRealizedPnl: beet.bignum,
     readonly takerIsBuy: boolean,
     readonly isMakerGlobal: boolean,
     readonly padding: number[] /* size: 14 */
@@ -74,6 +78,8 @@ export class FillLog implements FillLogArgs {
       args.quoteAtoms,
       args.makerSequenceNumber,
       args.takerSequenceNumber,
+      args.makerRealizedPnl,
+      args.takerRealizedPnl,
       args.takerIsBuy,
       args.isMakerGlobal,
       args.padding
@@ -210,6 +216,28 @@ export class FillLog implements FillLogArgs {
         }
         return x
       })(),
+      makerRealizedPnl: (() => {
+        const x = <{ toNumber: () => number }>this.makerRealizedPnl
+        if (typeof x.toNumber === 'function') {
+          try {
+            return x.toNumber()
+          } catch (_) {
+            return x
+          }
+        }
+        return x
+      })(),
+      takerRealizedPnl: (() => {
+        const x = <{ toNumber: () => number }>this.takerRealizedPnl
+        if (typeof x.toNumber === 'function') {
+          try {
+            return x.toNumber()
+          } catch (_) {
+            return x
+          }
+        }
+        return x
+      })(),
       takerIsBuy: this.takerIsBuy,
       isMakerGlobal: this.isMakerGlobal,
       padding: this.padding,
@@ -233,6 +261,8 @@ export const fillLogBeet = new beet.BeetStruct<FillLog, FillLogArgs>(
     ['quoteAtoms', quoteAtomsBeet],
     ['makerSequenceNumber', beet.u64],
     ['takerSequenceNumber', beet.u64],
+    ['makerRealizedPnl', beet.i64],
+    ['takerRealizedPnl', beet.i64],
     ['takerIsBuy', beet.bool],
     ['isMakerGlobal', beet.bool],
     ['padding', beet.uniformFixedSizeArray(beet.u8, 14)],
@@ -22,7 +22,7 @@ export type LiquidateInstructionArgs = {
  * @category Liquidate
  * @category generated
  */
-export const LiquidateStruct = new beet.BeetArgsStruct<
+export const LiquidateStruct = new beet.FixableBeetArgsStruct<
   LiquidateInstructionArgs & {
     instructionDiscriminator: number
   }
@@ -14,6 +14,12 @@ export type ClaimedSeat = {
   quoteWithdrawableBalance: beet.bignum
   quoteVolume: beet.bignum
   padding: number[] /* size: 8 */
+  lastCumulativeLossPerUnit: beet.bignum
+  unsettledPnl: beet.bignum
+  marginMode: number
+  subAccountId: number
+  padding2: number[] /* size: 2 */
+  unsettledPnlEpoch: number
 }
 
 /**
@@ -27,6 +33,12 @@ export const claimedSeatBeet = new beet.BeetArgsStruct<ClaimedSeat>(
     ['quoteWithdrawableBalance', beet.u64],
     ['quoteVolume', beet.u64],
     ['padding', beet.uniformFixedSizeArray(beet.u8, 8)],
+    ['lastCumulativeLossPerUnit', beet.u64],
+    ['unsettledPnl', beet.i64],
+    ['marginMode', beet.u8],
+    ['subAccountId', beet.u8],
+    ['padding2', beet.uniformFixedSizeArray(beet.u8, 2)],
+    ['unsettledPnlEpoch', beet.u32],
   ],
   'ClaimedSeat'
 )
@@ -5,10 +5,14 @@
  * See: https://github.com/metaplex-foundation/solita
  */
 
+import * as web3 from '@solana/web3.js'
 import * as beet from '@metaplex-foundation/beet'
+import * as beetSolana from '@metaplex-foundation/beet-solana'
 export type DepositParams = {
   amountAtoms: beet.bignum
   traderIndexHint: beet.COption<number>
+  subAccountId: number
+  beneficiary: beet.COption<web3.PublicKey>
 }
 
 /**
@@ -19,6 +23,8 @@ export const depositParamsBeet = new beet.FixableBeetArgsStruct<DepositParams>(
   [
     ['amountAtoms', beet.u64],
     ['traderIndexHint', beet.coption(beet.u32)],
+    ['subAccountId', beet.u8],
+    ['beneficiary', beet.coption(beetSolana.publicKey)],
   ],
   'DepositParams'
 )
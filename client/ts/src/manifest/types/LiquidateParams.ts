@@ -6,17 +6,29 @@
  */
 
 import * as web3 from '@solana/web3.js'
-import * as beetSolana from '@metaplex-foundation/beet-solana'
 import * as beet from '@metaplex-foundation/beet'
+import * as beetSolana from '@metaplex-foundation/beet-solana'
+import { LiquidationMode, liquidationModeBeet } from './LiquidationMode'
 export type LiquidateParams = {
-  traderToLiquidate: web3.PublicKey
+  tradersToLiquidate: web3.PublicKey[]
+  mode: LiquidationMode
+  scanStartIndex: number
+  scanLimit: number
+  subAccountIds: Uint8Array
 }
 
 /**
  * @category userTypes
  * @category generated
  */
-export const liquidateParamsBeet = new beet.BeetArgsStruct<LiquidateParams>(
-  [['traderToLiquidate', beetSolana.publicKey]],
-  'LiquidateParams'
-)
+export const liquidateParamsBeet =
+  new beet.FixableBeetArgsStruct<LiquidateParams>(
+    [
+      ['tradersToLiquidate', beet.array(beetSolana.publicKey)],
+      ['mode', liquidationModeBeet],
+      ['scanStartIndex', beet.u32],
+      ['scanLimit', beet.u32],
+      ['subAccountIds', beet.bytes],
+    ],
+    'LiquidateParams'
+  )
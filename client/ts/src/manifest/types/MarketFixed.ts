@@ -8,18 +8,16 @@
 import * as beet from '@metaplex-foundation/beet'
 import * as web3 from '@solana/web3.js'
 import * as beetSolana from '@metaplex-foundation/beet-solana'
+import { RiskTier, riskTierBeet } from './RiskTier'
+import { OracleSource, oracleSourceBeet } from './OracleSource'
 export type MarketFixed = {
   discriminant: beet.bignum
   version: number
+  baseMintIndex: number
   baseMintDecimals: number
   quoteMintDecimals: number
-  baseVaultBump: number
-  quoteVaultBump: number
-  padding1: number[] /* size: 3 */
-  baseMint: web3.PublicKey
+  padding1: number[] /* size: 4 */
   quoteMint: web3.PublicKey
-  baseVault: web3.PublicKey
-  quoteVault: web3.PublicKey
   orderSequenceNumber: beet.bignum
   numBytesAllocated: number
   bidsRootIndex: number
@@ -30,7 +28,45 @@ export type MarketFixed = {
   freeListHeadIndex: number
   padding2: number[] /* size: 1 */
   quoteVolume: beet.bignum
-  padding3: beet.bignum[] /* size: 8 */
+  initialMarginBps: beet.bignum
+  maintenanceMarginBps: beet.bignum
+  totalLongBaseAtoms: beet.bignum
+  totalShortBaseAtoms: beet.bignum
+  pythFeedAccount: web3.PublicKey
+  oraclePriceMantissa: beet.bignum
+  oraclePriceExpoAndPad: beet.bignum
+  lastFundingTimestamp: beet.bignum
+  cumulativeFunding: beet.bignum
+  insuranceFundBalance: beet.bignum
+  takerFeeBps: beet.bignum
+  liquidationBufferBps: beet.bignum
+  cumulativeLossPerUnit: beet.bignum
+  insuranceTotalShares: beet.bignum
+  insuranceShareEpoch: beet.bignum
+  marketAuthority: web3.PublicKey
+  liquidatorRewardBps: beet.bignum
+  minPositionSizeAtoms: beet.bignum
+  insuranceProtocolFeeBps: beet.bignum
+  insuranceProtocolShares: beet.bignum
+  maxLiquidationSlippageBps: beet.bignum
+  riskTiers: RiskTier[] /* size: 4 */
+  oracleConf: beet.bignum
+  maxOracleConfBps: beet.bignum
+  oraclePublishTime: beet.bignum
+  maxOracleStalenessSecs: beet.bignum
+  pythFeedId: number[] /* size: 32 */
+  oracleType: beet.bignum
+  extraOracleSources: OracleSource[] /* size: 2 */
+  emaPremiumRate: beet.bignum
+  premiumSampleTimestamp: beet.bignum
+  markEmaWindowSecs: beet.bignum
+  premiumTwapSum: beet.bignum
+  premiumTwapSecs: beet.bignum
+  fundingPeriodSecs: beet.bignum
+  maxFundingRate: beet.bignum
+  fundingInterestRate: beet.bignum
+  pnlPoolBalance: beet.bignum
+  padding3: beet.bignum[] /* size: 17 */
 }
 
 /**
@@ -41,15 +77,11 @@ export const marketFixedBeet = new beet.BeetArgsStruct<MarketFixed>(
   [
     ['discriminant', beet.u64],
     ['version', beet.u8],
+    ['baseMintIndex', beet.u8],
     ['baseMintDecimals', beet.u8],
     ['quoteMintDecimals', beet.u8],
-    ['baseVaultBump', beet.u8],
-    ['quoteVaultBump', beet.u8],
-    ['padding1', beet.uniformFixedSizeArray(beet.u8, 3)],
-    ['baseMint', beetSolana.publicKey],
+    ['padding1', beet.uniformFixedSizeArray(beet.u8, 4)],
     ['quoteMint', beetSolana.publicKey],
-    ['baseVault', beetSolana.publicKey],
-    ['quoteVault', beetSolana.publicKey],
     ['orderSequenceNumber', beet.u64],
     ['numBytesAllocated', beet.u32],
     ['bidsRootIndex', beet.u32],
@@ -60,7 +92,45 @@ export const marketFixedBeet = new beet.BeetArgsStruct<MarketFixed>(
     ['freeListHeadIndex', beet.u32],
     ['padding2', beet.uniformFixedSizeArray(beet.u32, 1)],
     ['quoteVolume', beet.u64],
-    ['padding3', beet.uniformFixedSizeArray(beet.u64, 8)],
+    ['initialMarginBps', beet.u64],
+    ['maintenanceMarginBps', beet.u64],
+    ['totalLongBaseAtoms', beet.u64],
+    ['totalShortBaseAtoms', beet.u64],
+    ['pythFeedAccount', beetSolana.publicKey],
+    ['oraclePriceMantissa', beet.u64],
+    ['oraclePriceExpoAndPad', beet.u64],
+    ['lastFundingTimestamp', beet.u64],
+    ['cumulativeFunding', beet.u64],
+    ['insuranceFundBalance', beet.u64],
+    ['takerFeeBps', beet.u64],
+    ['liquidationBufferBps', beet.u64],
+    ['cumulativeLossPerUnit', beet.u64],
+    ['insuranceTotalShares', beet.u64],
+    ['insuranceShareEpoch', beet.u64],
+    ['marketAuthority', beetSolana.publicKey],
+    ['liquidatorRewardBps', beet.u64],
+    ['minPositionSizeAtoms', beet.u64],
+    ['insuranceProtocolFeeBps', beet.u64],
+    ['insuranceProtocolShares', beet.u64],
+    ['maxLiquidationSlippageBps', beet.u64],
+    ['riskTiers', beet.uniformFixedSizeArray(riskTierBeet, 4)],
+    ['oracleConf', beet.u64],
+    ['maxOracleConfBps', beet.u64],
+    ['oraclePublishTime', beet.u64],
+    ['maxOracleStalenessSecs', beet.u64],
+    ['pythFeedId', beet.uniformFixedSizeArray(beet.u8, 32)],
+    ['oracleType', beet.u64],
+    ['extraOracleSources', beet.uniformFixedSizeArray(oracleSourceBeet, 2)],
+    ['emaPremiumRate', beet.u64],
+    ['premiumSampleTimestamp', beet.u64],
+    ['markEmaWindowSecs', beet.u64],
+    ['premiumTwapSum', beet.u64],
+    ['premiumTwapSecs', beet.u64],
+    ['fundingPeriodSecs', beet.u64],
+    ['maxFundingRate', beet.u64],
+    ['fundingInterestRate', beet.u64],
+    ['pnlPoolBalance', beet.u64],
+    ['padding3', beet.uniformFixedSizeArray(beet.u64, 17)],
   ],
   'MarketFixed'
 )
@@ -15,7 +15,9 @@ export type RestingOrder = {
   lastValidSlot: number
   isBid: boolean
   orderType: OrderType
-  padding: number[] /* size: 22 */
+  reverseSpread: number
+  padding: number[] /* size: 28 */
+  padding2: beet.bignum[] /* size: 2 */
 }
 
 /**
@@ -31,7 +33,9 @@ export const restingOrderBeet = new beet.BeetArgsStruct<RestingOrder>(
     ['lastValidSlot', beet.u32],
     ['isBid', beet.bool],
     ['orderType', orderTypeBeet],
-    ['padding', beet.uniformFixedSizeArray(beet.u8, 22)],
+    ['reverseSpread', beet.u16],
+    ['padding', beet.uniformFixedSizeArray(beet.u8, 28)],
+    ['padding2', beet.uniformFixedSizeArray(beet.u64, 2)],
   ],
   'RestingOrder'
 )
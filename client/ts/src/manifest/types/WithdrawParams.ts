@@ -9,6 +9,7 @@ import * as beet from '@metaplex-foundation/beet'
 export type WithdrawParams = {
   amountAtoms: beet.bignum
   traderIndexHint: beet.COption<number>
+  subAccountId: number
 }
 
 /**
@@ -20,6 +21,7 @@ export const withdrawParamsBeet =
     [
       ['amountAtoms', beet.u64],
       ['traderIndexHint', beet.coption(beet.u32)],
+      ['subAccountId', beet.u8],
     ],
     'WithdrawParams'
   )
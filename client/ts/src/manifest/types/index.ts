@@ -10,9 +10,13 @@ export * from './GlobalFixed'
 export * from './GlobalTrader'
 export * from './GlobalWithdrawParams'
 export * from './LiquidateParams'
+export * from './LiquidationMode'
 export * from './MarketFixed'
+export * from './OracleSource'
 export * from './OrderType'
 export * from './PlaceOrderParams'
 export * from './RestingOrder'
+export * from './RiskTier'
+export * from './SeatExtension'
 export * from './SwapParams'
 export * from './WithdrawParams'
@@ -1016,10 +1016,10 @@ impl<V: Payload> std::fmt::Display for RBNode<V> {
 }
 
 impl<V: Payload> RBNode<V> {
-    fn get_left_index(&self) -> DataIndex {
+    pub fn get_left_index(&self) -> DataIndex {
         self.left
     }
-    fn get_right_index(&self) -> DataIndex {
+    pub fn get_right_index(&self) -> DataIndex {
         self.right
     }
     pub fn get_payload_type(&self) -> u8 {
@@ -19,13 +19,25 @@ pub mod certora;
 use hypertree::trace;
 use program::{
     batch_update::process_batch_update, claim_seat::process_claim_seat,
-    crank_funding::process_crank_funding, create_market::process_create_market,
-    deposit::process_deposit, expand_market::process_expand_market,
-    global_add_trader::process_global_add_trader, global_clean::process_global_clean,
-    global_create::process_global_create, global_deposit::process_global_deposit,
-    global_evict::process_global_evict, global_withdraw::process_global_withdraw,
-    liquidate::process_liquidate, process_swap, release_seat::process_release_seat,
-    withdraw::process_withdraw, ManifestInstruction,
+    crank_funding::process_crank_funding,
+    create_cross_margin_account::process_create_cross_margin_account,
+    create_funding_history::process_create_funding_history, create_market::process_create_market,
+    create_seat_extension::process_create_seat_extension,
+    cross_margin_deposit::process_cross_margin_deposit,
+    cross_margin_link::process_cross_margin_link,
+    cross_margin_transfer::process_cross_margin_transfer,
+    cross_margin_withdraw::process_cross_margin_withdraw, deposit::process_deposit,
+    expand_market::process_expand_market, global_add_trader::process_global_add_trader,
+    global_clean::process_global_clean, global_create::process_global_create,
+    global_deposit::process_global_deposit, global_evict::process_global_evict,
+    global_withdraw::process_global_withdraw, insurance_deposit::process_insurance_deposit,
+    insurance_request_withdraw::process_insurance_request_withdraw,
+    insurance_withdraw::process_insurance_withdraw, liquidate::process_liquidate,
+    migrate_market::process_migrate_market, process_swap, release_seat::process_release_seat,
+    revoke_trade_delegate::process_revoke_trade_delegate,
+    set_session_scope::process_set_session_scope, set_trade_delegate::process_set_trade_delegate,
+    set_withdraw_allowlist::process_set_withdraw_allowlist, settle_pnl::process_settle_pnl,
+    update_market::process_update_market, withdraw::process_withdraw, ManifestInstruction,
 };
 use solana_program::{
     account_info::AccountInfo, declare_id, entrypoint::ProgramResult, program_error::ProgramError,
@@ -171,6 +183,57 @@ pub fn process_instruction(
         ManifestInstruction::ReleaseSeat => {
             process_release_seat(program_id, accounts, data)?;
         }
+        ManifestInstruction::MigrateMarket => {
+            process_migrate_market(program_id, accounts, data)?;
+        }
+        ManifestInstruction::InsuranceDeposit => {
+            process_insurance_deposit(program_id, accounts, data)?;
+        }
+        ManifestInstruction::InsuranceWithdraw => {
+            process_insurance_withdraw(program_id, accounts, data)?;
+        }
+        ManifestInstruction::InsuranceRequestWithdraw => {
+            process_insurance_request_withdraw(program_id, accounts, data)?;
+        }
+        ManifestInstruction::UpdateMarket => {
+            process_update_market(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CreateFundingHistory => {
+            process_create_funding_history(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CreateSeatExtension => {
+            process_create_seat_extension(program_id, accounts, data)?;
+        }
+        ManifestInstruction::SettlePnl => {
+            process_settle_pnl(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CreateCrossMarginAccount => {
+            process_create_cross_margin_account(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CrossMarginDeposit => {
+            process_cross_margin_deposit(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CrossMarginWithdraw => {
+            process_cross_margin_withdraw(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CrossMarginLink => {
+            process_cross_margin_link(program_id, accounts, data)?;
+        }
+        ManifestInstruction::CrossMarginTransfer => {
+            process_cross_margin_transfer(program_id, accounts, data)?;
+        }
+        ManifestInstruction::SetTradeDelegate => {
+            process_set_trade_delegate(program_id, accounts, data)?;
+        }
+        ManifestInstruction::RevokeTradeDelegate => {
+            process_revoke_trade_delegate(program_id, accounts, data)?;
+        }
+        ManifestInstruction::SetSessionScope => {
+            process_set_session_scope(program_id, accounts, data)?;
+        }
+        ManifestInstruction::SetWithdrawAllowlist => {
+            process_set_withdraw_allowlist(program_id, accounts, data)?;
+        }
     }
 
     Ok(())
@@ -81,6 +81,10 @@ pub struct FillLog {
     pub quote_atoms: QuoteAtoms,
     pub maker_sequence_number: u64,
     pub taker_sequence_number: u64,
+    /// PnL the fill realized on the maker's position, in quote atoms.
+    pub maker_realized_pnl: i64,
+    /// PnL the fill realized on the taker's position, in quote atoms.
+    pub taker_realized_pnl: i64,
     pub taker_is_buy: PodBool,
     pub is_maker_global: PodBool,
     pub _padding: [u8; 14],
@@ -197,6 +201,16 @@ pub struct LiquidateLog {
     pub close_amount: u64,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct LiquidationScanLog {
+    pub market: Pubkey,
+    pub liquidator: Pubkey,
+    /// Seat the next scan should start from
+    pub next_seat_index: u32,
+    pub num_liquidated: u32,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
 pub struct FundingCrankLog {
@@ -206,7 +220,183 @@ pub struct FundingCrankLog {
     /// Funding rate as i64 (positive = longs pay shorts)
     pub funding_rate: u64,
     pub timestamp: u64,
-    pub _padding: [u8; 8],
+    /// Book premium TWAP over the period as i64, scaled by PREMIUM_RATE_SCALE
+    pub premium_twap: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct OracleUpdateLog {
+    pub market: Pubkey,
+    pub cranker: Pubkey,
+    /// Median price cached on the market, as mantissa and exponent
+    pub oracle_price: u64,
+    pub oracle_conf: u64,
+    pub publish_time: u64,
+    pub oracle_expo: i32,
+    /// Bit i is set when oracle source i (0 = primary) went into the median
+    pub sources_used: u8,
+    /// Bit i is set when oracle source i was read but filtered out
+    pub sources_rejected: u8,
+    pub _padding: [u8; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct SocializeLossLog {
+    pub market: Pubkey,
+    /// Trader whose bad debt was socialized
+    pub trader: Pubkey,
+    /// Deficit left after the insurance fund draw (quote atoms)
+    pub amount_atoms: u64,
+    /// Market cumulative loss per unit after socializing, scaled by 1e9
+    pub cumulative_loss_per_unit: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct SocializedLossChargeLog {
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    /// Socialized loss deducted from the trader's margin (quote atoms)
+    pub amount_atoms: u64,
+    /// Market cumulative loss per unit the trader was settled up to, scaled by 1e9
+    pub cumulative_loss_per_unit: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct MigrateMarketLog {
+    pub market: Pubkey,
+    pub payer: Pubkey,
+    /// Seat and order blocks carried over into the current layout
+    pub num_blocks: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct InsuranceDepositLog {
+    pub market: Pubkey,
+    pub staker: Pubkey,
+    pub amount_atoms: u64,
+    pub shares: u64,
+    /// Insurance fund balance after the deposit (quote atoms)
+    pub insurance_fund_balance: u64,
+    /// Total insurance shares outstanding after the deposit
+    pub total_shares: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct InsuranceWithdrawLog {
+    pub market: Pubkey,
+    pub staker: Pubkey,
+    pub amount_atoms: u64,
+    pub shares: u64,
+    /// Insurance fund balance after the withdrawal (quote atoms)
+    pub insurance_fund_balance: u64,
+    /// Total insurance shares outstanding after the withdrawal
+    pub total_shares: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct InsuranceRequestWithdrawLog {
+    pub market: Pubkey,
+    pub staker: Pubkey,
+    /// Shares requested, replacing any earlier request
+    pub shares: u64,
+    /// Unix timestamp from which the requested shares can be withdrawn
+    pub unlock_timestamp: i64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct SettlePnlLog {
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    /// Realized PnL moved into the trader's margin, negative for a loss (quote atoms)
+    pub pnl_atoms: i64,
+    /// Trader's margin balance after settling (quote atoms)
+    pub margin_atoms: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct CrossMarginDepositLog {
+    pub cross_margin: Pubkey,
+    pub trader: Pubkey,
+    pub amount_atoms: u64,
+    /// Collateral held after the deposit (quote atoms)
+    pub collateral_atoms: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct CrossMarginWithdrawLog {
+    pub cross_margin: Pubkey,
+    pub trader: Pubkey,
+    pub amount_atoms: u64,
+    /// Collateral held after the withdrawal (quote atoms)
+    pub collateral_atoms: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct CrossMarginLinkLog {
+    pub cross_margin: Pubkey,
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    /// True when the seat was linked, false when it was unlinked
+    pub is_linked: PodBool,
+    pub sub_account_id: u8,
+    pub _padding: [u8; 6],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct CrossMarginTransferLog {
+    pub cross_margin: Pubkey,
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    pub amount_atoms: u64,
+    /// Collateral left in the cross-margin account after the transfer
+    pub collateral_atoms: u64,
+    /// True when moved from the seat into the cross-margin account
+    pub to_cross_margin: PodBool,
+    pub sub_account_id: u8,
+    pub _padding: [u8; 6],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct TradeDelegateLog {
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    /// New trade delegate of the seat, default when revoked
+    pub delegate: Pubkey,
+    pub sub_account_id: u8,
+    pub _padding: [u8; 7],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct SessionScopeLog {
+    pub authority: Pubkey,
+    pub session_signer: Pubkey,
+    pub max_order_notional_atoms: u64,
+    pub max_position_atoms: u64,
+    pub allowed_instructions: u64,
+    /// Number of markets the session may use, zero for every market
+    pub num_markets: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, ShankAccount)]
+pub struct WithdrawAllowlistLog {
+    pub trader: Pubkey,
+    /// Number of wallets withdrawals may now pay besides the trader
+    pub num_recipients: u64,
 }
 
 pub trait Discriminant {
@@ -247,7 +437,23 @@ const GLOBAL_WITHDRAW_LOG_DISCRIMINANT: [u8; 8] = [206, 118, 67, 64, 124, 109, 1
 const GLOBAL_EVICT_LOG_DISCRIMINANT: [u8; 8] = [250, 180, 155, 38, 98, 223, 82, 223];
 const GLOBAL_CLEANUP_LOG_DISCRIMINANT: [u8; 8] = [193, 249, 115, 186, 42, 126, 196, 82];
 const LIQUIDATE_LOG_DISCRIMINANT: [u8; 8] = [232, 126, 161, 135, 147, 57, 82, 153];
+const LIQUIDATION_SCAN_LOG_DISCRIMINANT: [u8; 8] = [32, 70, 186, 159, 200, 203, 62, 99];
 const FUNDING_CRANK_LOG_DISCRIMINANT: [u8; 8] = [56, 41, 215, 141, 163, 216, 83, 84];
+const ORACLE_UPDATE_LOG_DISCRIMINANT: [u8; 8] = [77, 14, 126, 135, 26, 208, 86, 143];
+const SOCIALIZE_LOSS_LOG_DISCRIMINANT: [u8; 8] = [88, 207, 91, 126, 223, 161, 200, 27];
+const SOCIALIZED_LOSS_CHARGE_LOG_DISCRIMINANT: [u8; 8] = [116, 218, 237, 195, 176, 8, 3, 169];
+const MIGRATE_MARKET_LOG_DISCRIMINANT: [u8; 8] = [37, 129, 69, 39, 255, 125, 214, 145];
+const INSURANCE_DEPOSIT_LOG_DISCRIMINANT: [u8; 8] = [46, 175, 234, 64, 170, 134, 161, 13];
+const INSURANCE_WITHDRAW_LOG_DISCRIMINANT: [u8; 8] = [187, 224, 204, 20, 113, 144, 24, 253];
+const INSURANCE_REQUEST_WITHDRAW_LOG_DISCRIMINANT: [u8; 8] = [122, 227, 148, 214, 37, 67, 79, 220];
+const SETTLE_PNL_LOG_DISCRIMINANT: [u8; 8] = [60, 145, 225, 128, 28, 249, 8, 71];
+const CROSS_MARGIN_DEPOSIT_LOG_DISCRIMINANT: [u8; 8] = [74, 53, 95, 3, 202, 173, 125, 190];
+const CROSS_MARGIN_WITHDRAW_LOG_DISCRIMINANT: [u8; 8] = [190, 46, 101, 232, 97, 252, 30, 29];
+const CROSS_MARGIN_LINK_LOG_DISCRIMINANT: [u8; 8] = [151, 104, 182, 14, 102, 98, 223, 71];
+const CROSS_MARGIN_TRANSFER_LOG_DISCRIMINANT: [u8; 8] = [118, 168, 17, 66, 40, 234, 194, 229];
+const TRADE_DELEGATE_LOG_DISCRIMINANT: [u8; 8] = [244, 210, 222, 187, 3, 179, 203, 252];
+const SESSION_SCOPE_LOG_DISCRIMINANT: [u8; 8] = [48, 150, 35, 126, 104, 79, 236, 1];
+const WITHDRAW_ALLOWLIST_LOG_DISCRIMINANT: [u8; 8] = [59, 128, 229, 69, 113, 88, 98, 158];
 
 discriminant!(
     CreateMarketLog,
@@ -312,13 +518,89 @@ discriminant!(
     GLOBAL_CLEANUP_LOG_DISCRIMINANT,
     test_global_cleanup_log
 );
+discriminant!(LiquidateLog, LIQUIDATE_LOG_DISCRIMINANT, test_liquidate_log);
 discriminant!(
-    LiquidateLog,
-    LIQUIDATE_LOG_DISCRIMINANT,
-    test_liquidate_log
+    LiquidationScanLog,
+    LIQUIDATION_SCAN_LOG_DISCRIMINANT,
+    test_liquidation_scan_log
 );
 discriminant!(
     FundingCrankLog,
     FUNDING_CRANK_LOG_DISCRIMINANT,
     test_funding_crank_log
 );
+discriminant!(
+    OracleUpdateLog,
+    ORACLE_UPDATE_LOG_DISCRIMINANT,
+    test_oracle_update_log
+);
+discriminant!(
+    SocializeLossLog,
+    SOCIALIZE_LOSS_LOG_DISCRIMINANT,
+    test_socialize_loss_log
+);
+discriminant!(
+    SocializedLossChargeLog,
+    SOCIALIZED_LOSS_CHARGE_LOG_DISCRIMINANT,
+    test_socialized_loss_charge_log
+);
+discriminant!(
+    MigrateMarketLog,
+    MIGRATE_MARKET_LOG_DISCRIMINANT,
+    test_migrate_market_log
+);
+discriminant!(
+    InsuranceDepositLog,
+    INSURANCE_DEPOSIT_LOG_DISCRIMINANT,
+    test_insurance_deposit_log
+);
+discriminant!(
+    InsuranceWithdrawLog,
+    INSURANCE_WITHDRAW_LOG_DISCRIMINANT,
+    test_insurance_withdraw_log
+);
+discriminant!(
+    InsuranceRequestWithdrawLog,
+    INSURANCE_REQUEST_WITHDRAW_LOG_DISCRIMINANT,
+    test_insurance_request_withdraw_log
+);
+discriminant!(
+    SettlePnlLog,
+    SETTLE_PNL_LOG_DISCRIMINANT,
+    test_settle_pnl_log
+);
+discriminant!(
+    CrossMarginDepositLog,
+    CROSS_MARGIN_DEPOSIT_LOG_DISCRIMINANT,
+    test_cross_margin_deposit_log
+);
+discriminant!(
+    CrossMarginWithdrawLog,
+    CROSS_MARGIN_WITHDRAW_LOG_DISCRIMINANT,
+    test_cross_margin_withdraw_log
+);
+discriminant!(
+    CrossMarginLinkLog,
+    CROSS_MARGIN_LINK_LOG_DISCRIMINANT,
+    test_cross_margin_link_log
+);
+discriminant!(
+    CrossMarginTransferLog,
+    CROSS_MARGIN_TRANSFER_LOG_DISCRIMINANT,
+    test_cross_margin_transfer_log
+);
+discriminant!(
+    TradeDelegateLog,
+    TRADE_DELEGATE_LOG_DISCRIMINANT,
+    test_trade_delegate_log
+);
+discriminant!(
+    SessionScopeLog,
+    SESSION_SCOPE_LOG_DISCRIMINANT,
+    test_session_scope_log
+);
+discriminant!(
+    WithdrawAllowlistLog,
+    WITHDRAW_ALLOWLIST_LOG_DISCRIMINANT,
+    test_withdraw_allowlist_log
+);
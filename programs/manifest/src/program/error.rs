@@ -74,6 +74,18 @@ pub enum ManifestError {
     SessionDurationTooLong = 33,
     #[error("Session authority does not match")]
     InvalidSessionAuthority = 34,
+    #[error("Insurance fund is depleted")]
+    InsuranceFundDepleted = 35,
+    #[error("Insurance withdrawal cooldown has not elapsed")]
+    InsuranceWithdrawCooldown = 36,
+    #[error("Signer is not the market authority")]
+    InvalidMarketAuthority = 37,
+    #[error("Seat extension does not belong to the seat")]
+    InvalidSeatExtension = 38,
+    #[error("Signer is not the seat's trade delegate")]
+    InvalidTradeDelegate = 39,
+    #[error("Session key scope does not allow this action")]
+    SessionScopeExceeded = 40,
 }
 
 impl From<ManifestError> for ProgramError {
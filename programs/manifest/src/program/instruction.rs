@@ -19,39 +19,55 @@ pub enum ManifestInstruction {
     #[account(9, name = "ephemeral_spl_token", desc = "Ephemeral SPL token program")]
     CreateMarket = 0,
 
-    /// Allocate a seat
+    /// Allocate a seat. Without params the seat is the payer's default
+    /// sub-account.
     #[account(0, writable, signer, name = "payer", desc = "Payer")]
     #[account(1, writable, name = "market", desc = "Account holding all market state")]
     #[account(2, name = "system_program", desc = "System program")]
     ClaimSeat = 1,
 
-    /// Deposit quote tokens (USDC) into the market
+    /// Deposit quote tokens (USDC) into the market. Through a session the
+    /// authority's seat is credited from an account owned by the session
+    /// signer or the authority. A beneficiary in the params has their seat
+    /// credited instead of the signer's.
     #[account(0, signer, name = "payer", desc = "Payer")]
     #[account(1, writable, name = "market", desc = "Account holding all market state")]
-    #[account(2, writable, name = "trader_token", desc = "Trader quote token account")]
-    #[account(3, writable, name = "vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
-    #[account(4, name = "token_program", desc = "Token program(22)")]
-    #[account(5, name = "quote_mint", desc = "Quote mint")]
+    #[account(2, optional, name = "session_token", desc = "Session token for delegated signing")]
+    #[account(3, optional, name = "session_scope", desc = "Session scope PDA, required with a session token")]
+    #[account(4, writable, name = "trader_token", desc = "Trader quote token account")]
+    #[account(5, writable, name = "vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
+    #[account(6, name = "token_program", desc = "Token program(22)")]
+    #[account(7, name = "quote_mint", desc = "Quote mint")]
     Deposit = 2,
 
-    /// Withdraw quote tokens (USDC) from the market
+    /// Withdraw quote tokens (USDC) from the market. trader_token is the
+    /// recipient and must belong to the trader, or to a wallet on the
+    /// trader's WithdrawAllowlist, which is then passed after the quote mint.
+    /// Through a session the authority's seat is debited and only the
+    /// authority can be paid.
     #[account(0, signer, name = "payer", desc = "Payer")]
     #[account(1, writable, name = "market", desc = "Account holding all market state")]
-    #[account(2, writable, name = "trader_token", desc = "Trader quote token account")]
-    #[account(3, writable, name = "vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
-    #[account(4, name = "token_program", desc = "Token program(22)")]
-    #[account(5, name = "quote_mint", desc = "Quote mint")]
+    #[account(2, optional, name = "session_token", desc = "Session token for delegated signing")]
+    #[account(3, optional, name = "session_scope", desc = "Session scope PDA, required with a session token")]
+    #[account(4, writable, name = "trader_token", desc = "Recipient quote token account")]
+    #[account(5, writable, name = "vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
+    #[account(6, name = "token_program", desc = "Token program(22)")]
+    #[account(7, name = "quote_mint", desc = "Quote mint")]
+    #[account(8, optional, name = "withdraw_allowlist", desc = "Trader's WithdrawAllowlist, required for other recipients")]
     Withdraw = 3,
 
-    /// Swap (perps): place an IOC order against the orderbook
+    /// Swap (perps): place an IOC order against the orderbook. A seat's trade
+    /// delegate signs as payer and passes the seat's TradeDelegate record
+    /// right after the session accounts.
     #[account(0, signer, name = "payer", desc = "Payer / trader")]
     #[account(1, writable, name = "market", desc = "Account holding all market state")]
     #[account(2, name = "system_program", desc = "System program")]
     #[account(3, optional, name = "session_token", desc = "Session token for delegated signing")]
-    #[account(4, writable, name = "trader_quote", desc = "Trader quote token account")]
-    #[account(5, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
-    #[account(6, name = "token_program_quote", desc = "Token program(22) for quote")]
-    #[account(7, optional, name = "quote_mint", desc = "Quote mint, required if Token22")]
+    #[account(4, optional, name = "session_scope", desc = "Session scope PDA, required with a session token")]
+    #[account(5, writable, name = "trader_quote", desc = "Trader quote token account")]
+    #[account(6, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
+    #[account(7, name = "token_program_quote", desc = "Token program(22) for quote")]
+    #[account(8, optional, name = "quote_mint", desc = "Quote mint, required if Token22")]
     Swap = 4,
 
     /// Expand a market using lamport escrow from ephemeral-rollups-spl.
@@ -61,16 +77,19 @@ pub enum ManifestInstruction {
     #[account(3, name = "er_spl_program", desc = "Ephemeral-rollups-spl program")]
     Expand = 5,
 
-    /// Batch update with multiple place orders and cancels.
+    /// Batch update with multiple place orders and cancels. A seat's trade
+    /// delegate signs as payer and passes the seat's TradeDelegate record
+    /// right after the session accounts.
     #[account(0, signer, name = "payer", desc = "Payer")]
     #[account(1, writable, name = "market", desc = "Account holding all market state")]
     #[account(2, name = "system_program", desc = "System program")]
     #[account(3, optional, name = "session_token", desc = "Session token for delegated signing")]
-    #[account(4, optional, name = "quote_mint", desc = "Quote mint for global account")]
-    #[account(5, optional, writable, name = "quote_global", desc = "Quote global account")]
-    #[account(6, optional, name = "quote_global_vault", desc = "Quote global vault")]
-    #[account(7, optional, name = "quote_market_vault", desc = "Quote market vault")]
-    #[account(8, optional, name = "quote_token_program", desc = "Token program(22) for quote")]
+    #[account(4, optional, name = "session_scope", desc = "Session scope PDA, required with a session token")]
+    #[account(5, optional, name = "quote_mint", desc = "Quote mint for global account")]
+    #[account(6, optional, writable, name = "quote_global", desc = "Quote global account")]
+    #[account(7, optional, name = "quote_global_vault", desc = "Quote global vault")]
+    #[account(8, optional, name = "quote_market_vault", desc = "Quote market vault")]
+    #[account(9, optional, name = "quote_token_program", desc = "Token program(22) for quote")]
     BatchUpdate = 6,
 
     /// Create global account for a given token.
@@ -137,10 +156,11 @@ pub enum ManifestInstruction {
     #[account(2, writable, name = "market", desc = "Account holding all market state")]
     #[account(3, name = "system_program", desc = "System program")]
     #[account(4, optional, name = "session_token", desc = "Session token for delegated signing")]
-    #[account(5, writable, name = "trader_quote", desc = "Trader quote token account")]
-    #[account(6, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
-    #[account(7, name = "token_program_quote", desc = "Token program(22) for quote")]
-    #[account(8, optional, name = "quote_mint", desc = "Quote mint, required if Token22")]
+    #[account(5, optional, name = "session_scope", desc = "Session scope PDA, required with a session token")]
+    #[account(6, writable, name = "trader_quote", desc = "Trader quote token account")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
+    #[account(8, name = "token_program_quote", desc = "Token program(22) for quote")]
+    #[account(9, optional, name = "quote_mint", desc = "Quote mint, required if Token22")]
     SwapV2 = 13,
 
     /// Delegate market account to MagicBlock ephemeral rollups.
@@ -165,20 +185,173 @@ pub enum ManifestInstruction {
     #[account(0, writable, signer, name = "liquidator", desc = "Liquidator")]
     #[account(1, writable, name = "market", desc = "Perps market account")]
     #[account(2, name = "system_program", desc = "System program")]
+    #[account(3, optional, name = "session_token", desc = "Session token for delegated signing")]
+    #[account(4, optional, name = "session_scope", desc = "Session scope PDA, required with a session token")]
     Liquidate = 16,
 
-    /// Crank funding rate using oracle price.
+    /// Crank funding rate using oracle price. The market's extra oracle
+    /// accounts follow the primary one, in market order, and the funding
+    /// history account, if passed, comes last.
     #[account(0, writable, signer, name = "payer", desc = "Payer / cranker")]
     #[account(1, writable, name = "market", desc = "Perps market account")]
-    #[account(2, name = "pyth_price_feed", desc = "Pyth price feed account")]
+    #[account(2, name = "pyth_price_feed", desc = "Primary oracle account")]
+    #[account(3, writable, optional, name = "funding_history", desc = "Funding history PDA, after the extra oracle accounts")]
     CrankFunding = 17,
 
     /// Release a claimed seat, freeing the block back to the free list.
-    /// Trader must have zero balances and no open position.
+    /// Trader must have zero balances and no open position. Without params
+    /// the default sub-account's seat is released.
     #[account(0, writable, signer, name = "payer", desc = "Payer / trader releasing seat")]
     #[account(1, writable, name = "market", desc = "Account holding all market state")]
     #[account(2, name = "system_program", desc = "System program")]
     ReleaseSeat = 18,
+
+    /// Rewrite a market created under an older account layout into the
+    /// current one, growing it first. Permissionless. A market too large to
+    /// grow in one instruction takes several calls; the last one migrates.
+    #[account(0, writable, signer, name = "payer", desc = "Payer of the added rent")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    #[account(2, name = "system_program", desc = "System program")]
+    MigrateMarket = 19,
+
+    /// Stake quote tokens (USDC) into the market insurance fund for shares.
+    #[account(0, writable, signer, name = "payer", desc = "Payer / staker")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "insurance_staker", desc = "Insurance staker PDA, seeds are [b'insurance-staker', market, staker]")]
+    #[account(3, writable, name = "trader_token", desc = "Staker quote token account")]
+    #[account(4, writable, name = "vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
+    #[account(5, name = "token_program", desc = "Token program(22)")]
+    #[account(6, name = "quote_mint", desc = "Quote mint")]
+    #[account(7, name = "system_program", desc = "System program")]
+    InsuranceDeposit = 20,
+
+    /// Redeem requested insurance fund shares for quote tokens (USDC) once the
+    /// cooldown since the request has elapsed.
+    #[account(0, signer, name = "payer", desc = "Payer / staker")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "insurance_staker", desc = "Insurance staker PDA, seeds are [b'insurance-staker', market, staker]")]
+    #[account(3, writable, name = "trader_token", desc = "Staker quote token account")]
+    #[account(4, writable, name = "vault", desc = "Quote vault PDA, seeds are [b'vault', market, quote_mint]")]
+    #[account(5, name = "token_program", desc = "Token program(22)")]
+    #[account(6, name = "quote_mint", desc = "Quote mint")]
+    InsuranceWithdraw = 21,
+
+    /// Ask to withdraw insurance fund shares, starting the withdraw cooldown.
+    /// Replaces any pending request.
+    #[account(0, signer, name = "payer", desc = "Payer / staker")]
+    #[account(1, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "insurance_staker", desc = "Insurance staker PDA, seeds are [b'insurance-staker', market, staker]")]
+    InsuranceRequestWithdraw = 22,
+
+    /// Update market parameters. Only the market authority can call this.
+    #[account(0, signer, name = "authority", desc = "Market authority")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    UpdateMarket = 23,
+
+    /// Create the market's funding history account, which CrankFunding
+    /// records every applied funding rate in.
+    #[account(0, writable, signer, name = "payer", desc = "Payer")]
+    #[account(1, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "funding_history", desc = "Funding history PDA, seeds are [b'funding-history', market]")]
+    #[account(3, name = "system_program", desc = "System program")]
+    CreateFundingHistory = 24,
+
+    /// Create the SeatExtension of one of the signer's seats, which keeps its
+    /// lifetime realized PnL, funding and fees. Called again on an existing
+    /// extension, it takes a new snapshot of the seat, for a seat released
+    /// and claimed again.
+    #[account(0, writable, signer, name = "payer", desc = "Trader")]
+    #[account(1, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "seat_extension", desc = "SeatExtension PDA, seeds are [b'seat-extension', market, trader, sub_account_id]")]
+    #[account(3, name = "system_program", desc = "System program")]
+    CreateSeatExtension = 25,
+
+    /// Move a trader's unsettled realized PnL into their margin. Anyone can
+    /// settle any trader, so makers' PnL does not wait on their next order.
+    #[account(0, signer, name = "payer", desc = "Payer")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    SettlePnl = 26,
+
+    /// Create the signer's cross-margin account for a quote mint and the
+    /// token account holding its collateral.
+    #[account(0, writable, signer, name = "payer", desc = "Payer and owner")]
+    #[account(1, writable, name = "cross_margin", desc = "Cross-margin PDA, seeds are [b'cross-margin', owner, quote_mint]")]
+    #[account(2, name = "quote_mint", desc = "Quote mint")]
+    #[account(3, writable, name = "vault", desc = "Collateral vault, ATA of the cross-margin account")]
+    #[account(4, name = "token_program", desc = "Token program")]
+    #[account(5, name = "associated_token_program", desc = "Associated token program")]
+    #[account(6, name = "system_program", desc = "System program")]
+    CreateCrossMarginAccount = 27,
+
+    /// Deposit quote tokens into a cross-margin account as collateral.
+    #[account(0, signer, name = "payer", desc = "Owner")]
+    #[account(1, writable, name = "cross_margin", desc = "Cross-margin account")]
+    #[account(2, writable, name = "trader_token", desc = "Owner quote token account")]
+    #[account(3, writable, name = "vault", desc = "Collateral vault, ATA of the cross-margin account")]
+    #[account(4, name = "token_program", desc = "Token program(22)")]
+    #[account(5, name = "quote_mint", desc = "Quote mint")]
+    CrossMarginDeposit = 28,
+
+    /// Withdraw collateral from a cross-margin account. Every linked market
+    /// follows, in link order, and the portfolio must stay above maintenance.
+    #[account(0, signer, name = "payer", desc = "Owner")]
+    #[account(1, writable, name = "cross_margin", desc = "Cross-margin account")]
+    #[account(2, writable, name = "trader_token", desc = "Owner quote token account")]
+    #[account(3, writable, name = "vault", desc = "Collateral vault, ATA of the cross-margin account")]
+    #[account(4, name = "token_program", desc = "Token program(22)")]
+    #[account(5, name = "quote_mint", desc = "Quote mint")]
+    CrossMarginWithdraw = 29,
+
+    /// Link the signer's seat on a market to their cross-margin account, or
+    /// unlink it. Linked seats pass the cross-margin account and their other
+    /// linked markets to every instruction that checks margin.
+    #[account(0, signer, name = "payer", desc = "Owner")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "cross_margin", desc = "Cross-margin account")]
+    CrossMarginLink = 30,
+
+    /// Move quote between the signer's seat margin on a market and their
+    /// cross-margin collateral. The linked markets other than this one
+    /// follow, in link order.
+    #[account(0, signer, name = "payer", desc = "Owner")]
+    #[account(1, writable, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "cross_margin", desc = "Cross-margin account")]
+    #[account(3, writable, name = "market_vault", desc = "Quote vault of the market")]
+    #[account(4, writable, name = "cross_margin_vault", desc = "Collateral vault, ATA of the cross-margin account")]
+    #[account(5, name = "token_program", desc = "Token program(22)")]
+    CrossMarginTransfer = 31,
+
+    /// Name a key that may place and cancel orders and swap for one of the
+    /// signer's seats. It can never withdraw or release the seat. Creates the
+    /// seat's TradeDelegate record on first use.
+    #[account(0, writable, signer, name = "payer", desc = "Trader")]
+    #[account(1, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "trade_delegate", desc = "TradeDelegate PDA, seeds are [b'trade-delegate', market, trader, sub_account_id]")]
+    #[account(3, name = "system_program", desc = "System program")]
+    SetTradeDelegate = 32,
+
+    /// Revoke the trade delegate of one of the signer's seats.
+    #[account(0, signer, name = "payer", desc = "Trader")]
+    #[account(1, name = "market", desc = "Account holding all market state")]
+    #[account(2, writable, name = "trade_delegate", desc = "TradeDelegate PDA, seeds are [b'trade-delegate', market, trader, sub_account_id]")]
+    RevokeTradeDelegate = 33,
+
+    /// Limit what a session signer acting for the signer may do: markets,
+    /// order notional, position size and instructions. Creates the
+    /// SessionScope record on first use; once it exists every use of the
+    /// session is checked against it.
+    #[account(0, writable, signer, name = "payer", desc = "Session authority")]
+    #[account(1, writable, name = "session_scope", desc = "SessionScope PDA, seeds are [b'session-scope', authority, session_signer]")]
+    #[account(2, name = "system_program", desc = "System program")]
+    SetSessionScope = 34,
+
+    /// Set the wallets the signer's withdrawals may pay besides the signer,
+    /// replacing any previous list. Creates the WithdrawAllowlist record on
+    /// first use.
+    #[account(0, writable, signer, name = "payer", desc = "Trader")]
+    #[account(1, writable, name = "withdraw_allowlist", desc = "WithdrawAllowlist PDA, seeds are [b'withdraw-allowlist', trader]")]
+    #[account(2, name = "system_program", desc = "System program")]
+    SetWithdrawAllowlist = 35,
 }
 
 impl ManifestInstruction {
@@ -189,7 +362,7 @@ impl ManifestInstruction {
 
 #[test]
 fn test_instruction_serialization() {
-    let num_instructions: u8 = 18;
+    let num_instructions: u8 = 35;
     for i in 0..=255 {
         let instruction: ManifestInstruction = match ManifestInstruction::try_from(i) {
             Ok(j) => {
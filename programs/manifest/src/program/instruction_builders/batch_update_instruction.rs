@@ -77,6 +77,33 @@ pub fn batch_update_instruction(
     }
 }
 
+/// Cancels and places orders for one of the payer's sub-account seats. Only
+/// for markets without global orders.
+#[cfg(not(feature = "certora"))]
+pub fn batch_update_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    sub_account_id: u8,
+    cancels: Vec<CancelOrderParams>,
+    orders: Vec<PlaceOrderParams>,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::BatchUpdate.to_vec(),
+            BatchUpdateParams::new_for_sub_account(None, cancels, orders, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
 #[cfg(feature = "certora")]
 pub fn batch_update_instruction(
     _market: &Pubkey,
@@ -1,4 +1,5 @@
-use crate::program::ManifestInstruction;
+use crate::program::{claim_seat::ClaimSeatParams, ManifestInstruction};
+use borsh::BorshSerialize;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
@@ -16,3 +17,23 @@ pub fn claim_seat_instruction(market: &Pubkey, payer: &Pubkey) -> Instruction {
         data: [ManifestInstruction::ClaimSeat.to_vec()].concat(),
     }
 }
+
+pub fn claim_seat_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::ClaimSeat.to_vec(),
+            ClaimSeatParams::new(sub_account_id).try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
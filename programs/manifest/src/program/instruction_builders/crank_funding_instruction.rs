@@ -1,4 +1,7 @@
-use crate::program::{crank_funding::CrankFundingParams, ManifestInstruction};
+use crate::{
+    program::{crank_funding::CrankFundingParams, ManifestInstruction},
+    validation::get_funding_history_address,
+};
 use borsh::BorshSerialize;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -10,13 +13,28 @@ pub fn crank_funding_instruction(
     payer: &Pubkey,
     pyth_price_feed: &Pubkey,
 ) -> Instruction {
+    crank_funding_with_oracles_instruction(market, payer, &[*pyth_price_feed])
+}
+
+/// Crank funding on a market with extra oracle sources. `oracle_feeds` holds
+/// the primary oracle account followed by the extra ones, in market order.
+pub fn crank_funding_with_oracles_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    oracle_feeds: &[Pubkey],
+) -> Instruction {
+    let mut accounts: Vec<AccountMeta> = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*market, false),
+    ];
+    accounts.extend(
+        oracle_feeds
+            .iter()
+            .map(|oracle_feed| AccountMeta::new_readonly(*oracle_feed, false)),
+    );
     Instruction {
         program_id: crate::id(),
-        accounts: vec![
-            AccountMeta::new(*payer, true),
-            AccountMeta::new(*market, false),
-            AccountMeta::new_readonly(*pyth_price_feed, false),
-        ],
+        accounts,
         data: [
             ManifestInstruction::CrankFunding.to_vec(),
             CrankFundingParams::new().try_to_vec().unwrap(),
@@ -24,3 +42,19 @@ pub fn crank_funding_instruction(
         .concat(),
     }
 }
+
+/// Crank funding and record it in the market's funding history account.
+/// `oracle_feeds` is the same as for `crank_funding_with_oracles_instruction`.
+pub fn crank_funding_with_history_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    oracle_feeds: &[Pubkey],
+) -> Instruction {
+    let mut instruction: Instruction =
+        crank_funding_with_oracles_instruction(market, payer, oracle_feeds);
+    let (funding_history, _) = get_funding_history_address(market);
+    instruction
+        .accounts
+        .push(AccountMeta::new(funding_history, false));
+    instruction
+}
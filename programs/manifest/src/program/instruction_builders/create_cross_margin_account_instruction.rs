@@ -0,0 +1,46 @@
+use crate::{
+    program::ManifestInstruction,
+    validation::{get_cross_margin_address, get_vault_address},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn create_cross_margin_account_instruction(payer: &Pubkey, quote_mint: &Pubkey) -> Instruction {
+    let (cross_margin, _) = get_cross_margin_address(payer, quote_mint);
+    let (vault, _) = get_vault_address(&cross_margin, quote_mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(cross_margin, false),
+            AccountMeta::new_readonly(*quote_mint, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: ManifestInstruction::CreateCrossMarginAccount.to_vec(),
+    }
+}
+
+/// Accounts a cross-margin seat passes to BatchUpdate, Swap and Withdraw on
+/// a market, and to Liquidate: the cross-margin account and every other
+/// linked market, in link order. They go after the optional session token,
+/// or at the end for Withdraw.
+pub fn cross_margin_account_metas(
+    owner: &Pubkey,
+    quote_mint: &Pubkey,
+    other_linked_markets: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let (cross_margin, _) = get_cross_margin_address(owner, quote_mint);
+    let mut account_metas: Vec<AccountMeta> = vec![AccountMeta::new(cross_margin, false)];
+    account_metas.extend(
+        other_linked_markets
+            .iter()
+            .map(|market| AccountMeta::new_readonly(*market, false)),
+    );
+    account_metas
+}
@@ -0,0 +1,20 @@
+use crate::{program::ManifestInstruction, validation::get_funding_history_address};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn create_funding_history_instruction(market: &Pubkey, payer: &Pubkey) -> Instruction {
+    let (funding_history, _) = get_funding_history_address(market);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*market, false),
+            AccountMeta::new(funding_history, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: ManifestInstruction::CreateFundingHistory.to_vec(),
+    }
+}
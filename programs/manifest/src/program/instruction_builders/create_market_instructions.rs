@@ -1,6 +1,6 @@
 use crate::{
     program::{create_market::CreateMarketParams, ManifestInstruction},
-    validation::{get_market_address, get_vault_address},
+    validation::get_market_address,
 };
 use borsh::BorshSerialize;
 use solana_program::{
@@ -26,6 +26,11 @@ pub fn create_market_instructions(
     pyth_feed_account: Pubkey,
     taker_fee_bps: u64,
     liquidation_buffer_bps: u64,
+    liquidator_reward_bps: u64,
+    min_position_size_atoms: u64,
+    funding_period_secs: u64,
+    max_funding_rate: u64,
+    funding_interest_rate: i64,
     num_blocks: u32,
 ) -> Vec<Instruction> {
     let (market, _) = get_market_address(base_mint_index, quote_mint);
@@ -40,6 +45,11 @@ pub fn create_market_instructions(
         pyth_feed_account,
         taker_fee_bps,
         liquidation_buffer_bps,
+        liquidator_reward_bps,
+        min_position_size_atoms,
+        funding_period_secs,
+        max_funding_rate,
+        funding_interest_rate,
         num_blocks,
     )]
 }
@@ -56,6 +66,11 @@ pub fn create_market_instruction(
     pyth_feed_account: Pubkey,
     taker_fee_bps: u64,
     liquidation_buffer_bps: u64,
+    liquidator_reward_bps: u64,
+    min_position_size_atoms: u64,
+    funding_period_secs: u64,
+    max_funding_rate: u64,
+    funding_interest_rate: i64,
     num_blocks: u32,
 ) -> Instruction {
     let quote_vault = get_associated_token_address(market, quote_mint);
@@ -87,6 +102,11 @@ pub fn create_market_instruction(
                 pyth_feed_account,
                 taker_fee_bps,
                 liquidation_buffer_bps,
+                liquidator_reward_bps,
+                min_position_size_atoms,
+                funding_period_secs,
+                max_funding_rate,
+                funding_interest_rate,
                 num_blocks,
             )
             .try_to_vec()
@@ -0,0 +1,48 @@
+use crate::{
+    program::{create_seat_extension::CreateSeatExtensionParams, ManifestInstruction},
+    validation::get_seat_extension_address,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn create_seat_extension_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    let (seat_extension, _) = get_seat_extension_address(market, payer, sub_account_id);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*market, false),
+            AccountMeta::new(seat_extension, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::CreateSeatExtension.to_vec(),
+            CreateSeatExtensionParams::new(sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Account that keeps a seat's lifetime statistics up to date. BatchUpdate
+/// and Swap take it after the optional trade delegate, Deposit and Withdraw
+/// after the optional session accounts, Liquidate after all other accounts
+/// for any of the seats involved, and SettlePnl, CrossMarginLink and
+/// CrossMarginTransfer at the end.
+pub fn seat_extension_account_meta(
+    market: &Pubkey,
+    trader: &Pubkey,
+    sub_account_id: u8,
+) -> AccountMeta {
+    let (seat_extension, _) = get_seat_extension_address(market, trader, sub_account_id);
+    AccountMeta::new(seat_extension, false)
+}
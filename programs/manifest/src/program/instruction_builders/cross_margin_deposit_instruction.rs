@@ -0,0 +1,38 @@
+use crate::{
+    program::{cross_margin_deposit::CrossMarginDepositParams, ManifestInstruction},
+    validation::{get_cross_margin_address, get_vault_address},
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn cross_margin_deposit_instruction(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+) -> Instruction {
+    let (cross_margin, _) = get_cross_margin_address(payer, mint);
+    let (vault, _) = get_vault_address(&cross_margin, mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(cross_margin, false),
+            AccountMeta::new(*trader_token_account, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data: [
+            ManifestInstruction::CrossMarginDeposit.to_vec(),
+            CrossMarginDepositParams::new(amount_atoms)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{
+    program::{cross_margin_link::CrossMarginLinkParams, ManifestInstruction},
+    validation::get_cross_margin_address,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn cross_margin_link_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    quote_mint: &Pubkey,
+    link: bool,
+) -> Instruction {
+    cross_margin_link_for_sub_account_instruction(market, payer, quote_mint, link, 0)
+}
+
+/// Link or unlink one of the payer's sub-account seats.
+pub fn cross_margin_link_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    quote_mint: &Pubkey,
+    link: bool,
+    sub_account_id: u8,
+) -> Instruction {
+    let (cross_margin, _) = get_cross_margin_address(payer, quote_mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(cross_margin, false),
+        ],
+        data: [
+            ManifestInstruction::CrossMarginLink.to_vec(),
+            CrossMarginLinkParams::new_for_sub_account(link, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
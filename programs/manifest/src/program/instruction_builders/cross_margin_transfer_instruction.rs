@@ -0,0 +1,78 @@
+use crate::{
+    program::{cross_margin_transfer::CrossMarginTransferParams, ManifestInstruction},
+    validation::{get_cross_margin_address, get_vault_address},
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+/// `other_linked_markets` are the markets the cross-margin account links
+/// other than `market`, in link order.
+pub fn cross_margin_transfer_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    quote_mint: &Pubkey,
+    amount_atoms: u64,
+    to_cross_margin: bool,
+    token_program: Pubkey,
+    other_linked_markets: &[Pubkey],
+) -> Instruction {
+    cross_margin_transfer_for_sub_account_instruction(
+        market,
+        payer,
+        quote_mint,
+        amount_atoms,
+        to_cross_margin,
+        token_program,
+        other_linked_markets,
+        0,
+    )
+}
+
+/// Move quote between one of the payer's sub-account seats and their
+/// cross-margin collateral.
+#[allow(clippy::too_many_arguments)]
+pub fn cross_margin_transfer_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    quote_mint: &Pubkey,
+    amount_atoms: u64,
+    to_cross_margin: bool,
+    token_program: Pubkey,
+    other_linked_markets: &[Pubkey],
+    sub_account_id: u8,
+) -> Instruction {
+    let (cross_margin, _) = get_cross_margin_address(payer, quote_mint);
+    let (market_vault, _) = get_vault_address(market, quote_mint);
+    let (cross_margin_vault, _) = get_vault_address(&cross_margin, quote_mint);
+    let mut account_metas: Vec<AccountMeta> = vec![
+        AccountMeta::new_readonly(*payer, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(cross_margin, false),
+        AccountMeta::new(market_vault, false),
+        AccountMeta::new(cross_margin_vault, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    account_metas.extend(
+        other_linked_markets
+            .iter()
+            .map(|market| AccountMeta::new_readonly(*market, false)),
+    );
+    Instruction {
+        program_id: crate::id(),
+        accounts: account_metas,
+        data: [
+            ManifestInstruction::CrossMarginTransfer.to_vec(),
+            CrossMarginTransferParams::new_for_sub_account(
+                amount_atoms,
+                to_cross_margin,
+                sub_account_id,
+            )
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
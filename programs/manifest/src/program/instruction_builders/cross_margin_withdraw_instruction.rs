@@ -0,0 +1,47 @@
+use crate::{
+    program::{cross_margin_withdraw::CrossMarginWithdrawParams, ManifestInstruction},
+    validation::{get_cross_margin_address, get_vault_address},
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+/// `linked_markets` are all the markets the cross-margin account links, in
+/// link order.
+pub fn cross_margin_withdraw_instruction(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+    linked_markets: &[Pubkey],
+) -> Instruction {
+    let (cross_margin, _) = get_cross_margin_address(payer, mint);
+    let (vault, _) = get_vault_address(&cross_margin, mint);
+    let mut account_metas: Vec<AccountMeta> = vec![
+        AccountMeta::new_readonly(*payer, true),
+        AccountMeta::new(cross_margin, false),
+        AccountMeta::new(*trader_token_account, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new_readonly(token_program, false),
+        AccountMeta::new_readonly(*mint, false),
+    ];
+    account_metas.extend(
+        linked_markets
+            .iter()
+            .map(|market| AccountMeta::new_readonly(*market, false)),
+    );
+    Instruction {
+        program_id: crate::id(),
+        accounts: account_metas,
+        data: [
+            ManifestInstruction::CrossMarginWithdraw.to_vec(),
+            CrossMarginWithdrawParams::new(amount_atoms)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
@@ -31,6 +31,70 @@ pub fn deposit_instruction(
     )
 }
 
+/// Deposit instruction for one of the payer's sub-account seats.
+pub fn deposit_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    let (vault_address, _) = get_vault_address(market, mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(*trader_token_account, false),
+            AccountMeta::new(vault_address, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data: [
+            ManifestInstruction::Deposit.to_vec(),
+            DepositParams::new_for_sub_account(amount_atoms, None, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Deposit instruction crediting `beneficiary`'s seat from the payer's
+/// tokens.
+pub fn deposit_for_beneficiary_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+    beneficiary: &Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    let (vault_address, _) = get_vault_address(market, mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(*trader_token_account, false),
+            AccountMeta::new(vault_address, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data: [
+            ManifestInstruction::Deposit.to_vec(),
+            DepositParams::new_for_beneficiary(amount_atoms, None, *beneficiary, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
 /// Deposit instruction with an explicit vault address.
 /// Use this for ephemeral mode where the vault is an EphemeralAta
 /// at a different address than the SPL vault PDA.
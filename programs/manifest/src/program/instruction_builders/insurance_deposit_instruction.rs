@@ -0,0 +1,42 @@
+use crate::{
+    program::{insurance_deposit::InsuranceDepositParams, ManifestInstruction},
+    validation::{get_insurance_staker_address, get_vault_address},
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn insurance_deposit_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+) -> Instruction {
+    let (vault_address, _) = get_vault_address(market, mint);
+    let (insurance_staker, _) = get_insurance_staker_address(market, payer);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(insurance_staker, false),
+            AccountMeta::new(*trader_token_account, false),
+            AccountMeta::new(vault_address, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::InsuranceDeposit.to_vec(),
+            InsuranceDepositParams::new(amount_atoms)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
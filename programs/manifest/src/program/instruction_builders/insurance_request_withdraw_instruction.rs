@@ -0,0 +1,32 @@
+use crate::{
+    program::{insurance_request_withdraw::InsuranceRequestWithdrawParams, ManifestInstruction},
+    validation::get_insurance_staker_address,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn insurance_request_withdraw_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    shares: u64,
+) -> Instruction {
+    let (insurance_staker, _) = get_insurance_staker_address(market, payer);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new_readonly(*market, false),
+            AccountMeta::new(insurance_staker, false),
+        ],
+        data: [
+            ManifestInstruction::InsuranceRequestWithdraw.to_vec(),
+            InsuranceRequestWithdrawParams::new(shares)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
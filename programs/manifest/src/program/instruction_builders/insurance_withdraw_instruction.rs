@@ -0,0 +1,38 @@
+use crate::{
+    program::{insurance_withdraw::InsuranceWithdrawParams, ManifestInstruction},
+    validation::{get_insurance_staker_address, get_vault_address},
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn insurance_withdraw_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    shares: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+) -> Instruction {
+    let (vault_address, _) = get_vault_address(market, mint);
+    let (insurance_staker, _) = get_insurance_staker_address(market, payer);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(insurance_staker, false),
+            AccountMeta::new(*trader_token_account, false),
+            AccountMeta::new(vault_address, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data: [
+            ManifestInstruction::InsuranceWithdraw.to_vec(),
+            InsuranceWithdrawParams::new(shares).try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
@@ -1,5 +1,13 @@
-use crate::program::{liquidate::LiquidateParams, ManifestInstruction};
+use super::cross_margin_account_metas;
+use crate::{
+    program::{
+        liquidate::{LiquidateParams, LiquidationMode},
+        ManifestInstruction,
+    },
+    validation::{get_cross_margin_address, get_vault_address},
+};
 use borsh::BorshSerialize;
+use hypertree::DataIndex;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
@@ -10,6 +18,61 @@ pub fn liquidate_instruction(
     market: &Pubkey,
     liquidator: &Pubkey,
     trader_to_liquidate: &Pubkey,
+) -> Instruction {
+    liquidate_instruction_with_mode(
+        market,
+        liquidator,
+        trader_to_liquidate,
+        LiquidationMode::MarkPrice,
+    )
+}
+
+/// Liquidate instruction with an explicit liquidation mode.
+pub fn liquidate_instruction_with_mode(
+    market: &Pubkey,
+    liquidator: &Pubkey,
+    trader_to_liquidate: &Pubkey,
+    mode: LiquidationMode,
+) -> Instruction {
+    liquidate_batch_instruction(market, liquidator, vec![*trader_to_liquidate], mode)
+}
+
+/// Liquidate several traders against one mark price. An empty list liquidates
+/// every seat that is below maintenance margin, as far as compute allows.
+pub fn liquidate_batch_instruction(
+    market: &Pubkey,
+    liquidator: &Pubkey,
+    traders_to_liquidate: Vec<Pubkey>,
+    mode: LiquidationMode,
+) -> Instruction {
+    liquidate_instruction_with_params(
+        market,
+        liquidator,
+        LiquidateParams::new_batch(traders_to_liquidate, mode),
+    )
+}
+
+/// Scan up to `scan_limit` seats with a position, from the seat at
+/// `scan_start_index` or the first seat when NIL, and liquidate those below
+/// maintenance margin.
+pub fn liquidate_scan_instruction(
+    market: &Pubkey,
+    liquidator: &Pubkey,
+    mode: LiquidationMode,
+    scan_start_index: DataIndex,
+    scan_limit: u32,
+) -> Instruction {
+    liquidate_instruction_with_params(
+        market,
+        liquidator,
+        LiquidateParams::new_scan(mode, scan_start_index, scan_limit),
+    )
+}
+
+fn liquidate_instruction_with_params(
+    market: &Pubkey,
+    liquidator: &Pubkey,
+    params: LiquidateParams,
 ) -> Instruction {
     Instruction {
         program_id: crate::id(),
@@ -20,10 +83,36 @@ pub fn liquidate_instruction(
         ],
         data: [
             ManifestInstruction::Liquidate.to_vec(),
-            LiquidateParams::new(*trader_to_liquidate)
-                .try_to_vec()
-                .unwrap(),
+            params.try_to_vec().unwrap(),
         ]
         .concat(),
     }
 }
+
+/// Liquidate a cross-margin trader. `other_linked_markets` are the markets
+/// their cross-margin account links other than this one, in link order.
+pub fn liquidate_cross_margin_instruction(
+    market: &Pubkey,
+    liquidator: &Pubkey,
+    trader_to_liquidate: &Pubkey,
+    quote_mint: &Pubkey,
+    other_linked_markets: &[Pubkey],
+    mode: LiquidationMode,
+) -> Instruction {
+    let mut instruction: Instruction =
+        liquidate_instruction_with_mode(market, liquidator, trader_to_liquidate, mode);
+    let (cross_margin, _) = get_cross_margin_address(trader_to_liquidate, quote_mint);
+    let (cross_margin_vault, _) = get_vault_address(&cross_margin, quote_mint);
+    let (market_vault, _) = get_vault_address(market, quote_mint);
+    instruction.accounts.extend(cross_margin_account_metas(
+        trader_to_liquidate,
+        quote_mint,
+        other_linked_markets,
+    ));
+    instruction.accounts.extend([
+        AccountMeta::new(cross_margin_vault, false),
+        AccountMeta::new(market_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]);
+    instruction
+}
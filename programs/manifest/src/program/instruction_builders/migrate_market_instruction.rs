@@ -0,0 +1,21 @@
+use crate::program::ManifestInstruction;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Migrate a market from an older account layout. Markets too large to grow
+/// in one instruction need the same instruction sent again until it logs
+/// MigrateMarketLog.
+pub fn migrate_market_instruction(market: &Pubkey, payer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: ManifestInstruction::MigrateMarket.to_vec(),
+    }
+}
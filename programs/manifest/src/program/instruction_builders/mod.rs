@@ -1,9 +1,14 @@
 pub mod batch_update_instruction;
 pub mod claim_seat_instruction;
 pub mod crank_funding_instruction;
+pub mod create_cross_margin_account_instruction;
+pub mod create_funding_history_instruction;
 pub mod create_market_instructions;
-pub mod liquidate_instruction;
-pub mod release_seat_instruction;
+pub mod create_seat_extension_instruction;
+pub mod cross_margin_deposit_instruction;
+pub mod cross_margin_link_instruction;
+pub mod cross_margin_transfer_instruction;
+pub mod cross_margin_withdraw_instruction;
 pub mod deposit_instruction;
 pub mod expand_market_instruction;
 pub mod global_add_trader_instruction;
@@ -12,16 +17,34 @@ pub mod global_create_instruction;
 pub mod global_deposit_instruction;
 pub mod global_evict_instruction;
 pub mod global_withdraw_instruction;
+pub mod insurance_deposit_instruction;
+pub mod insurance_request_withdraw_instruction;
+pub mod insurance_withdraw_instruction;
+pub mod liquidate_instruction;
+pub mod migrate_market_instruction;
+pub mod release_seat_instruction;
+pub mod revoke_trade_delegate_instruction;
+pub mod session_token;
+pub mod set_session_scope_instruction;
+pub mod set_trade_delegate_instruction;
+pub mod set_withdraw_allowlist_instruction;
+pub mod settle_pnl_instruction;
 pub mod swap_instruction;
 pub mod swap_v2_instruction;
+pub mod update_market_instruction;
 pub mod withdraw_instruction;
 
 pub use batch_update_instruction::*;
 pub use claim_seat_instruction::*;
 pub use crank_funding_instruction::*;
+pub use create_cross_margin_account_instruction::*;
+pub use create_funding_history_instruction::*;
 pub use create_market_instructions::*;
-pub use liquidate_instruction::*;
-pub use release_seat_instruction::*;
+pub use create_seat_extension_instruction::*;
+pub use cross_margin_deposit_instruction::*;
+pub use cross_margin_link_instruction::*;
+pub use cross_margin_transfer_instruction::*;
+pub use cross_margin_withdraw_instruction::*;
 pub use deposit_instruction::*;
 pub use expand_market_instruction::*;
 pub use global_add_trader_instruction::*;
@@ -30,6 +53,19 @@ pub use global_create_instruction::*;
 pub use global_deposit_instruction::*;
 pub use global_evict_instruction::*;
 pub use global_withdraw_instruction::*;
+pub use insurance_deposit_instruction::*;
+pub use insurance_request_withdraw_instruction::*;
+pub use insurance_withdraw_instruction::*;
+pub use liquidate_instruction::*;
+pub use migrate_market_instruction::*;
+pub use release_seat_instruction::*;
+pub use revoke_trade_delegate_instruction::*;
+pub use session_token::*;
+pub use set_session_scope_instruction::*;
+pub use set_trade_delegate_instruction::*;
+pub use set_withdraw_allowlist_instruction::*;
+pub use settle_pnl_instruction::*;
 pub use swap_instruction::*;
 pub use swap_v2_instruction::*;
+pub use update_market_instruction::*;
 pub use withdraw_instruction::*;
@@ -1,13 +1,27 @@
-use crate::program::ManifestInstruction;
+use crate::program::{release_seat::ReleaseSeatParams, ManifestInstruction};
+use borsh::BorshSerialize;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     system_program,
 };
 
-pub fn release_seat_instruction(
+pub fn release_seat_instruction(market: &Pubkey, payer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: ManifestInstruction::ReleaseSeat.to_vec(),
+    }
+}
+
+pub fn release_seat_for_sub_account_instruction(
     market: &Pubkey,
     payer: &Pubkey,
+    sub_account_id: u8,
 ) -> Instruction {
     Instruction {
         program_id: crate::id(),
@@ -16,6 +30,10 @@ pub fn release_seat_instruction(
             AccountMeta::new(*market, false),
             AccountMeta::new_readonly(system_program::id(), false),
         ],
-        data: ManifestInstruction::ReleaseSeat.to_vec(),
+        data: [
+            ManifestInstruction::ReleaseSeat.to_vec(),
+            ReleaseSeatParams::new(sub_account_id).try_to_vec().unwrap(),
+        ]
+        .concat(),
     }
 }
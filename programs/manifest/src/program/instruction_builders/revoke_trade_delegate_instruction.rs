@@ -0,0 +1,22 @@
+use crate::{program::ManifestInstruction, validation::get_trade_delegate_address};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn revoke_trade_delegate_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    let (trade_delegate, _) = get_trade_delegate_address(market, payer, sub_account_id);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new_readonly(*market, false),
+            AccountMeta::new(trade_delegate, false),
+        ],
+        data: ManifestInstruction::RevokeTradeDelegate.to_vec(),
+    }
+}
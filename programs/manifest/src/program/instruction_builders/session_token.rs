@@ -0,0 +1,23 @@
+use crate::{
+    state::{SessionToken, SESSION_KEYS_PROGRAM_ID},
+    validation::get_session_scope_address,
+};
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+
+/// Session token a session signer passes to act for `authority`, followed by
+/// the session's scope record, which need not exist. They go right after the
+/// market in Deposit and Withdraw, and right after the system program in
+/// Swap, BatchUpdate and Liquidate.
+pub fn session_account_metas(authority: &Pubkey, session_signer: &Pubkey) -> Vec<AccountMeta> {
+    let (session_token, _) = SessionToken::get_address(
+        &crate::id(),
+        authority,
+        session_signer,
+        &SESSION_KEYS_PROGRAM_ID,
+    );
+    let (session_scope, _) = get_session_scope_address(authority, session_signer);
+    vec![
+        AccountMeta::new_readonly(session_token, false),
+        AccountMeta::new_readonly(session_scope, false),
+    ]
+}
@@ -0,0 +1,43 @@
+use crate::{
+    program::{set_session_scope::SetSessionScopeParams, ManifestInstruction},
+    state::SessionScope,
+    validation::get_session_scope_address,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn set_session_scope_instruction(
+    payer: &Pubkey,
+    session_signer: &Pubkey,
+    markets: Vec<Pubkey>,
+    max_order_notional_atoms: u64,
+    max_position_atoms: u64,
+    allowed_instructions: &[ManifestInstruction],
+) -> Instruction {
+    let (session_scope, _) = get_session_scope_address(payer, session_signer);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(session_scope, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::SetSessionScope.to_vec(),
+            SetSessionScopeParams::new(
+                *session_signer,
+                markets,
+                max_order_notional_atoms,
+                max_position_atoms,
+                SessionScope::instructions_mask(allowed_instructions),
+            )
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    program::{set_trade_delegate::SetTradeDelegateParams, ManifestInstruction},
+    validation::get_trade_delegate_address,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn set_trade_delegate_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    delegate: &Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    let (trade_delegate, _) = get_trade_delegate_address(market, payer, sub_account_id);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*market, false),
+            AccountMeta::new(trade_delegate, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::SetTradeDelegate.to_vec(),
+            SetTradeDelegateParams::new(*delegate, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Account a trade delegate passes to BatchUpdate and Swap to act for a
+/// seat. It goes after the optional session token.
+pub fn trade_delegate_account_meta(
+    market: &Pubkey,
+    trader: &Pubkey,
+    sub_account_id: u8,
+) -> AccountMeta {
+    let (trade_delegate, _) = get_trade_delegate_address(market, trader, sub_account_id);
+    AccountMeta::new_readonly(trade_delegate, false)
+}
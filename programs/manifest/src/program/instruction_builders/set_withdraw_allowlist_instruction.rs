@@ -0,0 +1,29 @@
+use crate::{
+    program::{set_withdraw_allowlist::SetWithdrawAllowlistParams, ManifestInstruction},
+    validation::get_withdraw_allowlist_address,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn set_withdraw_allowlist_instruction(payer: &Pubkey, recipients: Vec<Pubkey>) -> Instruction {
+    let (withdraw_allowlist, _) = get_withdraw_allowlist_address(payer);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(withdraw_allowlist, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            ManifestInstruction::SetWithdrawAllowlist.to_vec(),
+            SetWithdrawAllowlistParams::new(recipients)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
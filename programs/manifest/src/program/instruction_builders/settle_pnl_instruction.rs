@@ -0,0 +1,43 @@
+use crate::program::{settle_pnl::SettlePnlParams, ManifestInstruction};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn settle_pnl_instruction(market: &Pubkey, payer: &Pubkey, trader: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+        ],
+        data: [
+            ManifestInstruction::SettlePnl.to_vec(),
+            SettlePnlParams::new(*trader).try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn settle_pnl_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    trader: &Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+        ],
+        data: [
+            ManifestInstruction::SettlePnl.to_vec(),
+            SettlePnlParams::new_for_sub_account(*trader, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
@@ -0,0 +1,25 @@
+use crate::program::{update_market::UpdateMarketParams, ManifestInstruction};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+pub fn update_market_instruction(
+    market: &Pubkey,
+    authority: &Pubkey,
+    params: UpdateMarketParams,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*market, false),
+        ],
+        data: [
+            ManifestInstruction::UpdateMarket.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     program::{withdraw::WithdrawParams, ManifestInstruction},
-    validation::get_vault_address,
+    validation::{get_vault_address, get_withdraw_allowlist_address},
 };
 use borsh::BorshSerialize;
 use hypertree::DataIndex;
@@ -31,6 +31,63 @@ pub fn withdraw_instruction(
     )
 }
 
+/// Withdraw instruction for one of the payer's sub-account seats.
+pub fn withdraw_for_sub_account_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    trader_token_account: &Pubkey,
+    token_program: Pubkey,
+    sub_account_id: u8,
+) -> Instruction {
+    let (vault_address, _) = get_vault_address(market, mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(*trader_token_account, false),
+            AccountMeta::new(vault_address, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data: [
+            ManifestInstruction::Withdraw.to_vec(),
+            WithdrawParams::new_for_sub_account(amount_atoms, None, sub_account_id)
+                .try_to_vec()
+                .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Withdraw instruction paying a token account owned by a wallet on the
+/// payer's withdraw allowlist.
+pub fn withdraw_to_recipient_instruction(
+    market: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount_atoms: u64,
+    recipient_token_account: &Pubkey,
+    token_program: Pubkey,
+) -> Instruction {
+    let mut instruction: Instruction = withdraw_instruction(
+        market,
+        payer,
+        mint,
+        amount_atoms,
+        recipient_token_account,
+        token_program,
+        None,
+    );
+    let (withdraw_allowlist, _) = get_withdraw_allowlist_address(payer);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(withdraw_allowlist, false));
+    instruction
+}
+
 /// Withdraw instruction with an explicit vault address.
 /// Use this for ephemeral mode where the vault is an EphemeralAta
 /// at a different address than the SPL vault PDA.
@@ -2,13 +2,12 @@ use std::cell::RefMut;
 
 use crate::{
     logs::{emit_stack, CancelOrderLog, PlaceOrderLog},
-    program::get_trader_index_with_hint,
     quantities::{BaseAtoms, PriceConversionError, QuoteAtomsPerBaseAtom, WrapperU64},
     require,
     state::{
         utils::{get_now_slot, try_to_pay_all_global_gas_prepayment},
         AddOrderToMarketArgs, AddOrderToMarketResult, MarketRefMut, OrderType, RestingOrder,
-        MARKET_BLOCK_SIZE,
+        SeatExtension, MARKET_BLOCK_SIZE,
     },
     validation::loaders::BatchUpdateContext,
 };
@@ -20,7 +19,12 @@ use solana_program::{
     pubkey::Pubkey,
 };
 
-use super::shared::get_mut_dynamic_account;
+#[cfg(not(feature = "certora"))]
+use super::shared::{cross_margin_summary, expect_cross_margin_accounts, MarginSummary};
+use super::shared::{
+    deserialize_trailing, get_mut_dynamic_account, get_seat_extension, require_params_consumed,
+    settle_funding_and_socialized_loss, settle_pnl,
+};
 
 use crate::validation::loaders::GlobalTradeAccounts;
 #[cfg(feature = "certora")]
@@ -121,6 +125,8 @@ pub struct BatchUpdateParams {
     pub orders: Vec<PlaceOrderParams>,
     #[cfg(feature = "certora")]
     pub orders: NoResizableVec<PlaceOrderParams>,
+    /// Which of the trader's sub-account seats to trade on.
+    pub sub_account_id: u8,
 }
 
 impl BatchUpdateParams {
@@ -130,13 +136,39 @@ impl BatchUpdateParams {
         #[cfg(feature = "certora")] cancels: NoResizableVec<CancelOrderParams>,
         #[cfg(not(feature = "certora"))] orders: Vec<PlaceOrderParams>,
         #[cfg(feature = "certora")] orders: NoResizableVec<PlaceOrderParams>,
+    ) -> Self {
+        BatchUpdateParams::new_for_sub_account(trader_index_hint, cancels, orders, 0)
+    }
+
+    pub fn new_for_sub_account(
+        trader_index_hint: Option<DataIndex>,
+        #[cfg(not(feature = "certora"))] cancels: Vec<CancelOrderParams>,
+        #[cfg(feature = "certora")] cancels: NoResizableVec<CancelOrderParams>,
+        #[cfg(not(feature = "certora"))] orders: Vec<PlaceOrderParams>,
+        #[cfg(feature = "certora")] orders: NoResizableVec<PlaceOrderParams>,
+        sub_account_id: u8,
     ) -> Self {
         BatchUpdateParams {
             trader_index_hint,
             cancels,
             orders,
+            sub_account_id,
         }
     }
+
+    /// Decode params, taking sub-account 0 for clients that predate
+    /// sub-accounts and end the data before it.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: BatchUpdateParams = BatchUpdateParams {
+            trader_index_hint: BorshDeserialize::deserialize(&mut data)?,
+            cancels: BorshDeserialize::deserialize(&mut data)?,
+            orders: BorshDeserialize::deserialize(&mut data)?,
+            sub_account_id: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -160,7 +192,7 @@ pub(crate) fn process_batch_update(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let params: BatchUpdateParams = BatchUpdateParams::try_from_slice(data)?;
+    let params: BatchUpdateParams = BatchUpdateParams::try_from_slice_with_defaults(data)?;
     process_batch_update_core(program_id, accounts, params)
 }
 
@@ -217,26 +249,38 @@ pub(crate) fn process_batch_update_core(
 ) -> ProgramResult {
     let batch_update_context: BatchUpdateContext = BatchUpdateContext::load(accounts)?;
 
-    // Validate session or authority and get the trader authority (before destructuring)
-    let trader_authority = batch_update_context.validate_and_get_trader_authority(program_id)?;
+    // Validate session, trade delegate or authority and get the trader
+    // authority (before destructuring)
+    let (trader_authority, session_scope) = batch_update_context
+        .validate_and_get_trader_authority(program_id, params.sub_account_id)?;
 
     let BatchUpdateContext {
         market,
-        payer,
-        session_token: _,  // Validated above, no longer needed
+        session_token: _, // Validated above, no longer needed
+        session_scope: _,
+        trade_delegate: _,
+        seat_extension,
+        cross_margin_accounts,
         global_trade_accounts_opts,
         ..
     } = batch_update_context;
+    let mut seat_extension: Option<RefMut<SeatExtension>> = get_seat_extension(
+        &seat_extension,
+        market.key,
+        &trader_authority,
+        params.sub_account_id,
+    )?;
 
     let BatchUpdateParams {
-        trader_index_hint,
+        trader_index_hint: _,
         cancels,
         orders,
+        sub_account_id,
     } = params;
 
     let current_slot: Option<u32> = Some(get_now_slot());
 
-    trace!("batch_update trader_index_hint:{trader_index_hint:?} cancels:{cancels:?} orders:{orders:?}");
+    trace!("batch_update cancels:{cancels:?} orders:{orders:?}");
 
     // Validate session or authority before performing operations
     let trader_index: DataIndex = {
@@ -245,11 +289,26 @@ pub(crate) fn process_batch_update_core(
         let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
 
         // Get trader index using the actual trader authority (not payer when using session)
-        let trader_index: DataIndex = dynamic_account.get_trader_index(&trader_authority);
+        let trader_index: DataIndex =
+            dynamic_account.get_sub_account_index(&trader_authority, sub_account_id);
 
         // Lazy funding settlement: settle accumulated funding and zero base_balance
         // before any cancel or place operations.
-        dynamic_account.settle_funding_for_trader(trader_index)?;
+        settle_funding_and_socialized_loss(
+            &mut dynamic_account,
+            market.key,
+            &trader_authority,
+            trader_index,
+            seat_extension.as_deref_mut(),
+        )?;
+
+        // Sample the book premium before this instruction moves the book.
+        #[cfg(not(feature = "certora"))]
+        {
+            use solana_program::{clock::Clock, sysvar::Sysvar};
+            let now: i64 = Clock::get()?.unix_timestamp;
+            super::sample_premium(&mut dynamic_account, now);
+        }
 
         for cancel_order_params in cancels {
             // Hinted is preferred because that is O(1) to find and O(log n) to
@@ -307,7 +366,7 @@ pub(crate) fn process_batch_update_core(
 
             emit_stack(CancelOrderLog {
                 market: *market.key,
-                trader: *payer.key,
+                trader: trader_authority,
                 order_sequence_number: cancel_order_params.order_sequence_number(),
             })?;
         }
@@ -325,6 +384,8 @@ pub(crate) fn process_batch_update_core(
     let mut result: Vec<(u64, DataIndex)> = Vec::with_capacity(orders.len());
     #[cfg(feature = "certora")]
     let mut result = NoResizableVec::<(u64, DataIndex)>::new(10);
+    #[cfg(not(feature = "certora"))]
+    let placed_orders: bool = !orders.is_empty();
     for place_order_params in orders {
         {
             let base_atoms: BaseAtoms = BaseAtoms::new(place_order_params.base_atoms());
@@ -343,6 +404,37 @@ pub(crate) fn process_batch_update_core(
                 "No free block available. Call Expand before BatchUpdate.",
             )?;
 
+            // A scoped session key is held to its order size, and to the
+            // position the order would leave if it filled in full.
+            #[cfg(not(feature = "certora"))]
+            if let Some(session_scope) = &session_scope {
+                let notional_atoms: u64 = price.checked_quote_for_base(base_atoms, true)?.as_u64();
+                require!(
+                    session_scope.allows_order_notional(notional_atoms),
+                    crate::program::ManifestError::SessionScopeExceeded,
+                    "Order notional {} exceeds the session limit {}",
+                    notional_atoms,
+                    session_scope.get_max_order_notional_atoms(),
+                )?;
+                let position_size: i128 = get_helper::<
+                    hypertree::RBNode<crate::state::claimed_seat::ClaimedSeat>,
+                >(&dynamic_account.dynamic, trader_index)
+                .get_value()
+                .get_position_size() as i128;
+                let filled_position: i128 = if place_order_params.is_bid() {
+                    position_size + base_atoms.as_u64() as i128
+                } else {
+                    position_size - base_atoms.as_u64() as i128
+                };
+                require!(
+                    session_scope.allows_position(filled_position),
+                    crate::program::ManifestError::SessionScopeExceeded,
+                    "Order could leave a position of {} over the session limit {}",
+                    filled_position,
+                    session_scope.get_max_position_atoms(),
+                )?;
+            }
+
             // For asks: virtually credit base atoms so the engine can
             // reserve them for the resting order. Base is virtual.
             if !place_order_params.is_bid() {
@@ -383,61 +475,17 @@ pub(crate) fn process_batch_update_core(
                         / 10000;
                     if fee_amount > 0 {
                         dynamic_account.withdraw(trader_index, fee_amount, false)?;
-                        dynamic_account.fixed.add_to_insurance_fund(fee_amount);
+                        if let Some(seat_extension) = seat_extension.as_deref_mut() {
+                            seat_extension.add_fees_paid(fee_amount);
+                        }
+                        dynamic_account.fixed.add_fee_to_insurance_fund(fee_amount);
                     }
                 }
             }
 
-            // Initial margin check after order placement
-            #[cfg(not(feature = "certora"))]
-            {
-                let claimed_seat: &crate::state::claimed_seat::ClaimedSeat =
-                    get_helper::<hypertree::RBNode<crate::state::claimed_seat::ClaimedSeat>>(
-                        &dynamic_account.dynamic,
-                        trader_index,
-                    )
-                    .get_value();
-                let position_size: i64 = claimed_seat.get_position_size();
-                if position_size != 0 {
-                    let abs_position: u64 = position_size.unsigned_abs();
-                    let mark_price =
-                        super::liquidate::compute_mark_price(&dynamic_account)?;
-                    let notional: u64 = mark_price
-                        .checked_quote_for_base(
-                            crate::quantities::BaseAtoms::new(abs_position),
-                            false,
-                        )?
-                        .as_u64();
-                    let initial_margin_bps: u64 =
-                        dynamic_account.fixed.get_initial_margin_bps();
-                    let required_margin: u64 = notional
-                        .checked_mul(initial_margin_bps)
-                        .unwrap_or(u64::MAX)
-                        / 10000;
-
-                    let cost_basis = claimed_seat.get_quote_cost_basis();
-                    // Use i128 to avoid overflow on large u64 values cast to i64
-                    let unrealized_pnl: i128 = if position_size > 0 {
-                        (notional as i128) - (cost_basis as i128)
-                    } else {
-                        (cost_basis as i128) - (notional as i128)
-                    };
-
-                    let margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
-                    let equity: i128 = (margin as i128) + unrealized_pnl;
-                    crate::require!(
-                        equity >= required_margin as i128,
-                        crate::program::ManifestError::InsufficientMargin,
-                        "Initial margin check failed: equity {} < required {}",
-                        equity,
-                        required_margin,
-                    )?;
-                }
-            }
-
             emit_stack(PlaceOrderLog {
                 market: *market.key,
-                trader: *payer.key,
+                trader: trader_authority,
                 base_atoms,
                 price,
                 order_type,
@@ -451,10 +499,76 @@ pub(crate) fn process_batch_update_core(
         }
     }
 
-    // Store current global cumulative funding checkpoint for lazy settlement.
     {
         let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
         let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+
+        // Move the PnL the orders realized into margin before checking it.
+        settle_pnl(
+            &mut dynamic_account,
+            market.key,
+            &trader_authority,
+            trader_index,
+            seat_extension.as_deref_mut(),
+        )?;
+
+        // Initial margin check after order placement. A cross-margin seat is
+        // checked together with the rest of its portfolio.
+        #[cfg(not(feature = "certora"))]
+        if placed_orders {
+            let claimed_seat: &crate::state::claimed_seat::ClaimedSeat =
+                get_helper::<hypertree::RBNode<crate::state::claimed_seat::ClaimedSeat>>(
+                    &dynamic_account.dynamic,
+                    trader_index,
+                )
+                .get_value();
+            let position_size: i64 = claimed_seat.get_position_size();
+            if claimed_seat.is_cross_margin() {
+                let portfolio: MarginSummary = cross_margin_summary(
+                    &dynamic_account,
+                    trader_index,
+                    &trader_authority,
+                    expect_cross_margin_accounts(&cross_margin_accounts)?,
+                )?;
+                crate::require!(
+                    portfolio.equity >= portfolio.initial_margin as i128,
+                    crate::program::ManifestError::InsufficientMargin,
+                    "Initial margin check failed: portfolio equity {} < required {}",
+                    portfolio.equity,
+                    portfolio.initial_margin,
+                )?;
+            } else if position_size != 0 {
+                let abs_position: u64 = position_size.unsigned_abs();
+                let mark_price =
+                    super::compute_conservative_mark_price(&dynamic_account, position_size)?;
+                let notional: u64 = mark_price
+                    .checked_quote_for_base(crate::quantities::BaseAtoms::new(abs_position), false)?
+                    .as_u64();
+                let required_margin: u64 = dynamic_account
+                    .fixed
+                    .get_initial_margin_for_notional(notional);
+
+                let cost_basis = claimed_seat.get_quote_cost_basis();
+                // Use i128 to avoid overflow on large u64 values cast to i64
+                let unrealized_pnl: i128 = if position_size > 0 {
+                    (notional as i128) - (cost_basis as i128)
+                } else {
+                    (cost_basis as i128) - (notional as i128)
+                };
+
+                let margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
+                let equity: i128 = (margin as i128) + unrealized_pnl;
+                crate::require!(
+                    equity >= required_margin as i128,
+                    crate::program::ManifestError::InsufficientMargin,
+                    "Initial margin check failed: equity {} < required {}",
+                    equity,
+                    required_margin,
+                )?;
+            }
+        }
+
+        // Store current global cumulative funding checkpoint for lazy settlement.
         dynamic_account.store_cumulative_for_trader(trader_index);
     }
 
@@ -5,6 +5,7 @@ use crate::{
     state::{MarketFixed, MarketRefMut},
     validation::{loaders::ClaimSeatContext, ManifestAccountInfo, Signer},
 };
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
 
 use super::shared::get_mut_dynamic_account;
@@ -12,12 +13,29 @@ use super::shared::get_mut_dynamic_account;
 #[cfg(feature = "certora")]
 use early_panic::early_panic;
 
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ClaimSeatParams {
+    pub sub_account_id: u8,
+}
+
+impl ClaimSeatParams {
+    pub fn new(sub_account_id: u8) -> Self {
+        ClaimSeatParams { sub_account_id }
+    }
+}
+
 #[cfg_attr(all(feature = "certora", not(feature = "certora-test")), early_panic)]
 pub(crate) fn process_claim_seat(
     _program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _data: &[u8],
+    data: &[u8],
 ) -> ProgramResult {
+    // Instructions without params claim the default sub-account.
+    let params: ClaimSeatParams = if data.is_empty() {
+        ClaimSeatParams::new(0)
+    } else {
+        ClaimSeatParams::try_from_slice(data)?
+    };
     let claim_seat_context: ClaimSeatContext = ClaimSeatContext::load(accounts)?;
     let ClaimSeatContext { market, payer, .. } = claim_seat_context;
 
@@ -34,7 +52,7 @@ pub(crate) fn process_claim_seat(
         )?;
     }
 
-    process_claim_seat_internal(&market, &payer)?;
+    process_claim_seat_internal(&market, &payer, params.sub_account_id)?;
 
     Ok(())
 }
@@ -43,10 +61,11 @@ pub(crate) fn process_claim_seat(
 pub(crate) fn process_claim_seat_internal<'a, 'info>(
     market: &ManifestAccountInfo<'a, 'info, MarketFixed>,
     payer: &Signer<'a, 'info>,
+    sub_account_id: u8,
 ) -> ProgramResult {
     let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
     let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
-    dynamic_account.claim_seat(payer.key)?;
+    dynamic_account.claim_sub_account_seat(payer.key, sub_account_id)?;
 
     emit_stack(ClaimSeatLog {
         market: *market.key,
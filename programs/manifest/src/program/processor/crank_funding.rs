@@ -1,11 +1,16 @@
 use crate::{
-    logs::{emit_stack, FundingCrankLog},
+    logs::{emit_stack, FundingCrankLog, OracleUpdateLog},
     program::{get_mut_dynamic_account, ManifestError},
-    quantities::{BaseAtoms, QuoteAtomsPerBaseAtom, WrapperU64},
-    state::MarketRefMut,
+    quantities::{BaseAtoms, WrapperU64},
+    require,
+    state::{
+        median_oracle_price, FundingHistory, FundingHistoryEntry, MarketFixed, MarketRefMut,
+        OraclePrice, OracleType, FUNDING_REFERENCE_BASE_ATOMS, FUNDING_SCALE, PREMIUM_RATE_SCALE,
+    },
     validation::loaders::CrankFundingContext,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
 use solana_program::{
     account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult,
     program_error::ProgramError, pubkey::Pubkey, sysvar::Sysvar,
@@ -16,6 +21,8 @@ use std::cell::RefMut;
 const PYTH_MAGIC: u32 = 0xa1b2c3d4;
 /// Offset of exponent (i32) in Pyth V2 price account
 const PYTH_EXPO_OFFSET: usize = 20;
+/// Offset of the publish timestamp (i64) in Pyth V2 price account
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
 /// Offset of aggregate price (i64) in Pyth V2 price account
 const PYTH_AGG_PRICE_OFFSET: usize = 208;
 /// Offset of aggregate confidence (u64) in Pyth V2 price account
@@ -27,12 +34,22 @@ const PYTH_STATUS_TRADING: u32 = 1;
 /// Minimum Pyth price account data length
 const PYTH_MIN_DATA_LEN: usize = 240;
 
-/// Funding period in seconds (1 hour)
-const FUNDING_PERIOD_SECS: i64 = 3600;
-/// Funding rate scaling factor (1e9)
-const FUNDING_SCALE: i64 = 1_000_000_000;
-/// Maximum funding rate per period: 1% of FUNDING_SCALE (caps at 1% per hour)
-const MAX_FUNDING_RATE_PER_PERIOD: i64 = FUNDING_SCALE / 100;
+/// Anchor discriminator of a Switchboard On-Demand PullFeedAccountData account
+const SWITCHBOARD_PULL_FEED_DISCRIMINATOR: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+/// Offset of last_update_timestamp (i64) in a Switchboard pull feed
+const SWITCHBOARD_LAST_UPDATE_OFFSET: usize = 2216;
+/// Offset of the current result's value (i128) in a Switchboard pull feed
+const SWITCHBOARD_RESULT_VALUE_OFFSET: usize = 2264;
+/// Offset of the current result's std_dev (i128) in a Switchboard pull feed
+const SWITCHBOARD_RESULT_STD_DEV_OFFSET: usize = 2280;
+/// Offset of the current result's num_samples (u8) in a Switchboard pull feed
+const SWITCHBOARD_RESULT_NUM_SAMPLES_OFFSET: usize = 2360;
+/// Minimum Switchboard pull feed data length (through the current result)
+const SWITCHBOARD_MIN_DATA_LEN: usize = 2392;
+/// Switchboard results are fixed point with 18 decimals
+const SWITCHBOARD_DECIMALS: i32 = 18;
+/// Exponent Switchboard results are rescaled to, so they fit an i64 mantissa
+const SWITCHBOARD_EXPO: i32 = -9;
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct CrankFundingParams {}
@@ -45,9 +62,9 @@ impl CrankFundingParams {
 
 /// Read Pyth price from account data.
 /// Supports both Pyth V2 push oracle (240+ bytes, magic 0xa1b2c3d4) and
-/// PriceUpdateV3 pull oracle (~134 bytes, used on MagicBlock ER).
-/// Returns (price: i64, expo: i32, confidence: u64)
-fn read_pyth_price(data: &[u8]) -> Result<(i64, i32, u64), ProgramError> {
+/// PriceUpdateV3 pull oracle (~134 bytes, used on MagicBlock ER), which must
+/// carry `expected_feed_id`.
+fn read_pyth_price(data: &[u8], expected_feed_id: &[u8; 32]) -> Result<OraclePrice, ProgramError> {
     // Try V2 push oracle first (magic number check)
     if data.len() >= PYTH_MIN_DATA_LEN {
         let magic = u32::from_le_bytes(data[0..4].try_into().unwrap());
@@ -57,11 +74,11 @@ fn read_pyth_price(data: &[u8]) -> Result<(i64, i32, u64), ProgramError> {
     }
 
     // Fall back to PriceUpdateV3 pull oracle
-    read_pyth_v3(data)
+    read_pyth_v3(data, expected_feed_id)
 }
 
 /// Parse Pyth V2 push oracle format (240+ bytes).
-fn read_pyth_v2(data: &[u8]) -> Result<(i64, i32, u64), ProgramError> {
+fn read_pyth_v2(data: &[u8]) -> Result<OraclePrice, ProgramError> {
     let expo = i32::from_le_bytes(
         data[PYTH_EXPO_OFFSET..PYTH_EXPO_OFFSET + 4]
             .try_into()
@@ -82,6 +99,11 @@ fn read_pyth_v2(data: &[u8]) -> Result<(i64, i32, u64), ProgramError> {
             .try_into()
             .unwrap(),
     );
+    let publish_time = i64::from_le_bytes(
+        data[PYTH_TIMESTAMP_OFFSET..PYTH_TIMESTAMP_OFFSET + 8]
+            .try_into()
+            .unwrap(),
+    );
 
     if status != PYTH_STATUS_TRADING {
         solana_program::msg!("Pyth price not trading: status={}", status);
@@ -93,52 +115,176 @@ fn read_pyth_v2(data: &[u8]) -> Result<(i64, i32, u64), ProgramError> {
         return Err(ManifestError::InvalidPerpsOperation.into());
     }
 
-    Ok((price, expo, conf))
+    Ok(OraclePrice {
+        price,
+        expo,
+        conf,
+        publish_time,
+    })
 }
 
 /// Parse PriceUpdateV3 pull oracle format (~134 bytes, MagicBlock ER).
 /// Layout: disc(8) + authority(32) + verification_level(1) + PriceFeedMessage
-///   PriceFeedMessage: feed_id(32) + price(i64) + conf(u64) + expo(i32) + ...
-fn read_pyth_v3(data: &[u8]) -> Result<(i64, i32, u64), ProgramError> {
+///   PriceFeedMessage: feed_id(32) + price(i64) + conf(u64) + expo(i32) +
+///   publish_time(i64) + ...
+/// Anyone can post an update account, so the feed id inside it has to match
+/// the market's and the update has to be fully verified.
+fn read_pyth_v3(data: &[u8], expected_feed_id: &[u8; 32]) -> Result<OraclePrice, ProgramError> {
     if data.len() < 93 {
         solana_program::msg!("PriceUpdateV3 account too small: {}", data.len());
         return Err(ManifestError::InvalidPerpsOperation.into());
     }
 
-    // verification_level at byte 40: 0x01 (Full) → msg at 41, 0x00 (Partial) → msg at 42
+    // verification_level at byte 40: 0x01 (Full) → msg at 41. 0x00 (Partial)
+    // means not enough guardian signatures were checked.
     let msg_start: usize = match data[40] {
         0x01 => 41,
-        0x00 => 42,
+        0x00 => {
+            solana_program::msg!("PriceUpdateV3 is only partially verified");
+            return Err(ManifestError::InvalidPerpsOperation.into());
+        }
         b => {
             solana_program::msg!("Unknown PriceUpdateV3 VerificationLevel: {}", b);
             return Err(ManifestError::InvalidPerpsOperation.into());
         }
     };
 
-    if data.len() < msg_start + 52 {
+    if data.len() < msg_start + 60 {
         solana_program::msg!("PriceUpdateV3 truncated at message payload");
         return Err(ManifestError::InvalidPerpsOperation.into());
     }
 
-    let price = i64::from_le_bytes(
-        data[msg_start + 32..msg_start + 40].try_into().unwrap(),
-    );
-    let conf = u64::from_le_bytes(
-        data[msg_start + 40..msg_start + 48].try_into().unwrap(),
-    );
+    if *expected_feed_id == [0; 32] {
+        solana_program::msg!("Market has no Pyth feed id for PriceUpdateV3 accounts");
+        return Err(ManifestError::InvalidPerpsOperation.into());
+    }
+    if data[msg_start..msg_start + 32] != expected_feed_id[..] {
+        solana_program::msg!("PriceUpdateV3 feed id does not match the market");
+        return Err(ManifestError::InvalidPerpsOperation.into());
+    }
+
+    let price = i64::from_le_bytes(data[msg_start + 32..msg_start + 40].try_into().unwrap());
+    let conf = u64::from_le_bytes(data[msg_start + 40..msg_start + 48].try_into().unwrap());
     // PriceUpdateV3 stores expo as positive decimal places (e.g. 8 means price/10^8).
     // Standard Pyth V2 uses negative exponent (e.g. -8). Negate to normalize.
-    let raw_expo = i32::from_le_bytes(
-        data[msg_start + 48..msg_start + 52].try_into().unwrap(),
-    );
+    let raw_expo = i32::from_le_bytes(data[msg_start + 48..msg_start + 52].try_into().unwrap());
     let expo = if raw_expo > 0 { -raw_expo } else { raw_expo };
+    let publish_time = i64::from_le_bytes(data[msg_start + 52..msg_start + 60].try_into().unwrap());
 
     if price <= 0 {
         solana_program::msg!("PriceUpdateV3 price not positive: {}", price);
         return Err(ManifestError::InvalidPerpsOperation.into());
     }
 
-    Ok((price, expo, conf))
+    Ok(OraclePrice {
+        price,
+        expo,
+        conf,
+        publish_time,
+    })
+}
+
+/// Parse a Switchboard On-Demand pull feed (PullFeedAccountData).
+/// Layout: disc(8) + submissions(32 * 64) + authority(32) + queue(32) +
+///   feed_hash(32) + initialized_slot(8) + permissions(8) + max_variance(8) +
+///   min_responses(4) + name(32) + 4 flag bytes + last_update_timestamp(i64) +
+///   lut_slot(8) + reserved(32) + CurrentResult
+///   CurrentResult: value(i128) + std_dev(i128) + mean + range + min + max +
+///   num_samples(u8) + ...
+/// The result is rescaled from 18 decimals to `SWITCHBOARD_EXPO`, and the
+/// standard deviation is used as the confidence interval.
+fn read_switchboard_price(data: &[u8]) -> Result<OraclePrice, ProgramError> {
+    if data.len() < SWITCHBOARD_MIN_DATA_LEN {
+        solana_program::msg!("Switchboard pull feed too small: {}", data.len());
+        return Err(ManifestError::InvalidPerpsOperation.into());
+    }
+    if data[0..8] != SWITCHBOARD_PULL_FEED_DISCRIMINATOR {
+        solana_program::msg!("Account is not a Switchboard pull feed");
+        return Err(ManifestError::InvalidPerpsOperation.into());
+    }
+
+    let num_samples: u8 = data[SWITCHBOARD_RESULT_NUM_SAMPLES_OFFSET];
+    if num_samples == 0 {
+        solana_program::msg!("Switchboard pull feed has no samples");
+        return Err(ManifestError::InvalidPerpsOperation.into());
+    }
+
+    let value = i128::from_le_bytes(
+        data[SWITCHBOARD_RESULT_VALUE_OFFSET..SWITCHBOARD_RESULT_VALUE_OFFSET + 16]
+            .try_into()
+            .unwrap(),
+    );
+    let std_dev = i128::from_le_bytes(
+        data[SWITCHBOARD_RESULT_STD_DEV_OFFSET..SWITCHBOARD_RESULT_STD_DEV_OFFSET + 16]
+            .try_into()
+            .unwrap(),
+    );
+    let publish_time = i64::from_le_bytes(
+        data[SWITCHBOARD_LAST_UPDATE_OFFSET..SWITCHBOARD_LAST_UPDATE_OFFSET + 8]
+            .try_into()
+            .unwrap(),
+    );
+
+    let divisor: i128 = 10i128.pow((SWITCHBOARD_DECIMALS + SWITCHBOARD_EXPO) as u32);
+    let price: i128 = value / divisor;
+    if price <= 0 || price > i64::MAX as i128 {
+        solana_program::msg!("Switchboard price out of range: {}", value);
+        return Err(ManifestError::InvalidPerpsOperation.into());
+    }
+    let conf: u64 = (std_dev.unsigned_abs() / divisor as u128).min(u64::MAX as u128) as u64;
+
+    Ok(OraclePrice {
+        price: price as i64,
+        expo: SWITCHBOARD_EXPO,
+        conf,
+        publish_time,
+    })
+}
+
+/// Read one oracle account in the given format.
+fn read_oracle_price(
+    market_fixed: &MarketFixed,
+    oracle_type: OracleType,
+    data: &[u8],
+) -> Result<OraclePrice, ProgramError> {
+    match oracle_type {
+        OracleType::Pyth => read_pyth_price(data, market_fixed.get_pyth_feed_id()),
+        OracleType::SwitchboardOnDemand => read_switchboard_price(data),
+    }
+}
+
+/// Whether an oracle price is certain and recent enough to use: its
+/// confidence is within the market's limit and it was published within the
+/// market's max staleness.
+fn is_oracle_price_usable(
+    market_fixed: &MarketFixed,
+    oracle_price: &OraclePrice,
+    now: i64,
+) -> bool {
+    // Both values share the price exponent, so the ratio needs no scaling.
+    let max_oracle_conf_bps: u64 = market_fixed.get_max_oracle_conf_bps();
+    if oracle_price.conf as u128 * 10000 > max_oracle_conf_bps as u128 * oracle_price.price as u128
+    {
+        solana_program::msg!(
+            "Oracle confidence {} exceeds {} bps of price {}",
+            oracle_price.conf,
+            max_oracle_conf_bps,
+            oracle_price.price,
+        );
+        return false;
+    }
+
+    let max_oracle_staleness_secs: u64 = market_fixed.get_max_oracle_staleness_secs();
+    let oracle_age: i64 = now.saturating_sub(oracle_price.publish_time);
+    if oracle_age > max_oracle_staleness_secs as i64 {
+        solana_program::msg!(
+            "Oracle price is stale: published {} seconds ago, max {}",
+            oracle_age,
+            max_oracle_staleness_secs,
+        );
+        return false;
+    }
+    true
 }
 
 pub(crate) fn process_crank_funding(
@@ -152,28 +298,95 @@ pub(crate) fn process_crank_funding(
     let CrankFundingContext {
         market,
         payer,
-        pyth_price_feed,
+        oracle_feeds,
+        funding_history,
     } = crank_context;
 
-    // Read Pyth price from the oracle account
-    let pyth_data = pyth_price_feed.try_borrow_data()?;
-    let (oracle_price, oracle_expo, _confidence) = read_pyth_price(&pyth_data)?;
-    drop(pyth_data);
+    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+    let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
 
     // Get current timestamp
     let clock = Clock::get()?;
     let now = clock.unix_timestamp;
 
-    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
-    let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+    // Read every oracle source, keep the ones that pass the market's
+    // confidence and staleness limits, and take the median of those.
+    let mut usable_prices: Vec<OraclePrice> = Vec::with_capacity(oracle_feeds.len());
+    let mut sources_used: u8 = 0;
+    let mut sources_rejected: u8 = 0;
+    let oracle_sources = dynamic_account.fixed.get_oracle_sources();
+    for (source_index, (source, oracle_feed)) in
+        oracle_sources.iter().zip(oracle_feeds.iter()).enumerate()
+    {
+        let oracle_data = oracle_feed.try_borrow_data()?;
+        let oracle_price_result: Result<OraclePrice, ProgramError> = read_oracle_price(
+            dynamic_account.fixed,
+            source.get_oracle_type(),
+            &oracle_data,
+        );
+        match oracle_price_result {
+            Ok(oracle_price)
+                if is_oracle_price_usable(dynamic_account.fixed, &oracle_price, now) =>
+            {
+                usable_prices.push(oracle_price);
+                sources_used |= 1 << source_index;
+            }
+            _ => {
+                solana_program::msg!("Skipping oracle source {}", source_index);
+                sources_rejected |= 1 << source_index;
+            }
+        }
+    }
+
+    let OraclePrice {
+        price: oracle_price,
+        expo: oracle_expo,
+        conf: confidence,
+        publish_time,
+    } = match median_oracle_price(&usable_prices) {
+        Some(oracle_price) => oracle_price,
+        None => {
+            solana_program::msg!("No usable oracle price from {} sources", oracle_feeds.len());
+            return Err(ManifestError::InvalidPerpsOperation.into());
+        }
+    };
+
+    // The newest source used must not be older than the cached price.
+    require!(
+        publish_time >= dynamic_account.fixed.get_oracle_publish_time(),
+        ManifestError::InvalidPerpsOperation,
+        "Oracle price published at {} is older than the cached one",
+        publish_time,
+    )?;
+
+    emit_stack(OracleUpdateLog {
+        market: *market.info.key,
+        cranker: *payer.key,
+        oracle_price: oracle_price as u64,
+        oracle_conf: confidence,
+        publish_time: publish_time as u64,
+        oracle_expo,
+        sources_used,
+        sources_rejected,
+        _padding: [0; 2],
+    })?;
 
     let last_funding_ts = dynamic_account.fixed.get_last_funding_timestamp();
 
-    // If first crank ever, just cache oracle, set the timestamp and return
+    // Cache the new oracle price first, then close the book premium TWAP for
+    // the period against it: the freshest index is the one funding pushes the
+    // book toward.
+    dynamic_account.fixed.set_oracle_price(
+        oracle_price as u64,
+        oracle_expo,
+        confidence,
+        publish_time,
+    );
+    super::sample_premium(&mut dynamic_account, now);
+    let premium_twap: i64 = dynamic_account.fixed.take_premium_twap();
+
+    // If first crank ever, just set the timestamp and return
     if last_funding_ts == 0 {
-        dynamic_account
-            .fixed
-            .set_oracle_price(oracle_price as u64, oracle_expo);
         dynamic_account.fixed.set_last_funding_timestamp(now);
         return Ok(());
     }
@@ -184,31 +397,8 @@ pub(crate) fn process_crank_funding(
     }
     // Cap time_elapsed to one funding period to prevent multi-period accumulation
     // in a single crank. Crankers should call more frequently for accurate funding.
-    let time_elapsed = raw_time_elapsed.min(FUNDING_PERIOD_SECS);
-
-    // Compute mark price from the orderbook (not the cached oracle).
-    // The mark price reflects what the market is actually trading at.
-    // The new Pyth oracle is the "index price" that funding pushes toward.
-    // Funding rate = (mark - index) / index — pushes orderbook toward oracle.
-    let mark_price_result =
-        super::liquidate::compute_orderbook_mark_price(&dynamic_account);
-
-    // If we can't compute mark price (empty book), just update oracle and timestamp
-    let mark_price: QuoteAtomsPerBaseAtom = match mark_price_result {
-        Ok(p) => p,
-        Err(_) => {
-            dynamic_account
-                .fixed
-                .set_oracle_price(oracle_price as u64, oracle_expo);
-            dynamic_account.fixed.set_last_funding_timestamp(now);
-            return Ok(());
-        }
-    };
-
-    // Now update cached oracle price to the new Pyth value
-    dynamic_account
-        .fixed
-        .set_oracle_price(oracle_price as u64, oracle_expo);
+    let funding_period_secs: i64 = dynamic_account.fixed.get_funding_period_secs().max(1) as i64;
+    let time_elapsed = raw_time_elapsed.min(funding_period_secs);
 
     // Convert oracle price to quote atoms for a reference amount of base atoms.
     // Oracle price = price * 10^expo (USD per unit)
@@ -217,10 +407,7 @@ pub(crate) fn process_crank_funding(
     let base_decimals = dynamic_account.fixed.get_base_mint_decimals() as i64;
     let quote_decimals = dynamic_account.fixed.get_quote_mint_decimals() as i64;
 
-    let reference_base = BaseAtoms::new(1_000_000_000); // 1e9 base atoms for precision
-    let mark_quote = mark_price
-        .checked_quote_for_base(reference_base, false)?
-        .as_u64() as i128;
+    let reference_base = BaseAtoms::new(FUNDING_REFERENCE_BASE_ATOMS);
 
     let oracle_quote_i128: i128 = {
         let adjusted_expo = oracle_expo as i64 + quote_decimals - base_decimals + 9;
@@ -239,24 +426,30 @@ pub(crate) fn process_crank_funding(
 
     // Funding rate in quote-atoms-per-base-atom units (× FUNDING_SCALE).
     //
-    // price_diff and oracle_quote are both in "quote atoms per reference_base base atoms".
-    // We first compute the dimensionless rate (mark-oracle)/oracle, clamp it to ±1% per
-    // hour, then convert to quote-per-base units by multiplying by oracle_quote and
-    // dividing by reference_base. This ensures settle_funding_for_trader (which computes
+    // The premium TWAP is the dimensionless (mark - index) / index, averaged
+    // over every trade and crank since the last crank, so neither a cranker
+    // timing the crank nor a maker moving the book just before it sets the
+    // rate. Add the market's fixed interest rate, scale the sum to the
+    // elapsed part of a funding period, clamp it to the market's max rate,
+    // then convert to quote-per-base units by multiplying by oracle_quote
+    // (quote atoms per reference_base base atoms) and dividing by
+    // reference_base. This ensures settle_funding_for_trader (which computes
     // position_size * cumulative_delta / FUNDING_SCALE) yields quote atoms directly.
-    let price_diff = mark_quote - oracle_quote_i128;
     let reference_base_i128 = reference_base.as_u64() as i128;
 
-    // Dimensionless rate (× FUNDING_SCALE), clamped to ±1% per hour
-    let rate_dimensionless: i128 = (price_diff * FUNDING_SCALE as i128 * time_elapsed as i128)
-        / (oracle_quote_i128 * FUNDING_PERIOD_SECS as i128);
+    // Dimensionless rate (× FUNDING_SCALE), clamped to the max rate per period
+    let funding_interest_rate: i64 = dynamic_account.fixed.get_funding_interest_rate();
+    let max_funding_rate: i128 = dynamic_account.fixed.get_max_funding_rate() as i128;
+    let rate_dimensionless: i128 = (premium_twap as i128 * FUNDING_SCALE as i128
+        + funding_interest_rate as i128 * PREMIUM_RATE_SCALE as i128)
+        * time_elapsed as i128
+        / (PREMIUM_RATE_SCALE as i128 * funding_period_secs as i128);
     let rate_clamped: i128 = rate_dimensionless
-        .max(-(MAX_FUNDING_RATE_PER_PERIOD as i128))
-        .min(MAX_FUNDING_RATE_PER_PERIOD as i128);
+        .max(-max_funding_rate)
+        .min(max_funding_rate);
 
     // Convert to quote-per-base units: multiply by oracle_quote / reference_base
-    let funding_rate_scaled: i64 =
-        (rate_clamped * oracle_quote_i128 / reference_base_i128) as i64;
+    let funding_rate_scaled: i64 = (rate_clamped * oracle_quote_i128 / reference_base_i128) as i64;
 
     // Update global cumulative funding rate (lazy settlement — no per-seat iteration).
     // Individual traders' funding is settled lazily on their next interaction
@@ -266,13 +459,27 @@ pub(crate) fn process_crank_funding(
     dynamic_account.fixed.set_cumulative_funding(new_cumulative);
     dynamic_account.fixed.set_last_funding_timestamp(now);
 
+    if let Some(funding_history) = funding_history {
+        let mark_price: u64 = super::compute_mark_price(&dynamic_account)
+            .and_then(|mark_price| mark_price.checked_quote_for_base(reference_base, false))
+            .map_or(oracle_quote_i128 as u64, |mark_quote| mark_quote.as_u64());
+        let funding_history_bytes: &mut [u8] = &mut funding_history.try_borrow_mut_data()?[..];
+        get_mut_helper::<FundingHistory>(funding_history_bytes, 0_u32).push(FundingHistoryEntry {
+            timestamp: now,
+            funding_rate: rate_clamped as i64,
+            elapsed_secs: time_elapsed as u64,
+            oracle_price: oracle_quote_i128 as u64,
+            mark_price,
+        });
+    }
+
     emit_stack(FundingCrankLog {
         market: *market.info.key,
         cranker: *payer.key,
         oracle_price: oracle_price as u64,
         funding_rate: funding_rate_scaled as u64,
         timestamp: now as u64,
-        _padding: [0; 8],
+        premium_twap: premium_twap as u64,
     })?;
 
     Ok(())
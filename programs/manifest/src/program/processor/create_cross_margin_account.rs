@@ -0,0 +1,77 @@
+use std::mem::size_of;
+
+use crate::{
+    program::invoke,
+    state::CrossMarginAccount,
+    utils::create_account,
+    validation::{get_cross_margin_address, loaders::CreateCrossMarginAccountContext},
+};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, rent::Rent,
+    sysvar::Sysvar,
+};
+
+/// Create the signer's cross-margin account for a quote mint, along with the
+/// token account that holds its collateral.
+pub(crate) fn process_create_cross_margin_account(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let create_cross_margin_account_context: CreateCrossMarginAccountContext =
+        CreateCrossMarginAccountContext::load(accounts)?;
+    let CreateCrossMarginAccountContext {
+        payer,
+        cross_margin,
+        quote_mint,
+        vault,
+        token_program,
+        associated_token_program,
+        system_program,
+    } = create_cross_margin_account_context;
+
+    let (_expected_cross_margin_key, cross_margin_bump) =
+        get_cross_margin_address(payer.key, quote_mint.info.key);
+    let cross_margin_seeds: Vec<Vec<u8>> = vec![
+        b"cross-margin".to_vec(),
+        payer.key.as_ref().to_vec(),
+        quote_mint.info.key.as_ref().to_vec(),
+        vec![cross_margin_bump],
+    ];
+    create_account(
+        payer.as_ref(),
+        cross_margin.info,
+        system_program.as_ref(),
+        &crate::id(),
+        &Rent::get()?,
+        size_of::<CrossMarginAccount>() as u64,
+        cross_margin_seeds,
+    )?;
+
+    // The collateral vault is an ATA owned by the cross-margin account, like
+    // the quote vault of a market.
+    invoke(
+        &spl_associated_token_account::instruction::create_associated_token_account(
+            payer.info.key,
+            cross_margin.info.key,
+            quote_mint.info.key,
+            token_program.key,
+        ),
+        &[
+            payer.as_ref().clone(),
+            vault.as_ref().clone(),
+            cross_margin.info.clone(),
+            quote_mint.as_ref().clone(),
+            system_program.as_ref().clone(),
+            token_program.as_ref().clone(),
+            associated_token_program.as_ref().clone(),
+        ],
+    )?;
+
+    let cross_margin_bytes: &mut [u8] = &mut cross_margin.info.try_borrow_mut_data()?[..];
+    *get_mut_helper::<CrossMarginAccount>(cross_margin_bytes, 0_u32) =
+        CrossMarginAccount::new_empty(payer.key, quote_mint.info.key);
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::mem::size_of;
+
+use crate::{
+    state::FundingHistory,
+    utils::create_account,
+    validation::{get_funding_history_address, loaders::CreateFundingHistoryContext},
+};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, rent::Rent,
+    sysvar::Sysvar,
+};
+
+/// Create the funding history account of a market. Anyone can pay for it.
+pub(crate) fn process_create_funding_history(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let create_funding_history_context: CreateFundingHistoryContext =
+        CreateFundingHistoryContext::load(accounts)?;
+    let CreateFundingHistoryContext {
+        payer,
+        market,
+        funding_history,
+        system_program,
+    } = create_funding_history_context;
+
+    let (_expected_funding_history_key, funding_history_bump) =
+        get_funding_history_address(market.key);
+    let funding_history_seeds: Vec<Vec<u8>> = vec![
+        b"funding-history".to_vec(),
+        market.key.as_ref().to_vec(),
+        vec![funding_history_bump],
+    ];
+    create_account(
+        payer.as_ref(),
+        funding_history.info,
+        system_program.as_ref(),
+        &crate::id(),
+        &Rent::get()?,
+        size_of::<FundingHistory>() as u64,
+        funding_history_seeds,
+    )?;
+
+    let funding_history_bytes: &mut [u8] = &mut funding_history.info.try_borrow_mut_data()?[..];
+    *get_mut_helper::<FundingHistory>(funding_history_bytes, 0_u32) =
+        FundingHistory::new_empty(market.key);
+
+    Ok(())
+}
@@ -4,11 +4,18 @@ use crate::{
     logs::{emit_stack, CreateMarketLog},
     program::{get_mut_dynamic_account, invoke},
     require,
-    state::{constants::MARKET_BLOCK_SIZE, MarketFixed},
-    utils::create_account,
-    validation::{
-        get_market_address, loaders::CreateMarketContext, ManifestAccountInfo,
+    state::{
+        constants::{
+            DEFAULT_FUNDING_PERIOD_SECS, DEFAULT_LIQUIDATOR_REWARD_BPS,
+            DEFAULT_MARK_EMA_WINDOW_SECS, DEFAULT_MAX_FUNDING_RATE,
+            DEFAULT_MAX_LIQUIDATION_SLIPPAGE_BPS, DEFAULT_MAX_ORACLE_CONF_BPS,
+            DEFAULT_MAX_ORACLE_STALENESS_SECS, DEFAULT_MIN_POSITION_SIZE_ATOMS, FUNDING_SCALE,
+            MARKET_BLOCK_SIZE,
+        },
+        validate_oracle_sources, MarketFixed, OracleSource,
     },
+    utils::create_account,
+    validation::{get_market_address, loaders::CreateMarketContext},
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use hypertree::{get_mut_helper, trace};
@@ -16,7 +23,7 @@ use solana_program::{
     account_info::AccountInfo,
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
-    program_pack::Pack,
+    program_error::ProgramError,
     pubkey::Pubkey,
     rent::Rent,
     sysvar::Sysvar,
@@ -30,6 +37,8 @@ use spl_token_2022::{
     state::Mint,
 };
 
+use super::{deserialize_trailing, deserialize_trailing_or, require_params_consumed};
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct CreateMarketParams {
     pub base_mint_index: u8,
@@ -40,6 +49,17 @@ pub struct CreateMarketParams {
     pub taker_fee_bps: u64,
     pub liquidation_buffer_bps: u64,
     pub num_blocks: u32,
+    // Params below were appended after clients shipped and take their
+    // defaults when the data ends before them.
+    pub liquidator_reward_bps: u64,
+    pub min_position_size_atoms: u64,
+    /// Length of a funding period in seconds
+    pub funding_period_secs: u64,
+    /// Largest funding rate per period, scaled by FUNDING_SCALE
+    pub max_funding_rate: u64,
+    /// Fixed interest rate per period added to the premium, scaled by
+    /// FUNDING_SCALE. Zero for none.
+    pub funding_interest_rate: i64,
 }
 
 impl CreateMarketParams {
@@ -52,6 +72,11 @@ impl CreateMarketParams {
         pyth_feed_account: Pubkey,
         taker_fee_bps: u64,
         liquidation_buffer_bps: u64,
+        liquidator_reward_bps: u64,
+        min_position_size_atoms: u64,
+        funding_period_secs: u64,
+        max_funding_rate: u64,
+        funding_interest_rate: i64,
         num_blocks: u32,
     ) -> Self {
         CreateMarketParams {
@@ -63,8 +88,42 @@ impl CreateMarketParams {
             taker_fee_bps,
             liquidation_buffer_bps,
             num_blocks,
+            liquidator_reward_bps,
+            min_position_size_atoms,
+            funding_period_secs,
+            max_funding_rate,
+            funding_interest_rate,
         }
     }
+
+    /// Decode params, giving the liquidation and funding params their
+    /// defaults for clients that end the data after `num_blocks`.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: CreateMarketParams = CreateMarketParams {
+            base_mint_index: BorshDeserialize::deserialize(&mut data)?,
+            base_mint_decimals: BorshDeserialize::deserialize(&mut data)?,
+            initial_margin_bps: BorshDeserialize::deserialize(&mut data)?,
+            maintenance_margin_bps: BorshDeserialize::deserialize(&mut data)?,
+            pyth_feed_account: BorshDeserialize::deserialize(&mut data)?,
+            taker_fee_bps: BorshDeserialize::deserialize(&mut data)?,
+            liquidation_buffer_bps: BorshDeserialize::deserialize(&mut data)?,
+            num_blocks: BorshDeserialize::deserialize(&mut data)?,
+            liquidator_reward_bps: deserialize_trailing_or(
+                &mut data,
+                DEFAULT_LIQUIDATOR_REWARD_BPS,
+            )?,
+            min_position_size_atoms: deserialize_trailing_or(
+                &mut data,
+                DEFAULT_MIN_POSITION_SIZE_ATOMS,
+            )?,
+            funding_period_secs: deserialize_trailing_or(&mut data, DEFAULT_FUNDING_PERIOD_SECS)?,
+            max_funding_rate: deserialize_trailing_or(&mut data, DEFAULT_MAX_FUNDING_RATE)?,
+            funding_interest_rate: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
 }
 
 pub(crate) fn process_create_market(
@@ -72,7 +131,7 @@ pub(crate) fn process_create_market(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let params: CreateMarketParams = CreateMarketParams::try_from_slice(data)?;
+    let params: CreateMarketParams = CreateMarketParams::try_from_slice_with_defaults(data)?;
 
     // Validate perps parameters
     require!(
@@ -100,6 +159,13 @@ pub(crate) fn process_create_market(
         crate::program::ManifestError::InvalidPerpsOperation,
         "Liquidation buffer must be < maintenance margin",
     )?;
+    validate_liquidator_reward_bps(params.liquidator_reward_bps, params.maintenance_margin_bps)?;
+    validate_min_position_size_atoms(params.min_position_size_atoms)?;
+    validate_funding_params(
+        params.funding_period_secs,
+        params.max_funding_rate,
+        params.funding_interest_rate,
+    )?;
 
     trace!("process_create_market accs={accounts:?}");
     let create_market_context: CreateMarketContext = CreateMarketContext::load(accounts)?;
@@ -125,9 +191,7 @@ pub(crate) fn process_create_market(
         if let Ok(extension) = pool_mint.get_extension::<MintCloseAuthority>() {
             let close_authority: Option<Pubkey> = extension.close_authority.into();
             if close_authority.is_some() {
-                solana_program::msg!(
-                    "Warning, you are creating a market with a close authority."
-                );
+                solana_program::msg!("Warning, you are creating a market with a close authority.");
             }
         }
         if let Ok(extension) = pool_mint.get_extension::<PermanentDelegate>() {
@@ -154,6 +218,7 @@ pub(crate) fn process_create_market(
             crate::program::ManifestError::InvalidMarketPubkey,
             "Market account is not at expected PDA address",
         )?;
+        validate_oracle_feed(market.info.key, &params.pyth_feed_account, &[])?;
 
         let market_seeds: Vec<Vec<u8>> = vec![
             b"market".to_vec(),
@@ -208,11 +273,10 @@ pub(crate) fn process_create_market(
 
         // Initialize the ephemeral vault ATA (owned by the market PDA, for use on the ER)
         {
-            let (ephemeral_vault_ata_key, ephemeral_vault_bump) =
-                Pubkey::find_program_address(
-                    &[market.info.key.as_ref(), quote_mint.info.key.as_ref()],
-                    ephemeral_spl_token.as_ref().key,
-                );
+            let (ephemeral_vault_ata_key, ephemeral_vault_bump) = Pubkey::find_program_address(
+                &[market.info.key.as_ref(), quote_mint.info.key.as_ref()],
+                ephemeral_spl_token.as_ref().key,
+            );
             require!(
                 ephemeral_vault_ata_key == *ephemeral_vault_ata.info.key,
                 crate::program::ManifestError::InvalidMarketPubkey,
@@ -243,21 +307,36 @@ pub(crate) fn process_create_market(
         }
 
         // Setup the empty market
-        let mut empty_market_fixed: MarketFixed =
-            MarketFixed::new_empty(params.base_mint_index, params.base_mint_decimals, &quote_mint);
+        let mut empty_market_fixed: MarketFixed = MarketFixed::new_empty(
+            params.base_mint_index,
+            params.base_mint_decimals,
+            &quote_mint,
+        );
 
         // Configure margin params
-        empty_market_fixed.set_perps_params(
-            params.initial_margin_bps,
-            params.maintenance_margin_bps,
-        );
+        empty_market_fixed
+            .set_perps_params(params.initial_margin_bps, params.maintenance_margin_bps);
 
         // Set the Pyth oracle feed account
         empty_market_fixed.set_pyth_feed(params.pyth_feed_account);
+        empty_market_fixed.set_max_oracle_conf_bps(DEFAULT_MAX_ORACLE_CONF_BPS);
+        empty_market_fixed.set_max_oracle_staleness_secs(DEFAULT_MAX_ORACLE_STALENESS_SECS);
+        empty_market_fixed.set_mark_ema_window_secs(DEFAULT_MARK_EMA_WINDOW_SECS);
+
+        // Configure funding
+        empty_market_fixed.set_funding_period_secs(params.funding_period_secs);
+        empty_market_fixed.set_max_funding_rate(params.max_funding_rate);
+        empty_market_fixed.set_funding_interest_rate(params.funding_interest_rate);
 
         // Configure insurance fund and liquidation params
         empty_market_fixed.set_taker_fee_bps(params.taker_fee_bps);
         empty_market_fixed.set_liquidation_buffer_bps(params.liquidation_buffer_bps);
+        empty_market_fixed
+            .set_liquidation_params(params.liquidator_reward_bps, params.min_position_size_atoms);
+        empty_market_fixed.set_max_liquidation_slippage_bps(DEFAULT_MAX_LIQUIDATION_SLIPPAGE_BPS);
+
+        // The creator can update the market parameters later.
+        empty_market_fixed.set_market_authority(*payer.key);
 
         assert_eq!(
             market.info.data_len(),
@@ -288,3 +367,79 @@ pub(crate) fn process_create_market(
 
     Ok(())
 }
+
+/// The reward is paid out of the liquidated trader's remaining margin. Keeping
+/// it below the maintenance margin leaves a trader liquidated right at
+/// maintenance with margin to pay it, and keeps the partial liquidation
+/// fraction well defined.
+pub(crate) fn validate_liquidator_reward_bps(
+    liquidator_reward_bps: u64,
+    maintenance_margin_bps: u64,
+) -> ProgramResult {
+    require!(
+        liquidator_reward_bps < maintenance_margin_bps,
+        crate::program::ManifestError::InvalidPerpsOperation,
+        "Liquidator reward {} must be < maintenance margin {}",
+        liquidator_reward_bps,
+        maintenance_margin_bps,
+    )?;
+    Ok(())
+}
+
+/// Partial liquidations round up to a full one below this size. Zero would
+/// let them leave dust positions too small to liquidate profitably.
+pub(crate) fn validate_min_position_size_atoms(min_position_size_atoms: u64) -> ProgramResult {
+    require!(
+        min_position_size_atoms > 0,
+        crate::program::ManifestError::InvalidPerpsOperation,
+        "Min position size must be > 0",
+    )?;
+    Ok(())
+}
+
+/// The primary oracle account cannot be the market itself or one of its extra
+/// sources. A market may be created without one, CrankFunding then refuses it
+/// until UpdateMarket sets it.
+pub(crate) fn validate_oracle_feed(
+    market: &Pubkey,
+    oracle_feed_account: &Pubkey,
+    extra_oracle_sources: &[OracleSource],
+) -> ProgramResult {
+    require!(
+        oracle_feed_account != market,
+        crate::program::ManifestError::InvalidPerpsOperation,
+        "Oracle feed account cannot be the market",
+    )?;
+    validate_oracle_sources(oracle_feed_account, extra_oracle_sources)?;
+    Ok(())
+}
+
+/// A funding period has to be at least a second long, and the max rate has to
+/// allow some funding but no more than the whole position value per period.
+/// The interest rate is charged within the same cap, so it cannot exceed it.
+pub(crate) fn validate_funding_params(
+    funding_period_secs: u64,
+    max_funding_rate: u64,
+    funding_interest_rate: i64,
+) -> ProgramResult {
+    require!(
+        funding_period_secs > 0,
+        crate::program::ManifestError::InvalidPerpsOperation,
+        "Funding period must be > 0",
+    )?;
+    require!(
+        max_funding_rate > 0 && max_funding_rate <= FUNDING_SCALE as u64,
+        crate::program::ManifestError::InvalidPerpsOperation,
+        "Max funding rate {} must be in (0, {}]",
+        max_funding_rate,
+        FUNDING_SCALE,
+    )?;
+    require!(
+        funding_interest_rate.unsigned_abs() <= max_funding_rate,
+        crate::program::ManifestError::InvalidPerpsOperation,
+        "Funding interest rate {} exceeds the max funding rate {}",
+        funding_interest_rate,
+        max_funding_rate,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::{cell::Ref, mem::size_of};
+
+use super::{deserialize_trailing, require_params_consumed};
+use crate::{
+    program::{get_dynamic_account, ManifestError},
+    require,
+    state::{claimed_seat::ClaimedSeat, MarketRef, SeatExtension},
+    utils::create_account,
+    validation::{
+        get_seat_extension_address, loaders::CreateSeatExtensionContext, ManifestAccountInfo,
+    },
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::{get_helper, get_mut_helper, DataIndex, RBNode, NIL};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey, rent::Rent, sysvar::Sysvar,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CreateSeatExtensionParams {
+    pub sub_account_id: u8,
+}
+
+impl CreateSeatExtensionParams {
+    pub fn new(sub_account_id: u8) -> Self {
+        CreateSeatExtensionParams { sub_account_id }
+    }
+
+    /// Decode params, taking sub-account 0 for clients that predate
+    /// sub-accounts and send none.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: CreateSeatExtensionParams = CreateSeatExtensionParams {
+            sub_account_id: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
+}
+
+/// Create the extension that keeps the lifetime statistics of one of the
+/// signer's seats. Called on an existing extension, it takes a new snapshot
+/// of the seat instead, for a seat released and claimed again.
+pub(crate) fn process_create_seat_extension(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: CreateSeatExtensionParams =
+        CreateSeatExtensionParams::try_from_slice_with_defaults(data)?;
+    let create_seat_extension_context: CreateSeatExtensionContext =
+        CreateSeatExtensionContext::load(accounts)?;
+    let CreateSeatExtensionParams { sub_account_id } = params;
+
+    let CreateSeatExtensionContext {
+        payer,
+        market,
+        seat_extension,
+        system_program,
+    } = create_seat_extension_context;
+
+    let market_data: Ref<&mut [u8]> = market.try_borrow_data()?;
+    let dynamic_account: MarketRef = get_dynamic_account(&market_data);
+    let trader_index: DataIndex = dynamic_account.get_sub_account_index(payer.key, sub_account_id);
+    require!(
+        trader_index != NIL,
+        ManifestError::InvalidPerpsOperation,
+        "Trader {} has no seat for sub-account {}",
+        payer.key,
+        sub_account_id,
+    )?;
+
+    let (expected_seat_extension, seat_extension_bump) =
+        get_seat_extension_address(market.key, payer.key, sub_account_id);
+    require!(
+        expected_seat_extension == *seat_extension.key,
+        ManifestError::IncorrectAccount,
+        "Incorrect seat extension account",
+    )?;
+
+    if seat_extension.data_is_empty() {
+        let seat_extension_seeds: Vec<Vec<u8>> = vec![
+            b"seat-extension".to_vec(),
+            market.key.as_ref().to_vec(),
+            payer.key.as_ref().to_vec(),
+            vec![sub_account_id],
+            vec![seat_extension_bump],
+        ];
+        create_account(
+            payer.as_ref(),
+            seat_extension,
+            system_program.as_ref(),
+            &crate::id(),
+            &Rent::get()?,
+            size_of::<SeatExtension>() as u64,
+            seat_extension_seeds,
+        )?;
+        let seat_extension_bytes: &mut [u8] = &mut seat_extension.try_borrow_mut_data()?[..];
+        *get_mut_helper::<SeatExtension>(seat_extension_bytes, 0_u32) =
+            SeatExtension::new(market.key, payer.key, sub_account_id);
+    } else {
+        ManifestAccountInfo::<SeatExtension>::new(seat_extension)?;
+    }
+
+    let seat_extension_bytes: &mut [u8] = &mut seat_extension.try_borrow_mut_data()?[..];
+    get_mut_helper::<SeatExtension>(seat_extension_bytes, 0_u32).rebase(
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value(),
+    );
+
+    Ok(())
+}
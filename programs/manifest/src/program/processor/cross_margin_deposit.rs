@@ -0,0 +1,63 @@
+use crate::{
+    logs::{emit_stack, CrossMarginDepositLog},
+    state::CrossMarginAccount,
+    validation::loaders::CrossMarginDepositContext,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+
+use super::deposit::spl_token_transfer_from_trader_to_vault;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CrossMarginDepositParams {
+    pub amount_atoms: u64,
+}
+
+impl CrossMarginDepositParams {
+    pub fn new(amount_atoms: u64) -> Self {
+        CrossMarginDepositParams { amount_atoms }
+    }
+}
+
+pub(crate) fn process_cross_margin_deposit(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: CrossMarginDepositParams = CrossMarginDepositParams::try_from_slice(data)?;
+    let cross_margin_deposit_context: CrossMarginDepositContext =
+        CrossMarginDepositContext::load(accounts)?;
+    let CrossMarginDepositParams { amount_atoms } = params;
+
+    let CrossMarginDepositContext {
+        payer,
+        cross_margin,
+        trader_token,
+        vault,
+        token_program,
+        mint: _,
+    } = cross_margin_deposit_context;
+
+    spl_token_transfer_from_trader_to_vault(
+        &token_program,
+        &trader_token,
+        &vault,
+        &payer,
+        amount_atoms,
+    )?;
+
+    let cross_margin_bytes: &mut [u8] = &mut cross_margin.try_borrow_mut_data()?[..];
+    let cross_margin_account: &mut CrossMarginAccount =
+        get_mut_helper::<CrossMarginAccount>(cross_margin_bytes, 0_u32);
+    cross_margin_account.deposit_collateral(amount_atoms);
+
+    emit_stack(CrossMarginDepositLog {
+        cross_margin: *cross_margin.key,
+        trader: *payer.key,
+        amount_atoms,
+        collateral_atoms: cross_margin_account.get_collateral_atoms(),
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::cell::RefMut;
+
+use super::{
+    deserialize_trailing, get_seat_extension, require_params_consumed,
+    settle_funding_and_socialized_loss, settle_pnl,
+};
+use crate::{
+    logs::{emit_stack, CrossMarginLinkLog},
+    program::{get_mut_dynamic_account, ManifestError},
+    require,
+    state::{
+        claimed_seat::{ClaimedSeat, MarginMode},
+        CrossMarginAccount, MarketRefMut, SeatExtension,
+    },
+    validation::loaders::CrossMarginLinkContext,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::{get_helper, get_mut_helper, DataIndex, PodBool, RBNode, NIL};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CrossMarginLinkParams {
+    /// Link the signer's seat on the market when true, unlink it when false.
+    pub link: bool,
+    /// Which of the signer's sub-account seats to link or unlink.
+    pub sub_account_id: u8,
+}
+
+impl CrossMarginLinkParams {
+    pub fn new(link: bool) -> Self {
+        CrossMarginLinkParams::new_for_sub_account(link, 0)
+    }
+
+    pub fn new_for_sub_account(link: bool, sub_account_id: u8) -> Self {
+        CrossMarginLinkParams {
+            link,
+            sub_account_id,
+        }
+    }
+
+    /// Decode params, taking sub-account 0 for clients that predate
+    /// sub-accounts and end the data before it.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: CrossMarginLinkParams = CrossMarginLinkParams {
+            link: BorshDeserialize::deserialize(&mut data)?,
+            sub_account_id: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
+}
+
+/// Link one of the signer's seats on a market to their cross-margin account,
+/// or unlink it. A linked seat is margined together with the collateral and
+/// the other linked seats. The market stays linked to the account while any
+/// of the signer's seats on it are. Unlinking needs the seat to be flat and
+/// owe nothing, since it goes back to standing on its own margin.
+pub(crate) fn process_cross_margin_link(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: CrossMarginLinkParams = CrossMarginLinkParams::try_from_slice_with_defaults(data)?;
+    let cross_margin_link_context: CrossMarginLinkContext = CrossMarginLinkContext::load(accounts)?;
+    let CrossMarginLinkParams {
+        link,
+        sub_account_id,
+    } = params;
+
+    let CrossMarginLinkContext {
+        payer,
+        market,
+        cross_margin,
+        seat_extension,
+    } = cross_margin_link_context;
+
+    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+    let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+
+    let trader_index: DataIndex = dynamic_account.get_sub_account_index(payer.key, sub_account_id);
+    require!(
+        trader_index != NIL,
+        ManifestError::InvalidPerpsOperation,
+        "Trader {} has no seat for sub-account {}",
+        payer.key,
+        sub_account_id,
+    )?;
+    let is_cross_margin: bool =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+            .get_value()
+            .is_cross_margin();
+    require!(
+        is_cross_margin != link,
+        ManifestError::InvalidPerpsOperation,
+        "Sub-account {} is already {}",
+        sub_account_id,
+        if link { "linked" } else { "unlinked" },
+    )?;
+    // Other linked seats of the signer keep the market linked.
+    let has_other_linked_seats: bool = dynamic_account
+        .get_trader_seat_indices(payer.key)
+        .into_iter()
+        .any(|seat_index| {
+            seat_index != trader_index
+                && get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, seat_index)
+                    .get_value()
+                    .is_cross_margin()
+        });
+
+    // Settle under the old mode, since it decides what happens to a loss the
+    // margin cannot cover.
+    let mut seat_extension: Option<RefMut<SeatExtension>> =
+        get_seat_extension(&seat_extension, market.key, payer.key, sub_account_id)?;
+    settle_funding_and_socialized_loss(
+        &mut dynamic_account,
+        market.key,
+        payer.key,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+    settle_pnl(
+        &mut dynamic_account,
+        market.key,
+        payer.key,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+
+    {
+        let cross_margin_bytes: &mut [u8] = &mut cross_margin.try_borrow_mut_data()?[..];
+        let cross_margin_account: &mut CrossMarginAccount =
+            get_mut_helper::<CrossMarginAccount>(cross_margin_bytes, 0_u32);
+        let claimed_seat: &mut ClaimedSeat =
+            get_mut_helper::<RBNode<ClaimedSeat>>(&mut dynamic_account.dynamic, trader_index)
+                .get_mut_value();
+        if link {
+            if !has_other_linked_seats {
+                cross_margin_account.link_market(market.key)?;
+            }
+        } else {
+            require!(
+                claimed_seat.get_position_size() == 0 && claimed_seat.get_unsettled_pnl() >= 0,
+                ManifestError::InvalidPerpsOperation,
+                "Close the position and repay what the seat owes before unlinking",
+            )?;
+            if !has_other_linked_seats {
+                cross_margin_account.unlink_market(market.key)?;
+            }
+        }
+        claimed_seat.set_margin_mode(if link {
+            MarginMode::Cross
+        } else {
+            MarginMode::Isolated
+        });
+    }
+
+    dynamic_account.store_cumulative_for_trader(trader_index);
+
+    emit_stack(CrossMarginLinkLog {
+        cross_margin: *cross_margin.key,
+        market: *market.key,
+        trader: *payer.key,
+        is_linked: PodBool::from(link),
+        sub_account_id,
+        _padding: [0; 6],
+    })?;
+
+    Ok(())
+}
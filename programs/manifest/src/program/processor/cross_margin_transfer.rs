@@ -0,0 +1,236 @@
+use std::cell::RefMut;
+
+use super::{
+    cross_margin_rest_summary, cross_margin_withdraw::spl_token_transfer_from_cross_margin_vault,
+    deserialize_trailing, get_seat_extension, linked_seats_summary, require_params_consumed,
+    seat_margin_summary, settle_funding_and_socialized_loss, settle_pnl,
+    withdraw::spl_token_transfer_from_vault_to_trader, MarginSummary,
+};
+use crate::{
+    logs::{emit_stack, CrossMarginTransferLog},
+    program::{get_mut_dynamic_account, ManifestError},
+    require,
+    state::{
+        claimed_seat::ClaimedSeat, CrossMarginAccount, MarketFixed, MarketRefMut, SeatExtension,
+    },
+    validation::loaders::{CrossMarginAccounts, CrossMarginTransferContext},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::{get_helper, get_mut_helper, DataIndex, PodBool, RBNode, NIL};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CrossMarginTransferParams {
+    pub amount_atoms: u64,
+    /// Move from the seat's margin into the cross-margin account when true,
+    /// the other way when false.
+    pub to_cross_margin: bool,
+    /// Which of the signer's sub-account seats to move from or to.
+    pub sub_account_id: u8,
+}
+
+impl CrossMarginTransferParams {
+    pub fn new(amount_atoms: u64, to_cross_margin: bool) -> Self {
+        CrossMarginTransferParams::new_for_sub_account(amount_atoms, to_cross_margin, 0)
+    }
+
+    pub fn new_for_sub_account(
+        amount_atoms: u64,
+        to_cross_margin: bool,
+        sub_account_id: u8,
+    ) -> Self {
+        CrossMarginTransferParams {
+            amount_atoms,
+            to_cross_margin,
+            sub_account_id,
+        }
+    }
+
+    /// Decode params, taking sub-account 0 for clients that predate
+    /// sub-accounts and end the data before it.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: CrossMarginTransferParams = CrossMarginTransferParams {
+            amount_atoms: BorshDeserialize::deserialize(&mut data)?,
+            to_cross_margin: BorshDeserialize::deserialize(&mut data)?,
+            sub_account_id: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
+}
+
+/// Move quote between the signer's seat margin on a market and their
+/// cross-margin collateral. Whichever side gives up funds must stay above
+/// maintenance margin: an isolated seat on its own, the cross-margin
+/// portfolio as a whole. Moving between a cross seat and the collateral
+/// leaves the portfolio's equity unchanged.
+pub(crate) fn process_cross_margin_transfer(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: CrossMarginTransferParams =
+        CrossMarginTransferParams::try_from_slice_with_defaults(data)?;
+    let cross_margin_transfer_context: CrossMarginTransferContext =
+        CrossMarginTransferContext::load(accounts)?;
+    let CrossMarginTransferParams {
+        amount_atoms,
+        to_cross_margin,
+        sub_account_id,
+    } = params;
+
+    let CrossMarginTransferContext {
+        payer,
+        market,
+        cross_margin,
+        market_vault,
+        cross_margin_vault,
+        token_program,
+        markets,
+        seat_extension,
+    } = cross_margin_transfer_context;
+
+    let (quote_mint, base_mint_index) = {
+        let market_fixed: std::cell::Ref<MarketFixed> = market.get_fixed()?;
+        (
+            *market_fixed.get_quote_mint(),
+            market_fixed.get_base_mint_index(),
+        )
+    };
+
+    // The vault owners sign the transfers, so neither account's data may be
+    // borrowed while they run.
+    if to_cross_margin {
+        spl_token_transfer_from_vault_to_trader(
+            &token_program,
+            &market_vault,
+            market.info,
+            &cross_margin_vault,
+            amount_atoms,
+            market.key,
+            base_mint_index,
+            &quote_mint,
+        )?;
+    } else {
+        spl_token_transfer_from_cross_margin_vault(
+            &token_program,
+            &cross_margin_vault,
+            cross_margin.info,
+            &market_vault,
+            amount_atoms,
+            payer.key,
+            &quote_mint,
+        )?;
+    }
+
+    let collateral_atoms: u64 = {
+        let cross_margin_bytes: &mut [u8] = &mut cross_margin.try_borrow_mut_data()?[..];
+        let cross_margin_account: &mut CrossMarginAccount =
+            get_mut_helper::<CrossMarginAccount>(cross_margin_bytes, 0_u32);
+        if to_cross_margin {
+            cross_margin_account.deposit_collateral(amount_atoms);
+        } else {
+            cross_margin_account.withdraw_collateral(amount_atoms)?;
+        }
+        cross_margin_account.get_collateral_atoms()
+    };
+
+    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+    let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+
+    let trader_index: DataIndex = dynamic_account.get_sub_account_index(payer.key, sub_account_id);
+    require!(
+        trader_index != NIL,
+        ManifestError::InvalidPerpsOperation,
+        "Trader {} has no seat for sub-account {}",
+        payer.key,
+        sub_account_id,
+    )?;
+
+    let mut seat_extension: Option<RefMut<SeatExtension>> =
+        get_seat_extension(&seat_extension, market.key, payer.key, sub_account_id)?;
+    settle_funding_and_socialized_loss(
+        &mut dynamic_account,
+        market.key,
+        payer.key,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+    settle_pnl(
+        &mut dynamic_account,
+        market.key,
+        payer.key,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+
+    if to_cross_margin {
+        dynamic_account.withdraw(trader_index, amount_atoms, false)?;
+    } else {
+        dynamic_account.deposit(trader_index, amount_atoms, false)?;
+        // Pays down what a cross seat owes before it counts as margin.
+        settle_pnl(
+            &mut dynamic_account,
+            market.key,
+            payer.key,
+            trader_index,
+            seat_extension.as_deref_mut(),
+        )?;
+    }
+
+    let is_cross_margin: bool =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+            .get_value()
+            .is_cross_margin();
+    if to_cross_margin && !is_cross_margin {
+        let seat: MarginSummary = seat_margin_summary(&dynamic_account, trader_index, true)?;
+        require!(
+            seat.withdrawable_equity() >= seat.maintenance_margin as i128,
+            ManifestError::InsufficientMargin,
+            "Transfer would bring equity {} below maintenance margin {}",
+            seat.withdrawable_equity(),
+            seat.maintenance_margin,
+        )?;
+    } else if !to_cross_margin && !is_cross_margin {
+        // The isolated seat does not back the portfolio, so the collateral it
+        // took must not be needed by the linked seats.
+        let mut portfolio: MarginSummary = cross_margin_rest_summary(
+            payer.key,
+            &CrossMarginAccounts {
+                cross_margin: cross_margin.clone(),
+                markets,
+            },
+        )?;
+        portfolio.add(&linked_seats_summary(
+            &dynamic_account,
+            payer.key,
+            trader_index,
+        )?);
+        require!(
+            portfolio.withdrawable_equity() >= portfolio.maintenance_margin as i128,
+            ManifestError::InsufficientMargin,
+            "Transfer would bring portfolio equity {} below maintenance margin {}",
+            portfolio.withdrawable_equity(),
+            portfolio.maintenance_margin,
+        )?;
+    }
+
+    dynamic_account.store_cumulative_for_trader(trader_index);
+
+    emit_stack(CrossMarginTransferLog {
+        cross_margin: *cross_margin.key,
+        market: *market.key,
+        trader: *payer.key,
+        amount_atoms,
+        collateral_atoms,
+        to_cross_margin: PodBool::from(to_cross_margin),
+        sub_account_id,
+        _padding: [0; 6],
+    })?;
+
+    Ok(())
+}
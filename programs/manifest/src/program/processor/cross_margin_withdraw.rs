@@ -0,0 +1,148 @@
+use crate::{
+    logs::{emit_stack, CrossMarginWithdrawLog},
+    program::ManifestError,
+    require,
+    state::CrossMarginAccount,
+    validation::loaders::{CrossMarginAccounts, CrossMarginWithdrawContext},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+
+use super::{cross_margin_rest_summary, MarginSummary};
+use crate::validation::{TokenAccountInfo, TokenProgram};
+
+#[cfg(not(feature = "certora"))]
+use {
+    crate::{cross_margin_seeds_with_bump, validation::get_cross_margin_address},
+    solana_program::program::invoke_signed,
+};
+
+#[cfg(feature = "certora")]
+use solana_cvt::token::spl_token_transfer;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CrossMarginWithdrawParams {
+    pub amount_atoms: u64,
+}
+
+impl CrossMarginWithdrawParams {
+    pub fn new(amount_atoms: u64) -> Self {
+        CrossMarginWithdrawParams { amount_atoms }
+    }
+}
+
+pub(crate) fn process_cross_margin_withdraw(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: CrossMarginWithdrawParams = CrossMarginWithdrawParams::try_from_slice(data)?;
+    let cross_margin_withdraw_context: CrossMarginWithdrawContext =
+        CrossMarginWithdrawContext::load(accounts)?;
+    let CrossMarginWithdrawParams { amount_atoms } = params;
+
+    let CrossMarginWithdrawContext {
+        payer,
+        cross_margin,
+        trader_token,
+        vault,
+        token_program,
+        mint: _,
+        markets,
+    } = cross_margin_withdraw_context;
+
+    let (quote_mint, collateral_atoms) = {
+        let cross_margin_bytes: &mut [u8] = &mut cross_margin.try_borrow_mut_data()?[..];
+        let cross_margin_account: &mut CrossMarginAccount =
+            get_mut_helper::<CrossMarginAccount>(cross_margin_bytes, 0_u32);
+        cross_margin_account.withdraw_collateral(amount_atoms)?;
+        (
+            *cross_margin_account.get_quote_mint(),
+            cross_margin_account.get_collateral_atoms(),
+        )
+    };
+
+    // The collateral backs every linked seat, so what is left has to keep the
+    // portfolio above maintenance. Unrealized profit cannot back it.
+    let portfolio: MarginSummary = cross_margin_rest_summary(
+        payer.key,
+        &CrossMarginAccounts {
+            cross_margin: cross_margin.clone(),
+            markets,
+        },
+    )?;
+    require!(
+        portfolio.withdrawable_equity() >= portfolio.maintenance_margin as i128,
+        ManifestError::InsufficientMargin,
+        "Withdrawal would bring portfolio equity {} below maintenance margin {}",
+        portfolio.withdrawable_equity(),
+        portfolio.maintenance_margin,
+    )?;
+
+    // Cross-margin data must not be borrowed here, the account signs the
+    // transfer.
+    spl_token_transfer_from_cross_margin_vault(
+        &token_program,
+        &vault,
+        cross_margin.info,
+        &trader_token,
+        amount_atoms,
+        payer.key,
+        &quote_mint,
+    )?;
+
+    emit_stack(CrossMarginWithdrawLog {
+        cross_margin: *cross_margin.key,
+        trader: *payer.key,
+        amount_atoms,
+        collateral_atoms,
+    })?;
+
+    Ok(())
+}
+
+/** Transfer from a cross-margin vault (ATA owned by the cross-margin PDA) using SPL Token **/
+#[cfg(not(feature = "certora"))]
+pub(crate) fn spl_token_transfer_from_cross_margin_vault<'a, 'info>(
+    token_program: &TokenProgram<'a, 'info>,
+    vault: &TokenAccountInfo<'a, 'info>,
+    cross_margin_info: &'a AccountInfo<'info>,
+    destination: &TokenAccountInfo<'a, 'info>,
+    amount: u64,
+    owner: &Pubkey,
+    quote_mint: &Pubkey,
+) -> ProgramResult {
+    let (_, cross_margin_bump) = get_cross_margin_address(owner, quote_mint);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            vault.key,
+            destination.key,
+            cross_margin_info.key, // authority = cross-margin PDA (owner of vault ATA)
+            &[],
+            amount,
+        )?,
+        &[
+            token_program.as_ref().clone(),
+            vault.as_ref().clone(),
+            destination.as_ref().clone(),
+            cross_margin_info.clone(),
+        ],
+        cross_margin_seeds_with_bump!(owner, quote_mint, cross_margin_bump),
+    )
+}
+
+#[cfg(feature = "certora")]
+/** (Summary) Transfer from a cross-margin vault using SPL Token **/
+pub(crate) fn spl_token_transfer_from_cross_margin_vault<'a, 'info>(
+    _token_program: &TokenProgram<'a, 'info>,
+    vault: &TokenAccountInfo<'a, 'info>,
+    _cross_margin_info: &'a AccountInfo<'info>,
+    destination: &TokenAccountInfo<'a, 'info>,
+    amount: u64,
+    _owner: &Pubkey,
+    _quote_mint: &Pubkey,
+) -> ProgramResult {
+    spl_token_transfer(vault.info, destination.info, vault.info, amount)
+}
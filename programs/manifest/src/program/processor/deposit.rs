@@ -2,17 +2,20 @@ use std::cell::RefMut;
 
 use crate::{
     logs::{emit_stack, DepositLog},
-    state::MarketRefMut,
-    validation::{
-        loaders::DepositContext,
-        Signer, TokenAccountInfo, TokenProgram,
-    },
+    state::{MarketRefMut, SeatExtension},
+    validation::{loaders::DepositContext, Signer, TokenAccountInfo, TokenProgram},
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use hypertree::DataIndex;
-use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
 
-use super::{get_trader_index_with_hint, shared::get_mut_dynamic_account};
+use super::{
+    deserialize_trailing, get_seat_extension, get_trader_index_with_hint, require_params_consumed,
+    settle_funding_and_socialized_loss, shared::get_mut_dynamic_account,
+};
 
 #[cfg(feature = "certora")]
 use early_panic::early_panic;
@@ -23,15 +26,57 @@ use solana_cvt::token::spl_token_transfer;
 pub struct DepositParams {
     pub amount_atoms: u64,
     pub trader_index_hint: Option<DataIndex>,
+    pub sub_account_id: u8,
+    /// Trader whose seat is credited, if not the signer. The hint is for
+    /// this trader's seat.
+    pub beneficiary: Option<Pubkey>,
 }
 
 impl DepositParams {
     pub fn new(amount_atoms: u64, trader_index_hint: Option<DataIndex>) -> Self {
+        DepositParams::new_for_sub_account(amount_atoms, trader_index_hint, 0)
+    }
+
+    pub fn new_for_sub_account(
+        amount_atoms: u64,
+        trader_index_hint: Option<DataIndex>,
+        sub_account_id: u8,
+    ) -> Self {
         DepositParams {
             amount_atoms,
             trader_index_hint,
+            sub_account_id,
+            beneficiary: None,
         }
     }
+
+    pub fn new_for_beneficiary(
+        amount_atoms: u64,
+        trader_index_hint: Option<DataIndex>,
+        beneficiary: Pubkey,
+        sub_account_id: u8,
+    ) -> Self {
+        DepositParams {
+            amount_atoms,
+            trader_index_hint,
+            sub_account_id,
+            beneficiary: Some(beneficiary),
+        }
+    }
+
+    /// Decode params, taking sub-account 0 and no beneficiary for clients
+    /// that predate them and end the data before them.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: DepositParams = DepositParams {
+            amount_atoms: BorshDeserialize::deserialize(&mut data)?,
+            trader_index_hint: BorshDeserialize::deserialize(&mut data)?,
+            sub_account_id: deserialize_trailing(&mut data)?,
+            beneficiary: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
 }
 
 pub(crate) fn process_deposit(
@@ -39,26 +84,35 @@ pub(crate) fn process_deposit(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let params: DepositParams = DepositParams::try_from_slice(data)?;
+    let params: DepositParams = DepositParams::try_from_slice_with_defaults(data)?;
     process_deposit_core(program_id, accounts, params)
 }
 
 #[cfg_attr(all(feature = "certora", not(feature = "certora-test")), early_panic)]
 pub(crate) fn process_deposit_core(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     params: DepositParams,
 ) -> ProgramResult {
     let deposit_context: DepositContext = DepositContext::load(accounts)?;
+    let depositor: Pubkey = deposit_context.validate_and_get_trader_authority(program_id)?;
     let DepositParams {
         amount_atoms,
         trader_index_hint,
+        sub_account_id,
+        beneficiary,
     } = params;
+    // Anyone may fund another trader's seat. The tokens still come from the
+    // signer, so this only ever adds to the beneficiary's margin.
+    let trader: Pubkey = beneficiary.unwrap_or(depositor);
     let deposited_amount_atoms: u64 = amount_atoms;
 
     let DepositContext {
         market,
         payer,
+        session_token: _,
+        session_scope: _,
+        seat_extension,
         trader_token,
         vault,
         token_program,
@@ -81,10 +135,18 @@ pub(crate) fn process_deposit_core(
     )?;
 
     let trader_index: DataIndex =
-        get_trader_index_with_hint(trader_index_hint, &dynamic_account, &payer)?;
+        get_trader_index_with_hint(trader_index_hint, &dynamic_account, &trader, sub_account_id)?;
 
     // Lazy funding settlement before any balance operations.
-    dynamic_account.settle_funding_for_trader(trader_index)?;
+    let mut seat_extension: Option<RefMut<SeatExtension>> =
+        get_seat_extension(&seat_extension, market.key, &trader, sub_account_id)?;
+    settle_funding_and_socialized_loss(
+        &mut dynamic_account,
+        market.key,
+        &trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
 
     // is_base = false: always depositing quote in perps
     dynamic_account.deposit(trader_index, deposited_amount_atoms, false)?;
@@ -94,7 +156,7 @@ pub(crate) fn process_deposit_core(
 
     emit_stack(DepositLog {
         market: *market.key,
-        trader: *payer.key,
+        trader,
         mint: *dynamic_account.get_quote_mint(),
         amount_atoms: deposited_amount_atoms,
     })?;
@@ -104,7 +166,7 @@ pub(crate) fn process_deposit_core(
 
 /** Transfer from base (quote) trader to base (quote) vault using SPL Token **/
 #[cfg(not(feature = "certora"))]
-fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
+pub(crate) fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
     token_program: &TokenProgram<'a, 'info>,
     trader_account: &TokenAccountInfo<'a, 'info>,
     vault: &TokenAccountInfo<'a, 'info>,
@@ -130,7 +192,7 @@ fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
 }
 #[cfg(feature = "certora")]
 /** (Summary) Transfer from base (quote) trader to base (quote) vault using SPL Token **/
-fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
+pub(crate) fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
     _token_program: &TokenProgram<'a, 'info>,
     trader_account: &TokenAccountInfo<'a, 'info>,
     vault: &TokenAccountInfo<'a, 'info>,
@@ -139,4 +201,3 @@ fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
 ) -> ProgramResult {
     spl_token_transfer(trader_account.info, vault.info, payer.info, amount)
 }
-
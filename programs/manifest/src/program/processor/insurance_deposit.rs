@@ -0,0 +1,110 @@
+use std::{cell::RefMut, mem::size_of};
+
+use crate::{
+    logs::{emit_stack, InsuranceDepositLog},
+    program::get_mut_dynamic_account,
+    state::{InsuranceStaker, MarketRefMut},
+    utils::create_account,
+    validation::{
+        get_insurance_staker_address, loaders::InsuranceDepositContext, ManifestAccountInfo,
+    },
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey, rent::Rent,
+    sysvar::Sysvar,
+};
+
+use super::deposit::spl_token_transfer_from_trader_to_vault;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct InsuranceDepositParams {
+    pub amount_atoms: u64,
+}
+
+impl InsuranceDepositParams {
+    pub fn new(amount_atoms: u64) -> Self {
+        InsuranceDepositParams { amount_atoms }
+    }
+}
+
+pub(crate) fn process_insurance_deposit(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: InsuranceDepositParams = InsuranceDepositParams::try_from_slice(data)?;
+    let insurance_deposit_context: InsuranceDepositContext =
+        InsuranceDepositContext::load(accounts)?;
+    let InsuranceDepositParams { amount_atoms } = params;
+
+    let InsuranceDepositContext {
+        payer,
+        market,
+        insurance_staker,
+        trader_token,
+        vault,
+        token_program,
+        mint: _,
+        system_program,
+    } = insurance_deposit_context;
+
+    // Create the staker record on first deposit.
+    if insurance_staker.data_len() == 0 {
+        let (_expected_staker_key, staker_bump) =
+            get_insurance_staker_address(market.key, payer.key);
+        let staker_seeds: Vec<Vec<u8>> = vec![
+            b"insurance-staker".to_vec(),
+            market.key.as_ref().to_vec(),
+            payer.key.as_ref().to_vec(),
+            vec![staker_bump],
+        ];
+        create_account(
+            payer.as_ref(),
+            insurance_staker,
+            system_program.as_ref(),
+            &crate::id(),
+            &Rent::get()?,
+            size_of::<InsuranceStaker>() as u64,
+            staker_seeds,
+        )?;
+        let staker_bytes: &mut [u8] = &mut insurance_staker.try_borrow_mut_data()?[..];
+        *get_mut_helper::<InsuranceStaker>(staker_bytes, 0_u32) =
+            InsuranceStaker::new_empty(market.key, payer.key);
+    }
+    // Validates ownership and discriminant. The address was checked in the
+    // loader, so the record belongs to this market and payer.
+    let insurance_staker: ManifestAccountInfo<InsuranceStaker> =
+        ManifestAccountInfo::<InsuranceStaker>::new(insurance_staker)?;
+
+    spl_token_transfer_from_trader_to_vault(
+        &token_program,
+        &trader_token,
+        &vault,
+        &payer,
+        amount_atoms,
+    )?;
+
+    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+    let dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+    let shares: u64 = dynamic_account.fixed.mint_insurance_shares(amount_atoms)?;
+
+    let now: i64 = Clock::get()?.unix_timestamp;
+    let staker_bytes: &mut [u8] = &mut insurance_staker.try_borrow_mut_data()?[..];
+    let staker: &mut InsuranceStaker = get_mut_helper::<InsuranceStaker>(staker_bytes, 0_u32);
+    // Minting may have started a new share epoch, voiding older shares.
+    staker.sync_share_epoch(dynamic_account.fixed.get_insurance_share_epoch());
+    staker.add_shares(shares, now);
+
+    emit_stack(InsuranceDepositLog {
+        market: *market.key,
+        staker: *payer.key,
+        amount_atoms,
+        shares,
+        insurance_fund_balance: dynamic_account.fixed.get_insurance_fund_balance(),
+        total_shares: dynamic_account.fixed.get_insurance_total_shares(),
+    })?;
+
+    Ok(())
+}
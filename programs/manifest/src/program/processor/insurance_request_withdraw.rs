@@ -0,0 +1,60 @@
+use crate::{
+    logs::{emit_stack, InsuranceRequestWithdrawLog},
+    state::InsuranceStaker,
+    validation::loaders::InsuranceRequestWithdrawContext,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use super::insurance_withdraw::INSURANCE_WITHDRAW_COOLDOWN_SECONDS;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct InsuranceRequestWithdrawParams {
+    pub shares: u64,
+}
+
+impl InsuranceRequestWithdrawParams {
+    pub fn new(shares: u64) -> Self {
+        InsuranceRequestWithdrawParams { shares }
+    }
+}
+
+pub(crate) fn process_insurance_request_withdraw(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: InsuranceRequestWithdrawParams =
+        InsuranceRequestWithdrawParams::try_from_slice(data)?;
+    let insurance_request_withdraw_context: InsuranceRequestWithdrawContext =
+        InsuranceRequestWithdrawContext::load(accounts)?;
+    let InsuranceRequestWithdrawParams { shares } = params;
+
+    let InsuranceRequestWithdrawContext {
+        payer,
+        market,
+        insurance_staker,
+    } = insurance_request_withdraw_context;
+
+    let share_epoch: u64 = market.get_fixed()?.get_insurance_share_epoch();
+    let now: i64 = Clock::get()?.unix_timestamp;
+    {
+        let staker_bytes: &mut [u8] = &mut insurance_staker.try_borrow_mut_data()?[..];
+        let staker: &mut InsuranceStaker = get_mut_helper::<InsuranceStaker>(staker_bytes, 0_u32);
+        staker.sync_share_epoch(share_epoch);
+        staker.request_withdraw(shares, now)?;
+    }
+
+    emit_stack(InsuranceRequestWithdrawLog {
+        market: *market.key,
+        staker: *payer.key,
+        shares,
+        unlock_timestamp: now.saturating_add(INSURANCE_WITHDRAW_COOLDOWN_SECONDS),
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::cell::RefMut;
+
+use crate::{
+    logs::{emit_stack, InsuranceWithdrawLog},
+    program::{get_mut_dynamic_account, ManifestError},
+    require,
+    state::{InsuranceStaker, MarketRefMut},
+    validation::loaders::InsuranceWithdrawContext,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use super::withdraw::spl_token_transfer_from_vault_to_trader;
+
+/// Time a staker must wait after requesting a withdrawal before the
+/// requested shares can be withdrawn (7 days). Keeps stakers from
+/// front-running a known deficit by pulling their shares just before it is
+/// drawn from the fund.
+pub const INSURANCE_WITHDRAW_COOLDOWN_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct InsuranceWithdrawParams {
+    pub shares: u64,
+}
+
+impl InsuranceWithdrawParams {
+    pub fn new(shares: u64) -> Self {
+        InsuranceWithdrawParams { shares }
+    }
+}
+
+pub(crate) fn process_insurance_withdraw(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: InsuranceWithdrawParams = InsuranceWithdrawParams::try_from_slice(data)?;
+    let insurance_withdraw_context: InsuranceWithdrawContext =
+        InsuranceWithdrawContext::load(accounts)?;
+    let InsuranceWithdrawParams { shares } = params;
+
+    let InsuranceWithdrawContext {
+        payer,
+        market,
+        insurance_staker,
+        trader_token,
+        vault,
+        token_program,
+        mint: _,
+    } = insurance_withdraw_context;
+
+    let share_epoch: u64 = market.get_fixed()?.get_insurance_share_epoch();
+    let now: i64 = Clock::get()?.unix_timestamp;
+    {
+        let staker_bytes: &mut [u8] = &mut insurance_staker.try_borrow_mut_data()?[..];
+        let staker: &mut InsuranceStaker = get_mut_helper::<InsuranceStaker>(staker_bytes, 0_u32);
+        staker.sync_share_epoch(share_epoch);
+        require!(
+            staker.get_withdraw_request_shares() > 0,
+            ManifestError::InsuranceWithdrawCooldown,
+            "No pending insurance withdraw request",
+        )?;
+        let unlock_timestamp: i64 = staker
+            .get_withdraw_request_timestamp()
+            .saturating_add(INSURANCE_WITHDRAW_COOLDOWN_SECONDS);
+        require!(
+            now >= unlock_timestamp,
+            ManifestError::InsuranceWithdrawCooldown,
+            "Insurance shares are locked until {}",
+            unlock_timestamp,
+        )?;
+        staker.remove_requested_shares(shares)?;
+    }
+
+    // Shares are redeemed at the current fund value, so deficits drawn during
+    // the cooldown are absorbed pro rata and accrued fees are paid out.
+    let (amount_atoms, insurance_fund_balance, total_shares, base_mint_index, quote_mint) = {
+        let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+        let dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+        let amount_atoms: u64 = dynamic_account.fixed.burn_insurance_shares(shares)?;
+        (
+            amount_atoms,
+            dynamic_account.fixed.get_insurance_fund_balance(),
+            dynamic_account.fixed.get_insurance_total_shares(),
+            dynamic_account.fixed.get_base_mint_index(),
+            *dynamic_account.get_quote_mint(),
+        )
+    };
+
+    // Market data must not be borrowed here, the market PDA signs the transfer.
+    spl_token_transfer_from_vault_to_trader(
+        &token_program,
+        &vault,
+        market.info,
+        &trader_token,
+        amount_atoms,
+        market.key,
+        base_mint_index,
+        &quote_mint,
+    )?;
+
+    emit_stack(InsuranceWithdrawLog {
+        market: *market.key,
+        staker: *payer.key,
+        amount_atoms,
+        shares,
+        insurance_fund_balance,
+        total_shares,
+    })?;
+
+    Ok(())
+}
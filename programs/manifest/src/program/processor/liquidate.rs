@@ -1,145 +1,555 @@
 use crate::{
-    logs::{emit_stack, LiquidateLog},
-    program::{get_mut_dynamic_account, ManifestError},
+    logs::{emit_stack, LiquidateLog, LiquidationScanLog, SocializeLossLog},
+    program::{
+        compute_conservative_mark_price, compute_mark_price, cross_margin_rest_summary,
+        deserialize_trailing, deserialize_trailing_or, get_mut_dynamic_account,
+        linked_seats_summary, require_params_consumed, sample_premium,
+        settle_funding_and_socialized_loss, settle_pnl, ManifestError, MarginSummary,
+    },
     quantities::{BaseAtoms, QuoteAtoms, QuoteAtomsPerBaseAtom, WrapperU64},
     require,
-    state::{claimed_seat::ClaimedSeat, MarketRefMut, RestingOrder},
-    validation::loaders::{GlobalTradeAccounts, LiquidateContext},
+    state::{
+        claimed_seat::{ClaimedSeat, MarginMode},
+        update_perps_position, AddOrderToMarketArgs, AddOrderToMarketResult, MarketRefMut,
+        OrderType, RestingOrder, SeatExtension, NO_EXPIRATION_LAST_VALID_SLOT,
+    },
+    validation::{
+        loaders::{CrossMarginAccounts, GlobalTradeAccounts, LiquidateContext},
+        ManifestAccountInfo,
+    },
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use hypertree::{get_helper, get_mut_helper, DataIndex, HyperTreeValueIteratorTrait, RBNode};
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    pubkey::Pubkey, sysvar::Sysvar,
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult,
+    program_error::ProgramError, pubkey::Pubkey, sysvar::Sysvar,
 };
-use std::cell::RefMut;
+use std::{cell::RefMut, mem::size_of};
+#[cfg(not(feature = "certora"))]
+use {
+    super::cross_margin_withdraw::spl_token_transfer_from_cross_margin_vault,
+    crate::state::CrossMarginAccount,
+    crate::validation::{TokenAccountInfo, TokenProgram},
+    hypertree::HyperTreeReadOperations,
+};
+
+/// Compute units that must remain before another trader in a batch is
+/// liquidated. The batch stops early rather than failing the transaction.
+const MIN_COMPUTE_UNITS_PER_LIQUIDATION: u64 = 40_000;
 
-/// Liquidator reward in basis points of closed notional (2.5%)
-const LIQUIDATOR_REWARD_BPS: u64 = 250;
-/// Minimum position size in base atoms to keep after partial liquidation.
-/// If the remaining position would be smaller, do a full liquidation instead.
-const MIN_POSITION_SIZE_ATOMS: u64 = 1000;
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiquidationMode {
+    /// Close at the mark price. The liquidator takes on no inventory.
+    MarkPrice,
+    /// Close by sending an IOC order for the liquidated trader into the book.
+    /// PnL is realized at the fill prices, so the insurance fund only pays for
+    /// slippage beyond the bankruptcy price.
+    Orderbook,
+    /// Move the closed portion onto the liquidator's seat at the mark price
+    /// less the market's liquidator reward. The discount replaces the reward,
+    /// and the liquidator must meet initial margin afterwards.
+    Takeover,
+}
 
+/// A liquidator cannot liquidate any seat of their own, on any sub-account.
+/// Their reward would come out of their own margin or the insurance fund.
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct LiquidateParams {
-    pub trader_to_liquidate: Pubkey,
+    /// Traders to liquidate. When empty, the claimed seats are scanned and
+    /// liquidatable positions are closed.
+    pub traders_to_liquidate: Vec<Pubkey>,
+    pub mode: LiquidationMode,
+    /// Seat a scan starts from, NIL for the first seat. A scan that stops
+    /// early logs the seat to start the next one from.
+    pub scan_start_index: DataIndex,
+    /// Most seats with a position a scan checks, zero for no limit.
+    pub scan_limit: u32,
+    /// Sub-account of each trader in `traders_to_liquidate`, by position.
+    /// Traders without an entry are liquidated on their default sub-account.
+    pub sub_account_ids: Vec<u8>,
 }
 
 impl LiquidateParams {
-    pub fn new(trader_to_liquidate: Pubkey) -> Self {
+    pub fn new(trader_to_liquidate: Pubkey, mode: LiquidationMode) -> Self {
+        LiquidateParams::new_batch(vec![trader_to_liquidate], mode)
+    }
+
+    pub fn new_for_sub_account(
+        trader_to_liquidate: Pubkey,
+        sub_account_id: u8,
+        mode: LiquidationMode,
+    ) -> Self {
+        LiquidateParams {
+            traders_to_liquidate: vec![trader_to_liquidate],
+            mode,
+            scan_start_index: hypertree::NIL,
+            scan_limit: 0,
+            sub_account_ids: vec![sub_account_id],
+        }
+    }
+
+    pub fn new_batch(traders_to_liquidate: Vec<Pubkey>, mode: LiquidationMode) -> Self {
         LiquidateParams {
-            trader_to_liquidate,
+            traders_to_liquidate,
+            mode,
+            scan_start_index: hypertree::NIL,
+            scan_limit: 0,
+            sub_account_ids: Vec::new(),
         }
     }
+
+    /// Scan at most `scan_limit` seats with a position, starting from the
+    /// seat at `scan_start_index`.
+    pub fn new_scan(mode: LiquidationMode, scan_start_index: DataIndex, scan_limit: u32) -> Self {
+        LiquidateParams {
+            traders_to_liquidate: Vec::new(),
+            mode,
+            scan_start_index,
+            scan_limit,
+            sub_account_ids: Vec::new(),
+        }
+    }
+
+    /// Decode params in the current layout or the original one, which is the
+    /// bare pubkey of one trader, closed at the mark price. A current layout
+    /// never takes exactly 32 bytes once `sub_account_ids` is checked against
+    /// the trader count, so the length tells them apart. The scan fields and
+    /// sub-accounts may be left out by clients that do not use them.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() == size_of::<Pubkey>() {
+            return Ok(LiquidateParams::new(
+                Pubkey::try_from_slice(data)?,
+                LiquidationMode::MarkPrice,
+            ));
+        }
+        let mut data: &[u8] = data;
+        let params: LiquidateParams = LiquidateParams {
+            traders_to_liquidate: BorshDeserialize::deserialize(&mut data)?,
+            mode: BorshDeserialize::deserialize(&mut data)?,
+            scan_start_index: deserialize_trailing_or(&mut data, hypertree::NIL)?,
+            scan_limit: deserialize_trailing(&mut data)?,
+            sub_account_ids: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        require!(
+            params.sub_account_ids.len() <= params.traders_to_liquidate.len(),
+            ProgramError::InvalidInstructionData,
+            "{} sub-account ids for {} traders",
+            params.sub_account_ids.len(),
+            params.traders_to_liquidate.len(),
+        )?;
+        require!(
+            params.traders_to_liquidate.is_empty()
+                || (params.scan_start_index == hypertree::NIL && params.scan_limit == 0),
+            ProgramError::InvalidInstructionData,
+            "Scan start and limit only apply to a scan",
+        )?;
+        Ok(params)
+    }
 }
 
 pub(crate) fn process_liquidate(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let params = LiquidateParams::try_from_slice(data)?;
+    let params: LiquidateParams = LiquidateParams::try_from_slice_with_defaults(data)?;
     let liquidate_context: LiquidateContext = LiquidateContext::load(accounts)?;
+    let liquidator: Pubkey = liquidate_context.validate_and_get_liquidator_authority(program_id)?;
 
     let LiquidateContext {
         market,
-        liquidator,
+        liquidator: _,
+        session_token: _,
+        session_scope: _,
+        cross_margin_accounts,
+        cross_margin_vault,
+        market_vault,
+        token_program,
+        seat_extensions,
     } = liquidate_context;
 
     let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
     let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
 
-    // Prevent self-liquidation (extracting insurance fund via self-reward)
+    // Prevent self-liquidation (extracting insurance fund via self-reward).
+    // This deliberately covers every sub-account of the liquidator, not just
+    // the seat the reward is paid to.
     require!(
-        *liquidator.key != params.trader_to_liquidate,
+        !params.traders_to_liquidate.contains(&liquidator),
         ManifestError::InvalidPerpsOperation,
         "Cannot liquidate your own position",
     )?;
 
-    // Find the trader's seat
-    let trader_index: DataIndex =
-        dynamic_account.get_trader_index(&params.trader_to_liquidate);
+    // Settle the liquidator once before any fill or takeover can change their
+    // position, so funding and socialized losses apply to the old size. The
+    // checkpoint is stored straight away so that losses socialized by this
+    // batch are charged on their next interaction.
+    let liquidator_index: DataIndex = dynamic_account.get_trader_index(&liquidator);
+    let mut liquidator_extension: Option<RefMut<SeatExtension>> = find_seat_extension(
+        &seat_extensions,
+        &dynamic_account,
+        market.key,
+        liquidator_index,
+    )?;
+    if liquidator_index != hypertree::NIL {
+        settle_funding_and_socialized_loss(
+            &mut dynamic_account,
+            market.key,
+            &liquidator,
+            liquidator_index,
+            liquidator_extension.as_deref_mut(),
+        )?;
+        settle_pnl(
+            &mut dynamic_account,
+            market.key,
+            &liquidator,
+            liquidator_index,
+            liquidator_extension.as_deref_mut(),
+        )?;
+        dynamic_account.store_cumulative_for_trader(liquidator_index);
+    }
     require!(
-        trader_index != hypertree::NIL,
-        ProgramError::InvalidArgument,
-        "Trader not found on market",
+        params.mode != LiquidationMode::Takeover || liquidator_index != hypertree::NIL,
+        ManifestError::InvalidPerpsOperation,
+        "Liquidator needs a seat to take over a position",
     )?;
 
-    // Lazy funding settlement for the trader being liquidated.
-    // Must happen before reading margin/position to ensure accurate equity computation.
-    dynamic_account.settle_funding_for_trader(trader_index)?;
+    // Liquidations are only priced off the oracle, never the orderbook alone.
+    // compute_mark_price below rejects a cached price that has gone stale.
+    require!(
+        dynamic_account.fixed.get_oracle_price_mantissa() > 0,
+        ManifestError::InvalidPerpsOperation,
+        "No oracle price cached, crank funding first",
+    )?;
 
-    let claimed_seat: &ClaimedSeat = get_helper::<RBNode<ClaimedSeat>>(
-        &dynamic_account.dynamic,
-        trader_index,
-    )
-    .get_value();
+    // Liquidations on the orderbook trade, so the book premium is sampled
+    // like before any other match.
+    sample_premium(&mut dynamic_account, Clock::get()?.unix_timestamp);
 
-    let position_size: i64 = claimed_seat.get_position_size();
+    // Compute mark price once (prefers oracle, falls back to orderbook). Every
+    // trader in the batch is valued and closed against this snapshot, so
+    // earlier liquidations moving the book cannot change later ones.
+    let mark_price: QuoteAtomsPerBaseAtom = compute_mark_price(&dynamic_account)?;
+
+    // A cross-margin trader is judged with the rest of their portfolio: what
+    // their collateral and other linked seats hold beyond maintenance backs
+    // this seat. Only the trader whose accounts were passed can be valued.
+    let cross_margin_trader: Option<(Pubkey, i128)> = match &cross_margin_accounts {
+        None => None,
+        Some(cross_margin_accounts) => {
+            let owner: Pubkey = *cross_margin_accounts.cross_margin.get_fixed()?.get_owner();
+            let rest: MarginSummary = cross_margin_rest_summary(&owner, cross_margin_accounts)?;
+            Some((owner, rest.equity - rest.maintenance_margin as i128))
+        }
+    };
+
+    // Seat a scan that stops here would continue from, NIL once it is done.
+    let mut next_scan_index: DataIndex = hypertree::NIL;
+    let candidates: Vec<(Pubkey, DataIndex)> = if params.traders_to_liquidate.is_empty() {
+        let (seats, next_index) = seats_with_open_positions(
+            &dynamic_account,
+            &liquidator,
+            params.scan_start_index,
+            params.scan_limit,
+        )?;
+        next_scan_index = next_index;
+        seats
+    } else {
+        params
+            .traders_to_liquidate
+            .iter()
+            .enumerate()
+            .map(|(i, trader)| {
+                let sub_account_id: u8 = params.sub_account_ids.get(i).copied().unwrap_or(0);
+                let trader_index: DataIndex =
+                    dynamic_account.get_sub_account_index(trader, sub_account_id);
+                require!(
+                    trader_index != hypertree::NIL,
+                    ProgramError::InvalidArgument,
+                    "Trader {} sub-account {} not found on market",
+                    trader,
+                    sub_account_id,
+                )?;
+                Ok((*trader, trader_index))
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?
+    };
+
+    let mut num_liquidated: usize = 0;
+    let mut liquidated_cross_margin_seats: Vec<DataIndex> = Vec::new();
+    for (trader, trader_index) in candidates.iter() {
+        if num_liquidated > 0
+            && solana_program::compute_units::sol_remaining_compute_units()
+                < MIN_COMPUTE_UNITS_PER_LIQUIDATION
+        {
+            next_scan_index = *trader_index;
+            break;
+        }
+        // Isolated seats stand on their own margin. Cross seats can only be
+        // valued with their portfolio, so the ones whose accounts were not
+        // passed are skipped.
+        let portfolio_surplus: Option<i128> =
+            match get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, *trader_index)
+                .get_value()
+                .get_margin_mode()
+            {
+                MarginMode::Isolated => None,
+                MarginMode::Cross => match cross_margin_trader {
+                    // The owner's other linked seats on this market back it too.
+                    Some((owner, surplus)) if owner == *trader => {
+                        let other_seats: MarginSummary =
+                            linked_seats_summary(&dynamic_account, trader, *trader_index)?;
+                        Some(surplus + other_seats.equity - other_seats.maintenance_margin as i128)
+                    }
+                    _ => continue,
+                },
+            };
+        let mut seat_extension: Option<RefMut<SeatExtension>> = find_seat_extension(
+            &seat_extensions,
+            &dynamic_account,
+            market.key,
+            *trader_index,
+        )?;
+        if liquidate_trader(
+            &mut dynamic_account,
+            market.key,
+            &liquidator,
+            liquidator_index,
+            trader,
+            *trader_index,
+            params.mode,
+            mark_price,
+            portfolio_surplus,
+            seat_extension.as_deref_mut(),
+        )? {
+            num_liquidated += 1;
+            if portfolio_surplus.is_some() {
+                liquidated_cross_margin_seats.push(*trader_index);
+            }
+        }
+    }
     require!(
-        position_size != 0,
+        num_liquidated > 0,
         ManifestError::NotLiquidatable,
-        "Trader has no open position",
+        "None of the {} traders checked could be liquidated",
+        candidates.len(),
     )?;
+    if params.traders_to_liquidate.is_empty() && next_scan_index != hypertree::NIL {
+        emit_stack(LiquidationScanLog {
+            market: *market.key,
+            liquidator,
+            next_seat_index: next_scan_index,
+            num_liquidated: num_liquidated as u32,
+        })?;
+    }
 
-    let quote_cost_basis: u64 = claimed_seat.get_quote_cost_basis();
+    // Taking over a position can reduce one the liquidator already held.
+    if params.mode == LiquidationMode::Takeover {
+        settle_pnl(
+            &mut dynamic_account,
+            market.key,
+            &liquidator,
+            liquidator_index,
+            liquidator_extension.as_deref_mut(),
+        )?;
+    }
+    #[cfg(not(feature = "certora"))]
+    if params.mode == LiquidationMode::Takeover {
+        require_initial_margin(&dynamic_account, liquidator_index)?;
+    }
 
-    // Cancel all open orders belonging to this trader before computing mark price
-    // This releases reserved funds back to the trader's balance
-    {
-        let no_global_accounts: [Option<GlobalTradeAccounts>; 2] = [None, None];
+    #[cfg(not(feature = "certora"))]
+    if let Some(cross_margin_accounts) = &cross_margin_accounts {
+        for trader_index in liquidated_cross_margin_seats {
+            let mut seat_extension: Option<RefMut<SeatExtension>> =
+                find_seat_extension(&seat_extensions, &dynamic_account, market.key, trader_index)?;
+            cover_cross_margin_debt(
+                &mut dynamic_account,
+                market.key,
+                trader_index,
+                seat_extension.as_deref_mut(),
+                cross_margin_accounts,
+                cross_margin_vault.as_ref().unwrap(),
+                market_vault.as_ref().unwrap(),
+                token_program.as_ref().unwrap(),
+            )?;
+        }
+    }
 
-        let bid_indices: Vec<DataIndex> = dynamic_account
-            .get_bids()
-            .iter::<RestingOrder>()
-            .filter(|(_, order)| order.get_trader_index() == trader_index)
-            .map(|(index, _)| index)
-            .collect();
+    Ok(())
+}
 
-        let ask_indices: Vec<DataIndex> = dynamic_account
-            .get_asks()
-            .iter::<RestingOrder>()
-            .filter(|(_, order)| order.get_trader_index() == trader_index)
-            .map(|(index, _)| index)
-            .collect();
+/// Pay what a liquidated cross-margin seat at `trader_index` owes out of the
+/// trader's collateral. Whatever is still owed once the collateral has run
+/// out and the seat is flat is written off like any other bad debt.
+#[cfg(not(feature = "certora"))]
+#[allow(clippy::too_many_arguments)]
+fn cover_cross_margin_debt<'a, 'info>(
+    dynamic_account: &mut MarketRefMut,
+    market: &Pubkey,
+    trader_index: DataIndex,
+    mut seat_extension: Option<&mut SeatExtension>,
+    cross_margin_accounts: &CrossMarginAccounts<'a, 'info>,
+    cross_margin_vault: &TokenAccountInfo<'a, 'info>,
+    market_vault: &TokenAccountInfo<'a, 'info>,
+    token_program: &TokenProgram<'a, 'info>,
+) -> ProgramResult {
+    let (owner, quote_mint, collateral_atoms) = {
+        let cross_margin: std::cell::Ref<CrossMarginAccount> =
+            cross_margin_accounts.cross_margin.get_fixed()?;
+        (
+            *cross_margin.get_owner(),
+            *cross_margin.get_quote_mint(),
+            cross_margin.get_collateral_atoms(),
+        )
+    };
+    let debt: u64 = get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+        .get_value()
+        .get_unsettled_pnl()
+        .min(0)
+        .unsigned_abs();
+    let amount_atoms: u64 = debt.min(collateral_atoms);
+    if amount_atoms > 0 {
+        // The cross-margin account signs the transfer, so its data must not be
+        // borrowed until it returns.
+        spl_token_transfer_from_cross_margin_vault(
+            token_program,
+            cross_margin_vault,
+            cross_margin_accounts.cross_margin.info,
+            market_vault,
+            amount_atoms,
+            &owner,
+            &quote_mint,
+        )?;
+        {
+            let cross_margin_data: &mut [u8] =
+                &mut cross_margin_accounts.cross_margin.try_borrow_mut_data()?[..];
+            get_mut_helper::<CrossMarginAccount>(cross_margin_data, 0_u32)
+                .withdraw_collateral(amount_atoms)?;
+        }
+        dynamic_account.deposit(trader_index, amount_atoms, false)?;
+        settle_pnl(
+            dynamic_account,
+            market,
+            &owner,
+            trader_index,
+            seat_extension.as_deref_mut(),
+        )?;
+    }
 
-        for order_index in bid_indices.iter().chain(ask_indices.iter()) {
-            dynamic_account.cancel_order_by_index(*order_index, &no_global_accounts)?;
+    if amount_atoms == collateral_atoms {
+        if let Some(seat_extension) = seat_extension.as_deref_mut() {
+            seat_extension.sync(
+                get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+                    .get_value(),
+            );
+        }
+        let socialized: u64 = dynamic_account.write_off_debt_for_trader(trader_index);
+        if let Some(seat_extension) = seat_extension {
+            seat_extension.rebase(
+                get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+                    .get_value(),
+            );
+        }
+        if socialized > 0 {
+            emit_stack(SocializeLossLog {
+                market: *market,
+                trader: owner,
+                amount_atoms: socialized,
+                cumulative_loss_per_unit: dynamic_account.fixed.get_cumulative_loss_per_unit(),
+            })?;
         }
     }
+    Ok(())
+}
 
-    // Re-read margin balance after order cancellations (funds released back)
-    let margin_balance: u64 = {
-        let seat: &ClaimedSeat = get_helper::<RBNode<ClaimedSeat>>(
-            &dynamic_account.dynamic,
-            trader_index,
-        )
-        .get_value();
-        seat.quote_withdrawable_balance.as_u64()
-    };
+/// Borrow the extension passed for the seat at `trader_index`, if any. One
+/// already borrowed is in use for another seat.
+fn find_seat_extension<'a>(
+    seat_extensions: &'a [ManifestAccountInfo<SeatExtension>],
+    dynamic_account: &MarketRefMut,
+    market: &Pubkey,
+    trader_index: DataIndex,
+) -> Result<Option<RefMut<'a, SeatExtension>>, ProgramError> {
+    if trader_index == hypertree::NIL {
+        return Ok(None);
+    }
+    let seat: &ClaimedSeat =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
+    for seat_extension in seat_extensions {
+        let Ok(data) = seat_extension.info.try_borrow_mut_data() else {
+            continue;
+        };
+        let extension: RefMut<SeatExtension> =
+            RefMut::map(data, |data| get_mut_helper::<SeatExtension>(data, 0_u32));
+        if extension.get_market() == market
+            && *extension.get_trader() == seat.trader
+            && extension.get_sub_account_id() == seat.get_sub_account_id()
+        {
+            return Ok(Some(extension));
+        }
+    }
+    Ok(None)
+}
 
-    // Require oracle has been updated recently (within 1 hour = 3600 seconds).
-    // This prevents liquidation at stale cached prices.
-    {
-        let last_funding_ts: i64 = dynamic_account.fixed.get_last_funding_timestamp();
-        let clock = solana_program::clock::Clock::get()?;
-        let now = clock.unix_timestamp;
-        let staleness = now.saturating_sub(last_funding_ts);
+/// Seats other than the liquidator's that hold a position, in seat tree
+/// order from `start_index`, or the first seat when NIL. Stops after `limit`
+/// of them unless it is zero. Also returns the seat a later scan continues
+/// from, NIL when the scan reached the end.
+#[cfg(not(feature = "certora"))]
+fn seats_with_open_positions(
+    dynamic_account: &MarketRefMut,
+    liquidator: &Pubkey,
+    start_index: DataIndex,
+    limit: u32,
+) -> Result<(Vec<(Pubkey, DataIndex)>, DataIndex), ProgramError> {
+    let claimed_seats = dynamic_account.get_claimed_seats();
+    let mut index: DataIndex = if start_index == hypertree::NIL {
+        claimed_seats.lookup_max_index::<ClaimedSeat>()
+    } else {
         require!(
-            last_funding_ts > 0 && staleness <= 3600,
-            ManifestError::InvalidPerpsOperation,
-            "Oracle price is stale: last updated {} seconds ago",
-            staleness,
+            dynamic_account.is_claimed_seat_index(start_index),
+            ProgramError::InvalidArgument,
+            "Scan start {} is not a claimed seat",
+            start_index,
         )?;
+        start_index
+    };
+    let mut seats: Vec<(Pubkey, DataIndex)> = Vec::new();
+    while index != hypertree::NIL && (limit == 0 || seats.len() < limit as usize) {
+        let seat: &ClaimedSeat =
+            get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, index).get_value();
+        if seat.trader != *liquidator && seat.get_position_size() != 0 {
+            seats.push((seat.trader, index));
+        }
+        index = claimed_seats.get_next_lower_index::<ClaimedSeat>(index);
     }
+    Ok((seats, index))
+}
 
-    // Compute mark price (prefers oracle, falls back to orderbook)
-    let mark_price: QuoteAtomsPerBaseAtom = compute_mark_price(&dynamic_account)?;
+#[cfg(feature = "certora")]
+fn seats_with_open_positions(
+    _dynamic_account: &MarketRefMut,
+    _liquidator: &Pubkey,
+    _start_index: DataIndex,
+    _limit: u32,
+) -> Result<(Vec<(Pubkey, DataIndex)>, DataIndex), ProgramError> {
+    Ok((Vec::new(), hypertree::NIL))
+}
 
+/// Equity and maintenance requirement of a position valued at `mark_price`.
+/// Returns `(equity, required_maintenance, current_value)`.
+fn equity_at_mark(
+    dynamic_account: &MarketRefMut,
+    margin_balance: u64,
+    position_size: i64,
+    quote_cost_basis: u64,
+    mark_price: QuoteAtomsPerBaseAtom,
+) -> Result<(i128, u64, u64), ProgramError> {
     // Compute current market value of position: mark_price * |position_size|
-    let abs_position: u64 = position_size.unsigned_abs();
     let current_value: u64 = mark_price
-        .checked_quote_for_base(BaseAtoms::new(abs_position), false)?
+        .checked_quote_for_base(BaseAtoms::new(position_size.unsigned_abs()), false)?
         .as_u64();
 
     // Compute unrealized PnL using i128 to avoid overflow on large u64 values
@@ -152,12 +562,139 @@ pub(crate) fn process_liquidate(
     // Equity = margin + unrealized_pnl
     let equity: i128 = (margin_balance as i128) + unrealized_pnl;
 
-    // Maintenance margin = current_value * maintenance_margin_bps / 10000
-    let maintenance_margin_bps: u64 = dynamic_account.fixed.get_maintenance_margin_bps();
-    let required_maintenance: u64 = current_value
-        .checked_mul(maintenance_margin_bps)
-        .unwrap_or(u64::MAX)
-        / 10000;
+    // Maintenance margin = current_value * maintenance_margin_bps / 10000, at
+    // the risk tier for this notional
+    let required_maintenance: u64 = dynamic_account
+        .fixed
+        .get_maintenance_margin_for_notional(current_value);
+
+    Ok((equity, required_maintenance, current_value))
+}
+
+/// Liquidate one trader at the batch's mark price. Returns false, having only
+/// settled the seat, when the trader has no position or is above maintenance
+/// margin, at the conservative mark price, once their resting bids are
+/// released. An orderbook close that finds nothing within the market's max
+/// liquidation slippage also returns false, with the orders still cancelled.
+/// A cross-margin seat passes the equity the rest of its portfolio
+/// holds beyond maintenance as `portfolio_surplus`. Its debt counts against
+/// it, and a deficit left by the close becomes debt for its collateral to
+/// cover instead of being drawn from the insurance fund.
+#[allow(clippy::too_many_arguments)]
+fn liquidate_trader(
+    dynamic_account: &mut MarketRefMut,
+    market: &Pubkey,
+    liquidator: &Pubkey,
+    liquidator_index: DataIndex,
+    trader: &Pubkey,
+    trader_index: DataIndex,
+    mode: LiquidationMode,
+    mark_price: QuoteAtomsPerBaseAtom,
+    portfolio_surplus: Option<i128>,
+    mut seat_extension: Option<&mut SeatExtension>,
+) -> Result<bool, ProgramError> {
+    // Lazy funding settlement for the trader being liquidated.
+    // Must happen before reading margin/position to ensure accurate equity computation.
+    settle_funding_and_socialized_loss(
+        dynamic_account,
+        market,
+        trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+    settle_pnl(
+        dynamic_account,
+        market,
+        trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+
+    let claimed_seat: &ClaimedSeat =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
+
+    let position_size: i64 = claimed_seat.get_position_size();
+    let quote_cost_basis: u64 = claimed_seat.get_quote_cost_basis();
+    let margin_before_cancel: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
+    // Profit the PnL pool could not pay yet. The close below applies its own
+    // PnL to the margin, so whatever it books as unsettled is reverted to this.
+    let unsettled_pnl: i64 = claimed_seat.get_unsettled_pnl();
+    let portfolio_equity: i128 = match portfolio_surplus {
+        None => 0,
+        Some(surplus) => surplus + unsettled_pnl.min(0) as i128,
+    };
+    if position_size == 0 {
+        dynamic_account.store_cumulative_for_trader(trader_index);
+        return Ok(false);
+    }
+    // Eligibility and the amount to close are judged at the edge of the
+    // oracle confidence interval against the trader. The close itself still
+    // settles at the mark price.
+    let valuation_price: QuoteAtomsPerBaseAtom =
+        compute_conservative_mark_price(dynamic_account, position_size)?;
+
+    // Collect this trader's open orders. Bids hold quote that cancelling them
+    // returns to the margin balance, so count it before deciding to liquidate.
+    let mut reserved_quote: u64 = 0;
+    let mut order_indices: Vec<DataIndex> = Vec::new();
+    for (index, order) in dynamic_account.get_bids().iter::<RestingOrder>() {
+        if order.get_trader_index() == trader_index {
+            if !order.is_global() {
+                reserved_quote = reserved_quote.saturating_add(
+                    order
+                        .get_price()
+                        .checked_quote_for_base(order.get_num_base_atoms(), true)?
+                        .as_u64(),
+                );
+            }
+            order_indices.push(index);
+        }
+    }
+    order_indices.extend(
+        dynamic_account
+            .get_asks()
+            .iter::<RestingOrder>()
+            .filter(|(_, order)| order.get_trader_index() == trader_index)
+            .map(|(index, _)| index),
+    );
+
+    let (equity, required_maintenance, _) = equity_at_mark(
+        dynamic_account,
+        margin_before_cancel.saturating_add(reserved_quote),
+        position_size,
+        quote_cost_basis,
+        valuation_price,
+    )?;
+    if equity + portfolio_equity >= required_maintenance as i128 {
+        dynamic_account.store_cumulative_for_trader(trader_index);
+        return Ok(false);
+    }
+
+    // Cancel all open orders belonging to this trader.
+    // This releases reserved funds back to the trader's balance
+    {
+        let no_global_accounts: [Option<GlobalTradeAccounts>; 2] = [None, None];
+        for order_index in order_indices.iter() {
+            dynamic_account.cancel_order_by_index(*order_index, &no_global_accounts)?;
+        }
+    }
+
+    // Re-read margin balance after order cancellations (funds released back)
+    let margin_balance: u64 = {
+        let seat: &ClaimedSeat =
+            get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
+        seat.quote_withdrawable_balance.as_u64()
+    };
+
+    let abs_position: u64 = position_size.unsigned_abs();
+    let (equity, required_maintenance, current_value) = equity_at_mark(
+        dynamic_account,
+        margin_balance,
+        position_size,
+        quote_cost_basis,
+        valuation_price,
+    )?;
+    let equity: i128 = equity + portfolio_equity;
 
     require!(
         equity < required_maintenance as i128,
@@ -176,14 +713,21 @@ pub(crate) fn process_liquidate(
     //
     // Solving: f = (target_bps - equity_bps) / (target_bps - REWARD_BPS)
     //   where equity_bps = equity * 10000 / current_value
+    // Use the tier of the position before closing. Closing can only move it to
+    // a lower tier, so the target errs towards closing more.
+    let (_, maintenance_margin_bps) = dynamic_account
+        .fixed
+        .get_margin_bps_for_notional(current_value);
     let liquidation_buffer_bps: u64 = dynamic_account.fixed.get_liquidation_buffer_bps();
+    let liquidator_reward_bps: u64 = dynamic_account.fixed.get_liquidator_reward_bps();
+    let min_position_size_atoms: u64 = dynamic_account.fixed.get_min_position_size_atoms();
     let target_bps: i128 = (maintenance_margin_bps + liquidation_buffer_bps) as i128;
 
     let close_amount: u64 = if current_value == 0 {
         abs_position
     } else {
         let equity_bps: i128 = equity * 10000 / current_value as i128;
-        let reward_bps: i128 = LIQUIDATOR_REWARD_BPS as i128;
+        let reward_bps: i128 = liquidator_reward_bps as i128;
 
         let f_numerator: i128 = target_bps - equity_bps;
         let f_denominator: i128 = target_bps - reward_bps;
@@ -198,8 +742,7 @@ pub(crate) fn process_liquidate(
             }
         } else {
             // f = f_numerator / f_denominator, close_amount = ceil(f * abs_position)
-            let close: u128 = (f_numerator as u128 * abs_position as u128
-                + f_denominator as u128
+            let close: u128 = (f_numerator as u128 * abs_position as u128 + f_denominator as u128
                 - 1)
                 / f_denominator as u128;
             (close.min(abs_position as u128)) as u64
@@ -208,30 +751,87 @@ pub(crate) fn process_liquidate(
 
     // If close_amount is 0, orders being cancelled was sufficient
     if close_amount == 0 {
-        return Ok(());
+        dynamic_account.store_cumulative_for_trader(trader_index);
+        return Ok(true);
     }
 
     // Round up to full liquidation if remaining position would be dust
-    let close_amount: u64 = if abs_position.saturating_sub(close_amount) < MIN_POSITION_SIZE_ATOMS {
+    let close_amount: u64 = if abs_position.saturating_sub(close_amount) < min_position_size_atoms {
         abs_position
     } else {
         close_amount
     };
 
-    let is_full_liquidation: bool = close_amount >= abs_position;
-
-    // Proportional cost basis for the closed portion
-    let closed_cost_basis: u64 = if is_full_liquidation {
-        quote_cost_basis
-    } else {
-        ((quote_cost_basis as u128 * close_amount as u128) / abs_position as u128) as u64
+    // Close the position, either at the mark price or against the book. Both
+    // paths yield the amount actually closed, its cost basis and the quote
+    // it was closed for.
+    let (close_amount, closed_cost_basis, closed_notional) = match mode {
+        LiquidationMode::MarkPrice => {
+            let closed_cost_basis: u64 = if close_amount >= abs_position {
+                quote_cost_basis
+            } else {
+                ((quote_cost_basis as u128 * close_amount as u128) / abs_position as u128) as u64
+            };
+            let closed_notional: u64 = mark_price
+                .checked_quote_for_base(BaseAtoms::new(close_amount), false)?
+                .as_u64();
+            close_position_at_mark(
+                dynamic_account,
+                trader_index,
+                position_size,
+                close_amount,
+                closed_cost_basis,
+            );
+            (close_amount, closed_cost_basis, closed_notional)
+        }
+        LiquidationMode::Orderbook => {
+            let (closed_amount, closed_notional) = close_position_on_orderbook(
+                dynamic_account,
+                market,
+                trader_index,
+                position_size,
+                close_amount,
+                mark_price,
+            )?;
+            if closed_amount == 0 {
+                // Nothing to close against near the mark. Skip the trader
+                // rather than failing the rest of the batch.
+                dynamic_account.store_cumulative_for_trader(trader_index);
+                return Ok(false);
+            }
+            // Same rounding as update_perps_position used to reduce the seat.
+            let closed_cost_basis: u64 =
+                ((quote_cost_basis as u128 * closed_amount as u128) / abs_position as u128) as u64;
+            (closed_amount, closed_cost_basis, closed_notional)
+        }
+        LiquidationMode::Takeover => {
+            let closed_cost_basis: u64 =
+                ((quote_cost_basis as u128 * close_amount as u128) / abs_position as u128) as u64;
+            let notional_at_mark: u64 = mark_price
+                .checked_quote_for_base(BaseAtoms::new(close_amount), false)?
+                .as_u64();
+            let discount: u64 = notional_at_mark
+                .checked_mul(liquidator_reward_bps)
+                .unwrap_or(0)
+                / 10000;
+            // The liquidator buys a long below mark and sells a short above it.
+            let closed_notional: u64 = if position_size > 0 {
+                notional_at_mark.saturating_sub(discount)
+            } else {
+                notional_at_mark.saturating_add(discount)
+            };
+            take_over_position(
+                dynamic_account,
+                trader_index,
+                liquidator_index,
+                position_size,
+                close_amount,
+                closed_notional,
+            )?;
+            (close_amount, closed_cost_basis, closed_notional)
+        }
     };
 
-    // Compute notional of the closed portion
-    let closed_notional: u64 = mark_price
-        .checked_quote_for_base(BaseAtoms::new(close_amount), false)?
-        .as_u64();
-
     // PnL on the closed portion (use i128 to avoid overflow)
     let closed_pnl: i128 = if position_size > 0 {
         (closed_notional as i128) - (closed_cost_basis as i128)
@@ -239,74 +839,168 @@ pub(crate) fn process_liquidate(
         (closed_cost_basis as i128) - (closed_notional as i128)
     };
 
-    // Liquidator reward = % of closed notional (always incentivizes liquidation)
-    let liquidator_reward: u64 = closed_notional
-        .checked_mul(LIQUIDATOR_REWARD_BPS)
-        .unwrap_or(0)
-        / 10000;
-
-    // Settlement: apply PnL to margin, deduct reward
-    let margin_after_pnl: i128 = margin_balance as i128 + closed_pnl;
-    let margin_after_reward: i128 = margin_after_pnl - liquidator_reward as i128;
-
-    // Insurance fund draw: if margin goes negative, there's bad debt
-    let (final_trader_margin, actual_liquidator_reward) = if margin_after_reward >= 0 {
-        (margin_after_reward as u64, liquidator_reward)
+    // Liquidator reward = % of closed notional (always incentivizes liquidation).
+    // A takeover is paid through the discount already in closed_pnl instead.
+    let liquidator_reward: u64 = if mode == LiquidationMode::Takeover {
+        0
     } else {
-        // Bad debt scenario
-        let deficit: u64 = (-margin_after_reward) as u64;
-        let drawn = dynamic_account.fixed.draw_from_insurance_fund(deficit);
-        if drawn >= deficit {
-            // Insurance fund fully covers the deficit
-            (0u64, liquidator_reward)
-        } else {
-            // Insurance fund insufficient; reduce liquidator reward
-            let remaining_deficit = deficit - drawn;
-            let adjusted_reward = liquidator_reward.saturating_sub(remaining_deficit);
-            (0u64, adjusted_reward)
-        }
+        closed_notional
+            .checked_mul(liquidator_reward_bps)
+            .unwrap_or(0)
+            / 10000
     };
 
-    // Update trader's seat
-    {
-        let claimed_seat_mut: &mut ClaimedSeat = get_mut_helper::<RBNode<ClaimedSeat>>(
-            &mut dynamic_account.dynamic,
-            trader_index,
-        )
-        .get_mut_value();
+    // Settlement: apply PnL to margin, deduct reward. Like any other realized
+    // profit, a profit on the close is paid out of the PnL pool and the part
+    // it cannot pay yet is left unsettled.
+    let closed_profit_paid: u64 = if closed_pnl > 0 {
+        dynamic_account
+            .fixed
+            .draw_from_pnl_pool(closed_pnl.min(u64::MAX as i128) as u64)
+    } else {
+        0
+    };
+    let margin_after_pnl: i128 =
+        margin_balance as i128 + closed_pnl.min(0) + closed_profit_paid as i128;
+    let margin_after_reward: i128 = margin_after_pnl - liquidator_reward as i128;
 
-        if is_full_liquidation {
-            claimed_seat_mut.set_position_size(0);
-            claimed_seat_mut.set_quote_cost_basis(0);
+    // Insurance fund draw: if margin goes negative, there's bad debt. A
+    // cross-margin seat owes it instead, until its collateral covers it.
+    let (final_trader_margin, actual_liquidator_reward, uncovered_deficit, cross_margin_debt) =
+        if margin_after_reward >= 0 {
+            (margin_after_reward as u64, liquidator_reward, 0u64, 0u64)
+        } else if portfolio_surplus.is_some() {
+            (0u64, liquidator_reward, 0u64, (-margin_after_reward) as u64)
         } else {
-            let new_position: i64 = if position_size > 0 {
-                position_size - close_amount as i64
+            // Bad debt scenario
+            let deficit: u64 = (-margin_after_reward) as u64;
+            let drawn = dynamic_account.fixed.draw_from_insurance_fund(deficit);
+            if drawn >= deficit {
+                // Insurance fund fully covers the deficit
+                (0u64, liquidator_reward, 0u64, 0u64)
             } else {
-                position_size + close_amount as i64
-            };
-            claimed_seat_mut.set_position_size(new_position);
-            claimed_seat_mut.set_quote_cost_basis(
-                quote_cost_basis.saturating_sub(closed_cost_basis),
-            );
-        }
+                // Insurance fund insufficient; reduce liquidator reward and
+                // socialize whatever is still left over.
+                let remaining_deficit = deficit - drawn;
+                let adjusted_reward = liquidator_reward.saturating_sub(remaining_deficit);
+                (
+                    0u64,
+                    adjusted_reward,
+                    remaining_deficit.saturating_sub(liquidator_reward),
+                    0u64,
+                )
+            }
+        };
+
+    // The loss collected from the margin and the insurance fund backs realized
+    // profits. The uncovered part joins the pool as it is charged to others,
+    // and a cross-margin debt as it is paid.
+    if closed_pnl < 0 {
+        let closed_loss: u64 = closed_pnl.unsigned_abs().min(u64::MAX as u128) as u64;
+        dynamic_account.fixed.add_to_pnl_pool(
+            closed_loss.saturating_sub(uncovered_deficit.saturating_add(cross_margin_debt)),
+        );
+    }
+
+    // The part of the reward the trader's own margin covered, the rest came
+    // from the insurance fund.
+    let reward_paid_by_trader: u64 =
+        (liquidator_reward as i128).min(margin_after_pnl.max(0)) as u64;
 
+    // Update trader's margin. The position was already reduced above.
+    {
+        let claimed_seat_mut: &mut ClaimedSeat =
+            get_mut_helper::<RBNode<ClaimedSeat>>(&mut dynamic_account.dynamic, trader_index)
+                .get_mut_value();
+        // Book and takeover closes booked their PnL like a fill, which the
+        // sync picks up before the seat is overwritten.
+        if let Some(seat_extension) = seat_extension.as_deref_mut() {
+            seat_extension.sync(claimed_seat_mut);
+        }
         claimed_seat_mut.quote_withdrawable_balance = QuoteAtoms::new(final_trader_margin);
+        claimed_seat_mut.set_unsettled_pnl(
+            unsettled_pnl
+                .saturating_add((closed_pnl.max(0) - closed_profit_paid as i128) as i64)
+                .saturating_sub(cross_margin_debt as i64),
+        );
+        if let Some(seat_extension) = seat_extension {
+            seat_extension.rebase(claimed_seat_mut);
+            if mode == LiquidationMode::MarkPrice {
+                seat_extension.add_realized_pnl(closed_pnl as i64);
+            }
+            seat_extension.add_fees_paid(reward_paid_by_trader);
+        }
     }
 
     // Credit liquidator reward (liquidator must have a seat)
-    if actual_liquidator_reward > 0 {
-        let liquidator_index: DataIndex = dynamic_account.get_trader_index(liquidator.key);
-        if liquidator_index != hypertree::NIL {
-            let liquidator_seat: &mut ClaimedSeat =
-                get_mut_helper::<RBNode<ClaimedSeat>>(
-                    &mut dynamic_account.dynamic,
-                    liquidator_index,
-                )
+    if actual_liquidator_reward > 0 && liquidator_index != hypertree::NIL {
+        let liquidator_seat: &mut ClaimedSeat =
+            get_mut_helper::<RBNode<ClaimedSeat>>(&mut dynamic_account.dynamic, liquidator_index)
                 .get_mut_value();
-            let current = liquidator_seat.quote_withdrawable_balance.as_u64();
-            liquidator_seat.quote_withdrawable_balance =
-                QuoteAtoms::new(current.saturating_add(actual_liquidator_reward));
-        }
+        let current = liquidator_seat.quote_withdrawable_balance.as_u64();
+        liquidator_seat.quote_withdrawable_balance =
+            QuoteAtoms::new(current.saturating_add(actual_liquidator_reward));
+    }
+
+    // Store current global cumulative funding checkpoint for the trader, who
+    // was settled above. The liquidator's was stored before the batch.
+    dynamic_account.store_cumulative_for_trader(trader_index);
+
+    // Spread bad debt the insurance fund could not cover over the remaining
+    // open interest. This happens after the checkpoints so that every open
+    // position, including the liquidator's and those of traders later in the
+    // batch, is charged on its next settlement. What is left of a partially
+    // liquidated position is already checkpointed past this loss, so it is
+    // left out of the open interest the loss is spread over.
+    #[cfg(not(feature = "certora"))]
+    if uncovered_deficit > 0
+        && dynamic_account
+            .fixed
+            .socialize_loss_excluding(uncovered_deficit, abs_position.saturating_sub(close_amount))
+            > 0
+    {
+        emit_stack(SocializeLossLog {
+            market: *market,
+            trader: *trader,
+            amount_atoms: uncovered_deficit,
+            cumulative_loss_per_unit: dynamic_account.fixed.get_cumulative_loss_per_unit(),
+        })?;
+    }
+
+    emit_stack(LiquidateLog {
+        market: *market,
+        liquidator: *liquidator,
+        trader: *trader,
+        position_size: abs_position,
+        settlement_price: current_value,
+        pnl: closed_pnl as i64 as u64,
+        close_amount,
+    })?;
+
+    Ok(true)
+}
+
+/// Reduce the liquidated position by `close_amount` without trading and keep
+/// the market open interest in step.
+fn close_position_at_mark(
+    dynamic_account: &mut MarketRefMut,
+    trader_index: DataIndex,
+    position_size: i64,
+    close_amount: u64,
+    closed_cost_basis: u64,
+) {
+    {
+        let claimed_seat_mut: &mut ClaimedSeat =
+            get_mut_helper::<RBNode<ClaimedSeat>>(&mut dynamic_account.dynamic, trader_index)
+                .get_mut_value();
+        let new_position: i64 = if position_size > 0 {
+            position_size - close_amount as i64
+        } else {
+            position_size + close_amount as i64
+        };
+        let quote_cost_basis: u64 = claimed_seat_mut.get_quote_cost_basis();
+        claimed_seat_mut.set_position_size(new_position);
+        claimed_seat_mut.set_quote_cost_basis(quote_cost_basis.saturating_sub(closed_cost_basis));
     }
 
     // Update global position tracking
@@ -324,147 +1018,128 @@ pub(crate) fn process_liquidate(
                 .set_total_short_base_atoms(current.saturating_sub(close_amount));
         }
     }
-
-    // Store current global cumulative funding checkpoint for both trader and liquidator.
-    dynamic_account.store_cumulative_for_trader(trader_index);
-    {
-        let liquidator_index: DataIndex = dynamic_account.get_trader_index(liquidator.key);
-        if liquidator_index != hypertree::NIL {
-            dynamic_account.store_cumulative_for_trader(liquidator_index);
-        }
-    }
-
-    emit_stack(LiquidateLog {
-        market: *market.key,
-        liquidator: *liquidator.key,
-        trader: params.trader_to_liquidate,
-        position_size: abs_position,
-        settlement_price: current_value,
-        pnl: closed_pnl as i64 as u64,
-        close_amount,
-    })?;
-
-    Ok(())
 }
 
-/// Compute mark price, preferring cached oracle price over orderbook.
-///
-/// If the oracle price is set (oracle_price_mantissa > 0), converts it to
-/// QuoteAtomsPerBaseAtom using the market's decimal configuration.
-/// Falls back to orderbook best bid/ask if oracle is not available.
-/// Compute mark price from the orderbook (mid-price of best bid/ask).
-/// Used by funding to get the actual market price (not oracle).
-/// Falls back to cached oracle only when the orderbook is empty.
-pub(crate) fn compute_orderbook_mark_price(market: &MarketRefMut) -> Result<QuoteAtomsPerBaseAtom, ProgramError> {
-    let best_bid_index = market.fixed.get_bids_best_index();
-    let best_ask_index = market.fixed.get_asks_best_index();
-
-    // Prefer orderbook mid-price when both sides are present
-    if best_bid_index != hypertree::NIL && best_ask_index != hypertree::NIL {
-        let best_bid: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
-        let best_ask: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
-        let bid_inner = crate::quantities::u64_slice_to_u128(best_bid.get_price().inner);
-        let ask_inner = crate::quantities::u64_slice_to_u128(best_ask.get_price().inner);
-        let mid_inner = (bid_inner / 2) + (ask_inner / 2) + ((bid_inner % 2 + ask_inner % 2) / 2);
-        return Ok(QuoteAtomsPerBaseAtom { inner: [mid_inner as u64, (mid_inner >> 64) as u64] });
-    }
+/// Close up to `close_amount` of the liquidated position with an IOC order on
+/// the trader's own seat. Matching updates both sides' positions and the open
+/// interest. Fills are limited to within the market's max liquidation
+/// slippage of the mark price. Returns `(base_atoms_closed, quote_atoms_traded)`,
+/// which are zero when the book has nothing in that range.
+fn close_position_on_orderbook(
+    dynamic_account: &mut MarketRefMut,
+    market: &Pubkey,
+    trader_index: DataIndex,
+    position_size: i64,
+    close_amount: u64,
+    mark_price: QuoteAtomsPerBaseAtom,
+) -> Result<(u64, u64), ProgramError> {
+    // A long is closed by selling into the bids, a short by buying the asks.
+    let is_bid: bool = position_size < 0;
+    let max_slippage_bps: u64 = dynamic_account.fixed.get_max_liquidation_slippage_bps();
+    let mark_inner: u128 = crate::quantities::u64_slice_to_u128(mark_price.inner);
+    let limit_inner: u128 = if is_bid {
+        mark_inner.saturating_mul((10000 + max_slippage_bps) as u128) / 10000
+    } else {
+        mark_inner.saturating_mul(10000_u64.saturating_sub(max_slippage_bps) as u128) / 10000
+    };
+    let limit_price: QuoteAtomsPerBaseAtom = QuoteAtomsPerBaseAtom {
+        inner: [limit_inner as u64, (limit_inner >> 64) as u64],
+    };
 
-    // One-sided book: use the available side
-    if best_bid_index != hypertree::NIL {
-        let best_bid: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
-        return Ok(best_bid.get_price());
-    }
-    if best_ask_index != hypertree::NIL {
-        let best_ask: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
-        return Ok(best_ask.get_price());
-    }
+    let no_global_accounts: [Option<GlobalTradeAccounts>; 2] = [None, None];
+    let AddOrderToMarketResult {
+        base_atoms_traded,
+        quote_atoms_traded,
+        ..
+    } = dynamic_account.place_order(AddOrderToMarketArgs {
+        market: *market,
+        trader_index,
+        num_base_atoms: BaseAtoms::new(close_amount),
+        price: limit_price,
+        is_bid,
+        last_valid_slot: NO_EXPIRATION_LAST_VALID_SLOT,
+        order_type: OrderType::ImmediateOrCancel,
+        global_trade_accounts_opts: &no_global_accounts,
+        current_slot: None,
+    })?;
 
-    // Empty book: fall back to cached oracle
-    let oracle_mantissa = market.fixed.get_oracle_price_mantissa();
-    if oracle_mantissa > 0 {
-        let expo = market.fixed.get_oracle_price_expo() as i64;
-        let base_decimals = market.fixed.get_base_mint_decimals() as i64;
-        let quote_decimals = market.fixed.get_quote_mint_decimals() as i64;
-        let adjusted_expo = expo + quote_decimals - base_decimals;
-        let mut m = oracle_mantissa as u128;
-        let mut e = adjusted_expo;
-        while m > u32::MAX as u128 && e < i8::MAX as i64 {
-            m /= 10;
-            e += 1;
-        }
-        if m <= u32::MAX as u128 && e >= i8::MIN as i64 && e <= i8::MAX as i64 {
-            if let Ok(price) =
-                QuoteAtomsPerBaseAtom::try_from_mantissa_and_exponent(m as u32, e as i8)
-            {
-                return Ok(price);
-            }
-        }
-    }
+    // A reverse maker may have used the spare block, same as in swap.
+    require!(
+        dynamic_account.has_free_block(),
+        ManifestError::InvalidFreeList,
+        "Cannot liquidate against a reverse order unless there is a free block"
+    )?;
 
-    Err(ManifestError::InvalidPerpsOperation.into())
+    Ok((base_atoms_traded.as_u64(), quote_atoms_traded.as_u64()))
 }
 
-pub(crate) fn compute_mark_price(market: &MarketRefMut) -> Result<QuoteAtomsPerBaseAtom, ProgramError> {
-    let oracle_mantissa = market.fixed.get_oracle_price_mantissa();
-    if oracle_mantissa > 0 {
-        // Oracle price = mantissa * 10^expo (USD per unit of base asset)
-        // Convert to QuoteAtomsPerBaseAtom:
-        //   qapba = mantissa * 10^(expo + quote_decimals - base_decimals)
-        let expo = market.fixed.get_oracle_price_expo() as i64;
-        let base_decimals = market.fixed.get_base_mint_decimals() as i64;
-        let quote_decimals = market.fixed.get_quote_mint_decimals() as i64;
-
-        let adjusted_expo = expo + quote_decimals - base_decimals;
-
-        // Normalize mantissa to fit in u32 while adjusting exponent
-        let mut m = oracle_mantissa as u128;
-        let mut e = adjusted_expo;
-        while m > u32::MAX as u128 && e < i8::MAX as i64 {
-            m /= 10;
-            e += 1;
-        }
+/// Move `close_amount` of the liquidated position onto the liquidator's seat
+/// for `closed_notional`. Both sides go through the same position update as a
+/// fill, which also keeps the open interest totals in step.
+fn take_over_position(
+    dynamic_account: &mut MarketRefMut,
+    trader_index: DataIndex,
+    liquidator_index: DataIndex,
+    position_size: i64,
+    close_amount: u64,
+    closed_notional: u64,
+) -> ProgramResult {
+    let liquidator_is_bid: bool = position_size > 0;
+    update_perps_position(
+        dynamic_account.fixed,
+        dynamic_account.dynamic,
+        trader_index,
+        close_amount,
+        closed_notional,
+        !liquidator_is_bid,
+    )?;
+    update_perps_position(
+        dynamic_account.fixed,
+        dynamic_account.dynamic,
+        liquidator_index,
+        close_amount,
+        closed_notional,
+        liquidator_is_bid,
+    )?;
+    Ok(())
+}
 
-        if m <= u32::MAX as u128 && e >= i8::MIN as i64 && e <= i8::MAX as i64 {
-            if let Ok(price) =
-                QuoteAtomsPerBaseAtom::try_from_mantissa_and_exponent(m as u32, e as i8)
-            {
-                return Ok(price);
-            }
-        }
-        // If conversion fails, fall through to orderbook
+/// Same initial margin check as after placing an order, for a seat whose
+/// position was just increased by a takeover.
+#[cfg(not(feature = "certora"))]
+fn require_initial_margin(
+    dynamic_account: &MarketRefMut,
+    trader_index: DataIndex,
+) -> ProgramResult {
+    let claimed_seat: &ClaimedSeat =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
+    let position_size: i64 = claimed_seat.get_position_size();
+    if position_size == 0 {
+        return Ok(());
     }
+    let mark_price: QuoteAtomsPerBaseAtom =
+        compute_conservative_mark_price(dynamic_account, position_size)?;
+    let notional: u64 = mark_price
+        .checked_quote_for_base(BaseAtoms::new(position_size.unsigned_abs()), false)?
+        .as_u64();
+    let required_margin: u64 = dynamic_account
+        .fixed
+        .get_initial_margin_for_notional(notional);
 
-    // Fallback: orderbook best bid/ask
-    let best_bid_index = market.fixed.get_bids_best_index();
-    let best_ask_index = market.fixed.get_asks_best_index();
-
+    let cost_basis: u64 = claimed_seat.get_quote_cost_basis();
+    let unrealized_pnl: i128 = if position_size > 0 {
+        (notional as i128) - (cost_basis as i128)
+    } else {
+        (cost_basis as i128) - (notional as i128)
+    };
+    let margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
+    let equity: i128 = (margin as i128) + unrealized_pnl;
     require!(
-        best_bid_index != hypertree::NIL || best_ask_index != hypertree::NIL,
-        ManifestError::InvalidPerpsOperation,
-        "Cannot compute mark price: empty orderbook",
+        equity >= required_margin as i128,
+        ManifestError::InsufficientMargin,
+        "Initial margin check failed: equity {} < required {}",
+        equity,
+        required_margin,
     )?;
-
-    if best_bid_index != hypertree::NIL && best_ask_index != hypertree::NIL {
-        let best_bid: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
-        let best_ask: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
-        // Use midpoint of bid and ask for a fair mark price
-        let bid_inner = crate::quantities::u64_slice_to_u128(best_bid.get_price().inner);
-        let ask_inner = crate::quantities::u64_slice_to_u128(best_ask.get_price().inner);
-        let mid_inner = (bid_inner / 2) + (ask_inner / 2) + ((bid_inner % 2 + ask_inner % 2) / 2);
-        Ok(QuoteAtomsPerBaseAtom { inner: [mid_inner as u64, (mid_inner >> 64) as u64] })
-    } else if best_bid_index != hypertree::NIL {
-        let best_bid: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
-        Ok(best_bid.get_price())
-    } else {
-        let best_ask: &RestingOrder =
-            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
-        Ok(best_ask.get_price())
-    }
+    Ok(())
 }
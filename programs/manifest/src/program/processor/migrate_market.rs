@@ -0,0 +1,53 @@
+use std::cell::Ref;
+
+use crate::{
+    logs::{emit_stack, MigrateMarketLog},
+    program::expand_account,
+    state::{
+        get_migrated_market_size, migrate_legacy_market, MARKET_BLOCK_SIZE, MARKET_FIXED_SIZE,
+    },
+    validation::loaders::MigrateMarketContext,
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, entrypoint::MAX_PERMITTED_DATA_INCREASE,
+    pubkey::Pubkey,
+};
+
+pub(crate) fn process_migrate_market(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let migrate_market_context: MigrateMarketContext = MigrateMarketContext::load(accounts)?;
+    let MigrateMarketContext {
+        payer,
+        market,
+        _system_program,
+    } = migrate_market_context;
+
+    let migrated_size: usize = {
+        let market_data: Ref<&mut [u8]> = market.try_borrow_data()?;
+        get_migrated_market_size(&market_data)?
+    };
+
+    // An instruction can only grow an account by MAX_PERMITTED_DATA_INCREASE,
+    // so large markets are grown over several calls. The legacy data stays
+    // untouched until the last one rewrites it.
+    if market.data_len() < migrated_size {
+        let new_size: usize = migrated_size.min(market.data_len() + MAX_PERMITTED_DATA_INCREASE);
+        expand_account(&payer, market, new_size)?;
+        if new_size < migrated_size {
+            return Ok(());
+        }
+    }
+
+    migrate_legacy_market(&mut market.try_borrow_mut_data()?)?;
+
+    emit_stack(MigrateMarketLog {
+        market: *market.key,
+        payer: *payer.key,
+        num_blocks: ((migrated_size - MARKET_FIXED_SIZE) / MARKET_BLOCK_SIZE) as u64,
+    })?;
+
+    Ok(())
+}
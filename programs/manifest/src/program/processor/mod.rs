@@ -2,7 +2,14 @@ pub mod batch_update;
 pub mod claim_seat;
 pub mod commit_market;
 pub mod crank_funding;
+pub mod create_cross_margin_account;
+pub mod create_funding_history;
 pub mod create_market;
+pub mod create_seat_extension;
+pub mod cross_margin_deposit;
+pub mod cross_margin_link;
+pub mod cross_margin_transfer;
+pub mod cross_margin_withdraw;
 pub mod delegate_market;
 pub mod deposit;
 pub mod expand_market;
@@ -12,10 +19,20 @@ pub mod global_create;
 pub mod global_deposit;
 pub mod global_evict;
 pub mod global_withdraw;
+pub mod insurance_deposit;
+pub mod insurance_request_withdraw;
+pub mod insurance_withdraw;
 pub mod liquidate;
+pub mod migrate_market;
 pub mod release_seat;
+pub mod revoke_trade_delegate;
+pub mod set_session_scope;
+pub mod set_trade_delegate;
+pub mod set_withdraw_allowlist;
+pub mod settle_pnl;
 pub mod shared;
 pub mod swap;
+pub mod update_market;
 pub mod withdraw;
 
 pub use shared::*;
@@ -3,22 +3,41 @@ use std::cell::RefMut;
 use crate::{
     program::ManifestError,
     require,
-    state::{MarketFixed, MarketRefMut},
+    state::{claimed_seat::ClaimedSeat, MarketFixed, MarketRefMut},
     validation::{loaders::ReleaseSeatContext, ManifestAccountInfo, Signer},
 };
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::{get_helper, DataIndex, RBNode, NIL};
 use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
 
 use super::shared::get_mut_dynamic_account;
 
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ReleaseSeatParams {
+    pub sub_account_id: u8,
+}
+
+impl ReleaseSeatParams {
+    pub fn new(sub_account_id: u8) -> Self {
+        ReleaseSeatParams { sub_account_id }
+    }
+}
+
 pub(crate) fn process_release_seat(
     _program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _data: &[u8],
+    data: &[u8],
 ) -> ProgramResult {
+    // Instructions without params release the default sub-account.
+    let params: ReleaseSeatParams = if data.is_empty() {
+        ReleaseSeatParams::new(0)
+    } else {
+        ReleaseSeatParams::try_from_slice(data)?
+    };
     let release_seat_context: ReleaseSeatContext = ReleaseSeatContext::load(accounts)?;
     let ReleaseSeatContext { market, payer, .. } = release_seat_context;
 
-    process_release_seat_internal(&market, &payer)?;
+    process_release_seat_internal(&market, &payer, params.sub_account_id)?;
 
     Ok(())
 }
@@ -26,12 +45,25 @@ pub(crate) fn process_release_seat(
 fn process_release_seat_internal<'a, 'info>(
     market: &ManifestAccountInfo<'a, 'info, MarketFixed>,
     payer: &Signer<'a, 'info>,
+    sub_account_id: u8,
 ) -> ProgramResult {
     let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
     let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
 
+    let trader_index: DataIndex = dynamic_account.get_sub_account_index(payer.key, sub_account_id);
+    require!(
+        trader_index != NIL,
+        ManifestError::InvalidPerpsOperation,
+        "Trader {} has no seat for sub-account {}",
+        payer.key,
+        sub_account_id,
+    )?;
+    let claimed_seat: &ClaimedSeat =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
+
     // Check that the trader has zero quote balance
-    let (base_balance, quote_balance) = dynamic_account.get_trader_balance(payer.key);
+    let quote_balance = claimed_seat.quote_withdrawable_balance;
+    let base_balance = claimed_seat.base_withdrawable_balance;
     require!(
         quote_balance == crate::quantities::QuoteAtoms::ZERO,
         ManifestError::InvalidWithdrawAccounts,
@@ -46,7 +78,7 @@ fn process_release_seat_internal<'a, 'info>(
     )?;
 
     // Check that the trader has no open position
-    let (position_size, _cost_basis) = dynamic_account.get_trader_position(payer.key);
+    let position_size: i64 = claimed_seat.get_position_size();
     require!(
         position_size == 0,
         ManifestError::InvalidPerpsOperation,
@@ -54,7 +86,15 @@ fn process_release_seat_internal<'a, 'info>(
         position_size,
     )?;
 
-    dynamic_account.release_seat(payer.key)?;
+    // A linked seat must be unlinked from its cross-margin account first,
+    // which also checks that it owes nothing.
+    require!(
+        !claimed_seat.is_cross_margin(),
+        ManifestError::InvalidPerpsOperation,
+        "Cannot release a seat linked to a cross-margin account",
+    )?;
+
+    dynamic_account.release_sub_account_seat(payer.key, sub_account_id)?;
 
     Ok(())
 }
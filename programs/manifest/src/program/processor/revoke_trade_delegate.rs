@@ -0,0 +1,41 @@
+use crate::{
+    logs::{emit_stack, TradeDelegateLog},
+    state::TradeDelegate,
+    validation::loaders::RevokeTradeDelegateContext,
+};
+use hypertree::get_mut_helper;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+
+/// Revoke the trade delegate of one of the signer's seats. The record stays
+/// so the trader can name a new delegate later without paying rent again.
+pub(crate) fn process_revoke_trade_delegate(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let revoke_trade_delegate_context: RevokeTradeDelegateContext =
+        RevokeTradeDelegateContext::load(accounts)?;
+    let RevokeTradeDelegateContext {
+        payer,
+        market,
+        trade_delegate,
+    } = revoke_trade_delegate_context;
+
+    let sub_account_id: u8 = {
+        let trade_delegate_bytes: &mut [u8] = &mut trade_delegate.try_borrow_mut_data()?[..];
+        let record: &mut TradeDelegate =
+            get_mut_helper::<TradeDelegate>(trade_delegate_bytes, 0_u32);
+        record.revoke();
+        record.get_sub_account_id()
+    };
+
+    emit_stack(TradeDelegateLog {
+        market: *market.info.key,
+        trader: *payer.key,
+        delegate: Pubkey::default(),
+        sub_account_id,
+        _padding: [0; 7],
+    })?;
+
+    Ok(())
+}
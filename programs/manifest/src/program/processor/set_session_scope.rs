@@ -0,0 +1,122 @@
+use std::mem::size_of;
+
+use crate::{
+    logs::{emit_stack, SessionScopeLog},
+    program::ManifestError,
+    require,
+    state::SessionScope,
+    utils::create_account,
+    validation::{get_session_scope_address, loaders::SetSessionScopeContext},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, rent::Rent,
+    sysvar::Sysvar,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SetSessionScopeParams {
+    pub session_signer: Pubkey,
+    /// Markets the session may act on, empty for every market.
+    pub markets: Vec<Pubkey>,
+    /// Zero for no limit.
+    pub max_order_notional_atoms: u64,
+    /// Zero for no limit.
+    pub max_position_atoms: u64,
+    /// See `SessionScope::instructions_mask`.
+    pub allowed_instructions: u64,
+}
+
+impl SetSessionScopeParams {
+    pub fn new(
+        session_signer: Pubkey,
+        markets: Vec<Pubkey>,
+        max_order_notional_atoms: u64,
+        max_position_atoms: u64,
+        allowed_instructions: u64,
+    ) -> Self {
+        SetSessionScopeParams {
+            session_signer,
+            markets,
+            max_order_notional_atoms,
+            max_position_atoms,
+            allowed_instructions,
+        }
+    }
+}
+
+/// Set the limits of a session signer acting for the signer, replacing any
+/// previous ones. The scope record is created the first time.
+pub(crate) fn process_set_session_scope(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: SetSessionScopeParams = SetSessionScopeParams::try_from_slice(data)?;
+    let set_session_scope_context: SetSessionScopeContext = SetSessionScopeContext::load(accounts)?;
+    let SetSessionScopeParams {
+        session_signer,
+        markets,
+        max_order_notional_atoms,
+        max_position_atoms,
+        allowed_instructions,
+    } = params;
+
+    let SetSessionScopeContext {
+        payer,
+        session_scope,
+        system_program,
+    } = set_session_scope_context;
+
+    let scope: SessionScope = SessionScope::new(
+        payer.key,
+        &session_signer,
+        &markets,
+        max_order_notional_atoms,
+        max_position_atoms,
+        allowed_instructions,
+    )?;
+
+    let (expected_session_scope, session_scope_bump) =
+        get_session_scope_address(payer.key, &session_signer);
+    require!(
+        expected_session_scope == *session_scope.key,
+        ManifestError::IncorrectAccount,
+        "Incorrect session scope account",
+    )?;
+
+    if session_scope.data_is_empty() {
+        let session_scope_seeds: Vec<Vec<u8>> = vec![
+            b"session-scope".to_vec(),
+            payer.key.as_ref().to_vec(),
+            session_signer.as_ref().to_vec(),
+            vec![session_scope_bump],
+        ];
+        create_account(
+            payer.as_ref(),
+            session_scope,
+            system_program.as_ref(),
+            &crate::id(),
+            &Rent::get()?,
+            size_of::<SessionScope>() as u64,
+            session_scope_seeds,
+        )?;
+    }
+
+    {
+        let session_scope_bytes: &mut [u8] = &mut session_scope.try_borrow_mut_data()?[..];
+        *get_mut_helper::<SessionScope>(session_scope_bytes, 0_u32) = scope;
+    }
+
+    emit_stack(SessionScopeLog {
+        authority: *payer.key,
+        session_signer,
+        max_order_notional_atoms,
+        max_position_atoms,
+        allowed_instructions,
+        num_markets: markets.len() as u64,
+    })?;
+
+    Ok(())
+}
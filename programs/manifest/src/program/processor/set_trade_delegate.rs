@@ -0,0 +1,120 @@
+use std::{cell::Ref, mem::size_of};
+
+use crate::{
+    logs::{emit_stack, TradeDelegateLog},
+    program::{get_dynamic_account, ManifestError},
+    require,
+    state::{MarketRef, TradeDelegate, TRADE_DELEGATE_DISCRIMINANT},
+    utils::create_account,
+    validation::{get_trade_delegate_address, loaders::SetTradeDelegateContext},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::{get_mut_helper, NIL};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, rent::Rent,
+    sysvar::Sysvar,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SetTradeDelegateParams {
+    pub delegate: Pubkey,
+    pub sub_account_id: u8,
+}
+
+impl SetTradeDelegateParams {
+    pub fn new(delegate: Pubkey, sub_account_id: u8) -> Self {
+        SetTradeDelegateParams {
+            delegate,
+            sub_account_id,
+        }
+    }
+}
+
+/// Name the trade delegate of one of the signer's seats, replacing any
+/// previous one. The seat's record is created the first time.
+pub(crate) fn process_set_trade_delegate(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: SetTradeDelegateParams = SetTradeDelegateParams::try_from_slice(data)?;
+    let set_trade_delegate_context: SetTradeDelegateContext =
+        SetTradeDelegateContext::load(accounts)?;
+    let SetTradeDelegateParams {
+        delegate,
+        sub_account_id,
+    } = params;
+
+    let SetTradeDelegateContext {
+        payer,
+        market,
+        trade_delegate,
+        system_program,
+    } = set_trade_delegate_context;
+
+    require!(
+        delegate != Pubkey::default() && delegate != *payer.key,
+        ManifestError::InvalidTradeDelegate,
+        "Trade delegate must be a key other than the trader",
+    )?;
+
+    {
+        let market_data: Ref<&mut [u8]> = market.try_borrow_data()?;
+        let dynamic_account: MarketRef = get_dynamic_account(&market_data);
+        require!(
+            dynamic_account.get_sub_account_index(payer.key, sub_account_id) != NIL,
+            ManifestError::InvalidPerpsOperation,
+            "Trader {} has no seat for sub-account {}",
+            payer.key,
+            sub_account_id,
+        )?;
+    }
+
+    let (expected_trade_delegate, trade_delegate_bump) =
+        get_trade_delegate_address(market.key, payer.key, sub_account_id);
+    require!(
+        expected_trade_delegate == *trade_delegate.key,
+        ManifestError::IncorrectAccount,
+        "Incorrect trade delegate account",
+    )?;
+
+    if trade_delegate.data_is_empty() {
+        let trade_delegate_seeds: Vec<Vec<u8>> = vec![
+            b"trade-delegate".to_vec(),
+            market.key.as_ref().to_vec(),
+            payer.key.as_ref().to_vec(),
+            vec![sub_account_id],
+            vec![trade_delegate_bump],
+        ];
+        create_account(
+            payer.as_ref(),
+            trade_delegate,
+            system_program.as_ref(),
+            &crate::id(),
+            &Rent::get()?,
+            size_of::<TradeDelegate>() as u64,
+            trade_delegate_seeds,
+        )?;
+    }
+
+    {
+        let trade_delegate_bytes: &mut [u8] = &mut trade_delegate.try_borrow_mut_data()?[..];
+        let record: &mut TradeDelegate =
+            get_mut_helper::<TradeDelegate>(trade_delegate_bytes, 0_u32);
+        if record.discriminant == TRADE_DELEGATE_DISCRIMINANT {
+            record.set_delegate(&delegate);
+        } else {
+            *record = TradeDelegate::new(market.key, payer.key, sub_account_id, &delegate);
+        }
+    }
+
+    emit_stack(TradeDelegateLog {
+        market: *market.key,
+        trader: *payer.key,
+        delegate,
+        sub_account_id,
+        _padding: [0; 7],
+    })?;
+
+    Ok(())
+}
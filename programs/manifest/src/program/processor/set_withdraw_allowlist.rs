@@ -0,0 +1,87 @@
+use std::mem::size_of;
+
+use crate::{
+    logs::{emit_stack, WithdrawAllowlistLog},
+    program::ManifestError,
+    require,
+    state::WithdrawAllowlist,
+    utils::create_account,
+    validation::{get_withdraw_allowlist_address, loaders::SetWithdrawAllowlistContext},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::get_mut_helper;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, rent::Rent,
+    sysvar::Sysvar,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SetWithdrawAllowlistParams {
+    /// Wallets withdrawals may pay besides the trader. Empty to clear.
+    pub recipients: Vec<Pubkey>,
+}
+
+impl SetWithdrawAllowlistParams {
+    pub fn new(recipients: Vec<Pubkey>) -> Self {
+        SetWithdrawAllowlistParams { recipients }
+    }
+}
+
+/// Set the wallets the signer's withdrawals may pay, replacing any previous
+/// list. The allowlist record is created the first time.
+pub(crate) fn process_set_withdraw_allowlist(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: SetWithdrawAllowlistParams = SetWithdrawAllowlistParams::try_from_slice(data)?;
+    let set_withdraw_allowlist_context: SetWithdrawAllowlistContext =
+        SetWithdrawAllowlistContext::load(accounts)?;
+    let SetWithdrawAllowlistParams { recipients } = params;
+
+    let SetWithdrawAllowlistContext {
+        payer,
+        withdraw_allowlist,
+        system_program,
+    } = set_withdraw_allowlist_context;
+
+    let allowlist: WithdrawAllowlist = WithdrawAllowlist::new(payer.key, &recipients)?;
+
+    let (expected_withdraw_allowlist, withdraw_allowlist_bump) =
+        get_withdraw_allowlist_address(payer.key);
+    require!(
+        expected_withdraw_allowlist == *withdraw_allowlist.key,
+        ManifestError::IncorrectAccount,
+        "Incorrect withdraw allowlist account",
+    )?;
+
+    if withdraw_allowlist.data_is_empty() {
+        let withdraw_allowlist_seeds: Vec<Vec<u8>> = vec![
+            b"withdraw-allowlist".to_vec(),
+            payer.key.as_ref().to_vec(),
+            vec![withdraw_allowlist_bump],
+        ];
+        create_account(
+            payer.as_ref(),
+            withdraw_allowlist,
+            system_program.as_ref(),
+            &crate::id(),
+            &Rent::get()?,
+            size_of::<WithdrawAllowlist>() as u64,
+            withdraw_allowlist_seeds,
+        )?;
+    }
+
+    {
+        let withdraw_allowlist_bytes: &mut [u8] =
+            &mut withdraw_allowlist.try_borrow_mut_data()?[..];
+        *get_mut_helper::<WithdrawAllowlist>(withdraw_allowlist_bytes, 0_u32) = allowlist;
+    }
+
+    emit_stack(WithdrawAllowlistLog {
+        trader: *payer.key,
+        num_recipients: recipients.len() as u64,
+    })?;
+
+    Ok(())
+}
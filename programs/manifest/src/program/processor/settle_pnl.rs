@@ -0,0 +1,87 @@
+use std::cell::RefMut;
+
+use super::{get_seat_extension, settle_funding_and_socialized_loss, settle_pnl};
+use crate::{
+    program::get_mut_dynamic_account,
+    require,
+    state::{MarketRefMut, SeatExtension},
+    validation::loaders::SettlePnlContext,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hypertree::{DataIndex, NIL};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SettlePnlParams {
+    pub trader: Pubkey,
+    pub sub_account_id: u8,
+}
+
+impl SettlePnlParams {
+    pub fn new(trader: Pubkey) -> Self {
+        SettlePnlParams::new_for_sub_account(trader, 0)
+    }
+
+    pub fn new_for_sub_account(trader: Pubkey, sub_account_id: u8) -> Self {
+        SettlePnlParams {
+            trader,
+            sub_account_id,
+        }
+    }
+}
+
+pub(crate) fn process_settle_pnl(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params = SettlePnlParams::try_from_slice(data)?;
+    let settle_pnl_context: SettlePnlContext = SettlePnlContext::load(accounts)?;
+    let SettlePnlContext {
+        payer: _,
+        market,
+        seat_extension,
+    } = settle_pnl_context;
+    let SettlePnlParams {
+        trader,
+        sub_account_id,
+    } = params;
+
+    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+    let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+
+    let trader_index: DataIndex = dynamic_account.get_sub_account_index(&trader, sub_account_id);
+    require!(
+        trader_index != NIL,
+        ProgramError::InvalidArgument,
+        "Trader {} sub-account {} not found on market",
+        trader,
+        sub_account_id,
+    )?;
+
+    let mut seat_extension: Option<RefMut<SeatExtension>> =
+        get_seat_extension(&seat_extension, market.key, &trader, sub_account_id)?;
+
+    // Funding and socialized losses come first, like on any other
+    // interaction, so the settled margin is up to date.
+    settle_funding_and_socialized_loss(
+        &mut dynamic_account,
+        market.key,
+        &trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+    settle_pnl(
+        &mut dynamic_account,
+        market.key,
+        &trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+    dynamic_account.store_cumulative_for_trader(trader_index);
+
+    Ok(())
+}
@@ -4,26 +4,30 @@ use std::{
 };
 
 use crate::{
+    logs::{emit_stack, SettlePnlLog, SocializeLossLog, SocializedLossChargeLog},
+    program::ManifestError,
+    quantities::{u64_slice_to_u128, BaseAtoms, QuoteAtomsPerBaseAtom, WrapperU64},
     require,
     state::{
-        claimed_seat::ClaimedSeat, constants::MARKET_BLOCK_SIZE, DynamicAccount, GlobalFixed,
-        MarketFixed, MarketRefMut, GLOBAL_BLOCK_SIZE,
+        claimed_seat::ClaimedSeat, constants::MARKET_BLOCK_SIZE, get_helper_seat,
+        CrossMarginAccount, DynamicAccount, GlobalFixed, MarketFixed, MarketRefMut, RestingOrder,
+        SeatExtension, FUNDING_SCALE, GLOBAL_BLOCK_SIZE, PREMIUM_RATE_SCALE,
     },
-    validation::{ManifestAccount, ManifestAccountInfo, Signer},
+    validation::{loaders::CrossMarginAccounts, ManifestAccount, ManifestAccountInfo, Signer},
 };
+use borsh::BorshDeserialize;
 use bytemuck::Pod;
-use hypertree::{get_helper, get_mut_helper, DataIndex, Get, RBNode};
+use hypertree::{get_helper, get_mut_helper, DataIndex, Get, RBNode, NIL};
 #[cfg(not(feature = "certora"))]
-use solana_program::sysvar::Sysvar;
+use solana_program::instruction::AccountMeta;
 use solana_program::{
     account_info::AccountInfo,
+    clock::Clock,
     entrypoint::ProgramResult,
     instruction::Instruction,
     pubkey::Pubkey,
-    sysvar::slot_history::ProgramError,
+    sysvar::{slot_history::ProgramError, Sysvar},
 };
-#[cfg(not(feature = "certora"))]
-use solana_program::instruction::AccountMeta;
 
 use super::batch_update::MarketDataTreeNodeType;
 
@@ -104,7 +108,24 @@ fn expand_dynamic<'a, 'info, T: ManifestAccount + Pod + Clone>(
     // accounts are in order: payer, expandable_account, ...
     let expandable_account: &AccountInfo = manifest_account.info;
     let new_size: usize = expandable_account.data_len() + block_size;
+    expand_account(payer, expandable_account, new_size)
+}
 
+/// Grow an account to `new_size` bytes, with the payer topping up its rent.
+#[cfg(feature = "certora")]
+pub(crate) fn expand_account<'info>(
+    _payer: &Signer<'_, 'info>,
+    _expandable_account: &AccountInfo<'info>,
+    _new_size: usize,
+) -> ProgramResult {
+    Ok(())
+}
+#[cfg(not(feature = "certora"))]
+pub(crate) fn expand_account<'info>(
+    payer: &Signer<'_, 'info>,
+    expandable_account: &AccountInfo<'info>,
+    new_size: usize,
+) -> ProgramResult {
     let rent: solana_program::rent::Rent = solana_program::rent::Rent::get()?;
     let new_minimum_balance: u64 = rent.minimum_balance(new_size);
     let old_minimum_balance: u64 = rent.minimum_balance(expandable_account.data_len());
@@ -138,8 +159,7 @@ fn expand_dynamic<'a, 'info, T: ManifestAccount + Pod + Clone>(
 
 /// ephemeral-rollups-spl `lamport_escrow_claim` instruction discriminant.
 #[cfg(not(feature = "certora"))]
-const LAMPORT_ESCROW_CLAIM_DISCRIMINANT: [u8; 8] =
-    [0x62, 0x2B, 0x40, 0xA9, 0xC1, 0xE1, 0x1D, 0x72];
+const LAMPORT_ESCROW_CLAIM_DISCRIMINANT: [u8; 8] = [0x62, 0x2B, 0x40, 0xA9, 0xC1, 0xE1, 0x1D, 0x72];
 
 /// Expand dynamic account by claiming lamports from ephemeral-rollups-spl lamport escrow.
 /// Used inside MagicBlock ER where `system_instruction::transfer` doesn't work on
@@ -273,26 +293,344 @@ pub fn get_dynamic_value<T: Get>(data: &[u8]) -> DynamicAccount<T, Vec<u8>> {
     dynamic_account
 }
 
+/// Read a param that was appended to an instruction's params after clients
+/// shipped. Data from those clients ends before it, so it takes its default,
+/// the way ClaimSeat without params claims sub-account 0.
+pub(crate) fn deserialize_trailing<T: BorshDeserialize + Default>(
+    data: &mut &[u8],
+) -> Result<T, ProgramError> {
+    deserialize_trailing_or(data, T::default())
+}
+
+/// `deserialize_trailing` for a param whose default is not `T::default()`.
+pub(crate) fn deserialize_trailing_or<T: BorshDeserialize>(
+    data: &mut &[u8],
+    default: T,
+) -> Result<T, ProgramError> {
+    if data.is_empty() {
+        return Ok(default);
+    }
+    Ok(T::deserialize(data)?)
+}
+
+/// Reject params with bytes left over, as `try_from_slice` does.
+pub(crate) fn require_params_consumed(data: &[u8]) -> ProgramResult {
+    require!(
+        data.is_empty(),
+        ProgramError::InvalidInstructionData,
+        "Instruction data has {} unread bytes",
+        data.len(),
+    )
+}
+
 // Uses a MarketRefMut instead of a MarketRef because callers will have mutable data.
 pub(crate) fn get_trader_index_with_hint(
     trader_index_hint: Option<DataIndex>,
     dynamic_account: &MarketRefMut,
-    payer: &Signer,
+    trader: &Pubkey,
+    sub_account_id: u8,
 ) -> Result<DataIndex, ProgramError> {
     let trader_index: DataIndex = match trader_index_hint {
-        None => dynamic_account.get_trader_index(payer.key),
+        None => dynamic_account.get_sub_account_index(trader, sub_account_id),
         Some(hinted_index) => {
-            verify_trader_index_hint(hinted_index, &dynamic_account, &payer)?;
+            verify_trader_index_hint(hinted_index, &dynamic_account, trader, sub_account_id)?;
             hinted_index
         }
     };
     Ok(trader_index)
 }
 
+/// Borrow the seat extension passed for a seat, after checking it is the
+/// extension of that seat.
+pub(crate) fn get_seat_extension<'a>(
+    seat_extension: &'a Option<ManifestAccountInfo<SeatExtension>>,
+    market: &Pubkey,
+    trader: &Pubkey,
+    sub_account_id: u8,
+) -> Result<Option<RefMut<'a, SeatExtension>>, ProgramError> {
+    let Some(seat_extension) = seat_extension else {
+        return Ok(None);
+    };
+    let extension: RefMut<SeatExtension> =
+        RefMut::map(seat_extension.info.try_borrow_mut_data()?, |data| {
+            get_mut_helper::<SeatExtension>(data, 0_u32)
+        });
+    require!(
+        extension.get_market() == market
+            && extension.get_trader() == trader
+            && extension.get_sub_account_id() == sub_account_id,
+        ManifestError::InvalidSeatExtension,
+        "Seat extension is not for {} sub-account {}",
+        trader,
+        sub_account_id,
+    )?;
+    Ok(Some(extension))
+}
+
+/// Settle funding and the socialized losses accrued since the trader's last
+/// interaction. Starts by syncing the seat extension, if any, so it picks up
+/// the PnL of fills made without it before settling moves the seat on.
+pub(crate) fn settle_funding_and_socialized_loss(
+    dynamic_account: &mut MarketRefMut,
+    market: &Pubkey,
+    trader: &Pubkey,
+    trader_index: DataIndex,
+    seat_extension: Option<&mut SeatExtension>,
+) -> ProgramResult {
+    if let Some(seat_extension) = seat_extension {
+        seat_extension.sync(get_helper_seat(&dynamic_account.dynamic, trader_index).get_value());
+        let funding_paid: i64 = dynamic_account.settle_funding_for_trader(trader_index)?;
+        seat_extension.add_funding_paid(funding_paid);
+        settle_socialized_loss(dynamic_account, market, trader, trader_index)?;
+        seat_extension.rebase(get_helper_seat(&dynamic_account.dynamic, trader_index).get_value());
+    } else {
+        dynamic_account.settle_funding_for_trader(trader_index)?;
+        settle_socialized_loss(dynamic_account, market, trader, trader_index)?;
+    }
+    Ok(())
+}
+
+/// Charge the trader's share of socialized losses accrued since their last
+/// interaction and log the amount charged, if any, and the part of their
+/// share socialized again because their margin could not cover it.
+pub(crate) fn settle_socialized_loss(
+    dynamic_account: &mut MarketRefMut,
+    market: &Pubkey,
+    trader: &Pubkey,
+    trader_index: DataIndex,
+) -> ProgramResult {
+    let (amount_atoms, resocialized_atoms) =
+        dynamic_account.settle_socialized_loss_for_trader(trader_index)?;
+    if amount_atoms > 0 {
+        emit_stack(SocializedLossChargeLog {
+            market: *market,
+            trader: *trader,
+            amount_atoms,
+            cumulative_loss_per_unit: dynamic_account.fixed.get_cumulative_loss_per_unit(),
+        })?;
+    }
+    if resocialized_atoms > 0 {
+        emit_stack(SocializeLossLog {
+            market: *market,
+            trader: *trader,
+            amount_atoms: resocialized_atoms,
+            cumulative_loss_per_unit: dynamic_account.fixed.get_cumulative_loss_per_unit(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Move the trader's unsettled realized PnL into their margin and log the
+/// amount settled, if any. The seat extension, if any, is synced around it.
+pub(crate) fn settle_pnl(
+    dynamic_account: &mut MarketRefMut,
+    market: &Pubkey,
+    trader: &Pubkey,
+    trader_index: DataIndex,
+    seat_extension: Option<&mut SeatExtension>,
+) -> ProgramResult {
+    // Fills booked since the last sync are realized PnL, what settling moves
+    // out of the seat is not.
+    let pnl_atoms: i64 = if let Some(seat_extension) = seat_extension {
+        seat_extension.sync(get_helper_seat(&dynamic_account.dynamic, trader_index).get_value());
+        let pnl_atoms: i64 = dynamic_account.settle_pnl_for_trader(trader_index)?;
+        seat_extension.rebase(get_helper_seat(&dynamic_account.dynamic, trader_index).get_value());
+        pnl_atoms
+    } else {
+        dynamic_account.settle_pnl_for_trader(trader_index)?
+    };
+    if pnl_atoms != 0 {
+        let margin_atoms: u64 =
+            get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+                .get_value()
+                .quote_withdrawable_balance
+                .as_u64();
+        emit_stack(SettlePnlLog {
+            market: *market,
+            trader: *trader,
+            pnl_atoms,
+            margin_atoms,
+        })?;
+    }
+    Ok(())
+}
+
+/// Equity and margin requirements of a seat, or of a whole cross-margin
+/// portfolio once summed, in quote atoms.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct MarginSummary {
+    /// Margin and unrealized PnL at the conservative mark price, less what is
+    /// owed. Unsettled profit is left out until the PnL pool pays it.
+    pub equity: i128,
+    /// Unrealized profit included in `equity`. Nobody has paid for it yet, so
+    /// it cannot back a withdrawal.
+    pub unrealized_profit: i128,
+    pub initial_margin: u64,
+    pub maintenance_margin: u64,
+}
+
+impl MarginSummary {
+    pub fn add(&mut self, other: &MarginSummary) {
+        self.equity += other.equity;
+        self.unrealized_profit += other.unrealized_profit;
+        self.initial_margin = self.initial_margin.saturating_add(other.initial_margin);
+        self.maintenance_margin = self
+            .maintenance_margin
+            .saturating_add(other.maintenance_margin);
+    }
+
+    /// Equity that may back a withdrawal.
+    pub fn withdrawable_equity(&self) -> i128 {
+        self.equity - self.unrealized_profit
+    }
+}
+
+/// Margin summary of one seat. `settled` tells whether funding and socialized
+/// losses were settled on the seat in this instruction. When they were not,
+/// what accrued since its last checkpoint is deducted from the equity. Quote
+/// locked in resting bids is not counted.
+pub(crate) fn seat_margin_summary(
+    dynamic_account: &MarketRefMut,
+    trader_index: DataIndex,
+    settled: bool,
+) -> Result<MarginSummary, ProgramError> {
+    let claimed_seat: &ClaimedSeat =
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
+    let margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
+    let mut summary: MarginSummary = MarginSummary {
+        equity: margin as i128 + claimed_seat.get_unsettled_pnl().min(0) as i128,
+        ..MarginSummary::default()
+    };
+
+    let position_size: i64 = claimed_seat.get_position_size();
+    if position_size == 0 {
+        return Ok(summary);
+    }
+    let abs_position: u64 = position_size.unsigned_abs();
+    let mark_price: QuoteAtomsPerBaseAtom =
+        compute_conservative_mark_price(dynamic_account, position_size)?;
+    let notional: u64 = mark_price
+        .checked_quote_for_base(BaseAtoms::new(abs_position), false)?
+        .as_u64();
+    let cost_basis: u64 = claimed_seat.get_quote_cost_basis();
+    let unrealized_pnl: i128 = if position_size > 0 {
+        (notional as i128) - (cost_basis as i128)
+    } else {
+        (cost_basis as i128) - (notional as i128)
+    };
+    summary.equity += unrealized_pnl;
+    summary.unrealized_profit = unrealized_pnl.max(0);
+    summary.initial_margin = dynamic_account
+        .fixed
+        .get_initial_margin_for_notional(notional);
+    summary.maintenance_margin = dynamic_account
+        .fixed
+        .get_maintenance_margin_for_notional(notional);
+
+    if !settled {
+        let funding_delta: i64 = dynamic_account
+            .fixed
+            .get_cumulative_funding()
+            .wrapping_sub(claimed_seat.get_last_cumulative_funding());
+        let pending_funding: i128 =
+            position_size as i128 * funding_delta as i128 / FUNDING_SCALE as i128;
+        let loss_delta: u64 = dynamic_account
+            .fixed
+            .get_cumulative_loss_per_unit()
+            .saturating_sub(claimed_seat.get_last_cumulative_loss_per_unit());
+        let pending_loss: u128 =
+            abs_position as u128 * loss_delta as u128 / MarketFixed::LOSS_PER_UNIT_SCALE as u128;
+        summary.equity -= pending_funding + pending_loss as i128;
+    }
+    Ok(summary)
+}
+
+/// Margin summary of a cross-margin trader's collateral and their linked
+/// seats, on every sub-account, on the markets in `cross_margin_accounts`.
+/// Those seats have not been settled in this instruction and are not written.
+pub(crate) fn cross_margin_rest_summary(
+    trader: &Pubkey,
+    cross_margin_accounts: &CrossMarginAccounts,
+) -> Result<MarginSummary, ProgramError> {
+    let collateral_atoms: u64 = {
+        let cross_margin: Ref<CrossMarginAccount> =
+            cross_margin_accounts.cross_margin.get_fixed()?;
+        require!(
+            cross_margin.get_owner() == trader,
+            ManifestError::IncorrectAccount,
+            "Cross-margin account does not belong to trader {}",
+            trader,
+        )?;
+        cross_margin.get_collateral_atoms()
+    };
+    let mut summary: MarginSummary = MarginSummary {
+        equity: collateral_atoms as i128,
+        ..MarginSummary::default()
+    };
+    for market in cross_margin_accounts.markets.iter() {
+        let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+        let dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+        summary.add(&linked_seats_summary(&dynamic_account, trader, NIL)?);
+    }
+    Ok(summary)
+}
+
+/// Margin summary of the trader's linked seats on one market other than
+/// `except_index`. They have not been settled in this instruction.
+pub(crate) fn linked_seats_summary(
+    dynamic_account: &MarketRefMut,
+    trader: &Pubkey,
+    except_index: DataIndex,
+) -> Result<MarginSummary, ProgramError> {
+    let mut summary: MarginSummary = MarginSummary::default();
+    for seat_index in dynamic_account.get_trader_seat_indices(trader) {
+        let is_cross_margin: bool =
+            get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, seat_index)
+                .get_value()
+                .is_cross_margin();
+        if seat_index != except_index && is_cross_margin {
+            summary.add(&seat_margin_summary(dynamic_account, seat_index, false)?);
+        }
+    }
+    Ok(summary)
+}
+
+/// Margin summary of a trader's whole cross-margin portfolio, given their
+/// settled seat on the current market.
+pub(crate) fn cross_margin_summary(
+    dynamic_account: &MarketRefMut,
+    trader_index: DataIndex,
+    trader: &Pubkey,
+    cross_margin_accounts: &CrossMarginAccounts,
+) -> Result<MarginSummary, ProgramError> {
+    let mut summary: MarginSummary = cross_margin_rest_summary(trader, cross_margin_accounts)?;
+    summary.add(&seat_margin_summary(dynamic_account, trader_index, true)?);
+    summary.add(&linked_seats_summary(
+        dynamic_account,
+        trader,
+        trader_index,
+    )?);
+    Ok(summary)
+}
+
+/// Cross-margin seats are margined with their whole portfolio, so they must
+/// pass its accounts to anything that checks margin.
+pub(crate) fn expect_cross_margin_accounts<'b, 'a, 'info>(
+    cross_margin_accounts: &'b Option<CrossMarginAccounts<'a, 'info>>,
+) -> Result<&'b CrossMarginAccounts<'a, 'info>, ProgramError> {
+    require!(
+        cross_margin_accounts.is_some(),
+        ManifestError::IncorrectAccount,
+        "Cross-margin seat is missing its cross-margin accounts",
+    )?;
+    Ok(cross_margin_accounts.as_ref().unwrap())
+}
+
 fn verify_trader_index_hint(
     hinted_index: DataIndex,
     dynamic_account: &MarketRefMut,
-    payer: &Signer,
+    trader: &Pubkey,
+    sub_account_id: u8,
 ) -> ProgramResult {
     require!(
         hinted_index % (MARKET_BLOCK_SIZE as DataIndex) == 0,
@@ -309,16 +647,233 @@ fn verify_trader_index_hint(
         hinted_index,
     )?;
     require!(
-        payer
-            .key
-            .eq(dynamic_account.get_trader_key_by_index(hinted_index)),
+        trader.eq(dynamic_account.get_trader_key_by_index(hinted_index)),
         crate::program::ManifestError::WrongIndexHintParams,
-        "Invalid trader hint index {} did not match payer",
+        "Invalid trader hint index {} did not match trader",
         hinted_index
     )?;
+    require!(
+        get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, hinted_index)
+            .get_value()
+            .get_sub_account_id()
+            == sub_account_id,
+        crate::program::ManifestError::WrongIndexHintParams,
+        "Invalid trader hint index {} did not match sub-account {}",
+        hinted_index,
+        sub_account_id,
+    )?;
     Ok(())
 }
 
+/// Mid price of the best bid and ask, or None unless both sides have orders.
+fn orderbook_mid_price(market: &MarketRefMut) -> Option<QuoteAtomsPerBaseAtom> {
+    let best_bid_index = market.fixed.get_bids_best_index();
+    let best_ask_index = market.fixed.get_asks_best_index();
+    if best_bid_index == hypertree::NIL || best_ask_index == hypertree::NIL {
+        return None;
+    }
+    let best_bid: &RestingOrder =
+        get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
+    let best_ask: &RestingOrder =
+        get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
+    let bid_inner = u64_slice_to_u128(best_bid.get_price().inner);
+    let ask_inner = u64_slice_to_u128(best_ask.get_price().inner);
+    let mid_inner = (bid_inner / 2) + (ask_inner / 2) + ((bid_inner % 2 + ask_inner % 2) / 2);
+    Some(price_from_inner(mid_inner))
+}
+
+fn price_from_inner(inner: u128) -> QuoteAtomsPerBaseAtom {
+    QuoteAtomsPerBaseAtom {
+        inner: [inner as u64, (inner >> 64) as u64],
+    }
+}
+
+/// Price the orderbook is trading at: the mid of the best bid and ask, or
+/// the best price on a one-sided book. None for an empty book.
+fn orderbook_price(market: &MarketRefMut) -> Option<QuoteAtomsPerBaseAtom> {
+    let best_bid_index = market.fixed.get_bids_best_index();
+    let best_ask_index = market.fixed.get_asks_best_index();
+
+    // Prefer orderbook mid-price when both sides are present
+    if let Some(mid_price) = orderbook_mid_price(market) {
+        return Some(mid_price);
+    }
+
+    // One-sided book: use the available side
+    if best_bid_index != hypertree::NIL {
+        let best_bid: &RestingOrder =
+            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
+        return Some(best_bid.get_price());
+    }
+    if best_ask_index != hypertree::NIL {
+        let best_ask: &RestingOrder =
+            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
+        return Some(best_ask.get_price());
+    }
+    None
+}
+
+/// Convert an oracle price mantissa, in the cached oracle exponent, to
+/// QuoteAtomsPerBaseAtom using the market's decimal configuration. Returns
+/// None if it does not fit.
+fn oracle_mantissa_to_price(market: &MarketRefMut, mantissa: u64) -> Option<QuoteAtomsPerBaseAtom> {
+    // Oracle price = mantissa * 10^expo (USD per unit of base asset)
+    // Convert to QuoteAtomsPerBaseAtom:
+    //   qapba = mantissa * 10^(expo + quote_decimals - base_decimals)
+    let expo = market.fixed.get_oracle_price_expo() as i64;
+    let base_decimals = market.fixed.get_base_mint_decimals() as i64;
+    let quote_decimals = market.fixed.get_quote_mint_decimals() as i64;
+
+    let adjusted_expo = expo + quote_decimals - base_decimals;
+
+    // Normalize mantissa to fit in u32 while adjusting exponent
+    let mut m = mantissa as u128;
+    let mut e = adjusted_expo;
+    while m > u32::MAX as u128 && e < i8::MAX as i64 {
+        m /= 10;
+        e += 1;
+    }
+
+    if m <= u32::MAX as u128 && e >= i8::MIN as i64 && e <= i8::MAX as i64 {
+        QuoteAtomsPerBaseAtom::try_from_mantissa_and_exponent(m as u32, e as i8).ok()
+    } else {
+        None
+    }
+}
+
+/// Fail if the cached oracle price was published longer than the market's
+/// max staleness ago. A market without a cached oracle price is valued off
+/// the orderbook instead and passes.
+fn require_fresh_oracle(market: &MarketRefMut) -> ProgramResult {
+    if market.fixed.get_oracle_price_mantissa() == 0 {
+        return Ok(());
+    }
+    let now: i64 = Clock::get()?.unix_timestamp;
+    let oracle_age: i64 = now.saturating_sub(market.fixed.get_oracle_publish_time());
+    let max_oracle_staleness_secs: u64 = market.fixed.get_max_oracle_staleness_secs();
+    require!(
+        oracle_age <= max_oracle_staleness_secs as i64,
+        ManifestError::InvalidPerpsOperation,
+        "Oracle price is stale: published {} seconds ago, max {}",
+        oracle_age,
+        max_oracle_staleness_secs,
+    )?;
+    Ok(())
+}
+
+/// Premium of `price` over the cached oracle price, scaled by
+/// PREMIUM_RATE_SCALE and capped at 100% either way. None without an oracle
+/// price.
+fn premium_rate_over_oracle(market: &MarketRefMut, price: QuoteAtomsPerBaseAtom) -> Option<i64> {
+    let oracle_price: QuoteAtomsPerBaseAtom =
+        oracle_mantissa_to_price(market, market.fixed.get_oracle_price_mantissa())?;
+    let oracle_inner = u64_slice_to_u128(oracle_price.inner) as i128;
+    let price_inner = u64_slice_to_u128(price.inner) as i128;
+    if oracle_inner == 0 {
+        return None;
+    }
+    let premium_rate: i128 =
+        (price_inner - oracle_inner).checked_mul(PREMIUM_RATE_SCALE as i128)? / oracle_inner;
+    Some(premium_rate.clamp(-PREMIUM_RATE_SCALE as i128, PREMIUM_RATE_SCALE as i128) as i64)
+}
+
+/// Sample the current book premium into the mark price moving average and
+/// the funding premium TWAP. Called by CrankFunding and before every match,
+/// with the book as it has stood since the previous sample.
+pub(crate) fn sample_premium(market: &mut MarketRefMut, now: i64) {
+    // One sample per second is all the averages can take, so later calls in
+    // the same second skip walking the book.
+    if now <= market.fixed.get_premium_sample_timestamp() {
+        return;
+    }
+    let mark_premium_rate: Option<i64> = orderbook_mid_price(market)
+        .and_then(|mid_price| premium_rate_over_oracle(market, mid_price));
+    let funding_premium_rate: Option<i64> =
+        orderbook_price(market).and_then(|book_price| premium_rate_over_oracle(market, book_price));
+    market
+        .fixed
+        .update_premium_samples(mark_premium_rate, funding_premium_rate, now);
+}
+
+/// Mark price to value a position of `position_size` at when checking its
+/// margin: the mark price moved down by the oracle confidence interval for a
+/// long, and up by it for a short.
+pub(crate) fn compute_conservative_mark_price(
+    market: &MarketRefMut,
+    position_size: i64,
+) -> Result<QuoteAtomsPerBaseAtom, ProgramError> {
+    let mark_price: QuoteAtomsPerBaseAtom = compute_mark_price(market)?;
+    let oracle_mantissa = market.fixed.get_oracle_price_mantissa();
+    if oracle_mantissa == 0 {
+        return Ok(mark_price);
+    }
+    let oracle_conf = market.fixed.get_oracle_conf();
+    let conservative_mantissa: u64 = if position_size > 0 {
+        oracle_mantissa.saturating_sub(oracle_conf).max(1)
+    } else {
+        oracle_mantissa.saturating_add(oracle_conf)
+    };
+    let mark_inner = u64_slice_to_u128(mark_price.inner);
+    let (numerator, denominator) = (conservative_mantissa as u128, oracle_mantissa as u128);
+    // Both remainder and numerator fit in u64, so their product fits in u128.
+    let conservative_inner = (mark_inner / denominator)
+        .saturating_mul(numerator)
+        .saturating_add(mark_inner % denominator * numerator / denominator);
+    Ok(price_from_inner(conservative_inner))
+}
+
+/// Mark price positions are valued at. With an oracle price this is the
+/// median of the oracle price, the oracle price moved by the moving average
+/// premium, and the orderbook mid, taken as the oracle price without a
+/// two-sided book. Without an oracle price it is the orderbook mid, or the
+/// best price on a one-sided book.
+pub(crate) fn compute_mark_price(
+    market: &MarketRefMut,
+) -> Result<QuoteAtomsPerBaseAtom, ProgramError> {
+    require_fresh_oracle(market)?;
+    let oracle_mantissa = market.fixed.get_oracle_price_mantissa();
+    if oracle_mantissa > 0 {
+        if let Some(oracle_price) = oracle_mantissa_to_price(market, oracle_mantissa) {
+            let oracle_inner = u64_slice_to_u128(oracle_price.inner);
+            let premium_factor = (PREMIUM_RATE_SCALE as i128
+                + market.fixed.get_ema_premium_rate() as i128)
+                .max(0) as u128;
+            let premium_inner = oracle_inner
+                .checked_mul(premium_factor)
+                .map_or(oracle_inner, |scaled| scaled / PREMIUM_RATE_SCALE as u128);
+            let mid_inner = orderbook_mid_price(market)
+                .map_or(oracle_inner, |mid_price| u64_slice_to_u128(mid_price.inner));
+            let mut candidates: [u128; 3] = [oracle_inner, premium_inner, mid_inner];
+            candidates.sort_unstable();
+            return Ok(price_from_inner(candidates[1]));
+        }
+        // If conversion fails, fall through to orderbook
+    }
+
+    // Fallback: orderbook best bid/ask
+    let best_bid_index = market.fixed.get_bids_best_index();
+    let best_ask_index = market.fixed.get_asks_best_index();
+
+    require!(
+        best_bid_index != hypertree::NIL || best_ask_index != hypertree::NIL,
+        ManifestError::InvalidPerpsOperation,
+        "Cannot compute mark price: empty orderbook",
+    )?;
+
+    // Use midpoint of bid and ask for a fair mark price
+    if let Some(mid_price) = orderbook_mid_price(market) {
+        Ok(mid_price)
+    } else if best_bid_index != hypertree::NIL {
+        let best_bid: &RestingOrder =
+            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_bid_index).get_value();
+        Ok(best_bid.get_price())
+    } else {
+        let best_ask: &RestingOrder =
+            get_helper::<RBNode<RestingOrder>>(&market.dynamic, best_ask_index).get_value();
+        Ok(best_ask.get_price())
+    }
+}
+
 // TODO: Same for invoke_signed
 
 pub fn invoke(ix: &Instruction, account_infos: &[AccountInfo<'_>]) -> ProgramResult {
@@ -331,3 +886,153 @@ pub fn invoke(ix: &Instruction, account_infos: &[AccountInfo<'_>]) -> ProgramRes
         solana_program::program::invoke(ix, account_infos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::{
+        program::{
+            batch_update::BatchUpdateParams,
+            create_market::CreateMarketParams,
+            deposit::DepositParams,
+            liquidate::{LiquidateParams, LiquidationMode},
+            withdraw::WithdrawParams,
+            SwapParams,
+        },
+        state::constants::{
+            DEFAULT_FUNDING_PERIOD_SECS, DEFAULT_LIQUIDATOR_REWARD_BPS, DEFAULT_MAX_FUNDING_RATE,
+            DEFAULT_MIN_POSITION_SIZE_ATOMS,
+        },
+    };
+
+    #[test]
+    fn test_params_from_clients_before_sub_accounts() {
+        // Params as clients serialized them before the trailing fields.
+        let mut deposit_data: Vec<u8> = 5_u64.try_to_vec().unwrap();
+        deposit_data.extend(Some(7_u32).try_to_vec().unwrap());
+        let deposit: DepositParams =
+            DepositParams::try_from_slice_with_defaults(&deposit_data).unwrap();
+        assert_eq!(deposit.amount_atoms, 5);
+        assert_eq!(deposit.trader_index_hint, Some(7));
+        assert_eq!(deposit.sub_account_id, 0);
+        assert_eq!(deposit.beneficiary, None);
+
+        let withdraw: WithdrawParams =
+            WithdrawParams::try_from_slice_with_defaults(&deposit_data).unwrap();
+        assert_eq!(withdraw.sub_account_id, 0);
+
+        let mut swap_data: Vec<u8> = (5_u64, 6_u64, true, false).try_to_vec().unwrap();
+        let swap: SwapParams = SwapParams::try_from_slice_with_defaults(&swap_data).unwrap();
+        assert_eq!(swap.sub_account_id, 0);
+        swap_data.push(2);
+        let swap: SwapParams = SwapParams::try_from_slice_with_defaults(&swap_data).unwrap();
+        assert_eq!(swap.sub_account_id, 2);
+        swap_data.push(0);
+        assert!(SwapParams::try_from_slice_with_defaults(&swap_data).is_err());
+
+        let batch_data: Vec<u8> = BatchUpdateParams::new_for_sub_account(None, vec![], vec![], 3)
+            .try_to_vec()
+            .unwrap();
+        let batch: BatchUpdateParams =
+            BatchUpdateParams::try_from_slice_with_defaults(&batch_data[..batch_data.len() - 1])
+                .unwrap();
+        assert_eq!(batch.sub_account_id, 0);
+        let batch: BatchUpdateParams =
+            BatchUpdateParams::try_from_slice_with_defaults(&batch_data).unwrap();
+        assert_eq!(batch.sub_account_id, 3);
+    }
+    #[test]
+    fn test_create_market_params_from_older_clients() {
+        let params: CreateMarketParams = CreateMarketParams::new(
+            1,
+            9,
+            1000,
+            500,
+            Pubkey::new_unique(),
+            5,
+            200,
+            100,
+            5000,
+            600,
+            1000,
+            -10,
+            4,
+        );
+        let data: Vec<u8> = params.try_to_vec().unwrap();
+        // base_mint_index through num_blocks, as sent before the
+        // liquidation and funding params.
+        let legacy_len: usize = 1 + 1 + 8 + 8 + 32 + 8 + 8 + 4;
+        let legacy: CreateMarketParams =
+            CreateMarketParams::try_from_slice_with_defaults(&data[..legacy_len]).unwrap();
+        assert_eq!(legacy.pyth_feed_account, params.pyth_feed_account);
+        assert_eq!(legacy.num_blocks, 4);
+        assert_eq!(legacy.liquidator_reward_bps, DEFAULT_LIQUIDATOR_REWARD_BPS);
+        assert_eq!(
+            legacy.min_position_size_atoms,
+            DEFAULT_MIN_POSITION_SIZE_ATOMS
+        );
+        assert_eq!(legacy.funding_period_secs, DEFAULT_FUNDING_PERIOD_SECS);
+        assert_eq!(legacy.max_funding_rate, DEFAULT_MAX_FUNDING_RATE);
+        assert_eq!(legacy.funding_interest_rate, 0);
+
+        let current: CreateMarketParams =
+            CreateMarketParams::try_from_slice_with_defaults(&data).unwrap();
+        assert_eq!(current.liquidator_reward_bps, 100);
+        assert_eq!(current.min_position_size_atoms, 5000);
+        assert_eq!(current.funding_period_secs, 600);
+        assert_eq!(current.max_funding_rate, 1000);
+        assert_eq!(current.funding_interest_rate, -10);
+    }
+
+    #[test]
+    fn test_liquidate_params_original_layout() {
+        let trader: Pubkey = Pubkey::new_unique();
+        let legacy: LiquidateParams =
+            LiquidateParams::try_from_slice_with_defaults(trader.as_ref()).unwrap();
+        assert_eq!(legacy.traders_to_liquidate, vec![trader]);
+        assert_eq!(legacy.mode, LiquidationMode::MarkPrice);
+        assert!(legacy.sub_account_ids.is_empty());
+
+        let mut data: Vec<u8> =
+            LiquidateParams::new_for_sub_account(trader, 2, LiquidationMode::Orderbook)
+                .try_to_vec()
+                .unwrap();
+        let params: LiquidateParams = LiquidateParams::try_from_slice_with_defaults(&data).unwrap();
+        assert_eq!(params.traders_to_liquidate, vec![trader]);
+        assert_eq!(params.mode, LiquidationMode::Orderbook);
+        assert_eq!(params.scan_start_index, hypertree::NIL);
+        assert_eq!(params.sub_account_ids, vec![2]);
+
+        // Without the sub-accounts, as sent before sub-accounts.
+        data.truncate(data.len() - 5);
+        let params: LiquidateParams = LiquidateParams::try_from_slice_with_defaults(&data).unwrap();
+        assert!(params.sub_account_ids.is_empty());
+
+        // Without the scan start and limit, as sent before scans could resume.
+        data.truncate(data.len() - 8);
+        let params: LiquidateParams = LiquidateParams::try_from_slice_with_defaults(&data).unwrap();
+        assert_eq!(params.scan_start_index, hypertree::NIL);
+        assert_eq!(params.scan_limit, 0);
+
+        // More sub-account ids than traders is refused.
+        let data: Vec<u8> = LiquidateParams {
+            traders_to_liquidate: vec![],
+            mode: LiquidationMode::MarkPrice,
+            scan_start_index: hypertree::NIL,
+            scan_limit: 0,
+            sub_account_ids: vec![0],
+        }
+        .try_to_vec()
+        .unwrap();
+        assert!(LiquidateParams::try_from_slice_with_defaults(&data).is_err());
+
+        // So is a scan limit on a list of traders.
+        let mut params: LiquidateParams =
+            LiquidateParams::new_batch(vec![trader], LiquidationMode::MarkPrice);
+        params.scan_limit = 1;
+        let data: Vec<u8> = params.try_to_vec().unwrap();
+        assert!(LiquidateParams::try_from_slice_with_defaults(&data).is_err());
+    }
+}
@@ -1,36 +1,35 @@
 use std::cell::RefMut;
 
+#[cfg(not(feature = "certora"))]
+use crate::program::{invoke, ManifestError};
 use crate::{
     logs::{emit_stack, PlaceOrderLogV2},
     quantities::{BaseAtoms, QuoteAtoms, QuoteAtomsPerBaseAtom, WrapperU64},
     require,
     state::{
-        AddOrderToMarketArgs, AddOrderToMarketResult, MarketRefMut, OrderType,
-        NO_EXPIRATION_LAST_VALID_SLOT,
+        claimed_seat::ClaimedSeat, AddOrderToMarketArgs, AddOrderToMarketResult, MarketRefMut,
+        OrderType, SeatExtension, NO_EXPIRATION_LAST_VALID_SLOT,
     },
     validation::loaders::SwapContext,
 };
-#[cfg(not(feature = "certora"))]
-use crate::{
-    program::{invoke, ManifestError},
-    validation::get_market_address,
-};
 use borsh::{BorshDeserialize, BorshSerialize};
-use hypertree::{trace, DataIndex, NIL};
+use hypertree::{get_helper, trace, DataIndex, RBNode, NIL};
 use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
 
-use super::shared::get_mut_dynamic_account;
+#[cfg(not(feature = "certora"))]
+use super::shared::{cross_margin_summary, expect_cross_margin_accounts, MarginSummary};
+use super::shared::{
+    deserialize_trailing, get_mut_dynamic_account, get_seat_extension, require_params_consumed,
+    settle_funding_and_socialized_loss, settle_pnl,
+};
 
 #[cfg(feature = "certora")]
 use {
     crate::certora::summaries::place_order::place_fully_match_order_with_same_base_and_quote,
-    early_panic::early_panic,
-    solana_cvt::token::spl_token_transfer,
+    early_panic::early_panic, solana_cvt::token::spl_token_transfer,
 };
 
-use crate::validation::{
-    Signer, TokenAccountInfo, TokenProgram,
-};
+use crate::validation::{Signer, TokenAccountInfo, TokenProgram};
 use solana_program::program_error::ProgramError;
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -42,17 +41,45 @@ pub struct SwapParams {
     // desired. If not that much can be fulfilled, less will be allowed assuming
     // the min_out/max_in is satisfied.
     pub is_exact_in: bool,
+    /// Which of the trader's sub-account seats to trade on.
+    pub sub_account_id: u8,
 }
 
 impl SwapParams {
     pub fn new(in_atoms: u64, out_atoms: u64, is_base_in: bool, is_exact_in: bool) -> Self {
+        SwapParams::new_for_sub_account(in_atoms, out_atoms, is_base_in, is_exact_in, 0)
+    }
+
+    pub fn new_for_sub_account(
+        in_atoms: u64,
+        out_atoms: u64,
+        is_base_in: bool,
+        is_exact_in: bool,
+        sub_account_id: u8,
+    ) -> Self {
         SwapParams {
             in_atoms,
             out_atoms,
             is_base_in,
             is_exact_in,
+            sub_account_id,
         }
     }
+
+    /// Decode params, taking sub-account 0 for clients that predate
+    /// sub-accounts and end the data before it.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: SwapParams = SwapParams {
+            in_atoms: BorshDeserialize::deserialize(&mut data)?,
+            out_atoms: BorshDeserialize::deserialize(&mut data)?,
+            is_base_in: BorshDeserialize::deserialize(&mut data)?,
+            is_exact_in: BorshDeserialize::deserialize(&mut data)?,
+            sub_account_id: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
 }
 
 pub(crate) fn process_swap(
@@ -60,7 +87,7 @@ pub(crate) fn process_swap(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let params = SwapParams::try_from_slice(data)?;
+    let params = SwapParams::try_from_slice_with_defaults(data)?;
     process_swap_core(program_id, accounts, params)
 }
 
@@ -72,8 +99,10 @@ pub(crate) fn process_swap_core(
 ) -> ProgramResult {
     let swap_context: SwapContext = SwapContext::load(accounts)?;
 
-    // Validate session or authority and get the trader authority (before destructuring)
-    let trader_authority = swap_context.validate_and_get_trader_authority(program_id)?;
+    // Validate session, trade delegate or authority and get the trader
+    // authority (before destructuring)
+    let (trader_authority, session_scope) =
+        swap_context.validate_and_get_trader_authority(program_id, params.sub_account_id)?;
 
     let SwapContext {
         market,
@@ -83,27 +112,68 @@ pub(crate) fn process_swap_core(
         quote_vault,
         token_program_quote,
         quote_mint: _,
-        session_token: _,  // Validated above, no longer needed
+        session_token: _, // Validated above, no longer needed
+        session_scope: _,
+        trade_delegate: _,
+        seat_extension,
+        cross_margin_accounts,
         global_trade_accounts_opts,
     } = swap_context;
+    let mut seat_extension: Option<RefMut<SeatExtension>> = get_seat_extension(
+        &seat_extension,
+        market.key,
+        &trader_authority,
+        params.sub_account_id,
+    )?;
 
     let (_existing_seat_index, trader_index, initial_base_atoms, initial_quote_atoms) = {
         let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
         let mut dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
 
         // Claim seat if needed (using actual trader authority, not session signer)
-        let existing_seat_index: DataIndex = dynamic_account.get_trader_index(&trader_authority);
+        let existing_seat_index: DataIndex =
+            dynamic_account.get_sub_account_index(&trader_authority, params.sub_account_id);
         if existing_seat_index == NIL {
-            dynamic_account.claim_seat(&trader_authority)?;
+            dynamic_account.claim_sub_account_seat(&trader_authority, params.sub_account_id)?;
+        }
+        let trader_index: DataIndex =
+            dynamic_account.get_sub_account_index(&trader_authority, params.sub_account_id);
+        if let Some(seat_extension) = seat_extension.as_deref_mut() {
+            if existing_seat_index == NIL {
+                seat_extension.rebase(
+                    get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+                        .get_value(),
+                );
+            }
         }
-        let trader_index: DataIndex = dynamic_account.get_trader_index(&trader_authority);
 
         // Lazy funding settlement: settle accumulated funding and zero base_balance
         // before any balance operations. This must happen before get_trader_balance.
-        dynamic_account.settle_funding_for_trader(trader_index)?;
+        settle_funding_and_socialized_loss(
+            &mut dynamic_account,
+            market.key,
+            &trader_authority,
+            trader_index,
+            seat_extension.as_deref_mut(),
+        )?;
+        settle_pnl(
+            &mut dynamic_account,
+            market.key,
+            &trader_authority,
+            trader_index,
+            seat_extension.as_deref_mut(),
+        )?;
+
+        // Sample the book premium before this instruction moves the book.
+        #[cfg(not(feature = "certora"))]
+        {
+            use solana_program::{clock::Clock, sysvar::Sysvar};
+            let now: i64 = Clock::get()?.unix_timestamp;
+            super::sample_premium(&mut dynamic_account, now);
+        }
 
         let (initial_base_atoms, initial_quote_atoms) =
-            dynamic_account.get_trader_balance(&trader_authority);
+            dynamic_account.get_trader_balance_by_index(trader_index);
 
         (
             existing_seat_index,
@@ -121,8 +191,7 @@ pub(crate) fn process_swap_core(
         {
             use crate::program::ManifestError;
             let market_data = market.try_borrow_data()?;
-            let dynamic_account =
-                crate::program::get_dynamic_account(&market_data);
+            let dynamic_account = crate::program::get_dynamic_account(&market_data);
             require!(
                 dynamic_account.has_free_block(),
                 ManifestError::InvalidFreeList,
@@ -139,6 +208,7 @@ pub(crate) fn process_swap_core(
         out_atoms,
         is_base_in,
         is_exact_in,
+        sub_account_id: _,
     } = params;
 
     // No transfer fees on ephemeral-spl-token
@@ -296,31 +366,82 @@ pub(crate) fn process_swap_core(
                 / 10000;
             if fee_amount > 0 {
                 dynamic_account.withdraw(trader_index, fee_amount, false)?;
-                dynamic_account.fixed.add_to_insurance_fund(fee_amount);
+                if let Some(seat_extension) = seat_extension.as_deref_mut() {
+                    seat_extension.add_fees_paid(fee_amount);
+                }
+                dynamic_account.fixed.add_fee_to_insurance_fund(fee_amount);
             }
         }
     }
 
-    let (end_base_atoms, end_quote_atoms) = dynamic_account.get_trader_balance(owner.key);
+    let (end_base_atoms, end_quote_atoms) =
+        dynamic_account.get_trader_balance_by_index(trader_index);
+
+    // Move the PnL this swap realized into margin before checking it. The end
+    // balances above are read first so the virtual quote cleanup below only
+    // removes what the swap itself added.
+    settle_pnl(
+        &mut dynamic_account,
+        market.key,
+        &trader_authority,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
 
-    // Initial margin check: ensure trader has sufficient margin for resulting position
+    // A scoped session key is held to its order size and position limits.
     #[cfg(not(feature = "certora"))]
-    {
-        use crate::state::claimed_seat::ClaimedSeat;
-        use hypertree::{get_helper, RBNode};
+    if let Some(session_scope) = &session_scope {
+        require!(
+            session_scope.allows_order_notional(quote_atoms_traded.as_u64()),
+            ManifestError::SessionScopeExceeded,
+            "Swap notional {} exceeds the session limit {}",
+            quote_atoms_traded.as_u64(),
+            session_scope.get_max_order_notional_atoms(),
+        )?;
+        let position_size: i64 =
+            get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index)
+                .get_value()
+                .get_position_size();
+        require!(
+            session_scope.allows_position(position_size as i128),
+            ManifestError::SessionScopeExceeded,
+            "Swap leaves a position of {} over the session limit {}",
+            position_size,
+            session_scope.get_max_position_atoms(),
+        )?;
+    }
 
+    // Initial margin check: ensure trader has sufficient margin for resulting
+    // position. A cross-margin seat is checked with the rest of its portfolio.
+    #[cfg(not(feature = "certora"))]
+    {
         let claimed_seat: &ClaimedSeat =
             get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
         let position_size: i64 = claimed_seat.get_position_size();
-        if position_size != 0 {
+        if claimed_seat.is_cross_margin() {
+            let portfolio: MarginSummary = cross_margin_summary(
+                &dynamic_account,
+                trader_index,
+                &trader_authority,
+                expect_cross_margin_accounts(&cross_margin_accounts)?,
+            )?;
+            require!(
+                portfolio.equity >= portfolio.initial_margin as i128,
+                ManifestError::InsufficientMargin,
+                "Initial margin check failed: portfolio equity {} < required {}",
+                portfolio.equity,
+                portfolio.initial_margin,
+            )?;
+        } else if position_size != 0 {
             let abs_position: u64 = position_size.unsigned_abs();
-            let mark_price = super::liquidate::compute_mark_price(&dynamic_account)?;
+            let mark_price =
+                super::compute_conservative_mark_price(&dynamic_account, position_size)?;
             let notional: u64 = mark_price
                 .checked_quote_for_base(BaseAtoms::new(abs_position), false)?
                 .as_u64();
-            let initial_margin_bps: u64 = dynamic_account.fixed.get_initial_margin_bps();
-            let required_margin: u64 =
-                notional.checked_mul(initial_margin_bps).unwrap_or(u64::MAX) / 10000;
+            let required_margin: u64 = dynamic_account
+                .fixed
+                .get_initial_margin_for_notional(notional);
 
             let cost_basis = claimed_seat.get_quote_cost_basis();
             let current_value: u64 = notional;
@@ -367,8 +488,9 @@ pub(crate) fn process_swap_core(
     // For SHORT: extra_quote is 0 (matching no longer credits quote to taker).
     dynamic_account.withdraw(trader_index, extra_base_atoms.as_u64(), true)?;
     if is_base_in {
-        let extra_quote_atoms: u64 =
-            end_quote_atoms.as_u64().saturating_sub(initial_quote_atoms.as_u64());
+        let extra_quote_atoms: u64 = end_quote_atoms
+            .as_u64()
+            .saturating_sub(initial_quote_atoms.as_u64());
         dynamic_account.withdraw(trader_index, extra_quote_atoms, false)?;
     }
 
@@ -384,7 +506,7 @@ pub(crate) fn process_swap_core(
 
     emit_stack(PlaceOrderLogV2 {
         market: *market.key,
-        trader: *owner.key,
+        trader: trader_authority,
         payer: *payer.key,
         base_atoms,
         price,
@@ -452,55 +574,3 @@ fn spl_token_transfer_from_trader_to_vault<'a, 'info>(
 ) -> ProgramResult {
     spl_token_transfer(trader_account.info, vault.info, owner.info, amount)
 }
-
-/** Transfer from quote vault (ATA owned by market PDA) to trader using SPL Token **/
-#[cfg(not(feature = "certora"))]
-fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
-    token_program: &TokenProgram<'a, 'info>,
-    vault: &TokenAccountInfo<'a, 'info>,
-    market_info: &'a solana_program::account_info::AccountInfo<'info>,
-    trader_account: &TokenAccountInfo<'a, 'info>,
-    amount: u64,
-    market_key: &Pubkey,
-    base_mint_index: u8,
-    quote_mint: &Pubkey,
-) -> ProgramResult {
-    let (_, market_bump) = get_market_address(base_mint_index, quote_mint);
-    solana_program::program::invoke_signed(
-        &spl_token::instruction::transfer(
-            token_program.key,
-            vault.key,
-            trader_account.key,
-            market_key, // authority = market PDA (owner of vault ATA)
-            &[],
-            amount,
-        )?,
-        &[
-            token_program.as_ref().clone(),
-            vault.as_ref().clone(),
-            trader_account.as_ref().clone(),
-            market_info.clone(),
-        ],
-        &[&[
-            b"market",
-            &[base_mint_index],
-            quote_mint.as_ref(),
-            &[market_bump],
-        ]],
-    )
-}
-
-#[cfg(feature = "certora")]
-/** (Summary) Transfer from base (quote) vault to base (quote) trader using SPL Token **/
-fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
-    _token_program: &TokenProgram<'a, 'info>,
-    vault: &TokenAccountInfo<'a, 'info>,
-    trader_account: &TokenAccountInfo<'a, 'info>,
-    amount: u64,
-    _market_key: &Pubkey,
-    _vault_bump: u8,
-    _mint_pubkey: &Pubkey,
-) -> ProgramResult {
-    spl_token_transfer(vault.info, trader_account.info, vault.info, amount)
-}
-
@@ -0,0 +1,269 @@
+use std::cell::RefMut;
+
+use crate::{
+    program::{get_mut_dynamic_account, ManifestError},
+    require,
+    state::{
+        validate_oracle_sources, validate_risk_tiers, MarketRefMut, OracleSource, OracleType,
+        RiskTier, MAX_ORACLE_SOURCES, MAX_RISK_TIERS,
+    },
+    validation::loaders::UpdateMarketContext,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+
+use super::create_market::{
+    validate_funding_params, validate_liquidator_reward_bps, validate_min_position_size_atoms,
+    validate_oracle_feed,
+};
+
+/// Market parameters to change. `None` leaves a parameter as it is.
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct UpdateMarketParams {
+    pub liquidator_reward_bps: Option<u64>,
+    pub min_position_size_atoms: Option<u64>,
+    /// Part of each taker fee, in basis points, that buys insurance shares
+    /// for the protocol.
+    pub insurance_protocol_fee_bps: Option<u64>,
+    /// Furthest from the mark price, in basis points, that an orderbook
+    /// liquidation fills.
+    pub max_liquidation_slippage_bps: Option<u64>,
+    /// Replaces the whole risk tier table. Up to `MAX_RISK_TIERS` entries in
+    /// increasing notional order; an empty list removes all tiers.
+    pub risk_tiers: Option<Vec<RiskTier>>,
+    /// Largest oracle confidence interval CrankFunding accepts, in basis
+    /// points of the oracle price.
+    pub max_oracle_conf_bps: Option<u64>,
+    /// Oldest oracle price, in seconds since publication, the market accepts.
+    pub max_oracle_staleness_secs: Option<u64>,
+    /// Pyth feed id that PriceUpdateV3 oracle accounts must carry.
+    pub pyth_feed_id: Option<[u8; 32]>,
+    /// Format of the oracle account CrankFunding reads. Changing it needs
+    /// `oracle_feed_account` too.
+    pub oracle_type: Option<OracleType>,
+    /// Oracle account CrankFunding reads. Set together with `oracle_type`
+    /// when moving the market to another oracle. The cached oracle price is
+    /// cleared until the next CrankFunding reads the new one.
+    pub oracle_feed_account: Option<Pubkey>,
+    /// Replaces the oracle accounts priced alongside the primary one. Up to
+    /// `MAX_ORACLE_SOURCES - 1` entries; an empty list removes them all.
+    pub extra_oracle_sources: Option<Vec<OracleSource>>,
+    /// Time constant, in seconds, of the premium average in the mark price.
+    pub mark_ema_window_secs: Option<u64>,
+    /// Length of a funding period in seconds.
+    pub funding_period_secs: Option<u64>,
+    /// Largest funding rate per period, scaled by FUNDING_SCALE.
+    pub max_funding_rate: Option<u64>,
+    /// Fixed interest rate per period added to the premium, scaled by
+    /// FUNDING_SCALE.
+    pub funding_interest_rate: Option<i64>,
+}
+
+impl UpdateMarketParams {
+    pub fn new(liquidator_reward_bps: Option<u64>, min_position_size_atoms: Option<u64>) -> Self {
+        UpdateMarketParams {
+            liquidator_reward_bps,
+            min_position_size_atoms,
+            insurance_protocol_fee_bps: None,
+            max_liquidation_slippage_bps: None,
+            risk_tiers: None,
+            max_oracle_conf_bps: None,
+            max_oracle_staleness_secs: None,
+            pyth_feed_id: None,
+            oracle_type: None,
+            oracle_feed_account: None,
+            extra_oracle_sources: None,
+            mark_ema_window_secs: None,
+            funding_period_secs: None,
+            max_funding_rate: None,
+            funding_interest_rate: None,
+        }
+    }
+}
+
+pub(crate) fn process_update_market(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let params: UpdateMarketParams = UpdateMarketParams::try_from_slice(data)?;
+    let update_market_context: UpdateMarketContext = UpdateMarketContext::load(accounts)?;
+
+    let UpdateMarketContext {
+        authority: _,
+        market,
+    } = update_market_context;
+
+    let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
+    let dynamic_account: MarketRefMut = get_mut_dynamic_account(market_data);
+
+    let liquidator_reward_bps: u64 = params
+        .liquidator_reward_bps
+        .unwrap_or(dynamic_account.fixed.get_liquidator_reward_bps());
+    let min_position_size_atoms: u64 = params
+        .min_position_size_atoms
+        .unwrap_or(dynamic_account.fixed.get_min_position_size_atoms());
+
+    validate_liquidator_reward_bps(
+        liquidator_reward_bps,
+        dynamic_account.fixed.get_maintenance_margin_bps(),
+    )?;
+    validate_min_position_size_atoms(min_position_size_atoms)?;
+    dynamic_account
+        .fixed
+        .set_liquidation_params(liquidator_reward_bps, min_position_size_atoms);
+
+    if let Some(insurance_protocol_fee_bps) = params.insurance_protocol_fee_bps {
+        require!(
+            insurance_protocol_fee_bps <= 10_000,
+            ManifestError::InvalidPerpsOperation,
+            "Insurance protocol fee must be <= 10000 bps, got {}",
+            insurance_protocol_fee_bps,
+        )?;
+        dynamic_account
+            .fixed
+            .set_insurance_protocol_fee_bps(insurance_protocol_fee_bps);
+    }
+
+    if let Some(max_liquidation_slippage_bps) = params.max_liquidation_slippage_bps {
+        require!(
+            max_liquidation_slippage_bps < 10_000,
+            ManifestError::InvalidPerpsOperation,
+            "Max liquidation slippage must be < 10000 bps, got {}",
+            max_liquidation_slippage_bps,
+        )?;
+        dynamic_account
+            .fixed
+            .set_max_liquidation_slippage_bps(max_liquidation_slippage_bps);
+    }
+
+    if let Some(risk_tiers) = params.risk_tiers {
+        validate_risk_tiers(
+            dynamic_account.fixed.get_initial_margin_bps(),
+            dynamic_account.fixed.get_maintenance_margin_bps(),
+            &risk_tiers,
+        )?;
+        let mut new_risk_tiers: [RiskTier; MAX_RISK_TIERS] = [RiskTier::default(); MAX_RISK_TIERS];
+        new_risk_tiers[..risk_tiers.len()].copy_from_slice(&risk_tiers);
+        dynamic_account.fixed.set_risk_tiers(new_risk_tiers);
+    }
+
+    if let Some(max_oracle_conf_bps) = params.max_oracle_conf_bps {
+        require!(
+            max_oracle_conf_bps > 0 && max_oracle_conf_bps <= 10000,
+            ManifestError::InvalidPerpsOperation,
+            "Max oracle confidence {} bps must be in (0, 10000]",
+            max_oracle_conf_bps,
+        )?;
+        dynamic_account
+            .fixed
+            .set_max_oracle_conf_bps(max_oracle_conf_bps);
+    }
+
+    if let Some(max_oracle_staleness_secs) = params.max_oracle_staleness_secs {
+        require!(
+            max_oracle_staleness_secs > 0,
+            ManifestError::InvalidPerpsOperation,
+            "Max oracle staleness must be > 0",
+        )?;
+        dynamic_account
+            .fixed
+            .set_max_oracle_staleness_secs(max_oracle_staleness_secs);
+    }
+
+    // A feed of one format is not readable as another, so the account has to
+    // move with the type.
+    let oracle_type: OracleType = dynamic_account.fixed.get_oracle_type();
+    require!(
+        params.oracle_type.unwrap_or(oracle_type) == oracle_type
+            || params.oracle_feed_account.is_some(),
+        ManifestError::InvalidPerpsOperation,
+        "Changing the oracle type needs the oracle feed account of the new type",
+    )?;
+    let is_new_oracle: bool = params.pyth_feed_id.is_some()
+        || params.oracle_type.is_some()
+        || params.oracle_feed_account.is_some();
+
+    if let Some(pyth_feed_id) = params.pyth_feed_id {
+        dynamic_account.fixed.set_pyth_feed_id(pyth_feed_id);
+    }
+
+    if let Some(oracle_type) = params.oracle_type {
+        dynamic_account.fixed.set_oracle_type(oracle_type);
+    }
+
+    if let Some(oracle_feed_account) = params.oracle_feed_account {
+        require!(
+            oracle_feed_account != Pubkey::default(),
+            ManifestError::InvalidPerpsOperation,
+            "Oracle feed account must be set",
+        )?;
+        dynamic_account.fixed.set_pyth_feed(oracle_feed_account);
+    }
+
+    if let Some(extra_oracle_sources) = params.extra_oracle_sources {
+        let mut new_extra_oracle_sources: [OracleSource; MAX_ORACLE_SOURCES - 1] =
+            [OracleSource::default(); MAX_ORACLE_SOURCES - 1];
+        validate_oracle_sources(dynamic_account.fixed.get_pyth_feed(), &extra_oracle_sources)?;
+        new_extra_oracle_sources[..extra_oracle_sources.len()]
+            .copy_from_slice(&extra_oracle_sources);
+        dynamic_account
+            .fixed
+            .set_extra_oracle_sources(new_extra_oracle_sources);
+    }
+
+    if is_new_oracle {
+        let extra_oracle_sources: Vec<OracleSource> = dynamic_account
+            .fixed
+            .get_extra_oracle_sources()
+            .iter()
+            .copied()
+            .filter(OracleSource::is_active)
+            .collect();
+        validate_oracle_feed(
+            market.key,
+            dynamic_account.fixed.get_pyth_feed(),
+            &extra_oracle_sources,
+        )?;
+        // The cached price and publish time came from the old oracle. Clear
+        // them so the next CrankFunding caches the new oracle's price without
+        // comparing it to the old publish time.
+        dynamic_account.fixed.set_oracle_price(0, 0, 0, 0);
+    }
+
+    if let Some(mark_ema_window_secs) = params.mark_ema_window_secs {
+        require!(
+            mark_ema_window_secs > 0,
+            ManifestError::InvalidPerpsOperation,
+            "Mark EMA window must be > 0",
+        )?;
+        dynamic_account
+            .fixed
+            .set_mark_ema_window_secs(mark_ema_window_secs);
+    }
+
+    if params.funding_period_secs.is_some()
+        || params.max_funding_rate.is_some()
+        || params.funding_interest_rate.is_some()
+    {
+        let funding_period_secs: u64 = params
+            .funding_period_secs
+            .unwrap_or(dynamic_account.fixed.get_funding_period_secs());
+        let max_funding_rate: u64 = params
+            .max_funding_rate
+            .unwrap_or(dynamic_account.fixed.get_max_funding_rate());
+        let funding_interest_rate: i64 = params
+            .funding_interest_rate
+            .unwrap_or(dynamic_account.fixed.get_funding_interest_rate());
+        validate_funding_params(funding_period_secs, max_funding_rate, funding_interest_rate)?;
+        dynamic_account
+            .fixed
+            .set_funding_period_secs(funding_period_secs);
+        dynamic_account.fixed.set_max_funding_rate(max_funding_rate);
+        dynamic_account
+            .fixed
+            .set_funding_interest_rate(funding_interest_rate);
+    }
+
+    Ok(())
+}
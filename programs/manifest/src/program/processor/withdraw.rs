@@ -1,41 +1,65 @@
 use std::cell::RefMut;
 
-use super::get_trader_index_with_hint;
+use super::{
+    cross_margin_summary, deserialize_trailing, expect_cross_margin_accounts, get_seat_extension,
+    get_trader_index_with_hint, require_params_consumed, settle_funding_and_socialized_loss,
+    settle_pnl, MarginSummary,
+};
 use crate::{
     logs::{emit_stack, WithdrawLog},
     program::get_mut_dynamic_account,
-    state::MarketRefMut,
-    validation::{
-        loaders::WithdrawContext,
-        TokenAccountInfo, TokenProgram,
-    },
+    state::{MarketRefMut, SeatExtension},
+    validation::{loaders::WithdrawContext, TokenAccountInfo, TokenProgram},
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use hypertree::DataIndex;
-use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
 
 #[cfg(not(feature = "certora"))]
 use {crate::validation::get_market_address, solana_program::program::invoke_signed};
 
 #[cfg(feature = "certora")]
-use {
-    early_panic::early_panic,
-    solana_cvt::token::spl_token_transfer,
-};
+use {early_panic::early_panic, solana_cvt::token::spl_token_transfer};
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct WithdrawParams {
     pub amount_atoms: u64,
     pub trader_index_hint: Option<DataIndex>,
+    pub sub_account_id: u8,
 }
 
 impl WithdrawParams {
     pub fn new(amount_atoms: u64, trader_index_hint: Option<DataIndex>) -> Self {
+        WithdrawParams::new_for_sub_account(amount_atoms, trader_index_hint, 0)
+    }
+
+    pub fn new_for_sub_account(
+        amount_atoms: u64,
+        trader_index_hint: Option<DataIndex>,
+        sub_account_id: u8,
+    ) -> Self {
         WithdrawParams {
             amount_atoms,
             trader_index_hint,
+            sub_account_id,
         }
     }
+
+    /// Decode params, taking sub-account 0 for clients that predate
+    /// sub-accounts and end the data before it.
+    pub fn try_from_slice_with_defaults(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut data: &[u8] = data;
+        let params: WithdrawParams = WithdrawParams {
+            amount_atoms: BorshDeserialize::deserialize(&mut data)?,
+            trader_index_hint: BorshDeserialize::deserialize(&mut data)?,
+            sub_account_id: deserialize_trailing(&mut data)?,
+        };
+        require_params_consumed(data)?;
+        Ok(params)
+    }
 }
 
 pub(crate) fn process_withdraw(
@@ -43,29 +67,36 @@ pub(crate) fn process_withdraw(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let params = WithdrawParams::try_from_slice(data)?;
+    let params = WithdrawParams::try_from_slice_with_defaults(data)?;
     process_withdraw_core(program_id, accounts, params)
 }
 
 #[cfg_attr(all(feature = "certora", not(feature = "certora-test")), early_panic)]
 pub(crate) fn process_withdraw_core(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     params: WithdrawParams,
 ) -> ProgramResult {
     let withdraw_context: WithdrawContext = WithdrawContext::load(accounts)?;
+    let trader: Pubkey = withdraw_context.validate_and_get_trader_authority(program_id)?;
     let WithdrawParams {
         amount_atoms,
         trader_index_hint,
+        sub_account_id,
     } = params;
 
     let WithdrawContext {
         market,
-        payer,
+        payer: _,
+        session_token: _,
+        session_scope: _,
+        seat_extension,
         trader_token,
         vault,
         token_program,
         mint: _,
+        withdraw_allowlist: _,
+        cross_margin_accounts,
     } = withdraw_context;
 
     let market_data: &mut RefMut<&mut [u8]> = &mut market.try_borrow_mut_data()?;
@@ -90,11 +121,26 @@ pub(crate) fn process_withdraw_core(
     )?;
 
     let trader_index: DataIndex =
-        get_trader_index_with_hint(trader_index_hint, &dynamic_account, &payer)?;
+        get_trader_index_with_hint(trader_index_hint, &dynamic_account, &trader, sub_account_id)?;
 
     // Lazy funding settlement before withdrawal + equity check.
     // This ensures margin reflects accumulated funding accurately.
-    dynamic_account.settle_funding_for_trader(trader_index)?;
+    let mut seat_extension: Option<RefMut<SeatExtension>> =
+        get_seat_extension(&seat_extension, market.key, &trader, sub_account_id)?;
+    settle_funding_and_socialized_loss(
+        &mut dynamic_account,
+        market.key,
+        &trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
+    settle_pnl(
+        &mut dynamic_account,
+        market.key,
+        &trader,
+        trader_index,
+        seat_extension.as_deref_mut(),
+    )?;
 
     // is_base = false: always withdrawing quote in perps
     dynamic_account.withdraw(trader_index, amount_atoms, false)?;
@@ -105,17 +151,30 @@ pub(crate) fn process_withdraw_core(
         use crate::state::claimed_seat::ClaimedSeat;
         use hypertree::{get_helper, RBNode};
 
-        let claimed_seat: &ClaimedSeat = get_helper::<RBNode<ClaimedSeat>>(
-            &dynamic_account.dynamic,
-            trader_index,
-        )
-        .get_value();
+        let claimed_seat: &ClaimedSeat =
+            get_helper::<RBNode<ClaimedSeat>>(&dynamic_account.dynamic, trader_index).get_value();
 
         let position_size: i64 = claimed_seat.get_position_size();
-        if position_size != 0 {
+        if claimed_seat.is_cross_margin() {
+            // A cross-margin seat may withdraw whatever the rest of its
+            // portfolio does not need.
+            let portfolio: MarginSummary = cross_margin_summary(
+                &dynamic_account,
+                trader_index,
+                &trader,
+                expect_cross_margin_accounts(&cross_margin_accounts)?,
+            )?;
+            crate::require!(
+                portfolio.withdrawable_equity() >= portfolio.maintenance_margin as i128,
+                crate::program::ManifestError::InsufficientMargin,
+                "Withdrawal would bring portfolio equity {} below maintenance margin {}",
+                portfolio.withdrawable_equity(),
+                portfolio.maintenance_margin,
+            )?;
+        } else if position_size != 0 {
             let abs_position: u64 = position_size.unsigned_abs();
             let mark_price =
-                super::liquidate::compute_mark_price(&dynamic_account)?;
+                super::compute_conservative_mark_price(&dynamic_account, position_size)?;
             let current_value: u64 = mark_price
                 .checked_quote_for_base(BaseAtoms::new(abs_position), false)?
                 .as_u64();
@@ -128,15 +187,15 @@ pub(crate) fn process_withdraw_core(
                 (quote_cost_basis as i128) - (current_value as i128)
             };
 
+            // Unrealized profit is not paid for by anyone yet, so it cannot
+            // back a withdrawal. Only losses count, which keeps the vault
+            // able to back the sum of margins.
             let remaining_margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
-            let equity: i128 = (remaining_margin as i128) + unrealized_pnl;
+            let equity: i128 = (remaining_margin as i128) + unrealized_pnl.min(0);
 
-            let maintenance_margin_bps: u64 =
-                dynamic_account.fixed.get_maintenance_margin_bps();
-            let required_maintenance: u64 = current_value
-                .checked_mul(maintenance_margin_bps)
-                .unwrap_or(u64::MAX)
-                / 10000;
+            let required_maintenance: u64 = dynamic_account
+                .fixed
+                .get_maintenance_margin_for_notional(current_value);
 
             crate::require!(
                 equity >= required_maintenance as i128,
@@ -153,7 +212,7 @@ pub(crate) fn process_withdraw_core(
 
     emit_stack(WithdrawLog {
         market: *market.key,
-        trader: *payer.key,
+        trader,
         mint: *dynamic_account.get_quote_mint(),
         amount_atoms,
     })?;
@@ -163,7 +222,7 @@ pub(crate) fn process_withdraw_core(
 
 /** Transfer from quote vault (ATA owned by market PDA) to trader using SPL Token **/
 #[cfg(not(feature = "certora"))]
-fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
+pub(crate) fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
     token_program: &TokenProgram<'a, 'info>,
     vault: &TokenAccountInfo<'a, 'info>,
     market_info: &'a solana_program::account_info::AccountInfo<'info>,
@@ -200,7 +259,7 @@ fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
 
 #[cfg(feature = "certora")]
 /** (Summary) Transfer from base (quote) vault to base (quote) trader using SPL Token **/
-fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
+pub(crate) fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
     _token_program: &TokenProgram<'a, 'info>,
     vault: &TokenAccountInfo<'a, 'info>,
     trader_account: &TokenAccountInfo<'a, 'info>,
@@ -211,4 +270,3 @@ fn spl_token_transfer_from_vault_to_trader<'a, 'info>(
 ) -> ProgramResult {
     spl_token_transfer(vault.info, trader_account.info, vault.info, amount)
 }
-
@@ -2,7 +2,9 @@ use std::mem::size_of;
 
 use crate::quantities::WrapperU64;
 use crate::quantities::{BaseAtoms, QuoteAtoms};
+use borsh::{BorshDeserialize, BorshSerialize};
 use bytemuck::{Pod, Zeroable};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use shank::ShankType;
 use solana_program::pubkey::Pubkey;
 use static_assertions::const_assert_eq;
@@ -10,6 +12,36 @@ use std::cmp::Ordering;
 
 use super::constants::CLAIMED_SEAT_SIZE;
 
+#[derive(
+    Debug,
+    BorshDeserialize,
+    BorshSerialize,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    ShankType,
+    IntoPrimitive,
+    TryFromPrimitive,
+)]
+#[repr(u8)]
+pub enum MarginMode {
+    // The seat stands on its own quote_withdrawable_balance.
+    Isolated = 0,
+
+    // The seat is linked into the trader's cross-margin account and margined
+    // together with its collateral and the other linked seats.
+    Cross = 1,
+}
+// Not derived: num_enum would treat a #[default] variant as the fallback for
+// unknown values.
+#[allow(clippy::derivable_impls)]
+impl Default for MarginMode {
+    fn default() -> Self {
+        MarginMode::Isolated
+    }
+}
+
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone, Zeroable, Pod, ShankType)]
 pub struct ClaimedSeat {
@@ -25,24 +57,57 @@ pub struct ClaimedSeat {
     /// manifest. Use at your own risk.
     pub quote_volume: QuoteAtoms,
     _padding: [u8; 8],
+    /// Snapshot of the market's cumulative socialized loss per unit at the
+    /// last settlement.
+    last_cumulative_loss_per_unit: u64,
+    /// PnL realized by fills that has not been moved into the margin balance
+    /// yet, in quote atoms. On a cross-margin seat this also carries the
+    /// losses and funding its margin could not cover, until collateral is
+    /// moved in.
+    unsettled_pnl: i64,
+    /// MarginMode of the seat. Isolated seats are margined on their own,
+    /// cross seats together with the trader's cross-margin account.
+    margin_mode: u8,
+    /// Lets one wallet hold several seats on a market. Seats are keyed by
+    /// (trader, sub_account_id), and 0 is the default sub-account.
+    sub_account_id: u8,
+    _padding2: [u8; 2],
+    /// Moves on whenever `unsettled_pnl` changes other than by a fill, so the
+    /// seat extension can tell realized PnL from settlements.
+    unsettled_pnl_epoch: u32,
 }
 // 32 + // trader
 //  8 + // base_balance
 //  8 + // quote_balance
 //  8 + // quote_volume
-//  8   // padding
-// = 64
+//  8 + // padding
+//  8 + // last_cumulative_loss_per_unit
+//  8 + // unsettled_pnl
+//  1 + // margin_mode
+//  1 + // sub_account_id
+//  2 + // padding 2
+//  4   // unsettled_pnl_epoch
+// = 88
 const_assert_eq!(size_of::<ClaimedSeat>(), CLAIMED_SEAT_SIZE);
 const_assert_eq!(size_of::<ClaimedSeat>() % 8, 0);
 
 impl ClaimedSeat {
     pub fn new_empty(trader: Pubkey) -> Self {
+        ClaimedSeat::new_empty_for_sub_account(trader, 0)
+    }
+
+    pub fn new_empty_for_sub_account(trader: Pubkey, sub_account_id: u8) -> Self {
         ClaimedSeat {
             trader,
+            sub_account_id,
             ..Default::default()
         }
     }
 
+    pub fn get_sub_account_id(&self) -> u8 {
+        self.sub_account_id
+    }
+
     /// Get position size for perps markets.
     /// Positive = long, negative = short. Stored as i64 in the quote_volume field.
     pub fn get_position_size(&self) -> i64 {
@@ -76,6 +141,46 @@ impl ClaimedSeat {
     pub fn set_last_cumulative_funding(&mut self, val: i64) {
         self.base_withdrawable_balance = BaseAtoms::new(val as u64);
     }
+
+    /// Get last cumulative socialized loss per unit snapshot for lazy settlement.
+    pub fn get_last_cumulative_loss_per_unit(&self) -> u64 {
+        self.last_cumulative_loss_per_unit
+    }
+
+    /// Set last cumulative socialized loss per unit snapshot.
+    pub fn set_last_cumulative_loss_per_unit(&mut self, val: u64) {
+        self.last_cumulative_loss_per_unit = val;
+    }
+
+    pub fn get_unsettled_pnl(&self) -> i64 {
+        self.unsettled_pnl
+    }
+
+    /// Set the unsettled PnL for anything but a fill, which moves the epoch
+    /// on.
+    pub fn set_unsettled_pnl(&mut self, pnl: i64) {
+        self.unsettled_pnl = pnl;
+        self.unsettled_pnl_epoch = self.unsettled_pnl_epoch.wrapping_add(1);
+    }
+
+    /// Book PnL realized by a fill as unsettled.
+    pub fn book_realized_pnl(&mut self, pnl: i64) {
+        self.unsettled_pnl = self.unsettled_pnl.saturating_add(pnl);
+    }
+
+    pub fn get_unsettled_pnl_epoch(&self) -> u32 {
+        self.unsettled_pnl_epoch
+    }
+
+    pub fn get_margin_mode(&self) -> MarginMode {
+        MarginMode::try_from(self.margin_mode).unwrap_or_default()
+    }
+    pub fn set_margin_mode(&mut self, margin_mode: MarginMode) {
+        self.margin_mode = u8::from(margin_mode);
+    }
+    pub fn is_cross_margin(&self) -> bool {
+        self.get_margin_mode() == MarginMode::Cross
+    }
 }
 
 #[cfg(feature = "certora")]
@@ -87,13 +192,19 @@ impl nondet::Nondet for ClaimedSeat {
             quote_withdrawable_balance: QuoteAtoms::new(nondet::nondet()),
             quote_volume: QuoteAtoms::new(nondet::nondet()),
             _padding: [0; 8],
+            last_cumulative_loss_per_unit: 0,
+            unsettled_pnl: 0,
+            margin_mode: 0,
+            sub_account_id: 0,
+            _padding2: [0; 2],
+            unsettled_pnl_epoch: 0,
         }
     }
 }
 
 impl Ord for ClaimedSeat {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.trader).cmp(&(other.trader))
+        (self.trader, self.sub_account_id).cmp(&(other.trader, other.sub_account_id))
     }
 }
 
@@ -105,7 +216,7 @@ impl PartialOrd for ClaimedSeat {
 
 impl PartialEq for ClaimedSeat {
     fn eq(&self, other: &Self) -> bool {
-        (self.trader) == (other.trader)
+        (self.trader, self.sub_account_id) == (other.trader, other.sub_account_id)
     }
 }
 
@@ -113,7 +224,7 @@ impl Eq for ClaimedSeat {}
 
 impl std::fmt::Display for ClaimedSeat {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.trader)
+        write!(f, "{}/{}", self.trader, self.sub_account_id)
     }
 }
 
@@ -122,3 +233,16 @@ fn test_display() {
     let claimed_seat: ClaimedSeat = ClaimedSeat::new_empty(Pubkey::default());
     format!("{}", claimed_seat);
 }
+
+#[test]
+fn test_sub_account_ordering() {
+    let trader: Pubkey = Pubkey::new_unique();
+    let default_seat: ClaimedSeat = ClaimedSeat::new_empty(trader);
+    let sub_account_seat: ClaimedSeat = ClaimedSeat::new_empty_for_sub_account(trader, 1);
+    assert!(default_seat != sub_account_seat);
+    assert!(default_seat < sub_account_seat);
+    assert_eq!(
+        default_seat,
+        ClaimedSeat::new_empty_for_sub_account(trader, 0)
+    );
+}
@@ -1,12 +1,37 @@
 use hypertree::RBTREE_OVERHEAD_BYTES;
 
-pub const MARKET_FIXED_SIZE: usize = 256;
+pub const MARKET_FIXED_SIZE: usize = 768;
 pub const GLOBAL_FIXED_SIZE: usize = 96;
+pub const INSURANCE_STAKER_SIZE: usize = 120;
+pub const RISK_TIER_SIZE: usize = 24;
+pub const ORACLE_SOURCE_SIZE: usize = 40;
+pub const FUNDING_HISTORY_ENTRY_SIZE: usize = 40;
+pub const FUNDING_HISTORY_SIZE: usize = 56 + FUNDING_HISTORY_LEN * FUNDING_HISTORY_ENTRY_SIZE;
+pub const SEAT_EXTENSION_SIZE: usize = 136;
+pub const CROSS_MARGIN_ACCOUNT_SIZE: usize = 120 + MAX_CROSS_MARGIN_MARKETS * 32;
+pub const TRADE_DELEGATE_SIZE: usize = 128;
+pub const SESSION_SCOPE_SIZE: usize = 136 + MAX_SESSION_SCOPE_MARKETS * 32;
+pub const WITHDRAW_ALLOWLIST_SIZE: usize = 80 + MAX_WITHDRAW_RECIPIENTS * 32;
 
-// Red black tree overhead is 16 bytes. If each block is 80 bytes, then we get
-// 64 bytes for a RestingOrder or ClaimedSeat.
+/// Number of notional tiers a market can add above its base margin.
+pub const MAX_RISK_TIERS: usize = 4;
+
+/// Number of oracle accounts a market can price from, including the primary.
+pub const MAX_ORACLE_SOURCES: usize = 3;
+
+/// Number of markets one cross-margin account can link.
+pub const MAX_CROSS_MARGIN_MARKETS: usize = 8;
+
+/// Number of markets one session scope can allow.
+pub const MAX_SESSION_SCOPE_MARKETS: usize = 8;
+
+/// Number of wallets a trader can allow withdrawals to pay.
+pub const MAX_WITHDRAW_RECIPIENTS: usize = 8;
+
+// Red black tree overhead is 16 bytes. If each block is 104 bytes, then we get
+// 88 bytes for a RestingOrder or ClaimedSeat.
 pub const GLOBAL_BLOCK_SIZE: usize = 64;
-pub const MARKET_BLOCK_SIZE: usize = 80;
+pub const MARKET_BLOCK_SIZE: usize = 104;
 const MARKET_BLOCK_PAYLOAD_SIZE: usize = MARKET_BLOCK_SIZE - RBTREE_OVERHEAD_BYTES;
 pub const RESTING_ORDER_SIZE: usize = MARKET_BLOCK_PAYLOAD_SIZE;
 pub const CLAIMED_SEAT_SIZE: usize = MARKET_BLOCK_PAYLOAD_SIZE;
@@ -20,7 +45,22 @@ pub const GLOBAL_FREE_LIST_BLOCK_SIZE: usize = GLOBAL_BLOCK_SIZE - FREE_LIST_OVE
 pub const NO_EXPIRATION_LAST_VALID_SLOT: u32 = 0;
 
 pub const MARKET_FIXED_DISCRIMINANT: u64 = 4859840929024028656;
+
+/// Layout version of market accounts. Markets of an older version have to go
+/// through MigrateMarket before any other instruction accepts them.
+pub const MARKET_LAYOUT_VERSION: u8 = 1;
+/// Header and block sizes of version 0 markets, before the perps fields grew
+/// MarketFixed, ClaimedSeat and RestingOrder.
+pub const LEGACY_MARKET_FIXED_SIZE: usize = 256;
+pub const LEGACY_MARKET_BLOCK_SIZE: usize = 80;
 pub const GLOBAL_FIXED_DISCRIMINANT: u64 = 10787423733276977665;
+pub const INSURANCE_STAKER_DISCRIMINANT: u64 = 9771760915748230131;
+pub const FUNDING_HISTORY_DISCRIMINANT: u64 = 1818663417226944466;
+pub const SEAT_EXTENSION_DISCRIMINANT: u64 = 12733510766812462658;
+pub const CROSS_MARGIN_ACCOUNT_DISCRIMINANT: u64 = 8498144832511721006;
+pub const TRADE_DELEGATE_DISCRIMINANT: u64 = 10996188246389558240;
+pub const SESSION_SCOPE_DISCRIMINANT: u64 = 15431205059170877422;
+pub const WITHDRAW_ALLOWLIST_DISCRIMINANT: u64 = 5379442043148901326;
 
 // Amount of gas deposited for every global order. This is done to as an
 // economic disincentive to spam.
@@ -37,6 +77,56 @@ pub const GLOBAL_FIXED_DISCRIMINANT: u64 = 10787423733276977665;
 // capital on the exchange to prevent that.
 pub const GAS_DEPOSIT_LAMPORTS: u64 = 5_000;
 
+/// Liquidator reward, in basis points of the closed notional, for a market
+/// created without one (2.5%). The market authority can change it with
+/// UpdateMarket.
+pub const DEFAULT_LIQUIDATOR_REWARD_BPS: u64 = 250;
+
+/// Smallest position, in base atoms, a partial liquidation leaves for a
+/// market created without one. The market authority can change it with
+/// UpdateMarket.
+pub const DEFAULT_MIN_POSITION_SIZE_ATOMS: u64 = 1000;
+
+/// Furthest from the mark price, in basis points, that an orderbook
+/// liquidation fills on a new market (10%). The market authority can change
+/// it with UpdateMarket.
+pub const DEFAULT_MAX_LIQUIDATION_SLIPPAGE_BPS: u64 = 1000;
+
+/// Largest oracle confidence interval a new market accepts, in basis points of
+/// the oracle price. The market authority can change it with UpdateMarket.
+pub const DEFAULT_MAX_ORACLE_CONF_BPS: u64 = 200;
+
+/// Oldest oracle price, in seconds since it was published, that a new market
+/// accepts. The market authority can change it with UpdateMarket.
+pub const DEFAULT_MAX_ORACLE_STALENESS_SECS: u64 = 3600;
+
+/// Time constant, in seconds, of the moving average of the book premium that
+/// the mark price is built from. The market authority can change it with
+/// UpdateMarket.
+pub const DEFAULT_MARK_EMA_WINDOW_SECS: u64 = 1800;
+
+/// Fixed point scale of premium rates: 1_000_000_000 is a premium of 100%.
+pub const PREMIUM_RATE_SCALE: i64 = 1_000_000_000;
+
+/// Fixed point scale of funding rates: 1_000_000_000 is a rate of 100% per
+/// funding period.
+pub const FUNDING_SCALE: i64 = 1_000_000_000;
+
+/// Amount of base atoms funding rates and funding history prices are quoted
+/// for.
+pub const FUNDING_REFERENCE_BASE_ATOMS: u64 = 1_000_000_000;
+
+/// Number of funding cranks a market's funding history keeps.
+pub const FUNDING_HISTORY_LEN: usize = 64;
+
+/// Length of a funding period, in seconds, for a new market. The market
+/// authority can change it with UpdateMarket.
+pub const DEFAULT_FUNDING_PERIOD_SECS: u64 = 3600;
+
+/// Largest funding rate per period for a new market, scaled by FUNDING_SCALE
+/// (1%). The market authority can change it with UpdateMarket.
+pub const DEFAULT_MAX_FUNDING_RATE: u64 = FUNDING_SCALE as u64 / 100;
+
 /// Limit on the number of global seats available. Set so that this is hit
 /// before the global account starts running into account size limits, but is
 /// generous enough that it really should only matter in deterring spam.  Sized
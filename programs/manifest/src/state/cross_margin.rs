@@ -0,0 +1,175 @@
+//! Cross-margin account holding a trader's quote collateral for several
+//! markets.
+//!
+//! The collateral sits in a token account owned by the cross-margin account
+//! PDA rather than in any one market's vault. Seats the account links are
+//! margined together: their equity and margin requirements are summed with
+//! the collateral, so a profit on one market backs a loss on another.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{program::ManifestError, require, validation::ManifestAccount};
+
+use super::{
+    CROSS_MARGIN_ACCOUNT_DISCRIMINANT, CROSS_MARGIN_ACCOUNT_SIZE, MAX_CROSS_MARGIN_MARKETS,
+};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct CrossMarginAccount {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Trader the collateral belongs to.
+    owner: Pubkey,
+
+    /// Quote mint of the collateral and of every linked market.
+    quote_mint: Pubkey,
+
+    /// Collateral held in the account's vault, in quote atoms.
+    collateral_atoms: u64,
+
+    /// Number of entries of `markets` in use.
+    num_markets: u64,
+
+    /// Markets whose seats are margined together with the collateral. Only
+    /// the first `num_markets` are set.
+    markets: [Pubkey; MAX_CROSS_MARGIN_MARKETS],
+
+    _padding: [u64; 4],
+}
+const_assert_eq!(
+    size_of::<CrossMarginAccount>(),
+    8 +   // discriminant
+    32 +  // owner
+    32 +  // quote_mint
+    8 +   // collateral_atoms
+    8 +   // num_markets
+    32 * MAX_CROSS_MARGIN_MARKETS + // markets
+    32 // padding
+);
+const_assert_eq!(size_of::<CrossMarginAccount>(), CROSS_MARGIN_ACCOUNT_SIZE);
+const_assert_eq!(size_of::<CrossMarginAccount>() % 8, 0);
+impl Get for CrossMarginAccount {}
+
+impl CrossMarginAccount {
+    pub fn new_empty(owner: &Pubkey, quote_mint: &Pubkey) -> Self {
+        CrossMarginAccount {
+            discriminant: CROSS_MARGIN_ACCOUNT_DISCRIMINANT,
+            owner: *owner,
+            quote_mint: *quote_mint,
+            collateral_atoms: 0,
+            num_markets: 0,
+            markets: [Pubkey::default(); MAX_CROSS_MARGIN_MARKETS],
+            _padding: [0; 4],
+        }
+    }
+
+    pub fn get_owner(&self) -> &Pubkey {
+        &self.owner
+    }
+    pub fn get_quote_mint(&self) -> &Pubkey {
+        &self.quote_mint
+    }
+    pub fn get_collateral_atoms(&self) -> u64 {
+        self.collateral_atoms
+    }
+
+    /// Linked markets, in the order they were linked.
+    pub fn get_markets(&self) -> &[Pubkey] {
+        &self.markets[..self.num_markets as usize]
+    }
+
+    pub fn is_linked(&self, market: &Pubkey) -> bool {
+        self.get_markets().contains(market)
+    }
+
+    pub fn deposit_collateral(&mut self, amount_atoms: u64) {
+        self.collateral_atoms = self.collateral_atoms.saturating_add(amount_atoms);
+    }
+
+    pub fn withdraw_collateral(&mut self, amount_atoms: u64) -> ProgramResult {
+        require!(
+            amount_atoms <= self.collateral_atoms,
+            ProgramError::InsufficientFunds,
+            "Insufficient cross-margin collateral {} < {}",
+            self.collateral_atoms,
+            amount_atoms
+        )?;
+        self.collateral_atoms -= amount_atoms;
+        Ok(())
+    }
+
+    pub fn link_market(&mut self, market: &Pubkey) -> ProgramResult {
+        require!(
+            !self.is_linked(market),
+            ManifestError::InvalidPerpsOperation,
+            "Market {} is already linked",
+            market
+        )?;
+        require!(
+            (self.num_markets as usize) < MAX_CROSS_MARGIN_MARKETS,
+            ManifestError::InvalidPerpsOperation,
+            "Cross-margin account already links {} markets",
+            MAX_CROSS_MARGIN_MARKETS
+        )?;
+        self.markets[self.num_markets as usize] = *market;
+        self.num_markets += 1;
+        Ok(())
+    }
+
+    /// Remove a linked market. The remaining markets keep their order.
+    pub fn unlink_market(&mut self, market: &Pubkey) -> ProgramResult {
+        let position: Option<usize> = self.get_markets().iter().position(|key| key == market);
+        require!(
+            position.is_some(),
+            ManifestError::InvalidPerpsOperation,
+            "Market {} is not linked",
+            market
+        )?;
+        let num_markets: usize = self.num_markets as usize;
+        self.markets
+            .copy_within(position.unwrap() + 1..num_markets, position.unwrap());
+        self.markets[num_markets - 1] = Pubkey::default();
+        self.num_markets -= 1;
+        Ok(())
+    }
+}
+
+impl ManifestAccount for CrossMarginAccount {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == CROSS_MARGIN_ACCOUNT_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid cross-margin account discriminant actual: {} expected: {}",
+            self.discriminant,
+            CROSS_MARGIN_ACCOUNT_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_link_and_unlink_markets() {
+    let markets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let mut account: CrossMarginAccount =
+        CrossMarginAccount::new_empty(&Pubkey::new_unique(), &Pubkey::new_unique());
+    for market in markets.iter() {
+        account.link_market(market).unwrap();
+    }
+    assert!(account.link_market(&markets[0]).is_err());
+
+    account.unlink_market(&markets[1]).unwrap();
+    assert_eq!(account.get_markets(), &[markets[0], markets[2]]);
+    assert!(account.unlink_market(&markets[1]).is_err());
+
+    for _ in 2..MAX_CROSS_MARGIN_MARKETS {
+        account.link_market(&Pubkey::new_unique()).unwrap();
+    }
+    assert!(account.link_market(&Pubkey::new_unique()).is_err());
+}
@@ -0,0 +1,147 @@
+//! Per-market ring buffer of recent funding cranks.
+//!
+//! `FundingCrankLog` events can be truncated or missed, so CrankFunding also
+//! writes every applied funding rate, with the oracle and mark prices it was
+//! charged at, into a fixed-size account. The oldest entry is overwritten
+//! once the buffer is full.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{require, validation::ManifestAccount};
+
+use super::{
+    FUNDING_HISTORY_DISCRIMINANT, FUNDING_HISTORY_ENTRY_SIZE, FUNDING_HISTORY_LEN,
+    FUNDING_HISTORY_SIZE,
+};
+
+/// One applied funding crank.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Zeroable, Pod, ShankType)]
+pub struct FundingHistoryEntry {
+    /// Unix timestamp of the crank.
+    pub timestamp: i64,
+    /// Funding rate charged, as a fraction of position value scaled by
+    /// FUNDING_SCALE. Positive means longs paid shorts.
+    pub funding_rate: i64,
+    /// Seconds of funding the rate covers.
+    pub elapsed_secs: u64,
+    /// Oracle price, in quote atoms per FUNDING_REFERENCE_BASE_ATOMS.
+    pub oracle_price: u64,
+    /// Mark price, in quote atoms per FUNDING_REFERENCE_BASE_ATOMS.
+    pub mark_price: u64,
+}
+const_assert_eq!(size_of::<FundingHistoryEntry>(), FUNDING_HISTORY_ENTRY_SIZE);
+
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct FundingHistory {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Market whose funding is recorded.
+    market: Pubkey,
+
+    /// Slot the next entry is written to.
+    next_index: u64,
+
+    /// Number of entries written so far, at most FUNDING_HISTORY_LEN.
+    num_entries: u64,
+
+    entries: [FundingHistoryEntry; FUNDING_HISTORY_LEN],
+}
+const_assert_eq!(
+    size_of::<FundingHistory>(),
+    8 +   // discriminant
+    32 +  // market
+    8 +   // next_index
+    8 +   // num_entries
+    FUNDING_HISTORY_LEN * FUNDING_HISTORY_ENTRY_SIZE // entries
+);
+const_assert_eq!(size_of::<FundingHistory>(), FUNDING_HISTORY_SIZE);
+const_assert_eq!(size_of::<FundingHistory>() % 8, 0);
+impl Get for FundingHistory {}
+
+impl FundingHistory {
+    pub fn new_empty(market: &Pubkey) -> Self {
+        FundingHistory {
+            discriminant: FUNDING_HISTORY_DISCRIMINANT,
+            market: *market,
+            next_index: 0,
+            num_entries: 0,
+            entries: [FundingHistoryEntry::default(); FUNDING_HISTORY_LEN],
+        }
+    }
+
+    pub fn get_market(&self) -> &Pubkey {
+        &self.market
+    }
+
+    /// Record a crank, overwriting the oldest entry when full.
+    pub fn push(&mut self, entry: FundingHistoryEntry) {
+        let index: usize = self.next_index as usize % FUNDING_HISTORY_LEN;
+        self.entries[index] = entry;
+        self.next_index = ((index + 1) % FUNDING_HISTORY_LEN) as u64;
+        self.num_entries = (self.num_entries + 1).min(FUNDING_HISTORY_LEN as u64);
+    }
+
+    /// Recorded entries, oldest first.
+    pub fn get_entries(&self) -> Vec<FundingHistoryEntry> {
+        let num_entries: usize = self.num_entries as usize;
+        let start: usize =
+            (self.next_index as usize + FUNDING_HISTORY_LEN - num_entries) % FUNDING_HISTORY_LEN;
+        (0..num_entries)
+            .map(|offset| self.entries[(start + offset) % FUNDING_HISTORY_LEN])
+            .collect()
+    }
+}
+
+impl ManifestAccount for FundingHistory {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == FUNDING_HISTORY_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid funding history discriminant actual: {} expected: {}",
+            self.discriminant,
+            FUNDING_HISTORY_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(timestamp: i64) -> FundingHistoryEntry {
+        FundingHistoryEntry {
+            timestamp,
+            ..FundingHistoryEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_funding_history_wraps() {
+        let mut history: FundingHistory = FundingHistory::new_empty(&Pubkey::new_unique());
+        assert!(history.get_entries().is_empty());
+
+        history.push(entry(1));
+        history.push(entry(2));
+        assert_eq!(history.get_entries(), vec![entry(1), entry(2)]);
+
+        for timestamp in 3..=(FUNDING_HISTORY_LEN as i64 + 2) {
+            history.push(entry(timestamp));
+        }
+        let entries: Vec<FundingHistoryEntry> = history.get_entries();
+        assert_eq!(entries.len(), FUNDING_HISTORY_LEN);
+        assert_eq!(entries[0], entry(3));
+        assert_eq!(
+            entries[FUNDING_HISTORY_LEN - 1],
+            entry(FUNDING_HISTORY_LEN as i64 + 2)
+        );
+    }
+}
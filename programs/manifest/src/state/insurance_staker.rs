@@ -0,0 +1,173 @@
+//! Per-staker record of shares held in a market's insurance fund.
+//!
+//! Stakers deposit real quote tokens into the market's quote vault and receive
+//! shares of the (virtual) insurance fund balance. Fees flowing into the fund
+//! raise the value of every share and deficits drawn from the fund lower it,
+//! so stakers earn and absorb losses pro rata to their shares.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{require, validation::ManifestAccount};
+
+use super::{INSURANCE_STAKER_DISCRIMINANT, INSURANCE_STAKER_SIZE};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct InsuranceStaker {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Market whose insurance fund the shares are in.
+    market: Pubkey,
+
+    /// Owner of the shares.
+    staker: Pubkey,
+
+    /// Shares of the market insurance fund held by this staker.
+    shares: u64,
+
+    /// Unix timestamp of the most recent deposit.
+    last_deposit_timestamp: i64,
+
+    /// Shares the staker has asked to withdraw. Only these can be withdrawn,
+    /// once the cooldown has elapsed since the request.
+    withdraw_request_shares: u64,
+
+    /// Unix timestamp of the pending withdraw request.
+    withdraw_request_timestamp: i64,
+
+    /// Market insurance share epoch the shares were minted in. Shares from an
+    /// earlier epoch were voided when the fund was drained.
+    share_epoch: u64,
+
+    _padding: [u64; 1],
+}
+const_assert_eq!(
+    size_of::<InsuranceStaker>(),
+    8 +   // discriminant
+    32 +  // market
+    32 +  // staker
+    8 +   // shares
+    8 +   // last_deposit_timestamp
+    8 +   // withdraw_request_shares
+    8 +   // withdraw_request_timestamp
+    8 +   // share_epoch
+    8 // padding
+);
+const_assert_eq!(size_of::<InsuranceStaker>(), INSURANCE_STAKER_SIZE);
+const_assert_eq!(size_of::<InsuranceStaker>() % 8, 0);
+impl Get for InsuranceStaker {}
+
+impl InsuranceStaker {
+    pub fn new_empty(market: &Pubkey, staker: &Pubkey) -> Self {
+        InsuranceStaker {
+            discriminant: INSURANCE_STAKER_DISCRIMINANT,
+            market: *market,
+            staker: *staker,
+            shares: 0,
+            last_deposit_timestamp: 0,
+            withdraw_request_shares: 0,
+            withdraw_request_timestamp: 0,
+            share_epoch: 0,
+            _padding: [0; 1],
+        }
+    }
+
+    pub fn get_market(&self) -> &Pubkey {
+        &self.market
+    }
+    pub fn get_staker(&self) -> &Pubkey {
+        &self.staker
+    }
+    pub fn get_shares(&self) -> u64 {
+        self.shares
+    }
+    pub fn get_last_deposit_timestamp(&self) -> i64 {
+        self.last_deposit_timestamp
+    }
+    pub fn get_withdraw_request_shares(&self) -> u64 {
+        self.withdraw_request_shares
+    }
+    pub fn get_withdraw_request_timestamp(&self) -> i64 {
+        self.withdraw_request_timestamp
+    }
+    pub fn get_share_epoch(&self) -> u64 {
+        self.share_epoch
+    }
+
+    /// Drop shares minted in an earlier insurance share epoch. They were
+    /// voided when the fund was drained and are no longer counted in the
+    /// market's total.
+    pub fn sync_share_epoch(&mut self, market_share_epoch: u64) {
+        if self.share_epoch != market_share_epoch {
+            self.shares = 0;
+            self.withdraw_request_shares = 0;
+            self.withdraw_request_timestamp = 0;
+            self.share_epoch = market_share_epoch;
+        }
+    }
+
+    pub fn add_shares(&mut self, shares: u64, now: i64) {
+        self.shares = self.shares.saturating_add(shares);
+        self.last_deposit_timestamp = now;
+    }
+
+    pub fn remove_shares(&mut self, shares: u64) -> ProgramResult {
+        require!(
+            shares <= self.shares,
+            ProgramError::InsufficientFunds,
+            "Insufficient insurance shares {} < {}",
+            self.shares,
+            shares
+        )?;
+        self.shares -= shares;
+        Ok(())
+    }
+
+    /// Replace any pending request with one for `shares`, starting the
+    /// cooldown at `now`.
+    pub fn request_withdraw(&mut self, shares: u64, now: i64) -> ProgramResult {
+        require!(
+            shares > 0 && shares <= self.shares,
+            ProgramError::InsufficientFunds,
+            "Cannot request {} of {} insurance shares",
+            shares,
+            self.shares
+        )?;
+        self.withdraw_request_shares = shares;
+        self.withdraw_request_timestamp = now;
+        Ok(())
+    }
+
+    /// Withdraw `shares` out of the pending request.
+    pub fn remove_requested_shares(&mut self, shares: u64) -> ProgramResult {
+        require!(
+            shares <= self.withdraw_request_shares,
+            ProgramError::InsufficientFunds,
+            "Only {} insurance shares requested, cannot withdraw {}",
+            self.withdraw_request_shares,
+            shares
+        )?;
+        self.remove_shares(shares)?;
+        self.withdraw_request_shares -= shares;
+        Ok(())
+    }
+}
+
+impl ManifestAccount for InsuranceStaker {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == INSURANCE_STAKER_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid insurance staker discriminant actual: {} expected: {}",
+            self.discriminant,
+            INSURANCE_STAKER_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
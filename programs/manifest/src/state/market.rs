@@ -14,7 +14,7 @@ use hypertree::{
 use shank::ShankType;
 use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
 use static_assertions::const_assert_eq;
-use std::mem::size_of;
+use std::mem::{offset_of, size_of};
 
 use crate::{
     logs::{emit_stack, FillLog},
@@ -23,23 +23,29 @@ use crate::{
     require,
     state::{
         utils::{assert_can_take, remove_from_global, try_to_move_global_tokens},
-        OrderType,
-    },
-    validation::{
-        get_vault_address, loaders::GlobalTradeAccounts, ManifestAccount, MintAccountInfo,
+        OracleSource, OracleType, OrderType,
     },
+    validation::{loaders::GlobalTradeAccounts, ManifestAccount, MintAccountInfo},
 };
 
 use super::{
     claimed_seat::ClaimedSeat,
-    constants::{MARKET_BLOCK_SIZE, MARKET_FIXED_SIZE},
-    order_type_can_rest,
+    constants::{
+        DEFAULT_FUNDING_PERIOD_SECS, DEFAULT_LIQUIDATOR_REWARD_BPS, DEFAULT_MARK_EMA_WINDOW_SECS,
+        DEFAULT_MAX_FUNDING_RATE, DEFAULT_MAX_LIQUIDATION_SLIPPAGE_BPS,
+        DEFAULT_MAX_ORACLE_CONF_BPS, DEFAULT_MAX_ORACLE_STALENESS_SECS,
+        DEFAULT_MIN_POSITION_SIZE_ATOMS, FUNDING_SCALE, LEGACY_MARKET_BLOCK_SIZE,
+        LEGACY_MARKET_FIXED_SIZE, MARKET_BLOCK_SIZE, MARKET_FIXED_SIZE, MAX_ORACLE_SOURCES,
+        MAX_RISK_TIERS,
+    },
+    margin_bps_for_notional, order_type_can_rest,
     utils::{
         assert_already_has_seat, assert_not_already_expired, can_back_order, get_now_slot,
         try_to_add_to_global,
     },
-    DerefOrBorrow, DerefOrBorrowMut, DynamicAccount, RestingOrder, MARKET_FIXED_DISCRIMINANT,
-    MARKET_FREE_LIST_BLOCK_SIZE, NO_EXPIRATION_LAST_VALID_SLOT,
+    DerefOrBorrow, DerefOrBorrowMut, DynamicAccount, RestingOrder, RiskTier,
+    MARKET_FIXED_DISCRIMINANT, MARKET_FREE_LIST_BLOCK_SIZE, MARKET_LAYOUT_VERSION,
+    NO_EXPIRATION_LAST_VALID_SLOT,
 };
 
 #[path = "market_helpers.rs"]
@@ -118,7 +124,7 @@ pub struct AddOrderToMarketResult {
 #[repr(C, packed)]
 #[derive(Default, Copy, Clone, Pod, Zeroable)]
 pub struct MarketUnusedFreeListPadding {
-    _padding: [u64; 9],
+    _padding: [u64; 12],
     _padding2: [u8; 4],
 }
 // 4 bytes are for the free list, rest is payload.
@@ -212,7 +218,59 @@ pub struct MarketFixed {
     #[cfg(feature = "certora")]
     liquidation_buffer_bps: u64,
     #[cfg(feature = "certora")]
-    _padding3: [u64; 3],
+    cumulative_loss_per_unit: u64,
+    #[cfg(feature = "certora")]
+    insurance_total_shares: u64,
+    #[cfg(feature = "certora")]
+    insurance_share_epoch: u64,
+    #[cfg(feature = "certora")]
+    market_authority: Pubkey,
+    #[cfg(feature = "certora")]
+    liquidator_reward_bps: u64,
+    #[cfg(feature = "certora")]
+    min_position_size_atoms: u64,
+    #[cfg(feature = "certora")]
+    insurance_protocol_fee_bps: u64,
+    #[cfg(feature = "certora")]
+    insurance_protocol_shares: u64,
+    #[cfg(feature = "certora")]
+    max_liquidation_slippage_bps: u64,
+    #[cfg(feature = "certora")]
+    risk_tiers: [RiskTier; MAX_RISK_TIERS],
+    #[cfg(feature = "certora")]
+    oracle_conf: u64,
+    #[cfg(feature = "certora")]
+    max_oracle_conf_bps: u64,
+    #[cfg(feature = "certora")]
+    oracle_publish_time: u64,
+    #[cfg(feature = "certora")]
+    max_oracle_staleness_secs: u64,
+    #[cfg(feature = "certora")]
+    pyth_feed_id: [u8; 32],
+    #[cfg(feature = "certora")]
+    oracle_type: u64,
+    #[cfg(feature = "certora")]
+    extra_oracle_sources: [OracleSource; MAX_ORACLE_SOURCES - 1],
+    #[cfg(feature = "certora")]
+    ema_premium_rate: u64,
+    #[cfg(feature = "certora")]
+    premium_sample_timestamp: u64,
+    #[cfg(feature = "certora")]
+    mark_ema_window_secs: u64,
+    #[cfg(feature = "certora")]
+    premium_twap_sum: u64,
+    #[cfg(feature = "certora")]
+    premium_twap_secs: u64,
+    #[cfg(feature = "certora")]
+    funding_period_secs: u64,
+    #[cfg(feature = "certora")]
+    max_funding_rate: u64,
+    #[cfg(feature = "certora")]
+    funding_interest_rate: u64,
+    #[cfg(feature = "certora")]
+    pnl_pool_balance: u64,
+    #[cfg(feature = "certora")]
+    _padding3: [u64; 15],
 
     /// Initial margin in basis points (e.g., 1000 = 10% = 10x leverage)
     #[cfg(not(feature = "certora"))]
@@ -250,8 +308,114 @@ pub struct MarketFixed {
     /// Buffer above maintenance margin to target after partial liquidation (basis points)
     #[cfg(not(feature = "certora"))]
     liquidation_buffer_bps: u64,
+    /// Cumulative uncovered bad debt socialized per base atom of open
+    /// interest, scaled by 1e9. Only increases. Shares a seat cannot pay from
+    /// its margin are socialized again over the rest.
+    #[cfg(not(feature = "certora"))]
+    cumulative_loss_per_unit: u64,
+    /// Total shares outstanding against the insurance fund balance. Shares
+    /// are held by insurance stakers, except for the protocol's seed shares
+    /// minted 1:1 against the balance accrued before the first stake.
+    #[cfg(not(feature = "certora"))]
+    insurance_total_shares: u64,
+    /// Bumped when a deposit finds the fund empty with shares outstanding.
+    /// Shares minted in an earlier epoch were worth nothing and are void.
+    #[cfg(not(feature = "certora"))]
+    insurance_share_epoch: u64,
+    /// Signer allowed to update market parameters after creation. Set to the
+    /// market creator.
+    #[cfg(not(feature = "certora"))]
+    market_authority: Pubkey,
+    /// Liquidator reward in basis points of closed notional. Also the
+    /// discount a takeover liquidation is priced at.
+    #[cfg(not(feature = "certora"))]
+    liquidator_reward_bps: u64,
+    /// Minimum position in base atoms to keep after a partial liquidation.
+    /// A smaller remainder is liquidated in full.
+    #[cfg(not(feature = "certora"))]
+    min_position_size_atoms: u64,
+    /// Part of each taker fee, in basis points, that buys insurance shares
+    /// for the protocol. The rest raises the value of every share, so
+    /// stakers earn it pro rata.
+    #[cfg(not(feature = "certora"))]
+    insurance_protocol_fee_bps: u64,
+    /// Shares of `insurance_total_shares` owned by the protocol: the seed
+    /// shares and those bought with its cut of the fees. They cannot be
+    /// redeemed and stay in the fund as a backstop.
+    #[cfg(not(feature = "certora"))]
+    insurance_protocol_shares: u64,
+    /// Furthest from the mark price, in basis points, that an orderbook
+    /// liquidation fills. Stops a thin or manipulated book from turning a
+    /// liquidation into an insurance fund drain.
+    #[cfg(not(feature = "certora"))]
+    max_liquidation_slippage_bps: u64,
+    /// Margin tiers by position notional, applied above the base initial and
+    /// maintenance margin. Active tiers first, unused ones zeroed.
+    #[cfg(not(feature = "certora"))]
+    risk_tiers: [RiskTier; MAX_RISK_TIERS],
+    /// Cached oracle confidence interval, in the same exponent as the cached
+    /// price (updated by CrankFunding)
+    #[cfg(not(feature = "certora"))]
+    oracle_conf: u64,
+    /// Largest oracle confidence accepted by CrankFunding, in basis points of
+    /// the price
+    #[cfg(not(feature = "certora"))]
+    max_oracle_conf_bps: u64,
+    /// Unix timestamp the cached oracle price was published at by the oracle.
+    /// Stored as u64, interpret as i64.
+    #[cfg(not(feature = "certora"))]
+    oracle_publish_time: u64,
+    /// Oldest oracle price, in seconds since publication, that CrankFunding
+    /// caches and that margin checks and liquidations value positions at
+    #[cfg(not(feature = "certora"))]
+    max_oracle_staleness_secs: u64,
+    /// Pyth feed id a PriceUpdateV3 oracle account must carry. Unset (zero)
+    /// rejects PriceUpdateV3 accounts.
+    #[cfg(not(feature = "certora"))]
+    pyth_feed_id: [u8; 32],
+    /// Format of the oracle account, an `OracleType` stored as u64
+    #[cfg(not(feature = "certora"))]
+    oracle_type: u64,
+    /// Oracle accounts priced alongside the primary one. Active sources
+    /// first, unused ones zeroed.
+    #[cfg(not(feature = "certora"))]
+    extra_oracle_sources: [OracleSource; MAX_ORACLE_SOURCES - 1],
+    /// Moving average of the book premium over the oracle, (mid - oracle) /
+    /// oracle scaled by PREMIUM_RATE_SCALE, stored as u64 (i64 bits)
+    #[cfg(not(feature = "certora"))]
+    ema_premium_rate: u64,
+    /// Unix timestamp the book premium was last sampled at. Zero before the
+    /// first sample.
     #[cfg(not(feature = "certora"))]
-    _padding3: [u64; 5],
+    premium_sample_timestamp: u64,
+    /// Time constant of the premium average in seconds
+    #[cfg(not(feature = "certora"))]
+    mark_ema_window_secs: u64,
+    /// Sum of premium rate times seconds since the last funding crank, the
+    /// numerator of the funding premium TWAP, stored as u64 (i64 bits)
+    #[cfg(not(feature = "certora"))]
+    premium_twap_sum: u64,
+    /// Seconds sampled since the last funding crank
+    #[cfg(not(feature = "certora"))]
+    premium_twap_secs: u64,
+    /// Length of a funding period in seconds. Funding rates are quoted per
+    /// period and one crank charges at most one period.
+    #[cfg(not(feature = "certora"))]
+    funding_period_secs: u64,
+    /// Largest funding rate magnitude per period, scaled by FUNDING_SCALE
+    #[cfg(not(feature = "certora"))]
+    max_funding_rate: u64,
+    /// Fixed interest rate per period added to the premium, scaled by
+    /// FUNDING_SCALE, stored as u64 (i64 bits). Zero for none.
+    #[cfg(not(feature = "certora"))]
+    funding_interest_rate: u64,
+    /// Realized losses settled out of margins and not yet paid out as realized
+    /// profit, in quote atoms. Settled profits are capped at this balance so
+    /// the vault always backs every margin.
+    #[cfg(not(feature = "certora"))]
+    pnl_pool_balance: u64,
+    #[cfg(not(feature = "certora"))]
+    _padding3: [u64; 17],
 }
 const_assert_eq!(
     size_of::<MarketFixed>(),
@@ -272,7 +436,7 @@ const_assert_eq!(
     4 +   // free_list_head_index
     4 +   // padding2
     8 +   // quote_volume
-    160   // perps + padding
+    672 // perps + padding
 );
 const_assert_eq!(size_of::<MarketFixed>(), MARKET_FIXED_SIZE);
 const_assert_eq!(size_of::<MarketFixed>() % 8, 0);
@@ -286,7 +450,7 @@ impl MarketFixed {
     ) -> Self {
         MarketFixed {
             discriminant: MARKET_FIXED_DISCRIMINANT,
-            version: 0,
+            version: MARKET_LAYOUT_VERSION,
             base_mint_index,
             base_mint_decimals,
             quote_mint_decimals: quote_mint.mint.decimals,
@@ -331,7 +495,59 @@ impl MarketFixed {
             #[cfg(not(feature = "certora"))]
             liquidation_buffer_bps: 0,
             #[cfg(not(feature = "certora"))]
-            _padding3: [0; 5],
+            cumulative_loss_per_unit: 0,
+            #[cfg(not(feature = "certora"))]
+            insurance_total_shares: 0,
+            #[cfg(not(feature = "certora"))]
+            insurance_share_epoch: 0,
+            #[cfg(not(feature = "certora"))]
+            market_authority: Pubkey::default(),
+            #[cfg(not(feature = "certora"))]
+            liquidator_reward_bps: 0,
+            #[cfg(not(feature = "certora"))]
+            min_position_size_atoms: 0,
+            #[cfg(not(feature = "certora"))]
+            insurance_protocol_fee_bps: 0,
+            #[cfg(not(feature = "certora"))]
+            insurance_protocol_shares: 0,
+            #[cfg(not(feature = "certora"))]
+            max_liquidation_slippage_bps: 0,
+            #[cfg(not(feature = "certora"))]
+            risk_tiers: [RiskTier::default(); MAX_RISK_TIERS],
+            #[cfg(not(feature = "certora"))]
+            oracle_conf: 0,
+            #[cfg(not(feature = "certora"))]
+            max_oracle_conf_bps: 0,
+            #[cfg(not(feature = "certora"))]
+            oracle_publish_time: 0,
+            #[cfg(not(feature = "certora"))]
+            max_oracle_staleness_secs: 0,
+            #[cfg(not(feature = "certora"))]
+            pyth_feed_id: [0; 32],
+            #[cfg(not(feature = "certora"))]
+            oracle_type: 0,
+            #[cfg(not(feature = "certora"))]
+            extra_oracle_sources: [OracleSource::default(); MAX_ORACLE_SOURCES - 1],
+            #[cfg(not(feature = "certora"))]
+            ema_premium_rate: 0,
+            #[cfg(not(feature = "certora"))]
+            premium_sample_timestamp: 0,
+            #[cfg(not(feature = "certora"))]
+            mark_ema_window_secs: 0,
+            #[cfg(not(feature = "certora"))]
+            premium_twap_sum: 0,
+            #[cfg(not(feature = "certora"))]
+            premium_twap_secs: 0,
+            #[cfg(not(feature = "certora"))]
+            funding_period_secs: 0,
+            #[cfg(not(feature = "certora"))]
+            max_funding_rate: 0,
+            #[cfg(not(feature = "certora"))]
+            funding_interest_rate: 0,
+            #[cfg(not(feature = "certora"))]
+            pnl_pool_balance: 0,
+            #[cfg(not(feature = "certora"))]
+            _padding3: [0; 17],
             #[cfg(feature = "certora")]
             withdrawable_base_atoms: BaseAtoms::new(0),
             #[cfg(feature = "certora")]
@@ -361,7 +577,59 @@ impl MarketFixed {
             #[cfg(feature = "certora")]
             liquidation_buffer_bps: 0,
             #[cfg(feature = "certora")]
-            _padding3: [0; 3],
+            cumulative_loss_per_unit: 0,
+            #[cfg(feature = "certora")]
+            insurance_total_shares: 0,
+            #[cfg(feature = "certora")]
+            insurance_share_epoch: 0,
+            #[cfg(feature = "certora")]
+            market_authority: Pubkey::default(),
+            #[cfg(feature = "certora")]
+            liquidator_reward_bps: 0,
+            #[cfg(feature = "certora")]
+            min_position_size_atoms: 0,
+            #[cfg(feature = "certora")]
+            insurance_protocol_fee_bps: 0,
+            #[cfg(feature = "certora")]
+            insurance_protocol_shares: 0,
+            #[cfg(feature = "certora")]
+            max_liquidation_slippage_bps: 0,
+            #[cfg(feature = "certora")]
+            risk_tiers: [RiskTier::default(); MAX_RISK_TIERS],
+            #[cfg(feature = "certora")]
+            oracle_conf: 0,
+            #[cfg(feature = "certora")]
+            max_oracle_conf_bps: 0,
+            #[cfg(feature = "certora")]
+            oracle_publish_time: 0,
+            #[cfg(feature = "certora")]
+            max_oracle_staleness_secs: 0,
+            #[cfg(feature = "certora")]
+            pyth_feed_id: [0; 32],
+            #[cfg(feature = "certora")]
+            oracle_type: 0,
+            #[cfg(feature = "certora")]
+            extra_oracle_sources: [OracleSource::default(); MAX_ORACLE_SOURCES - 1],
+            #[cfg(feature = "certora")]
+            ema_premium_rate: 0,
+            #[cfg(feature = "certora")]
+            premium_sample_timestamp: 0,
+            #[cfg(feature = "certora")]
+            mark_ema_window_secs: 0,
+            #[cfg(feature = "certora")]
+            premium_twap_sum: 0,
+            #[cfg(feature = "certora")]
+            premium_twap_secs: 0,
+            #[cfg(feature = "certora")]
+            funding_period_secs: 0,
+            #[cfg(feature = "certora")]
+            max_funding_rate: 0,
+            #[cfg(feature = "certora")]
+            funding_interest_rate: 0,
+            #[cfg(feature = "certora")]
+            pnl_pool_balance: 0,
+            #[cfg(feature = "certora")]
+            _padding3: [0; 15],
         }
     }
 
@@ -372,7 +640,7 @@ impl MarketFixed {
         cvt::cvt_assume!(claimed_seats_root_index == NIL);
         MarketFixed {
             discriminant: MARKET_FIXED_DISCRIMINANT,
-            version: 0,
+            version: MARKET_LAYOUT_VERSION,
             base_mint_index: nondet(),
             base_mint_decimals: nondet(),
             quote_mint_decimals: nondet(),
@@ -402,7 +670,33 @@ impl MarketFixed {
             insurance_fund_balance: 0,
             taker_fee_bps: 0,
             liquidation_buffer_bps: 0,
-            _padding3: [0; 3],
+            cumulative_loss_per_unit: 0,
+            insurance_total_shares: 0,
+            insurance_share_epoch: 0,
+            market_authority: Pubkey::default(),
+            liquidator_reward_bps: 0,
+            min_position_size_atoms: 0,
+            insurance_protocol_fee_bps: 0,
+            insurance_protocol_shares: 0,
+            max_liquidation_slippage_bps: 0,
+            risk_tiers: [RiskTier::default(); MAX_RISK_TIERS],
+            oracle_conf: 0,
+            max_oracle_conf_bps: 0,
+            oracle_publish_time: 0,
+            max_oracle_staleness_secs: 0,
+            pyth_feed_id: [0; 32],
+            oracle_type: 0,
+            extra_oracle_sources: [OracleSource::default(); MAX_ORACLE_SOURCES - 1],
+            ema_premium_rate: 0,
+            premium_sample_timestamp: 0,
+            mark_ema_window_secs: 0,
+            premium_twap_sum: 0,
+            premium_twap_secs: 0,
+            funding_period_secs: 0,
+            max_funding_rate: 0,
+            funding_interest_rate: 0,
+            pnl_pool_balance: 0,
+            _padding3: [0; 15],
         }
     }
 
@@ -475,11 +769,39 @@ impl MarketFixed {
     pub fn get_total_short_base_atoms(&self) -> u64 {
         self.total_short_base_atoms
     }
-    pub fn set_perps_params(
-        &mut self,
-        initial_margin_bps: u64,
-        maintenance_margin_bps: u64,
-    ) {
+    pub fn get_risk_tiers(&self) -> &[RiskTier; MAX_RISK_TIERS] {
+        &self.risk_tiers
+    }
+    pub fn set_risk_tiers(&mut self, risk_tiers: [RiskTier; MAX_RISK_TIERS]) {
+        self.risk_tiers = risk_tiers;
+    }
+    /// `(initial_margin_bps, maintenance_margin_bps)` for a position of
+    /// `notional_atoms`, after applying the risk tiers.
+    pub fn get_margin_bps_for_notional(&self, notional_atoms: u64) -> (u64, u64) {
+        margin_bps_for_notional(
+            self.initial_margin_bps,
+            self.maintenance_margin_bps,
+            &self.risk_tiers,
+            notional_atoms,
+        )
+    }
+    /// Initial margin in quote atoms required for a position of `notional_atoms`.
+    pub fn get_initial_margin_for_notional(&self, notional_atoms: u64) -> u64 {
+        let (initial_margin_bps, _) = self.get_margin_bps_for_notional(notional_atoms);
+        notional_atoms
+            .checked_mul(initial_margin_bps)
+            .unwrap_or(u64::MAX)
+            / 10000
+    }
+    /// Maintenance margin in quote atoms required for a position of `notional_atoms`.
+    pub fn get_maintenance_margin_for_notional(&self, notional_atoms: u64) -> u64 {
+        let (_, maintenance_margin_bps) = self.get_margin_bps_for_notional(notional_atoms);
+        notional_atoms
+            .checked_mul(maintenance_margin_bps)
+            .unwrap_or(u64::MAX)
+            / 10000
+    }
+    pub fn set_perps_params(&mut self, initial_margin_bps: u64, maintenance_margin_bps: u64) {
         self.initial_margin_bps = initial_margin_bps;
         self.maintenance_margin_bps = maintenance_margin_bps;
     }
@@ -498,15 +820,146 @@ impl MarketFixed {
     pub fn set_pyth_feed(&mut self, feed: Pubkey) {
         self.pyth_feed_account = feed;
     }
+    pub fn get_pyth_feed_id(&self) -> &[u8; 32] {
+        &self.pyth_feed_id
+    }
+    pub fn set_pyth_feed_id(&mut self, feed_id: [u8; 32]) {
+        self.pyth_feed_id = feed_id;
+    }
+    pub fn get_oracle_type(&self) -> OracleType {
+        OracleType::try_from(self.oracle_type as u8).unwrap_or_default()
+    }
+    pub fn set_oracle_type(&mut self, oracle_type: OracleType) {
+        self.oracle_type = u8::from(oracle_type) as u64;
+    }
+    /// All oracle sources, primary first. Unused sources have a default feed.
+    pub fn get_oracle_sources(&self) -> [OracleSource; MAX_ORACLE_SOURCES] {
+        let mut sources: [OracleSource; MAX_ORACLE_SOURCES] =
+            [OracleSource::default(); MAX_ORACLE_SOURCES];
+        sources[0] = OracleSource::new(self.pyth_feed_account, self.get_oracle_type());
+        sources[1..].copy_from_slice(&self.extra_oracle_sources);
+        sources
+    }
+    pub fn get_extra_oracle_sources(&self) -> &[OracleSource; MAX_ORACLE_SOURCES - 1] {
+        &self.extra_oracle_sources
+    }
+    pub fn set_extra_oracle_sources(
+        &mut self,
+        extra_oracle_sources: [OracleSource; MAX_ORACLE_SOURCES - 1],
+    ) {
+        self.extra_oracle_sources = extra_oracle_sources;
+    }
+    pub fn get_ema_premium_rate(&self) -> i64 {
+        self.ema_premium_rate as i64
+    }
+    pub fn get_premium_sample_timestamp(&self) -> i64 {
+        self.premium_sample_timestamp as i64
+    }
+    pub fn get_mark_ema_window_secs(&self) -> u64 {
+        self.mark_ema_window_secs
+    }
+    pub fn set_mark_ema_window_secs(&mut self, mark_ema_window_secs: u64) {
+        self.mark_ema_window_secs = mark_ema_window_secs;
+    }
+    /// Fold book premium samples taken at `now` into the mark price moving
+    /// average and the funding premium TWAP. Each sample is weighted by the
+    /// time since the previous one, so a book price counts for as long as it
+    /// stood. `mark_premium_rate` is the premium of the book mid and needs a
+    /// two-sided book; `funding_premium_rate` also takes the one side of a
+    /// one-sided book. Time without a funding sample counts as no premium.
+    pub fn update_premium_samples(
+        &mut self,
+        mark_premium_rate: Option<i64>,
+        funding_premium_rate: Option<i64>,
+        now: i64,
+    ) {
+        let last_timestamp: i64 = self.get_premium_sample_timestamp();
+        if now <= last_timestamp {
+            return;
+        }
+        // The first sample only starts the clock.
+        if last_timestamp != 0 {
+            let elapsed: i64 = now - last_timestamp;
+            if let Some(premium_rate) = mark_premium_rate {
+                let window: i128 = self.mark_ema_window_secs.max(1) as i128;
+                let weight: i128 = (elapsed as i128).min(window);
+                let ema: i128 = self.get_ema_premium_rate() as i128;
+                let next_ema: i128 = ema + (premium_rate as i128 - ema) * weight / window;
+                self.ema_premium_rate = next_ema as i64 as u64;
+            }
+            if let Some(premium_rate) = funding_premium_rate {
+                let premium_twap_sum: i64 = (self.premium_twap_sum as i64)
+                    .saturating_add(premium_rate.saturating_mul(elapsed));
+                self.premium_twap_sum = premium_twap_sum as u64;
+            }
+            self.premium_twap_secs = self.premium_twap_secs.saturating_add(elapsed as u64);
+        }
+        self.premium_sample_timestamp = now as u64;
+    }
+    /// Time-weighted average funding premium since the last funding crank,
+    /// scaled by PREMIUM_RATE_SCALE. Zero if no time was sampled.
+    pub fn get_premium_twap(&self) -> i64 {
+        if self.premium_twap_secs == 0 {
+            0
+        } else {
+            ((self.premium_twap_sum as i64 as i128) / self.premium_twap_secs as i128) as i64
+        }
+    }
+    /// Time-weighted average funding premium since the last call, and restart
+    /// the average.
+    pub fn take_premium_twap(&mut self) -> i64 {
+        let premium_twap: i64 = self.get_premium_twap();
+        self.premium_twap_sum = 0;
+        self.premium_twap_secs = 0;
+        premium_twap
+    }
+    pub fn get_funding_period_secs(&self) -> u64 {
+        self.funding_period_secs
+    }
+    pub fn set_funding_period_secs(&mut self, funding_period_secs: u64) {
+        self.funding_period_secs = funding_period_secs;
+    }
+    pub fn get_max_funding_rate(&self) -> u64 {
+        self.max_funding_rate
+    }
+    pub fn set_max_funding_rate(&mut self, max_funding_rate: u64) {
+        self.max_funding_rate = max_funding_rate;
+    }
+    pub fn get_funding_interest_rate(&self) -> i64 {
+        self.funding_interest_rate as i64
+    }
+    pub fn set_funding_interest_rate(&mut self, funding_interest_rate: i64) {
+        self.funding_interest_rate = funding_interest_rate as u64;
+    }
     pub fn get_oracle_price_mantissa(&self) -> u64 {
         self.oracle_price_mantissa
     }
     pub fn get_oracle_price_expo(&self) -> i32 {
         self.oracle_price_expo_and_pad as i32
     }
-    pub fn set_oracle_price(&mut self, mantissa: u64, expo: i32) {
+    pub fn set_oracle_price(&mut self, mantissa: u64, expo: i32, conf: u64, publish_time: i64) {
         self.oracle_price_mantissa = mantissa;
         self.oracle_price_expo_and_pad = expo as u32 as u64;
+        self.oracle_conf = conf;
+        self.oracle_publish_time = publish_time as u64;
+    }
+    pub fn get_oracle_publish_time(&self) -> i64 {
+        self.oracle_publish_time as i64
+    }
+    pub fn get_max_oracle_staleness_secs(&self) -> u64 {
+        self.max_oracle_staleness_secs
+    }
+    pub fn set_max_oracle_staleness_secs(&mut self, max_oracle_staleness_secs: u64) {
+        self.max_oracle_staleness_secs = max_oracle_staleness_secs;
+    }
+    pub fn get_oracle_conf(&self) -> u64 {
+        self.oracle_conf
+    }
+    pub fn get_max_oracle_conf_bps(&self) -> u64 {
+        self.max_oracle_conf_bps
+    }
+    pub fn set_max_oracle_conf_bps(&mut self, max_oracle_conf_bps: u64) {
+        self.max_oracle_conf_bps = max_oracle_conf_bps;
     }
     pub fn get_last_funding_timestamp(&self) -> i64 {
         self.last_funding_timestamp as i64
@@ -536,6 +989,121 @@ impl MarketFixed {
         self.insurance_fund_balance = self.insurance_fund_balance.saturating_sub(drawn);
         drawn
     }
+
+    pub fn get_pnl_pool_balance(&self) -> u64 {
+        self.pnl_pool_balance
+    }
+    pub fn add_to_pnl_pool(&mut self, amount: u64) {
+        self.pnl_pool_balance = self.pnl_pool_balance.saturating_add(amount);
+    }
+    /// Pay realized profit out of the PnL pool, returns the amount actually
+    /// paid (capped by balance).
+    pub fn draw_from_pnl_pool(&mut self, amount: u64) -> u64 {
+        let drawn = amount.min(self.pnl_pool_balance);
+        self.pnl_pool_balance -= drawn;
+        drawn
+    }
+
+    pub fn get_insurance_total_shares(&self) -> u64 {
+        self.insurance_total_shares
+    }
+    pub fn get_insurance_protocol_shares(&self) -> u64 {
+        self.insurance_protocol_shares
+    }
+    pub fn get_insurance_share_epoch(&self) -> u64 {
+        self.insurance_share_epoch
+    }
+    pub fn get_insurance_protocol_fee_bps(&self) -> u64 {
+        self.insurance_protocol_fee_bps
+    }
+    pub fn set_insurance_protocol_fee_bps(&mut self, val: u64) {
+        self.insurance_protocol_fee_bps = val;
+    }
+    /// Add `amount` of real quote to the insurance fund and mint shares at the
+    /// current share price. Returns the number of shares minted.
+    pub fn mint_insurance_shares(&mut self, amount: u64) -> Result<u64, ProgramError> {
+        if self.insurance_total_shares == 0 {
+            // Balance accrued from fees before anyone staked belongs to the
+            // protocol. Seed it with shares so the first staker buys in at
+            // 1:1 instead of being handed that balance.
+            self.insurance_total_shares = self.insurance_fund_balance;
+            self.insurance_protocol_shares = self.insurance_fund_balance;
+        } else if self.insurance_fund_balance == 0 {
+            // Losses drained the fund while shares were outstanding. Those
+            // shares are worth nothing, so start a new epoch and price this
+            // deposit at 1:1 rather than refusing every new staker.
+            self.void_insurance_shares();
+        }
+        let shares: u64 = if self.insurance_total_shares == 0 {
+            amount
+        } else {
+            u64::try_from(
+                amount as u128 * self.insurance_total_shares as u128
+                    / self.insurance_fund_balance as u128,
+            )
+            .map_err(|_| ManifestError::Overflow)?
+        };
+        require!(
+            shares > 0,
+            ManifestError::InvalidPerpsOperation,
+            "Insurance deposit of {} is too small to mint a share",
+            amount,
+        )?;
+        self.insurance_fund_balance = self
+            .insurance_fund_balance
+            .checked_add(amount)
+            .ok_or(ManifestError::Overflow)?;
+        self.insurance_total_shares = self
+            .insurance_total_shares
+            .checked_add(shares)
+            .ok_or(ManifestError::Overflow)?;
+        Ok(shares)
+    }
+    fn void_insurance_shares(&mut self) {
+        self.insurance_share_epoch += 1;
+        self.insurance_total_shares = 0;
+        self.insurance_protocol_shares = 0;
+    }
+    /// Add a taker fee to the insurance fund. The protocol's cut buys shares
+    /// at the current price; the rest accrues to every share pro rata.
+    pub fn add_fee_to_insurance_fund(&mut self, fee: u64) {
+        if self.insurance_total_shares > 0 && self.insurance_fund_balance == 0 {
+            // Shares left over a drained fund are void. Without this the fee
+            // would go to them and price the next deposit near zero.
+            self.void_insurance_shares();
+        } else if self.insurance_total_shares > 0 {
+            let protocol_cut: u128 = fee as u128 * self.insurance_protocol_fee_bps as u128 / 10_000;
+            let shares: u64 = u64::try_from(
+                protocol_cut * self.insurance_total_shares as u128
+                    / self.insurance_fund_balance as u128,
+            )
+            .unwrap_or(0);
+            if let Some(total_shares) = self.insurance_total_shares.checked_add(shares) {
+                self.insurance_total_shares = total_shares;
+                self.insurance_protocol_shares += shares;
+            }
+        }
+        self.add_to_insurance_fund(fee);
+    }
+    /// Burn `shares` and remove their pro-rata part of the insurance fund.
+    /// Returns the quote atoms the shares were worth, rounded down.
+    pub fn burn_insurance_shares(&mut self, shares: u64) -> Result<u64, ProgramError> {
+        require!(
+            shares <= self.insurance_total_shares,
+            ManifestError::InvalidPerpsOperation,
+            "Cannot burn {} of {} insurance shares",
+            shares,
+            self.insurance_total_shares,
+        )?;
+        if shares == 0 {
+            return Ok(0);
+        }
+        let amount: u64 = (shares as u128 * self.insurance_fund_balance as u128
+            / self.insurance_total_shares as u128) as u64;
+        self.insurance_fund_balance -= amount;
+        self.insurance_total_shares -= shares;
+        Ok(amount)
+    }
     pub fn get_taker_fee_bps(&self) -> u64 {
         self.taker_fee_bps
     }
@@ -548,6 +1116,72 @@ impl MarketFixed {
     pub fn set_liquidation_buffer_bps(&mut self, val: u64) {
         self.liquidation_buffer_bps = val;
     }
+
+    pub fn get_market_authority(&self) -> &Pubkey {
+        &self.market_authority
+    }
+    pub fn set_market_authority(&mut self, authority: Pubkey) {
+        self.market_authority = authority;
+    }
+    pub fn get_liquidator_reward_bps(&self) -> u64 {
+        self.liquidator_reward_bps
+    }
+    pub fn get_min_position_size_atoms(&self) -> u64 {
+        self.min_position_size_atoms
+    }
+    pub fn set_liquidation_params(
+        &mut self,
+        liquidator_reward_bps: u64,
+        min_position_size_atoms: u64,
+    ) {
+        self.liquidator_reward_bps = liquidator_reward_bps;
+        self.min_position_size_atoms = min_position_size_atoms;
+    }
+    pub fn get_max_liquidation_slippage_bps(&self) -> u64 {
+        self.max_liquidation_slippage_bps
+    }
+    pub fn set_max_liquidation_slippage_bps(&mut self, val: u64) {
+        self.max_liquidation_slippage_bps = val;
+    }
+
+    /// Scaling factor (1e9) for the cumulative socialized loss per unit.
+    pub const LOSS_PER_UNIT_SCALE: u64 = 1_000_000_000;
+
+    pub fn get_cumulative_loss_per_unit(&self) -> u64 {
+        self.cumulative_loss_per_unit
+    }
+    /// Spread a deficit that the insurance fund could not cover over all open
+    /// interest. Traders are charged lazily on their next interaction by
+    /// `settle_socialized_loss_for_trader`. Returns the amount socialized, which
+    /// is zero when there is no open interest left to absorb it.
+    ///
+    /// The loss is only meant to fall on seats with positive equity, but the
+    /// equity of every seat is not known here. Underwater seats count towards
+    /// the open interest, yet their charge is capped at their margin and the
+    /// rest is socialized again, so the loss ends up on the seats with margin
+    /// left to pay it.
+    #[cfg(not(feature = "certora"))]
+    pub fn socialize_loss(&mut self, amount: u64) -> u64 {
+        self.socialize_loss_excluding(amount, 0)
+    }
+    /// `socialize_loss` over the open interest other than `excluded_base_atoms`,
+    /// for a position whose checkpoint is stored after this loss.
+    #[cfg(not(feature = "certora"))]
+    pub fn socialize_loss_excluding(&mut self, amount: u64, excluded_base_atoms: u64) -> u64 {
+        let open_interest: u128 = (self.total_long_base_atoms as u128
+            + self.total_short_base_atoms as u128)
+            .saturating_sub(excluded_base_atoms as u128);
+        if amount == 0 || open_interest == 0 {
+            return 0;
+        }
+        // Round up so the charges collected cover the whole deficit.
+        let scaled: u128 = amount as u128 * Self::LOSS_PER_UNIT_SCALE as u128;
+        let delta: u128 = (scaled + open_interest - 1) / open_interest;
+        self.cumulative_loss_per_unit = self
+            .cumulative_loss_per_unit
+            .saturating_add(delta.min(u64::MAX as u128) as u64);
+        amount
+    }
 }
 
 impl ManifestAccount for MarketFixed {
@@ -559,10 +1193,157 @@ impl ManifestAccount for MarketFixed {
             self.discriminant,
             MARKET_FIXED_DISCRIMINANT
         )?;
+        require!(
+            self.version == MARKET_LAYOUT_VERSION,
+            ProgramError::InvalidAccountData,
+            "Market layout version {} is not {}, run MigrateMarket first",
+            self.version,
+            MARKET_LAYOUT_VERSION
+        )?;
         Ok(())
     }
 }
 
+/// Size a version 0 market grows to in the current layout. Its header still
+/// holds the legacy block count, so this also holds while MigrateMarket is
+/// partway through growing the account.
+pub fn get_migrated_market_size(data: &[u8]) -> Result<usize, ProgramError> {
+    require!(
+        data.len() >= LEGACY_MARKET_FIXED_SIZE,
+        ProgramError::InvalidAccountData,
+        "Market account is shorter than a legacy header",
+    )?;
+    let version: u8 = data[offset_of!(MarketFixed, version)];
+    require!(
+        version == 0,
+        ManifestError::InvalidPerpsOperation,
+        "Market layout version {} does not need migrating",
+        version,
+    )?;
+    let num_bytes_offset: usize = offset_of!(MarketFixed, num_bytes_allocated);
+    let num_bytes_allocated: usize = u32::from_le_bytes(
+        data[num_bytes_offset..num_bytes_offset + 4]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    require!(
+        num_bytes_allocated % LEGACY_MARKET_BLOCK_SIZE == 0,
+        ProgramError::InvalidAccountData,
+        "Legacy market allocated {} bytes, not a whole number of blocks",
+        num_bytes_allocated,
+    )?;
+    Ok(MARKET_FIXED_SIZE + num_bytes_allocated / LEGACY_MARKET_BLOCK_SIZE * MARKET_BLOCK_SIZE)
+}
+
+/// Block indexes scale with the block size, so a legacy index maps to the
+/// same block in the current layout.
+fn migrate_legacy_index(index: DataIndex) -> DataIndex {
+    if index == NIL {
+        return NIL;
+    }
+    index / LEGACY_MARKET_BLOCK_SIZE as DataIndex * MARKET_BLOCK_SIZE as DataIndex
+}
+
+fn migrate_legacy_index_at(data: &mut [u8], offset: usize) {
+    let index: DataIndex = DataIndex::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    data[offset..offset + 4].copy_from_slice(&migrate_legacy_index(index).to_le_bytes());
+}
+
+/// Rewrite a version 0 market in place into the current layout. `data` is
+/// already sized by `get_migrated_market_size` and still holds the legacy
+/// header and blocks at its front. Seats and orders keep their fields, the
+/// fields added since start at zero and the market parameters added since
+/// take the CreateMarket defaults. The market is left without an authority.
+pub fn migrate_legacy_market(data: &mut [u8]) -> ProgramResult {
+    let migrated_size: usize = get_migrated_market_size(data)?;
+    require!(
+        data.len() == migrated_size,
+        ProgramError::InvalidAccountData,
+        "Market account is {} bytes, expected {} to migrate",
+        data.len(),
+        migrated_size,
+    )?;
+    let num_blocks: usize = (migrated_size - MARKET_FIXED_SIZE) / MARKET_BLOCK_SIZE;
+
+    // Find the free blocks while the free list is still in the legacy layout.
+    let mut is_free: Vec<bool> = vec![false; num_blocks];
+    let head_offset: usize = offset_of!(MarketFixed, free_list_head_index);
+    let mut free_index: DataIndex =
+        DataIndex::from_le_bytes(data[head_offset..head_offset + 4].try_into().unwrap());
+    while free_index != NIL {
+        let block: usize = free_index as usize / LEGACY_MARKET_BLOCK_SIZE;
+        require!(
+            block < num_blocks && !is_free[block],
+            ProgramError::InvalidAccountData,
+            "Legacy free list is corrupt at index {}",
+            free_index,
+        )?;
+        is_free[block] = true;
+        let next_offset: usize = LEGACY_MARKET_FIXED_SIZE + free_index as usize;
+        free_index =
+            DataIndex::from_le_bytes(data[next_offset..next_offset + 4].try_into().unwrap());
+    }
+
+    // Blocks only move towards the end, so moving the last one first never
+    // overwrites a block that has not moved yet.
+    for block in (0..num_blocks).rev() {
+        let old_offset: usize = LEGACY_MARKET_FIXED_SIZE + block * LEGACY_MARKET_BLOCK_SIZE;
+        let new_offset: usize = MARKET_FIXED_SIZE + block * MARKET_BLOCK_SIZE;
+        data.copy_within(
+            old_offset..old_offset + LEGACY_MARKET_BLOCK_SIZE,
+            new_offset,
+        );
+        data[new_offset + LEGACY_MARKET_BLOCK_SIZE..new_offset + MARKET_BLOCK_SIZE].fill(0);
+    }
+    data[LEGACY_MARKET_FIXED_SIZE..MARKET_FIXED_SIZE].fill(0);
+
+    let (fixed_data, dynamic) = data.split_at_mut(MARKET_FIXED_SIZE);
+    for (block, block_is_free) in is_free.into_iter().enumerate() {
+        let offset: usize = block * MARKET_BLOCK_SIZE;
+        if block_is_free {
+            // Free blocks start with the index of the next free block.
+            migrate_legacy_index_at(dynamic, offset);
+            continue;
+        }
+        // Tree nodes start with their left, right and parent indexes.
+        for index_offset in [0, 4, 8] {
+            migrate_legacy_index_at(dynamic, offset + index_offset);
+        }
+        let node: &mut RBNode<RestingOrder> =
+            get_mut_helper::<RBNode<RestingOrder>>(dynamic, offset as DataIndex);
+        if node.get_payload_type() == MarketDataTreeNodeType::RestingOrder as u8 {
+            let order: &mut RestingOrder = node.get_mut_value();
+            order.set_trader_index(migrate_legacy_index(order.get_trader_index()));
+        }
+    }
+
+    let fixed: &mut MarketFixed = get_mut_helper::<MarketFixed>(fixed_data, 0_u32);
+    fixed.num_bytes_allocated = (num_blocks * MARKET_BLOCK_SIZE) as u32;
+    for index in [
+        &mut fixed.bids_root_index,
+        &mut fixed.bids_best_index,
+        &mut fixed.asks_root_index,
+        &mut fixed.asks_best_index,
+        &mut fixed.claimed_seats_root_index,
+        &mut fixed.free_list_head_index,
+    ] {
+        *index = migrate_legacy_index(*index);
+    }
+
+    // The liquidator reward has to stay below the market's maintenance margin.
+    let liquidator_reward_bps: u64 =
+        DEFAULT_LIQUIDATOR_REWARD_BPS.min(fixed.get_maintenance_margin_bps().saturating_sub(1));
+    fixed.set_liquidation_params(liquidator_reward_bps, DEFAULT_MIN_POSITION_SIZE_ATOMS);
+    fixed.set_max_liquidation_slippage_bps(DEFAULT_MAX_LIQUIDATION_SLIPPAGE_BPS);
+    fixed.set_max_oracle_conf_bps(DEFAULT_MAX_ORACLE_CONF_BPS);
+    fixed.set_max_oracle_staleness_secs(DEFAULT_MAX_ORACLE_STALENESS_SECS);
+    fixed.set_mark_ema_window_secs(DEFAULT_MARK_EMA_WINDOW_SECS);
+    fixed.set_funding_period_secs(DEFAULT_FUNDING_PERIOD_SECS);
+    fixed.set_max_funding_rate(DEFAULT_MAX_FUNDING_RATE);
+    fixed.version = MARKET_LAYOUT_VERSION;
+    Ok(())
+}
+
 /// Fully owned Market, used in clients that can copy.
 pub type MarketValue = DynamicAccount<MarketFixed, Vec<u8>>;
 /// Full market reference type.
@@ -887,6 +1668,16 @@ impl<Fixed: DerefOrBorrow<MarketFixed>, Dynamic: DerefOrBorrow<[u8]>>
         )
     }
 
+    pub fn get_trader_balance_by_index(&self, trader_index: DataIndex) -> (BaseAtoms, QuoteAtoms) {
+        let DynamicAccount { dynamic, .. } = self.borrow_market();
+
+        let claimed_seat: &ClaimedSeat = get_helper_seat(dynamic, trader_index).get_value();
+        (
+            claimed_seat.base_withdrawable_balance,
+            claimed_seat.quote_withdrawable_balance,
+        )
+    }
+
     pub fn get_trader_key_by_index(&self, index: DataIndex) -> &Pubkey {
         let DynamicAccount { dynamic, .. } = self.borrow_market();
 
@@ -921,6 +1712,20 @@ impl<Fixed: DerefOrBorrow<MarketFixed>, Dynamic: DerefOrBorrow<[u8]>>
         )
     }
 
+    /// Get the trader's realized PnL that has not been settled into their
+    /// margin balance yet, in quote atoms.
+    pub fn get_trader_unsettled_pnl(&self, trader: &Pubkey) -> i64 {
+        let DynamicAccount { fixed, dynamic } = self.borrow_market();
+
+        let claimed_seats_tree: ClaimedSeatTreeReadOnly =
+            ClaimedSeatTreeReadOnly::new(dynamic, fixed.claimed_seats_root_index, NIL);
+        let trader_index: DataIndex =
+            claimed_seats_tree.lookup_index(&ClaimedSeat::new_empty(*trader));
+        get_helper_seat(dynamic, trader_index)
+            .get_value()
+            .get_unsettled_pnl()
+    }
+
     pub fn get_bids(&self) -> BooksideReadOnly {
         let DynamicAccount { dynamic, fixed } = self.borrow_market();
         BooksideReadOnly::new(
@@ -939,6 +1744,12 @@ impl<Fixed: DerefOrBorrow<MarketFixed>, Dynamic: DerefOrBorrow<[u8]>>
         )
     }
 
+    #[cfg(not(feature = "certora"))]
+    pub fn get_claimed_seats(&self) -> ClaimedSeatTreeReadOnly {
+        let DynamicAccount { dynamic, fixed } = self.borrow_market();
+        ClaimedSeatTreeReadOnly::new(dynamic, fixed.claimed_seats_root_index, NIL)
+    }
+
     fn is_missing_global_account(
         &self,
         resting_order: &RestingOrder,
@@ -998,15 +1809,76 @@ impl<Fixed: DerefOrBorrow<MarketFixed>, Dynamic: DerefOrBorrow<[u8]>>
         return false;
     }
 
+    /// Index of the trader's seat on their default sub-account.
     pub fn get_trader_index(&self, trader: &Pubkey) -> DataIndex {
+        self.get_sub_account_index(trader, 0)
+    }
+
+    pub fn get_sub_account_index(&self, trader: &Pubkey, sub_account_id: u8) -> DataIndex {
         let DynamicAccount { fixed, dynamic } = self.borrow_market();
 
         let claimed_seats_tree: ClaimedSeatTreeReadOnly =
             ClaimedSeatTreeReadOnly::new(dynamic, fixed.claimed_seats_root_index, NIL);
-        let trader_index: DataIndex =
-            claimed_seats_tree.lookup_index(&ClaimedSeat::new_empty(*trader));
+        let trader_index: DataIndex = claimed_seats_tree.lookup_index(
+            &ClaimedSeat::new_empty_for_sub_account(*trader, sub_account_id),
+        );
         trader_index
     }
+
+    /// Whether `index` is the block of a claimed seat, and not a free block or
+    /// an order.
+    #[cfg(not(feature = "certora"))]
+    pub fn is_claimed_seat_index(&self, index: DataIndex) -> bool {
+        let DynamicAccount { fixed, dynamic } = self.borrow_market();
+        if index as usize % MARKET_BLOCK_SIZE != 0 || index >= fixed.num_bytes_allocated {
+            return false;
+        }
+        let node: &RBNode<ClaimedSeat> = get_helper::<RBNode<ClaimedSeat>>(dynamic, index);
+        if node.get_payload_type() != MarketDataTreeNodeType::ClaimedSeat as u8 {
+            return false;
+        }
+        // A freed block keeps the payload it last held, so look the seat up.
+        let seat: &ClaimedSeat = node.get_value();
+        self.get_sub_account_index(&seat.trader, seat.get_sub_account_id()) == index
+    }
+
+    /// Indices of all of the trader's seats, one per sub-account they hold.
+    #[cfg(not(feature = "certora"))]
+    pub fn get_trader_seat_indices(&self, trader: &Pubkey) -> Vec<DataIndex> {
+        let DynamicAccount { fixed, dynamic } = self.borrow_market();
+
+        // Seats are ordered by trader, then sub-account, so only subtrees
+        // that can hold the trader's seats are walked.
+        let mut seat_indices: Vec<DataIndex> = Vec::new();
+        let mut to_visit: Vec<DataIndex> = vec![fixed.claimed_seats_root_index];
+        while let Some(index) = to_visit.pop() {
+            if index == NIL {
+                continue;
+            }
+            let node: &RBNode<ClaimedSeat> = get_helper::<RBNode<ClaimedSeat>>(dynamic, index);
+            let seat_trader: &Pubkey = &node.get_value().trader;
+            if seat_trader >= trader {
+                to_visit.push(node.get_left_index());
+            }
+            if seat_trader <= trader {
+                to_visit.push(node.get_right_index());
+            }
+            if seat_trader == trader {
+                seat_indices.push(index);
+            }
+        }
+        seat_indices
+    }
+
+    /// The mock seat tree holds one seat per trader.
+    #[cfg(feature = "certora")]
+    pub fn get_trader_seat_indices(&self, trader: &Pubkey) -> Vec<DataIndex> {
+        let trader_index: DataIndex = self.get_trader_index(trader);
+        if trader_index == NIL {
+            return Vec::new();
+        }
+        vec![trader_index]
+    }
 }
 
 // This generic impl covers MarketRef, MarketRefMut and other
@@ -1048,13 +1920,25 @@ impl<
     }
 
     pub fn claim_seat(&mut self, trader: &Pubkey) -> ProgramResult {
+        self.claim_sub_account_seat(trader, 0)
+    }
+
+    /// Claim a seat for one of the trader's sub-accounts. Each sub-account
+    /// holds its own balances, position and orders.
+    pub fn claim_sub_account_seat(&mut self, trader: &Pubkey, sub_account_id: u8) -> ProgramResult {
         let DynamicAccount { fixed, dynamic } = self.borrow_mut();
         let free_address: DataIndex = get_free_address_on_market_fixed_for_seat(fixed, dynamic);
 
         let mut claimed_seats_tree: ClaimedSeatTree =
             ClaimedSeatTree::new(dynamic, fixed.claimed_seats_root_index, NIL);
 
-        let claimed_seat: ClaimedSeat = ClaimedSeat::new_empty(*trader);
+        let mut claimed_seat: ClaimedSeat =
+            ClaimedSeat::new_empty_for_sub_account(*trader, sub_account_id);
+        // Start from the current checkpoints, so a seat that gets a position
+        // before it is first settled is not charged funding or losses that
+        // accrued before it existed.
+        claimed_seat.set_last_cumulative_funding(fixed.get_cumulative_funding());
+        claimed_seat.set_last_cumulative_loss_per_unit(fixed.get_cumulative_loss_per_unit());
         require!(
             claimed_seats_tree.lookup_index(&claimed_seat) == NIL,
             ManifestError::AlreadyClaimedSeat,
@@ -1079,7 +1963,15 @@ impl<
     // program to expand. Otherwise, there is no reason to ever give up your
     // seat.
     pub fn release_seat(&mut self, trader: &Pubkey) -> ProgramResult {
-        let trader_seat_index: DataIndex = self.get_trader_index(trader);
+        self.release_sub_account_seat(trader, 0)
+    }
+
+    pub fn release_sub_account_seat(
+        &mut self,
+        trader: &Pubkey,
+        sub_account_id: u8,
+    ) -> ProgramResult {
+        let trader_seat_index: DataIndex = self.get_sub_account_index(trader, sub_account_id);
         let DynamicAccount { fixed, dynamic } = self.borrow_mut();
 
         let mut claimed_seats_tree: ClaimedSeatTree =
@@ -1119,9 +2011,6 @@ impl<
         Ok(())
     }
 
-    /// Funding rate scaling factor (1e9). Shared with crank_funding.
-    const FUNDING_SCALE: i64 = 1_000_000_000;
-
     /// Settle accumulated funding for a trader using lazy cumulative approach.
     ///
     /// Reads the trader's `last_cumulative_funding` (stored in base_withdrawable_balance),
@@ -1131,12 +2020,17 @@ impl<
     ///
     /// Must be called at the START of any user interaction (swap, batch_update,
     /// liquidate, deposit, withdraw) before any base balance operations.
-    pub fn settle_funding_for_trader(&mut self, trader_index: DataIndex) -> ProgramResult {
+    /// Returns the funding settled, positive when the trader paid.
+    pub fn settle_funding_for_trader(
+        &mut self,
+        trader_index: DataIndex,
+    ) -> Result<i64, ProgramError> {
         let DynamicAccount { fixed, dynamic } = self.borrow_mut();
         let claimed_seat: &mut ClaimedSeat =
             get_mut_helper_seat(dynamic, trader_index).get_mut_value();
 
         let position_size: i64 = claimed_seat.get_position_size();
+        let mut funding_paid: i64 = 0;
         if position_size != 0 {
             let global_cumulative: i64 = fixed.get_cumulative_funding();
             let last_cumulative: i64 = claimed_seat.get_last_cumulative_funding();
@@ -1145,19 +2039,33 @@ impl<
             if delta != 0 {
                 // funding_owed = position_size * delta / FUNDING_SCALE
                 // Longs pay positive funding, shorts receive positive funding
-                let funding_owed: i64 = ((position_size as i128 * delta as i128)
-                    / Self::FUNDING_SCALE as i128) as i64;
+                let funding_owed: i64 =
+                    ((position_size as i128 * delta as i128) / FUNDING_SCALE as i128) as i64;
+                funding_paid = funding_owed;
 
                 let current_margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
                 let new_margin: u64 = if funding_owed >= 0 {
                     let owed: u64 = funding_owed as u64;
                     if owed <= current_margin {
                         current_margin - owed
+                    } else if claimed_seat.is_cross_margin() {
+                        // A cross-margin seat keeps owing the rest until
+                        // collateral is moved in to cover it.
+                        let unsettled_pnl: i64 = claimed_seat.get_unsettled_pnl();
+                        claimed_seat.set_unsettled_pnl(
+                            unsettled_pnl.saturating_sub((owed - current_margin) as i64),
+                        );
+                        0
                     } else {
                         // Funding exceeds margin — draw deficit from insurance fund.
                         // This prevents silent vault insolvency.
+                        // Whatever the fund cannot cover is socialized.
                         let deficit: u64 = owed - current_margin;
-                        fixed.draw_from_insurance_fund(deficit);
+                        let drawn: u64 = fixed.draw_from_insurance_fund(deficit);
+                        #[cfg(not(feature = "certora"))]
+                        fixed.socialize_loss(deficit - drawn);
+                        #[cfg(feature = "certora")]
+                        let _ = drawn;
                         0
                     }
                 } else {
@@ -1170,19 +2078,150 @@ impl<
         // Zero base_withdrawable_balance for matching engine (base is virtual in perps).
         // The cumulative funding value was consumed above.
         claimed_seat.base_withdrawable_balance = BaseAtoms::new(0);
-        Ok(())
+        Ok(funding_paid)
     }
 
-    /// Store the current global cumulative funding rate into the trader's seat.
+    /// Move the trader's unsettled realized PnL into their margin balance.
+    ///
+    /// A loss is taken from the margin and added to the PnL pool. Whatever
+    /// the margin cannot cover is drawn from the insurance fund into the pool
+    /// and the rest is socialized, the same as bad debt. A profit is paid out
+    /// of the PnL pool, so it only reaches the margin once the matching
+    /// losses have been collected. The part the pool cannot pay yet stays
+    /// unsettled. A cross-margin seat keeps a loss its margin cannot cover
+    /// unsettled as well. Returns the PnL moved into the margin.
+    pub fn settle_pnl_for_trader(&mut self, trader_index: DataIndex) -> Result<i64, ProgramError> {
+        let DynamicAccount { fixed, dynamic } = self.borrow_mut();
+        let claimed_seat: &mut ClaimedSeat =
+            get_mut_helper_seat(dynamic, trader_index).get_mut_value();
+
+        let pnl: i64 = claimed_seat.get_unsettled_pnl();
+        if pnl == 0 {
+            return Ok(0);
+        }
+
+        let current_margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
+        if pnl > 0 {
+            let paid: u64 = fixed.draw_from_pnl_pool(pnl as u64);
+            claimed_seat.quote_withdrawable_balance =
+                QuoteAtoms::new(current_margin.saturating_add(paid));
+            claimed_seat.set_unsettled_pnl(pnl - paid as i64);
+            return Ok(paid as i64);
+        }
+
+        let loss: u64 = pnl.unsigned_abs();
+        let from_margin: u64 = loss.min(current_margin);
+        if claimed_seat.is_cross_margin() {
+            // What the margin cannot cover stays owed by the seat and is
+            // backed by the trader's cross-margin collateral instead.
+            fixed.add_to_pnl_pool(from_margin);
+            claimed_seat.quote_withdrawable_balance = QuoteAtoms::new(current_margin - from_margin);
+            claimed_seat.set_unsettled_pnl(pnl + from_margin as i64);
+            return Ok(-(from_margin as i64));
+        }
+        let drawn: u64 = fixed.draw_from_insurance_fund(loss - from_margin);
+        fixed.add_to_pnl_pool(from_margin + drawn);
+        #[cfg(not(feature = "certora"))]
+        fixed.socialize_loss(loss - from_margin - drawn);
+        claimed_seat.quote_withdrawable_balance = QuoteAtoms::new(current_margin - from_margin);
+        claimed_seat.set_unsettled_pnl(0);
+        Ok(pnl)
+    }
+
+    /// Charge a trader their share of losses socialized since their last
+    /// interaction, proportional to the absolute position size.
+    ///
+    /// The charge is capped at the trader's margin, so seats without equity
+    /// left pay nothing. What cannot be collected is drawn from the insurance
+    /// fund and the rest is socialized again over the other open interest.
+    /// Returns the amount charged and the amount socialized again.
+    /// Like funding, must be called at the START of a user interaction. It
+    /// moves the seat's loss checkpoint on itself, so a seat is never charged
+    /// twice even where `store_cumulative_for_trader` does not follow.
+    pub fn settle_socialized_loss_for_trader(
+        &mut self,
+        trader_index: DataIndex,
+    ) -> Result<(u64, u64), ProgramError> {
+        let DynamicAccount { fixed, dynamic } = self.borrow_mut();
+        let claimed_seat: &mut ClaimedSeat =
+            get_mut_helper_seat(dynamic, trader_index).get_mut_value();
+
+        let abs_position: u64 = claimed_seat.get_position_size().unsigned_abs();
+        let delta: u64 = fixed
+            .get_cumulative_loss_per_unit()
+            .saturating_sub(claimed_seat.get_last_cumulative_loss_per_unit());
+        if abs_position == 0 || delta == 0 {
+            claimed_seat.set_last_cumulative_loss_per_unit(fixed.get_cumulative_loss_per_unit());
+            return Ok((0, 0));
+        }
+
+        let loss: u128 =
+            abs_position as u128 * delta as u128 / MarketFixed::LOSS_PER_UNIT_SCALE as u128;
+        let current_margin: u64 = claimed_seat.quote_withdrawable_balance.as_u64();
+        let charged: u64 = loss.min(current_margin as u128) as u64;
+        claimed_seat.quote_withdrawable_balance = QuoteAtoms::new(current_margin - charged);
+        let uncollected: u64 = (loss - charged as u128).min(u64::MAX as u128) as u64;
+        let resocialized: u64 = if uncollected == 0 {
+            0
+        } else if claimed_seat.is_cross_margin() {
+            // Like funding, a cross-margin seat owes what its margin lacks.
+            let owed: i64 = uncollected.min(i64::MAX as u64) as i64;
+            let unsettled_pnl: i64 = claimed_seat.get_unsettled_pnl();
+            claimed_seat.set_unsettled_pnl(unsettled_pnl.saturating_sub(owed));
+            0
+        } else {
+            // Dropping the shortfall would leave the vault short of the
+            // profits already booked against it.
+            let drawn: u64 = fixed.draw_from_insurance_fund(uncollected);
+            fixed.add_to_pnl_pool(drawn);
+            #[cfg(not(feature = "certora"))]
+            let resocialized: u64 =
+                fixed.socialize_loss_excluding(uncollected - drawn, abs_position);
+            #[cfg(feature = "certora")]
+            let resocialized: u64 = 0;
+            resocialized
+        };
+        // The charge covers losses already booked against someone's margin,
+        // so it backs the realized profits waiting on the PnL pool.
+        fixed.add_to_pnl_pool(charged);
+        // What was socialized again left this seat out, so the checkpoint
+        // takes it in.
+        claimed_seat.set_last_cumulative_loss_per_unit(fixed.get_cumulative_loss_per_unit());
+        Ok((charged, resocialized))
+    }
+
+    /// Write off what a flat cross-margin seat still owes once its collateral
+    /// has run out. The insurance fund covers what it can and the rest is
+    /// socialized, like any other bad debt. Returns the amount socialized.
+    #[cfg(not(feature = "certora"))]
+    pub fn write_off_debt_for_trader(&mut self, trader_index: DataIndex) -> u64 {
+        let DynamicAccount { fixed, dynamic } = self.borrow_mut();
+        let claimed_seat: &mut ClaimedSeat =
+            get_mut_helper_seat(dynamic, trader_index).get_mut_value();
+        let pnl: i64 = claimed_seat.get_unsettled_pnl();
+        if pnl >= 0 || claimed_seat.get_position_size() != 0 {
+            return 0;
+        }
+        let debt: u64 = pnl.unsigned_abs();
+        let drawn: u64 = fixed.draw_from_insurance_fund(debt);
+        fixed.add_to_pnl_pool(drawn);
+        claimed_seat.set_unsettled_pnl(0);
+        fixed.socialize_loss(debt - drawn)
+    }
+
+    /// Store the current global cumulative funding rate and socialized loss
+    /// per unit into the trader's seat.
     ///
     /// Must be called at the END of any user interaction, after all matching
     /// engine operations are complete, to persist the funding checkpoint.
     pub fn store_cumulative_for_trader(&mut self, trader_index: DataIndex) {
         let DynamicAccount { fixed, dynamic } = self.borrow_mut();
         let global_cumulative: i64 = fixed.get_cumulative_funding();
+        let global_loss_per_unit: u64 = fixed.get_cumulative_loss_per_unit();
         let claimed_seat: &mut ClaimedSeat =
             get_mut_helper_seat(dynamic, trader_index).get_mut_value();
         claimed_seat.set_last_cumulative_funding(global_cumulative);
+        claimed_seat.set_last_cumulative_loss_per_unit(global_loss_per_unit);
     }
 
     pub fn place_order_(
@@ -1401,7 +2440,7 @@ impl<
             }
 
             // Update perps position tracking for maker and taker
-            update_perps_position(
+            let maker_realized_pnl: i64 = update_perps_position(
                 fixed,
                 dynamic,
                 maker_trader_index,
@@ -1409,7 +2448,7 @@ impl<
                 quote_atoms_traded.as_u64(),
                 !is_bid,
             )?;
-            update_perps_position(
+            let taker_realized_pnl: i64 = update_perps_position(
                 fixed,
                 dynamic,
                 trader_index,
@@ -1429,6 +2468,8 @@ impl<
                 price: matched_price,
                 maker_sequence_number,
                 taker_sequence_number: fixed.order_sequence_number,
+                maker_realized_pnl,
+                taker_realized_pnl,
                 taker_is_buy: PodBool::from(is_bid),
                 is_maker_global: PodBool::from(is_global),
                 _padding: [0; 14],
@@ -2012,7 +3053,10 @@ pub fn update_balance(
 }
 
 /// Update perps position tracking for a trader after a fill.
-/// `is_bid` means the trader is buying (going long).
+/// `is_bid` means the trader is buying (going long). Returns the PnL realized
+/// by the part of the fill that reduced the position. It is booked as
+/// unsettled PnL, which `settle_pnl_for_trader` later moves into the margin
+/// balance, and picked up from there by the seat extension.
 pub fn update_perps_position(
     fixed: &mut MarketFixed,
     dynamic: &mut [u8],
@@ -2020,9 +3064,8 @@ pub fn update_perps_position(
     base_atoms_traded: u64,
     quote_atoms_traded: u64,
     is_bid: bool,
-) -> ProgramResult {
-    let claimed_seat: &mut ClaimedSeat =
-        get_mut_helper_seat(dynamic, trader_index).get_mut_value();
+) -> Result<i64, ProgramError> {
+    let claimed_seat: &mut ClaimedSeat = get_mut_helper_seat(dynamic, trader_index).get_mut_value();
 
     let old_position: i64 = claimed_seat.get_position_size();
     let old_cost_basis: u64 = claimed_seat.get_quote_cost_basis();
@@ -2037,13 +3080,14 @@ pub fn update_perps_position(
             .ok_or(ProgramError::ArithmeticOverflow)?
     };
 
-    // Update cost basis
-    let new_cost_basis: u64 = if old_position == 0 {
+    // Update cost basis. The closed cost and the quote it was closed for
+    // give the realized PnL of a reducing fill.
+    let (new_cost_basis, closed_cost, closed_quote): (u64, u64, u64) = if old_position == 0 {
         // Opening fresh position
-        quote_atoms_traded
+        (quote_atoms_traded, 0, 0)
     } else if (old_position > 0 && is_bid) || (old_position < 0 && !is_bid) {
         // Increasing position in same direction
-        old_cost_basis.saturating_add(quote_atoms_traded)
+        (old_cost_basis.saturating_add(quote_atoms_traded), 0, 0)
     } else {
         // Reducing or flipping position
         let old_abs: u64 = old_position.unsigned_abs();
@@ -2053,19 +3097,36 @@ pub fn update_perps_position(
                 .checked_mul(base_atoms_traded as u128)
                 .unwrap_or(u128::MAX)
                 / (old_abs as u128)) as u64;
-            old_cost_basis.saturating_sub(closed_cost)
+            (
+                old_cost_basis.saturating_sub(closed_cost),
+                closed_cost,
+                quote_atoms_traded,
+            )
         } else {
             // Full close + open in opposite direction
             let remaining: u64 = base_atoms_traded - old_abs;
-            ((quote_atoms_traded as u128)
+            let opened_cost: u64 = ((quote_atoms_traded as u128)
                 .checked_mul(remaining as u128)
                 .unwrap_or(u128::MAX)
-                / (base_atoms_traded as u128)) as u64
+                / (base_atoms_traded as u128)) as u64;
+            (
+                opened_cost,
+                old_cost_basis,
+                quote_atoms_traded.saturating_sub(opened_cost),
+            )
         }
     };
 
+    // A long realizes proceeds minus cost, a short cost minus proceeds.
+    let realized_pnl: i64 = if old_position > 0 {
+        (closed_quote as i128 - closed_cost as i128) as i64
+    } else {
+        (closed_cost as i128 - closed_quote as i128) as i64
+    };
+
     claimed_seat.set_position_size(new_position);
     claimed_seat.set_quote_cost_basis(new_cost_basis);
+    claimed_seat.book_realized_pnl(realized_pnl);
 
     // Update global position tracking
     #[cfg(not(feature = "certora"))]
@@ -2109,7 +3170,7 @@ pub fn update_perps_position(
         }
     }
 
-    Ok(())
+    Ok(realized_pnl)
 }
 
 #[inline(always)]
@@ -2275,3 +3336,293 @@ pub fn create_empty_market(
     let market_fixed: MarketFixed = MarketFixed::new_empty(0, base_mint.mint.decimals, &quote_mint);
     market_fixed
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insurance_fee_split_buys_protocol_shares() {
+        let mut market_fixed: MarketFixed = MarketFixed::zeroed();
+        market_fixed.set_insurance_protocol_fee_bps(5_000);
+        assert_eq!(market_fixed.mint_insurance_shares(1_000).unwrap(), 1_000);
+
+        // Half of the fee buys shares at the pre-fee price of 1.
+        market_fixed.add_fee_to_insurance_fund(100);
+        assert_eq!(market_fixed.get_insurance_fund_balance(), 1_100);
+        assert_eq!(market_fixed.get_insurance_total_shares(), 1_050);
+        assert_eq!(market_fixed.get_insurance_protocol_shares(), 50);
+        assert_eq!(market_fixed.burn_insurance_shares(1_000).unwrap(), 1_047);
+    }
+
+    #[test]
+    fn test_insurance_deposit_into_drained_fund_starts_new_epoch() {
+        let mut market_fixed: MarketFixed = MarketFixed::zeroed();
+        market_fixed.mint_insurance_shares(1_000).unwrap();
+        assert_eq!(market_fixed.draw_from_insurance_fund(5_000), 1_000);
+
+        assert_eq!(market_fixed.mint_insurance_shares(500).unwrap(), 500);
+        assert_eq!(market_fixed.get_insurance_share_epoch(), 1);
+        assert_eq!(market_fixed.get_insurance_total_shares(), 500);
+        assert_eq!(market_fixed.get_insurance_fund_balance(), 500);
+    }
+
+    #[test]
+    fn test_insurance_fee_into_drained_fund_goes_to_protocol() {
+        let mut market_fixed: MarketFixed = MarketFixed::zeroed();
+        market_fixed.mint_insurance_shares(1_000).unwrap();
+        market_fixed.draw_from_insurance_fund(1_000);
+
+        // The fee voids the old shares and seeds the protocol on next mint.
+        market_fixed.add_fee_to_insurance_fund(1);
+        assert_eq!(market_fixed.get_insurance_share_epoch(), 1);
+        assert_eq!(market_fixed.mint_insurance_shares(500).unwrap(), 500);
+        assert_eq!(market_fixed.get_insurance_protocol_shares(), 1);
+        assert_eq!(market_fixed.get_insurance_total_shares(), 501);
+    }
+
+    fn test_market_with_seats(traders: &[Pubkey]) -> MarketValue {
+        test_market_with_seats_after_loss(traders, 0)
+    }
+
+    /// Market with `loss` socialized over 1000 base atoms of open interest
+    /// before the seats are claimed.
+    fn test_market_with_seats_after_loss(traders: &[Pubkey], loss: u64) -> MarketValue {
+        let mut market: MarketValue = MarketValue {
+            fixed: create_empty_market(
+                &Pubkey::new_unique().to_string(),
+                &Pubkey::new_unique().to_string(),
+                9,
+                6,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+            ),
+            dynamic: vec![0; MARKET_BLOCK_SIZE * traders.len()],
+        };
+        market.market_expand_n(traders.len() as u32).unwrap();
+        market.fixed.set_total_long_base_atoms(1_000);
+        market.fixed.socialize_loss(loss);
+        market.fixed.set_total_long_base_atoms(0);
+        for trader in traders {
+            market.claim_seat(trader).unwrap();
+        }
+        market
+    }
+
+    fn seat_mut<'a>(market: &'a mut MarketValue, trader: &Pubkey) -> &'a mut ClaimedSeat {
+        let trader_index: DataIndex = market.get_trader_index(trader);
+        get_mut_helper_seat(&mut market.dynamic, trader_index).get_mut_value()
+    }
+
+    #[test]
+    fn test_claimed_seat_starts_at_current_loss_checkpoint() {
+        let trader: Pubkey = Pubkey::new_unique();
+        let mut market: MarketValue = test_market_with_seats_after_loss(&[trader], 1_000);
+        let cumulative_loss_per_unit: u64 = market.fixed.get_cumulative_loss_per_unit();
+        assert!(cumulative_loss_per_unit > 0);
+        assert_eq!(
+            seat_mut(&mut market, &trader).get_last_cumulative_loss_per_unit(),
+            cumulative_loss_per_unit
+        );
+    }
+
+    #[test]
+    fn test_uncollectable_socialized_loss_is_socialized_again() {
+        let short_of_margin: Pubkey = Pubkey::new_unique();
+        let well_funded: Pubkey = Pubkey::new_unique();
+        let mut market: MarketValue = test_market_with_seats(&[short_of_margin, well_funded]);
+        for (trader, position, margin) in [(short_of_margin, 100, 10), (well_funded, -100, 1_000)] {
+            let claimed_seat: &mut ClaimedSeat = seat_mut(&mut market, &trader);
+            claimed_seat.set_position_size(position);
+            claimed_seat.quote_withdrawable_balance = QuoteAtoms::new(margin);
+        }
+        market.fixed.set_total_long_base_atoms(100);
+        market.fixed.set_total_short_base_atoms(100);
+
+        // Each seat owes 100, but the first can only pay 10.
+        market.fixed.socialize_loss(200);
+        let short_of_margin_index: DataIndex = market.get_trader_index(&short_of_margin);
+        assert_eq!(
+            market
+                .settle_socialized_loss_for_trader(short_of_margin_index)
+                .unwrap(),
+            (10, 90)
+        );
+        // The seat is not charged again for what it could not pay.
+        assert_eq!(
+            market
+                .settle_socialized_loss_for_trader(short_of_margin_index)
+                .unwrap(),
+            (0, 0)
+        );
+
+        // The rest lands on the other open interest, so the whole deficit is
+        // collected.
+        let well_funded_index: DataIndex = market.get_trader_index(&well_funded);
+        assert_eq!(
+            market
+                .settle_socialized_loss_for_trader(well_funded_index)
+                .unwrap(),
+            (190, 0)
+        );
+        assert_eq!(market.fixed.get_pnl_pool_balance(), 200);
+    }
+
+    /// Write `market` back in the version 0 layout, as deployed markets hold
+    /// it. Only valid while the fields added since are still zero.
+    fn to_legacy_market_data(market: &MarketValue) -> Vec<u8> {
+        let to_legacy_index = |index: DataIndex| -> DataIndex {
+            if index == NIL {
+                return NIL;
+            }
+            index / MARKET_BLOCK_SIZE as DataIndex * LEGACY_MARKET_BLOCK_SIZE as DataIndex
+        };
+        let write_index = |data: &mut [u8], offset: usize, index: DataIndex| {
+            data[offset..offset + 4].copy_from_slice(&to_legacy_index(index).to_le_bytes());
+        };
+
+        let num_blocks: usize = market.dynamic.len() / MARKET_BLOCK_SIZE;
+        let mut data: Vec<u8> =
+            vec![0; LEGACY_MARKET_FIXED_SIZE + num_blocks * LEGACY_MARKET_BLOCK_SIZE];
+        data[..LEGACY_MARKET_FIXED_SIZE]
+            .copy_from_slice(&bytemuck::bytes_of(&market.fixed)[..LEGACY_MARKET_FIXED_SIZE]);
+        data[offset_of!(MarketFixed, version)] = 0;
+        data[offset_of!(MarketFixed, num_bytes_allocated)..][..4]
+            .copy_from_slice(&((num_blocks * LEGACY_MARKET_BLOCK_SIZE) as u32).to_le_bytes());
+        for (offset, index) in [
+            (
+                offset_of!(MarketFixed, bids_root_index),
+                market.fixed.bids_root_index,
+            ),
+            (
+                offset_of!(MarketFixed, bids_best_index),
+                market.fixed.bids_best_index,
+            ),
+            (
+                offset_of!(MarketFixed, asks_root_index),
+                market.fixed.asks_root_index,
+            ),
+            (
+                offset_of!(MarketFixed, asks_best_index),
+                market.fixed.asks_best_index,
+            ),
+            (
+                offset_of!(MarketFixed, claimed_seats_root_index),
+                market.fixed.claimed_seats_root_index,
+            ),
+            (
+                offset_of!(MarketFixed, free_list_head_index),
+                market.fixed.free_list_head_index,
+            ),
+        ] {
+            write_index(&mut data, offset, index);
+        }
+
+        let mut free_blocks: Vec<DataIndex> = Vec::new();
+        let mut free_index: DataIndex = market.fixed.free_list_head_index;
+        while free_index != NIL {
+            free_blocks.push(free_index);
+            free_index = get_helper::<FreeListNode<MarketUnusedFreeListPadding>>(
+                &market.dynamic,
+                free_index,
+            )
+            .get_next_index();
+        }
+
+        for block in 0..num_blocks {
+            let index: DataIndex = (block * MARKET_BLOCK_SIZE) as DataIndex;
+            let old_offset: usize = LEGACY_MARKET_FIXED_SIZE + block * LEGACY_MARKET_BLOCK_SIZE;
+            let mut new_block: Vec<u8> =
+                market.dynamic[index as usize..][..MARKET_BLOCK_SIZE].to_vec();
+            assert!(new_block[LEGACY_MARKET_BLOCK_SIZE..]
+                .iter()
+                .all(|b| *b == 0));
+            if free_blocks.contains(&index) {
+                data[old_offset..][..LEGACY_MARKET_BLOCK_SIZE]
+                    .copy_from_slice(&new_block[..LEGACY_MARKET_BLOCK_SIZE]);
+                let next_index: DataIndex =
+                    get_helper::<FreeListNode<MarketUnusedFreeListPadding>>(&market.dynamic, index)
+                        .get_next_index();
+                write_index(&mut data, old_offset, next_index);
+                continue;
+            }
+            let node: &mut RBNode<RestingOrder> =
+                get_mut_helper::<RBNode<RestingOrder>>(&mut new_block, 0);
+            let (left, right) = (node.get_left_index(), node.get_right_index());
+            if node.get_payload_type() == MarketDataTreeNodeType::RestingOrder as u8 {
+                let order: &mut RestingOrder = node.get_mut_value();
+                order.set_trader_index(to_legacy_index(order.get_trader_index()));
+            }
+            let parent: DataIndex = DataIndex::from_le_bytes(new_block[8..12].try_into().unwrap());
+            data[old_offset..][..LEGACY_MARKET_BLOCK_SIZE]
+                .copy_from_slice(&new_block[..LEGACY_MARKET_BLOCK_SIZE]);
+            for (index_offset, node_index) in [(0, left), (4, right), (8, parent)] {
+                write_index(&mut data, old_offset + index_offset, node_index);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_migrate_legacy_market() {
+        let maker: Pubkey = Pubkey::new_unique();
+        let taker: Pubkey = Pubkey::new_unique();
+        let mut market: MarketValue = test_market_with_seats(&[maker, taker]);
+        market.dynamic.resize(MARKET_BLOCK_SIZE * 4, 0);
+        market.market_expand_n(2).unwrap();
+        let maker_index: DataIndex = market.get_trader_index(&maker);
+        market.deposit(maker_index, 1_000_000, false).unwrap();
+        let order_index: DataIndex = market
+            .place_order(AddOrderToMarketArgs {
+                market: Pubkey::new_unique(),
+                trader_index: maker_index,
+                num_base_atoms: BaseAtoms::new(10),
+                price: QuoteAtomsPerBaseAtom::try_from_mantissa_and_exponent(1, 0).unwrap(),
+                is_bid: true,
+                last_valid_slot: NO_EXPIRATION_LAST_VALID_SLOT,
+                order_type: OrderType::Limit,
+                global_trade_accounts_opts: &[None, None],
+                current_slot: Some(0),
+            })
+            .unwrap()
+            .order_index;
+
+        let legacy_data: Vec<u8> = to_legacy_market_data(&market);
+        let mut data: Vec<u8> = legacy_data.clone();
+        data.resize(get_migrated_market_size(&legacy_data).unwrap(), 0);
+        migrate_legacy_market(&mut data).unwrap();
+
+        let (fixed_data, dynamic) = data.split_at(MARKET_FIXED_SIZE);
+        let mut migrated: MarketValue = MarketValue {
+            fixed: *get_helper::<MarketFixed>(fixed_data, 0_u32),
+            dynamic: dynamic.to_vec(),
+        };
+        migrated.fixed.verify_discriminant().unwrap();
+        assert_eq!(migrated.dynamic, market.dynamic);
+        assert_eq!(migrated.fixed.bids_best_index, order_index);
+        assert_eq!(migrated.get_trader_index(&maker), maker_index);
+        assert_eq!(
+            migrated.get_order_by_index(order_index).get_trader_index(),
+            maker_index
+        );
+        assert_eq!(
+            migrated.fixed.get_funding_period_secs(),
+            DEFAULT_FUNDING_PERIOD_SECS
+        );
+
+        // The free list carried over, so the last free block takes a seat.
+        migrated.claim_seat(&Pubkey::new_unique()).unwrap();
+        assert!(!migrated.has_free_block());
+
+        // A migrated market is not migrated again.
+        assert!(get_migrated_market_size(&data).is_err());
+    }
+
+    #[test]
+    fn test_insurance_mint_overflow_is_an_error() {
+        let mut market_fixed: MarketFixed = MarketFixed::zeroed();
+        market_fixed.mint_insurance_shares(u64::MAX).unwrap();
+        market_fixed.draw_from_insurance_fund(u64::MAX - 1);
+        assert!(market_fixed.mint_insurance_shares(2).is_err());
+    }
+}
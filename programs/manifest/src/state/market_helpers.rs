@@ -402,7 +402,7 @@ impl<'a, 'b, 'info> AddSingleOrderCtx<'a, 'b, 'info> {
         )?;
 
         // Update perps position tracking for maker and taker
-        update_perps_position(
+        let maker_realized_pnl: i64 = update_perps_position(
             fixed,
             dynamic,
             other_trader_index,
@@ -410,7 +410,7 @@ impl<'a, 'b, 'info> AddSingleOrderCtx<'a, 'b, 'info> {
             quote_atoms_traded.as_u64(),
             !is_bid,
         )?;
-        update_perps_position(
+        let taker_realized_pnl: i64 = update_perps_position(
             fixed,
             dynamic,
             trader_index,
@@ -428,6 +428,8 @@ impl<'a, 'b, 'info> AddSingleOrderCtx<'a, 'b, 'info> {
             price: matched_price,
             maker_sequence_number,
             taker_sequence_number: fixed.order_sequence_number,
+            maker_realized_pnl,
+            taker_realized_pnl,
             taker_is_buy: PodBool::from(is_bid),
             base_mint: Pubkey::default(),
             quote_mint: *fixed.get_quote_mint(),
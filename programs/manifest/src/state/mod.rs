@@ -1,15 +1,33 @@
 pub mod claimed_seat;
 pub mod constants;
+pub mod cross_margin;
 pub mod dynamic_account;
+pub mod funding_history;
 pub mod global;
+pub mod insurance_staker;
 pub mod market;
+pub mod oracle;
 pub mod resting_order;
+pub mod risk_tier;
+pub mod seat_extension;
+pub mod session_scope;
 pub mod session_token;
+pub mod trade_delegate;
 pub mod utils;
+pub mod withdraw_allowlist;
 
 pub use constants::*;
+pub use cross_margin::*;
 pub use dynamic_account::*;
+pub use funding_history::*;
 pub use global::*;
+pub use insurance_staker::*;
 pub use market::*;
+pub use oracle::*;
 pub use resting_order::*;
+pub use risk_tier::*;
+pub use seat_extension::*;
+pub use session_scope::*;
 pub use session_token::*;
+pub use trade_delegate::*;
+pub use withdraw_allowlist::*;
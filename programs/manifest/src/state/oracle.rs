@@ -0,0 +1,240 @@
+//! Oracle sources a perps market can read its index price from.
+//!
+//! A market always has a primary oracle account and can add up to
+//! `MAX_ORACLE_SOURCES - 1` extra ones, each with its own format. CrankFunding
+//! reads every source, drops the ones that are stale or too uncertain, and
+//! caches the median of the rest.
+use std::mem::size_of;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{program::ManifestError, require};
+
+use super::{MAX_ORACLE_SOURCES, ORACLE_SOURCE_SIZE};
+
+#[derive(
+    Debug,
+    BorshDeserialize,
+    BorshSerialize,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    ShankType,
+    IntoPrimitive,
+    TryFromPrimitive,
+)]
+#[repr(u8)]
+pub enum OracleType {
+    // Pyth V2 push price account, or a PriceUpdateV3 pull account carrying
+    // the market's feed id.
+    Pyth = 0,
+
+    // Switchboard On-Demand pull feed (PullFeedAccountData).
+    SwitchboardOnDemand = 1,
+}
+// Not derived: num_enum would treat a #[default] variant as the fallback for
+// unknown values.
+#[allow(clippy::derivable_impls)]
+impl Default for OracleType {
+    fn default() -> Self {
+        OracleType::Pyth
+    }
+}
+
+/// An extra oracle account the market prices from, next to its primary one.
+#[repr(C)]
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Zeroable,
+    Pod,
+    ShankType,
+    BorshDeserialize,
+    BorshSerialize,
+)]
+pub struct OracleSource {
+    /// Oracle account. Default marks an unused source.
+    pub feed: Pubkey,
+    /// Format of the oracle account, an `OracleType` stored as u64
+    pub oracle_type: u64,
+}
+const_assert_eq!(size_of::<OracleSource>(), ORACLE_SOURCE_SIZE);
+
+impl OracleSource {
+    pub fn new(feed: Pubkey, oracle_type: OracleType) -> Self {
+        OracleSource {
+            feed,
+            oracle_type: u8::from(oracle_type) as u64,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.feed != Pubkey::default()
+    }
+
+    pub fn get_oracle_type(&self) -> OracleType {
+        OracleType::try_from(self.oracle_type as u8).unwrap_or_default()
+    }
+}
+
+/// Price read from an oracle account. `price` and `conf` share the exponent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub expo: i32,
+    pub conf: u64,
+    /// Unix timestamp the oracle published this price at
+    pub publish_time: i64,
+}
+
+/// Combine prices from several oracle sources. All prices are moved to the
+/// smallest exponent among them. An odd count takes the middle price with its
+/// own confidence. An even count averages the two middle prices and widens the
+/// larger of their confidences by half their spread, so the interval covers
+/// both. The publish time is the oldest among the prices the median is taken
+/// from, so a fresh outlier cannot make a stale median look fresh. Returns
+/// `None` for no prices or a result that does not fit.
+pub fn median_oracle_price(prices: &[OraclePrice]) -> Option<OraclePrice> {
+    let expo: i32 = prices.iter().map(|p| p.expo).min()?;
+
+    let mut rescaled: Vec<(i128, i128, i64)> = prices
+        .iter()
+        .map(|p| {
+            let scale: i128 = 10i128.checked_pow((p.expo - expo) as u32)?;
+            Some((
+                (p.price as i128).checked_mul(scale)?,
+                (p.conf as i128).checked_mul(scale)?,
+                p.publish_time,
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    rescaled.sort_unstable_by_key(|(price, _, _)| *price);
+
+    let mid: usize = rescaled.len() / 2;
+    let (price, conf, publish_time): (i128, i128, i64) = if rescaled.len() % 2 == 1 {
+        rescaled[mid]
+    } else {
+        let (low_price, low_conf, low_publish_time) = rescaled[mid - 1];
+        let (high_price, high_conf, high_publish_time) = rescaled[mid];
+        (
+            (low_price + high_price) / 2,
+            low_conf.max(high_conf) + (high_price - low_price) / 2,
+            low_publish_time.min(high_publish_time),
+        )
+    };
+
+    Some(OraclePrice {
+        price: i64::try_from(price).ok()?,
+        expo,
+        conf: u64::try_from(conf).ok()?,
+        publish_time,
+    })
+}
+
+/// Check extra oracle sources against the market's primary oracle account.
+/// Every source needs an account and a known type, and no account repeats.
+pub fn validate_oracle_sources(primary_feed: &Pubkey, sources: &[OracleSource]) -> ProgramResult {
+    require!(
+        sources.len() < MAX_ORACLE_SOURCES,
+        ManifestError::InvalidPerpsOperation,
+        "At most {} extra oracle sources, got {}",
+        MAX_ORACLE_SOURCES - 1,
+        sources.len(),
+    )?;
+    for (index, source) in sources.iter().enumerate() {
+        require!(
+            source.is_active(),
+            ManifestError::InvalidPerpsOperation,
+            "Oracle source {} has no account",
+            index + 1,
+        )?;
+        require!(
+            u8::try_from(source.oracle_type)
+                .is_ok_and(|oracle_type| OracleType::try_from(oracle_type).is_ok()),
+            ManifestError::InvalidPerpsOperation,
+            "Oracle source {} has unknown type {}",
+            index + 1,
+            source.oracle_type,
+        )?;
+        require!(
+            source.feed != *primary_feed
+                && sources[..index].iter().all(|prev| prev.feed != source.feed),
+            ManifestError::InvalidPerpsOperation,
+            "Oracle source {} repeats another oracle account",
+            index + 1,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn price(price: i64, expo: i32, conf: u64, publish_time: i64) -> OraclePrice {
+        OraclePrice {
+            price,
+            expo,
+            conf,
+            publish_time,
+        }
+    }
+
+    #[test]
+    fn test_median_oracle_price() {
+        assert_eq!(median_oracle_price(&[]), None);
+        assert_eq!(
+            median_oracle_price(&[price(10_0000_0000, -8, 100, 5)]),
+            Some(price(10_0000_0000, -8, 100, 5))
+        );
+        // 10.0 at -8, 10.2 at -9 and 50.0 at -8: the outlier is ignored, its
+        // later publish time included.
+        assert_eq!(
+            median_oracle_price(&[
+                price(10_0000_0000, -8, 100, 5),
+                price(10_200_000_000, -9, 7, 9),
+                price(50_0000_0000, -8, 100, 12),
+            ]),
+            Some(price(10_200_000_000, -9, 7, 9))
+        );
+        // Two sources average, and the interval reaches both prices. The
+        // older of the two publish times is kept.
+        assert_eq!(
+            median_oracle_price(&[price(10_000, -3, 10, 1), price(10_400, -3, 50, 2)]),
+            Some(price(10_200, -3, 250, 1))
+        );
+    }
+
+    #[test]
+    fn test_validate_oracle_sources() {
+        let primary: Pubkey = Pubkey::new_unique();
+        let extra: Pubkey = Pubkey::new_unique();
+        let switchboard: OracleSource = OracleSource::new(extra, OracleType::SwitchboardOnDemand);
+        assert!(validate_oracle_sources(&primary, &[]).is_ok());
+        assert!(validate_oracle_sources(&primary, &[switchboard]).is_ok());
+        assert!(validate_oracle_sources(&primary, &[switchboard, switchboard]).is_err());
+        assert!(
+            validate_oracle_sources(&primary, &[OracleSource::new(primary, OracleType::Pyth)])
+                .is_err()
+        );
+        assert!(validate_oracle_sources(&primary, &[OracleSource::default()]).is_err());
+        assert!(validate_oracle_sources(
+            &primary,
+            &[OracleSource {
+                feed: extra,
+                oracle_type: 7,
+            }]
+        )
+        .is_err());
+    }
+}
@@ -91,7 +91,8 @@ pub struct RestingOrder {
     order_type: OrderType,
     // Spread for reverse orders. Defaults to zero.
     reverse_spread: u16,
-    _padding: [u8; 20],
+    _padding: [u8; 28],
+    _padding2: [u64; 2],
 }
 
 // 16 +  // price
@@ -102,8 +103,9 @@ pub struct RestingOrder {
 //  1 +  // is_bid
 //  1 +  // order_type
 //  2 +  // spread
-// 20    // padding 2
-// = 64
+// 28 +  // padding
+// 16    // padding 2
+// = 88
 const_assert_eq!(size_of::<RestingOrder>(), RESTING_ORDER_SIZE);
 const_assert_eq!(size_of::<RestingOrder>() % 8, 0);
 
@@ -131,6 +133,7 @@ impl RestingOrder {
             order_type,
             reverse_spread: 0,
             _padding: Default::default(),
+            _padding2: Default::default(),
         })
     }
 
@@ -138,6 +141,10 @@ impl RestingOrder {
         self.trader_index
     }
 
+    pub(crate) fn set_trader_index(&mut self, trader_index: DataIndex) {
+        self.trader_index = trader_index;
+    }
+
     pub fn get_num_base_atoms(&self) -> BaseAtoms {
         self.num_base_atoms
     }
@@ -0,0 +1,188 @@
+//! Notional-based margin tiers for a perps market.
+//!
+//! The market's own initial and maintenance margin apply to positions below
+//! the first tier. Each active tier raises both requirements for positions
+//! whose notional at the mark price is at or above its threshold, so larger
+//! positions get less leverage.
+use std::mem::size_of;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use shank::ShankType;
+use solana_program::entrypoint::ProgramResult;
+use static_assertions::const_assert_eq;
+
+use crate::{program::ManifestError, require};
+
+use super::{MAX_RISK_TIERS, RISK_TIER_SIZE};
+
+#[repr(C)]
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Zeroable,
+    Pod,
+    ShankType,
+    BorshDeserialize,
+    BorshSerialize,
+)]
+pub struct RiskTier {
+    /// Position notional in quote atoms from which this tier applies. Zero
+    /// marks an unused tier.
+    pub notional_threshold_atoms: u64,
+    pub initial_margin_bps: u64,
+    pub maintenance_margin_bps: u64,
+}
+const_assert_eq!(size_of::<RiskTier>(), RISK_TIER_SIZE);
+
+impl RiskTier {
+    pub fn new(
+        notional_threshold_atoms: u64,
+        initial_margin_bps: u64,
+        maintenance_margin_bps: u64,
+    ) -> Self {
+        RiskTier {
+            notional_threshold_atoms,
+            initial_margin_bps,
+            maintenance_margin_bps,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.notional_threshold_atoms > 0
+    }
+}
+
+/// Margin requirement `(initial_margin_bps, maintenance_margin_bps)` for a
+/// position of `notional_atoms`. Falls back to the base requirement below the
+/// first active tier.
+pub fn margin_bps_for_notional(
+    base_initial_margin_bps: u64,
+    base_maintenance_margin_bps: u64,
+    risk_tiers: &[RiskTier],
+    notional_atoms: u64,
+) -> (u64, u64) {
+    risk_tiers
+        .iter()
+        .take_while(|tier| tier.is_active())
+        .filter(|tier| notional_atoms >= tier.notional_threshold_atoms)
+        .last()
+        .map_or(
+            (base_initial_margin_bps, base_maintenance_margin_bps),
+            |tier| (tier.initial_margin_bps, tier.maintenance_margin_bps),
+        )
+}
+
+/// Check a tier table against the market's base margin. Active tiers come
+/// first, in strictly increasing notional order, and never lower either
+/// requirement below the tier before them.
+pub fn validate_risk_tiers(
+    base_initial_margin_bps: u64,
+    base_maintenance_margin_bps: u64,
+    risk_tiers: &[RiskTier],
+) -> ProgramResult {
+    require!(
+        risk_tiers.len() <= MAX_RISK_TIERS,
+        ManifestError::InvalidPerpsOperation,
+        "At most {} risk tiers, got {}",
+        MAX_RISK_TIERS,
+        risk_tiers.len(),
+    )?;
+
+    let mut previous: RiskTier =
+        RiskTier::new(0, base_initial_margin_bps, base_maintenance_margin_bps);
+    let mut seen_unused: bool = false;
+    for tier in risk_tiers {
+        if !tier.is_active() {
+            seen_unused = true;
+            continue;
+        }
+        require!(
+            !seen_unused,
+            ManifestError::InvalidPerpsOperation,
+            "Active risk tiers must come before unused ones",
+        )?;
+        require!(
+            tier.notional_threshold_atoms > previous.notional_threshold_atoms,
+            ManifestError::InvalidPerpsOperation,
+            "Risk tier thresholds must increase: {} after {}",
+            tier.notional_threshold_atoms,
+            previous.notional_threshold_atoms,
+        )?;
+        require!(
+            tier.maintenance_margin_bps >= previous.maintenance_margin_bps
+                && tier.initial_margin_bps >= previous.initial_margin_bps,
+            ManifestError::InvalidPerpsOperation,
+            "Risk tier margins must not decrease with notional",
+        )?;
+        require!(
+            tier.initial_margin_bps >= tier.maintenance_margin_bps
+                && tier.initial_margin_bps <= 50000,
+            ManifestError::InvalidPerpsOperation,
+            "Risk tier initial margin {} must be in [{}, 50000]",
+            tier.initial_margin_bps,
+            tier.maintenance_margin_bps,
+        )?;
+        previous = *tier;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_margin_bps_for_notional() {
+        let tiers: [RiskTier; MAX_RISK_TIERS] = [
+            RiskTier::new(1_000, 2_000, 1_000),
+            RiskTier::new(10_000, 5_000, 2_500),
+            RiskTier::default(),
+            RiskTier::default(),
+        ];
+        assert!(validate_risk_tiers(1_000, 500, &tiers).is_ok());
+        assert_eq!(
+            margin_bps_for_notional(1_000, 500, &tiers, 999),
+            (1_000, 500)
+        );
+        assert_eq!(
+            margin_bps_for_notional(1_000, 500, &tiers, 1_000),
+            (2_000, 1_000)
+        );
+        assert_eq!(
+            margin_bps_for_notional(1_000, 500, &tiers, 50_000),
+            (5_000, 2_500)
+        );
+        assert_eq!(
+            margin_bps_for_notional(1_000, 500, &[RiskTier::default(); MAX_RISK_TIERS], 50_000),
+            (1_000, 500)
+        );
+    }
+
+    #[test]
+    fn test_validate_risk_tiers_rejects_bad_tables() {
+        // Margin lower than the base requirement.
+        assert!(validate_risk_tiers(1_000, 500, &[RiskTier::new(1_000, 800, 400)]).is_err());
+        // Thresholds out of order.
+        assert!(validate_risk_tiers(
+            1_000,
+            500,
+            &[
+                RiskTier::new(10_000, 2_000, 1_000),
+                RiskTier::new(1_000, 5_000, 2_500)
+            ]
+        )
+        .is_err());
+        // Gap before an active tier.
+        assert!(validate_risk_tiers(
+            1_000,
+            500,
+            &[RiskTier::default(), RiskTier::new(1_000, 2_000, 1_000)]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Lifetime statistics of a seat.
+//!
+//! Market blocks are shared by seats and resting orders, so every byte a seat
+//! grows by is paid for by every order on the book too. Figures that only
+//! inform the trader are kept here instead, in a PDA per seat, and move only
+//! when the account is passed for the seat.
+//!
+//! Fills against a resting order happen without the maker's extension. They
+//! book their PnL in the seat's `unsettled_pnl`, and the extension picks it up
+//! the next time it is synced. Every other change to `unsettled_pnl` moves the
+//! seat's epoch on, so a sync across such a change only resets the snapshot.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{require, validation::ManifestAccount};
+
+use super::{claimed_seat::ClaimedSeat, SEAT_EXTENSION_DISCRIMINANT, SEAT_EXTENSION_SIZE};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct SeatExtension {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Market of the seat.
+    market: Pubkey,
+
+    /// Trader the seat belongs to.
+    trader: Pubkey,
+
+    /// Sub-account of the seat.
+    sub_account_id: u8,
+    _padding: [u8; 3],
+
+    /// Seat epoch when `last_unsettled_pnl` was read.
+    last_unsettled_pnl_epoch: u32,
+
+    /// Lifetime PnL realized by reducing or closing the position, in quote
+    /// atoms. Informational only, it does not move margin.
+    realized_pnl: i64,
+
+    /// Lifetime funding settled against the seat, in quote atoms. Negative
+    /// when the trader received more funding than it paid.
+    cumulative_funding_paid: i64,
+
+    /// Lifetime trading fees paid, in quote atoms.
+    fees_paid: u64,
+
+    /// Seat's `unsettled_pnl` at the last sync.
+    last_unsettled_pnl: i64,
+
+    _padding2: [u64; 3],
+}
+const_assert_eq!(
+    size_of::<SeatExtension>(),
+    8 +   // discriminant
+    32 +  // market
+    32 +  // trader
+    1 +   // sub_account_id
+    3 +   // padding
+    4 +   // last_unsettled_pnl_epoch
+    8 +   // realized_pnl
+    8 +   // cumulative_funding_paid
+    8 +   // fees_paid
+    8 +   // last_unsettled_pnl
+    24 // padding2
+);
+const_assert_eq!(size_of::<SeatExtension>(), SEAT_EXTENSION_SIZE);
+const_assert_eq!(size_of::<SeatExtension>() % 8, 0);
+impl Get for SeatExtension {}
+
+impl SeatExtension {
+    pub fn new(market: &Pubkey, trader: &Pubkey, sub_account_id: u8) -> Self {
+        SeatExtension {
+            discriminant: SEAT_EXTENSION_DISCRIMINANT,
+            market: *market,
+            trader: *trader,
+            sub_account_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_market(&self) -> &Pubkey {
+        &self.market
+    }
+    pub fn get_trader(&self) -> &Pubkey {
+        &self.trader
+    }
+    pub fn get_sub_account_id(&self) -> u8 {
+        self.sub_account_id
+    }
+
+    pub fn get_realized_pnl(&self) -> i64 {
+        self.realized_pnl
+    }
+
+    /// Accumulate PnL realized by a liquidation. Fills are picked up by
+    /// `sync` instead.
+    pub fn add_realized_pnl(&mut self, pnl: i64) {
+        self.realized_pnl = self.realized_pnl.saturating_add(pnl);
+    }
+
+    pub fn get_cumulative_funding_paid(&self) -> i64 {
+        self.cumulative_funding_paid
+    }
+
+    /// Accumulate settled funding. Positive amounts were paid, negative
+    /// amounts received.
+    pub fn add_funding_paid(&mut self, funding: i64) {
+        self.cumulative_funding_paid = self.cumulative_funding_paid.saturating_add(funding);
+    }
+
+    pub fn get_fees_paid(&self) -> u64 {
+        self.fees_paid
+    }
+
+    /// Accumulate trading fees charged to the seat.
+    pub fn add_fees_paid(&mut self, fee: u64) {
+        self.fees_paid = self.fees_paid.saturating_add(fee);
+    }
+
+    /// Add the PnL fills booked on `seat` since the last sync to the realized
+    /// PnL and take a new snapshot. PnL booked before a change of epoch is
+    /// lost, so a sync must come before anything that moves the epoch on.
+    pub fn sync(&mut self, seat: &ClaimedSeat) {
+        if seat.get_unsettled_pnl_epoch() == self.last_unsettled_pnl_epoch {
+            self.realized_pnl = self.realized_pnl.saturating_add(
+                seat.get_unsettled_pnl()
+                    .wrapping_sub(self.last_unsettled_pnl),
+            );
+        }
+        self.rebase(seat);
+    }
+
+    /// Take a snapshot of `seat` without counting what changed since the last
+    /// one, for a new extension or a seat claimed again.
+    pub fn rebase(&mut self, seat: &ClaimedSeat) {
+        self.last_unsettled_pnl = seat.get_unsettled_pnl();
+        self.last_unsettled_pnl_epoch = seat.get_unsettled_pnl_epoch();
+    }
+}
+
+impl ManifestAccount for SeatExtension {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == SEAT_EXTENSION_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid seat extension discriminant actual: {} expected: {}",
+            self.discriminant,
+            SEAT_EXTENSION_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_sync_counts_fills_within_an_epoch() {
+    let mut seat: ClaimedSeat = ClaimedSeat::new_empty(Pubkey::new_unique());
+    let mut extension: SeatExtension = SeatExtension::new(&Pubkey::new_unique(), &seat.trader, 0);
+    extension.rebase(&seat);
+
+    seat.book_realized_pnl(100);
+    seat.book_realized_pnl(-30);
+    extension.sync(&seat);
+    assert_eq!(extension.get_realized_pnl(), 70);
+
+    // Settling moves the epoch on, so the PnL it moved out is not counted.
+    seat.set_unsettled_pnl(0);
+    seat.book_realized_pnl(5);
+    extension.sync(&seat);
+    assert_eq!(extension.get_realized_pnl(), 70);
+    seat.book_realized_pnl(5);
+    extension.sync(&seat);
+    assert_eq!(extension.get_realized_pnl(), 75);
+}
@@ -0,0 +1,179 @@
+//! Limits on what a session key may do.
+//!
+//! A session token on its own lets its signer trade any amount on any market
+//! until it expires. An authority can narrow that with a scope record for the
+//! session signer: which markets and instructions it may use, how large an
+//! order it may place and how large a position it may hold. Once a scope
+//! exists every use of the session is checked against it. Sessions without a
+//! scope record are unrestricted.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{program::ManifestInstruction, require, validation::ManifestAccount};
+
+use super::{MAX_SESSION_SCOPE_MARKETS, SESSION_SCOPE_DISCRIMINANT, SESSION_SCOPE_SIZE};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct SessionScope {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Wallet the session acts for.
+    authority: Pubkey,
+
+    /// Ephemeral key the session token names as signer.
+    session_signer: Pubkey,
+
+    /// Largest quote notional of a single order. Zero for no limit.
+    max_order_notional_atoms: u64,
+
+    /// Largest absolute position, in base atoms, an order may leave or,
+    /// for resting orders, could leave once fully filled. Zero for no limit.
+    max_position_atoms: u64,
+
+    /// Bit `1 << instruction` is set for each ManifestInstruction the session
+    /// may sign.
+    allowed_instructions: u64,
+
+    /// Number of entries of `markets` in use. Zero allows every market.
+    num_markets: u64,
+
+    /// Markets the session may act on. Only the first `num_markets` are set.
+    markets: [Pubkey; MAX_SESSION_SCOPE_MARKETS],
+
+    _padding: [u64; 4],
+}
+const_assert_eq!(
+    size_of::<SessionScope>(),
+    8 +   // discriminant
+    32 +  // authority
+    32 +  // session_signer
+    8 +   // max_order_notional_atoms
+    8 +   // max_position_atoms
+    8 +   // allowed_instructions
+    8 +   // num_markets
+    32 * MAX_SESSION_SCOPE_MARKETS + // markets
+    32 // padding
+);
+const_assert_eq!(size_of::<SessionScope>(), SESSION_SCOPE_SIZE);
+const_assert_eq!(size_of::<SessionScope>() % 8, 0);
+impl Get for SessionScope {}
+
+impl SessionScope {
+    pub fn new(
+        authority: &Pubkey,
+        session_signer: &Pubkey,
+        markets: &[Pubkey],
+        max_order_notional_atoms: u64,
+        max_position_atoms: u64,
+        allowed_instructions: u64,
+    ) -> Result<Self, ProgramError> {
+        require!(
+            markets.len() <= MAX_SESSION_SCOPE_MARKETS,
+            ProgramError::InvalidArgument,
+            "A session scope lists at most {} markets, got {}",
+            MAX_SESSION_SCOPE_MARKETS,
+            markets.len(),
+        )?;
+        let mut scope: SessionScope = SessionScope {
+            discriminant: SESSION_SCOPE_DISCRIMINANT,
+            authority: *authority,
+            session_signer: *session_signer,
+            max_order_notional_atoms,
+            max_position_atoms,
+            allowed_instructions,
+            num_markets: markets.len() as u64,
+            markets: [Pubkey::default(); MAX_SESSION_SCOPE_MARKETS],
+            _padding: [0; 4],
+        };
+        scope.markets[..markets.len()].copy_from_slice(markets);
+        Ok(scope)
+    }
+
+    /// Bitmask of `allowed_instructions` allowing each of `instructions`.
+    pub fn instructions_mask(instructions: &[ManifestInstruction]) -> u64 {
+        instructions
+            .iter()
+            .fold(0, |mask, instruction| mask | 1 << (*instruction as u8))
+    }
+
+    pub fn get_authority(&self) -> &Pubkey {
+        &self.authority
+    }
+    pub fn get_session_signer(&self) -> &Pubkey {
+        &self.session_signer
+    }
+    pub fn get_max_order_notional_atoms(&self) -> u64 {
+        self.max_order_notional_atoms
+    }
+    pub fn get_max_position_atoms(&self) -> u64 {
+        self.max_position_atoms
+    }
+    pub fn get_markets(&self) -> &[Pubkey] {
+        &self.markets[..self.num_markets as usize]
+    }
+
+    pub fn allows_market(&self, market: &Pubkey) -> bool {
+        self.num_markets == 0 || self.get_markets().contains(market)
+    }
+
+    pub fn allows_instruction(&self, instruction: ManifestInstruction) -> bool {
+        (instruction as u8) < 64 && self.allowed_instructions & (1 << (instruction as u8)) != 0
+    }
+
+    pub fn allows_order_notional(&self, notional_atoms: u64) -> bool {
+        self.max_order_notional_atoms == 0 || notional_atoms <= self.max_order_notional_atoms
+    }
+
+    pub fn allows_position(&self, position_atoms: i128) -> bool {
+        self.max_position_atoms == 0
+            || position_atoms.unsigned_abs() <= self.max_position_atoms as u128
+    }
+}
+
+impl ManifestAccount for SessionScope {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == SESSION_SCOPE_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid session scope discriminant actual: {} expected: {}",
+            self.discriminant,
+            SESSION_SCOPE_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_scope_limits() {
+        let market: Pubkey = Pubkey::new_unique();
+        let scope: SessionScope = SessionScope::new(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &[market],
+            1_000,
+            500,
+            SessionScope::instructions_mask(&[ManifestInstruction::BatchUpdate]),
+        )
+        .unwrap();
+
+        assert!(scope.allows_market(&market));
+        assert!(!scope.allows_market(&Pubkey::new_unique()));
+        assert!(scope.allows_instruction(ManifestInstruction::BatchUpdate));
+        assert!(!scope.allows_instruction(ManifestInstruction::Withdraw));
+        assert!(scope.allows_order_notional(1_000));
+        assert!(!scope.allows_order_notional(1_001));
+        assert!(scope.allows_position(-500));
+        assert!(!scope.allows_position(501));
+    }
+}
@@ -23,7 +23,7 @@ pub const SESSION_TOKEN_SIZE: usize = SESSION_TOKEN_DISCRIMINATOR_SIZE + size_of
 /// - A specific ephemeral signer keypair
 ///
 /// On-chain account data: 8-byte discriminator + 136-byte struct = 144 bytes
-/// PDA seeds: [b"session_token_v2", authority, session_signer]
+/// PDA seeds: [b"session_token_v2", target_program, session_signer, authority]
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone, Zeroable, Pod, ShankType)]
 pub struct SessionToken {
@@ -77,12 +77,16 @@ impl SessionToken {
     }
 
     /// Get the PDA seeds for this session token (v2)
-    pub fn get_seeds(target_program: &Pubkey, authority: &Pubkey, session_signer: &Pubkey) -> Vec<Vec<u8>> {
+    pub fn get_seeds(
+        target_program: &Pubkey,
+        authority: &Pubkey,
+        session_signer: &Pubkey,
+    ) -> Vec<Vec<u8>> {
         vec![
             b"session_token_v2".to_vec(),
             target_program.to_bytes().to_vec(),
-            authority.to_bytes().to_vec(),
             session_signer.to_bytes().to_vec(),
+            authority.to_bytes().to_vec(),
         ]
     }
 
@@ -97,8 +101,8 @@ impl SessionToken {
             &[
                 b"session_token_v2",
                 target_program.as_ref(),
-                authority.as_ref(),
                 session_signer.as_ref(),
+                authority.as_ref(),
             ],
             session_keys_program_id,
         )
@@ -108,6 +112,7 @@ impl SessionToken {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::SESSION_KEYS_PROGRAM_ID;
 
     #[test]
     fn test_session_token_size() {
@@ -149,10 +154,48 @@ mod tests {
         let session_signer = Pubkey::new_unique();
         let session_keys_program_id = Pubkey::new_unique();
 
-        let (pda, _bump) = SessionToken::get_address(&target_program, &authority, &session_signer, &session_keys_program_id);
+        let (pda, _bump) = SessionToken::get_address(
+            &target_program,
+            &authority,
+            &session_signer,
+            &session_keys_program_id,
+        );
 
         // PDA should be deterministic
-        let (pda2, _bump2) = SessionToken::get_address(&target_program, &authority, &session_signer, &session_keys_program_id);
+        let (pda2, _bump2) = SessionToken::get_address(
+            &target_program,
+            &authority,
+            &session_signer,
+            &session_keys_program_id,
+        );
         assert_eq!(pda, pda2);
     }
+
+    #[test]
+    fn test_pda_matches_session_keys_program() {
+        // Address the session-keys program creates a session for Manifest at,
+        // seeded [b"session_token_v2", target_program, session_signer,
+        // authority].
+        let authority = Pubkey::new_from_array([1; 32]);
+        let session_signer = Pubkey::new_from_array([2; 32]);
+        let (pda, _bump) = SessionToken::get_address(
+            &crate::ID,
+            &authority,
+            &session_signer,
+            &SESSION_KEYS_PROGRAM_ID,
+        );
+        assert_eq!(
+            pda,
+            solana_program::pubkey!("C9ktJJnkTm7uoCsfqC9ecia4C3HsQoDbnbfu7ahrj5Nw")
+        );
+
+        // The authority and signer are not interchangeable.
+        let (swapped_pda, _bump) = SessionToken::get_address(
+            &crate::ID,
+            &session_signer,
+            &authority,
+            &SESSION_KEYS_PROGRAM_ID,
+        );
+        assert_ne!(pda, swapped_pda);
+    }
 }
@@ -0,0 +1,105 @@
+//! Trade-only delegate of a seat.
+//!
+//! A trader can name one key per seat that may place and cancel orders and
+//! swap on their behalf. Unlike a session token it does not expire and does
+//! not depend on an external program, which suits long-running market-maker
+//! hot keys. The delegate can never withdraw or release the seat: those
+//! instructions only act for their signer.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{require, validation::ManifestAccount};
+
+use super::{TRADE_DELEGATE_DISCRIMINANT, TRADE_DELEGATE_SIZE};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct TradeDelegate {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Market of the seat.
+    market: Pubkey,
+
+    /// Trader the seat belongs to.
+    trader: Pubkey,
+
+    /// Key allowed to trade for the seat. Default when revoked.
+    delegate: Pubkey,
+
+    /// Sub-account of the seat.
+    sub_account_id: u8,
+    _padding: [u8; 7],
+
+    _padding2: [u64; 2],
+}
+const_assert_eq!(
+    size_of::<TradeDelegate>(),
+    8 +   // discriminant
+    32 +  // market
+    32 +  // trader
+    32 +  // delegate
+    1 +   // sub_account_id
+    7 +   // padding
+    16 // padding2
+);
+const_assert_eq!(size_of::<TradeDelegate>(), TRADE_DELEGATE_SIZE);
+const_assert_eq!(size_of::<TradeDelegate>() % 8, 0);
+impl Get for TradeDelegate {}
+
+impl TradeDelegate {
+    pub fn new(market: &Pubkey, trader: &Pubkey, sub_account_id: u8, delegate: &Pubkey) -> Self {
+        TradeDelegate {
+            discriminant: TRADE_DELEGATE_DISCRIMINANT,
+            market: *market,
+            trader: *trader,
+            delegate: *delegate,
+            sub_account_id,
+            _padding: [0; 7],
+            _padding2: [0; 2],
+        }
+    }
+
+    pub fn get_market(&self) -> &Pubkey {
+        &self.market
+    }
+    pub fn get_trader(&self) -> &Pubkey {
+        &self.trader
+    }
+    pub fn get_delegate(&self) -> &Pubkey {
+        &self.delegate
+    }
+    pub fn get_sub_account_id(&self) -> u8 {
+        self.sub_account_id
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.delegate == Pubkey::default()
+    }
+
+    pub fn set_delegate(&mut self, delegate: &Pubkey) {
+        self.delegate = *delegate;
+    }
+
+    pub fn revoke(&mut self) {
+        self.delegate = Pubkey::default();
+    }
+}
+
+impl ManifestAccount for TradeDelegate {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == TRADE_DELEGATE_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid trade delegate discriminant actual: {} expected: {}",
+            self.discriminant,
+            TRADE_DELEGATE_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
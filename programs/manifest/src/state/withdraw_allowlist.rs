@@ -0,0 +1,93 @@
+//! Wallets a trader's withdrawals may pay besides the trader.
+//!
+//! Withdraw normally pays only a token account the trader owns. Treasury and
+//! custody setups want withdrawals to land in a cold wallet instead, so a
+//! trader can list up to `MAX_WITHDRAW_RECIPIENTS` wallets. A withdrawal the
+//! trader signs may then pay any token account owned by one of them. Session
+//! withdrawals still pay only the trader.
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use hypertree::Get;
+use shank::ShankType;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use static_assertions::const_assert_eq;
+
+use crate::{require, validation::ManifestAccount};
+
+use super::{MAX_WITHDRAW_RECIPIENTS, WITHDRAW_ALLOWLIST_DISCRIMINANT, WITHDRAW_ALLOWLIST_SIZE};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod, ShankType)]
+pub struct WithdrawAllowlist {
+    /// Discriminant for identifying this type of account.
+    pub discriminant: u64,
+
+    /// Trader whose withdrawals the list applies to.
+    trader: Pubkey,
+
+    /// Number of entries of `recipients` in use.
+    num_recipients: u64,
+
+    /// Wallets that may own the recipient token account of a withdrawal.
+    /// Only the first `num_recipients` are set.
+    recipients: [Pubkey; MAX_WITHDRAW_RECIPIENTS],
+
+    _padding: [u64; 4],
+}
+const_assert_eq!(
+    size_of::<WithdrawAllowlist>(),
+    8 +   // discriminant
+    32 +  // trader
+    8 +   // num_recipients
+    32 * MAX_WITHDRAW_RECIPIENTS + // recipients
+    32 // padding
+);
+const_assert_eq!(size_of::<WithdrawAllowlist>(), WITHDRAW_ALLOWLIST_SIZE);
+const_assert_eq!(size_of::<WithdrawAllowlist>() % 8, 0);
+impl Get for WithdrawAllowlist {}
+
+impl WithdrawAllowlist {
+    pub fn new(trader: &Pubkey, recipients: &[Pubkey]) -> Result<Self, ProgramError> {
+        require!(
+            recipients.len() <= MAX_WITHDRAW_RECIPIENTS,
+            ProgramError::InvalidArgument,
+            "A withdraw allowlist holds at most {} recipients, got {}",
+            MAX_WITHDRAW_RECIPIENTS,
+            recipients.len(),
+        )?;
+        let mut allowlist: WithdrawAllowlist = WithdrawAllowlist {
+            discriminant: WITHDRAW_ALLOWLIST_DISCRIMINANT,
+            trader: *trader,
+            num_recipients: recipients.len() as u64,
+            recipients: [Pubkey::default(); MAX_WITHDRAW_RECIPIENTS],
+            _padding: [0; 4],
+        };
+        allowlist.recipients[..recipients.len()].copy_from_slice(recipients);
+        Ok(allowlist)
+    }
+
+    pub fn get_trader(&self) -> &Pubkey {
+        &self.trader
+    }
+    pub fn get_recipients(&self) -> &[Pubkey] {
+        &self.recipients[..self.num_recipients as usize]
+    }
+
+    pub fn allows_recipient(&self, recipient: &Pubkey) -> bool {
+        self.get_recipients().contains(recipient)
+    }
+}
+
+impl ManifestAccount for WithdrawAllowlist {
+    fn verify_discriminant(&self) -> ProgramResult {
+        require!(
+            self.discriminant == WITHDRAW_ALLOWLIST_DISCRIMINANT,
+            ProgramError::InvalidAccountData,
+            "Invalid withdraw allowlist discriminant actual: {} expected: {}",
+            self.discriminant,
+            WITHDRAW_ALLOWLIST_DISCRIMINANT
+        )?;
+        Ok(())
+    }
+}
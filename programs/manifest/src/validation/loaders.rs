@@ -1,6 +1,6 @@
 use std::{cell::Ref, slice::Iter};
 
-use hypertree::{get_helper, trace};
+use hypertree::get_helper;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     program_error::ProgramError,
@@ -9,12 +9,20 @@ use solana_program::{
 };
 
 use crate::{
-    program::ManifestError,
+    program::{ManifestError, ManifestInstruction},
     require,
-    state::{GlobalFixed, MarketFixed},
+    state::{
+        CrossMarginAccount, FundingHistory, GlobalFixed, InsuranceStaker, MarketFixed,
+        SeatExtension, SessionScope, SessionToken, TradeDelegate, WithdrawAllowlist,
+        CROSS_MARGIN_ACCOUNT_SIZE, LEGACY_MARKET_FIXED_SIZE, MARKET_FIXED_DISCRIMINANT,
+        SEAT_EXTENSION_SIZE, SESSION_KEYS_PROGRAM_ID, SESSION_TOKEN_DISCRIMINATOR_SIZE,
+        SESSION_TOKEN_SIZE, TRADE_DELEGATE_SIZE, WITHDRAW_ALLOWLIST_SIZE,
+    },
     validation::{
-        get_global_address, get_market_address, EmptyAccount, MintAccountInfo, Program, Signer,
-        TokenAccountInfo,
+        get_cross_margin_address, get_funding_history_address, get_global_address,
+        get_insurance_staker_address, get_session_scope_address, get_trade_delegate_address,
+        get_withdraw_allowlist_address, validate_session_or_authority, validate_trade_delegate,
+        verify_owned_by_manifest, EmptyAccount, MintAccountInfo, Program, Signer, TokenAccountInfo,
     },
 };
 
@@ -52,8 +60,7 @@ impl<'a, 'info> CreateMarketContext<'a, 'info> {
         // PDA verification is done in the processor after params are parsed
         // (seeds depend on base_mint_index from params)
 
-        let (expected_quote_vault, _) =
-            get_vault_address(market.info.key, quote_mint.info.key);
+        let (expected_quote_vault, _) = get_vault_address(market.info.key, quote_mint.info.key);
         require!(
             expected_quote_vault == *quote_vault.info.key,
             ManifestError::IncorrectAccount,
@@ -186,9 +193,13 @@ impl<'a, 'info> ExpandMarketContext<'a, 'info> {
 pub(crate) struct DepositContext<'a, 'info> {
     pub payer: Signer<'a, 'info>,
     pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub session_token: Option<&'a AccountInfo<'info>>,
+    pub session_scope: Option<&'a AccountInfo<'info>>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
     pub trader_token: TokenAccountInfo<'a, 'info>,
     pub vault: TokenAccountInfo<'a, 'info>,
     pub token_program: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
     pub mint: Option<MintAccountInfo<'a, 'info>>,
 }
 
@@ -209,6 +220,10 @@ impl<'a, 'info> DepositContext<'a, 'info> {
         // Derive quote vault address on-the-fly
         let (expected_vault_address, _) = get_vault_address(market.info.key, &quote_mint);
 
+        let (session_token, session_scope) = load_session_accounts_opt(account_iter)?;
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
         let token_account_info: &AccountInfo<'info> = next_account_info(account_iter)?;
 
         // Only quote (USDC) deposits are allowed — verify the trader token is for quote mint
@@ -223,8 +238,14 @@ impl<'a, 'info> DepositContext<'a, 'info> {
 
         solana_program::msg!("trader token account {:?}", token_account_info.key);
         solana_program::msg!("payer key {:?}", payer.key);
-        let trader_token: TokenAccountInfo =
-            TokenAccountInfo::new_with_owner(token_account_info, &quote_mint, payer.key)?;
+        // A session signer may fund the seat from its own account or, as an
+        // approved SPL delegate, from the authority's. The owner is checked
+        // once the authority is known.
+        let trader_token: TokenAccountInfo = if session_token.is_some() {
+            TokenAccountInfo::new(token_account_info, &quote_mint)?
+        } else {
+            TokenAccountInfo::new_with_owner(token_account_info, &quote_mint, payer.key)?
+        };
 
         let vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
         let vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
@@ -242,22 +263,60 @@ impl<'a, 'info> DepositContext<'a, 'info> {
         Ok(Self {
             payer,
             market,
+            session_token,
+            session_scope,
+            seat_extension,
             trader_token,
             vault,
             token_program,
             mint,
         })
     }
+
+    /// Validates session or authority and returns the trader whose seat is
+    /// credited. Through a session the tokens must come from the session
+    /// signer or the authority.
+    pub fn validate_and_get_trader_authority(
+        &self,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, ProgramError> {
+        let (trader, _) = validate_session_or_authority(
+            self.payer.info,
+            self.session_token,
+            self.session_scope,
+            self.market.info.key,
+            ManifestInstruction::Deposit,
+            &SESSION_KEYS_PROGRAM_ID,
+            program_id,
+        )?;
+        if self.session_token.is_some() {
+            let source_owner: Pubkey = self.trader_token.get_owner();
+            require!(
+                source_owner == *self.payer.key || source_owner == trader,
+                ManifestError::InvalidWithdrawAccounts,
+                "Session deposits must come from a token account owned by {} or {}",
+                self.payer.key,
+                trader,
+            )?;
+        }
+        Ok(trader)
+    }
 }
 
 /// Withdraw account infos
 pub(crate) struct WithdrawContext<'a, 'info> {
     pub payer: Signer<'a, 'info>,
     pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub session_token: Option<&'a AccountInfo<'info>>,
+    pub session_scope: Option<&'a AccountInfo<'info>>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
     pub trader_token: TokenAccountInfo<'a, 'info>,
     pub vault: TokenAccountInfo<'a, 'info>,
     pub token_program: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
     pub mint: Option<MintAccountInfo<'a, 'info>>,
+    pub withdraw_allowlist: Option<ManifestAccountInfo<'a, 'info, WithdrawAllowlist>>,
+    pub cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>>,
 }
 
 impl<'a, 'info> WithdrawContext<'a, 'info> {
@@ -276,6 +335,10 @@ impl<'a, 'info> WithdrawContext<'a, 'info> {
         // Derive quote vault address on-the-fly
         let (expected_vault_address, _) = get_vault_address(market.info.key, &quote_mint);
 
+        let (session_token, session_scope) = load_session_accounts_opt(account_iter)?;
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
         let token_account_info: &AccountInfo<'info> = next_account_info(account_iter)?;
 
         // Only quote (USDC) withdrawals are allowed
@@ -288,14 +351,251 @@ impl<'a, 'info> WithdrawContext<'a, 'info> {
             )?;
         }
 
+        // The recipient's owner is checked against the trader, or the
+        // trader's allowlist, once the trader is known.
         let trader_token: TokenAccountInfo =
-            TokenAccountInfo::new_with_owner(token_account_info, &quote_mint, payer.key)?;
+            TokenAccountInfo::new(token_account_info, &quote_mint)?;
+
+        let vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
+        let vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+            vault_info,
+            &quote_mint,
+            &expected_vault_address,
+            &expected_vault_address,
+        )?;
+
+        let token_program: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let mint: Option<MintAccountInfo> =
+            Some(MintAccountInfo::new(next_account_info(account_iter)?)?);
+        drop(market_fixed);
+
+        let withdraw_allowlist: Option<ManifestAccountInfo<WithdrawAllowlist>> =
+            load_withdraw_allowlist_opt(account_iter)?;
+
+        let cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>> =
+            CrossMarginAccounts::load_opt(account_iter, market.info.key)?;
+
+        Ok(Self {
+            payer,
+            market,
+            session_token,
+            session_scope,
+            seat_extension,
+            trader_token,
+            vault,
+            token_program,
+            mint,
+            withdraw_allowlist,
+            cross_margin_accounts,
+        })
+    }
+
+    /// Validates session or authority and returns the trader whose seat is
+    /// withdrawn from. Withdrawals only pay out to a token account owned by
+    /// the trader, or by a wallet on the trader's allowlist when the trader
+    /// signs directly. A session signer can only pay the trader.
+    pub fn validate_and_get_trader_authority(
+        &self,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, ProgramError> {
+        let (trader, _) = validate_session_or_authority(
+            self.payer.info,
+            self.session_token,
+            self.session_scope,
+            self.market.info.key,
+            ManifestInstruction::Withdraw,
+            &SESSION_KEYS_PROGRAM_ID,
+            program_id,
+        )?;
+        let recipient: Pubkey = self.trader_token.get_owner();
+        if self.session_token.is_some() {
+            require!(
+                recipient == trader,
+                ManifestError::InvalidWithdrawAccounts,
+                "Session withdrawals must go to a token account owned by {}",
+                trader,
+            )?;
+        } else if recipient != trader {
+            let withdraw_allowlist: &ManifestAccountInfo<WithdrawAllowlist> = self
+                .withdraw_allowlist
+                .as_ref()
+                .ok_or(ManifestError::InvalidWithdrawAccounts)?;
+            let (expected_withdraw_allowlist, _) = get_withdraw_allowlist_address(&trader);
+            require!(
+                *withdraw_allowlist.info.key == expected_withdraw_allowlist,
+                ManifestError::InvalidWithdrawAccounts,
+                "Withdraw allowlist does not belong to {}",
+                trader,
+            )?;
+            require!(
+                withdraw_allowlist.get_fixed()?.allows_recipient(&recipient),
+                ManifestError::InvalidWithdrawAccounts,
+                "Withdrawals must go to a token account owned by {} or an allowlisted wallet",
+                trader,
+            )?;
+        }
+        Ok(trader)
+    }
+}
+
+/// MigrateMarket account infos
+pub(crate) struct MigrateMarketContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: &'a AccountInfo<'info>,
+    pub _system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> MigrateMarketContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+
+        // The market is not loaded as a ManifestAccountInfo because its header
+        // is still in the layout being migrated from.
+        let market: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        verify_owned_by_manifest(market.owner)?;
+        {
+            let market_data: Ref<&mut [u8]> = market.try_borrow_data()?;
+            require!(
+                market_data.len() >= LEGACY_MARKET_FIXED_SIZE
+                    && u64::from_le_bytes(market_data[0..8].try_into().unwrap())
+                        == MARKET_FIXED_DISCRIMINANT,
+                ProgramError::InvalidAccountData,
+                "Market account is not a market",
+            )?;
+        }
+
+        let _system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            market,
+            _system_program,
+        })
+    }
+}
+
+/// InsuranceDeposit account infos
+pub(crate) struct InsuranceDepositContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub insurance_staker: &'a AccountInfo<'info>,
+    pub trader_token: TokenAccountInfo<'a, 'info>,
+    pub vault: TokenAccountInfo<'a, 'info>,
+    pub token_program: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
+    pub mint: Option<MintAccountInfo<'a, 'info>>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> InsuranceDepositContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let market_info: &AccountInfo = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+
+        let market_fixed: Ref<MarketFixed> = market.get_fixed()?;
+        let quote_mint: Pubkey = *market_fixed.get_quote_mint();
+        let (expected_vault_address, _) = get_vault_address(market.info.key, &quote_mint);
+
+        // Staker record may not exist yet, it is created on first deposit.
+        let insurance_staker: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let (expected_staker_address, _) = get_insurance_staker_address(market.info.key, payer.key);
+        require!(
+            expected_staker_address == *insurance_staker.key,
+            ManifestError::IncorrectAccount,
+            "Incorrect insurance staker account",
+        )?;
+
+        let trader_token: TokenAccountInfo = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_mint,
+            payer.key,
+        )?;
 
         let vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
         let vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
             vault_info,
             &quote_mint,
+            market.info.key,
             &expected_vault_address,
+        )?;
+
+        let token_program: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let mint: Option<MintAccountInfo> =
+            Some(MintAccountInfo::new(next_account_info(account_iter)?)?);
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        drop(market_fixed);
+        Ok(Self {
+            payer,
+            market,
+            insurance_staker,
+            trader_token,
+            vault,
+            token_program,
+            mint,
+            system_program,
+        })
+    }
+}
+
+/// InsuranceWithdraw account infos
+pub(crate) struct InsuranceWithdrawContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub insurance_staker: ManifestAccountInfo<'a, 'info, InsuranceStaker>,
+    pub trader_token: TokenAccountInfo<'a, 'info>,
+    pub vault: TokenAccountInfo<'a, 'info>,
+    pub token_program: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
+    pub mint: Option<MintAccountInfo<'a, 'info>>,
+}
+
+impl<'a, 'info> InsuranceWithdrawContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &AccountInfo = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+
+        let market_fixed: Ref<MarketFixed> = market.get_fixed()?;
+        let quote_mint: Pubkey = *market_fixed.get_quote_mint();
+        let (expected_vault_address, _) = get_vault_address(market.info.key, &quote_mint);
+
+        let insurance_staker: ManifestAccountInfo<InsuranceStaker> =
+            ManifestAccountInfo::<InsuranceStaker>::new(next_account_info(account_iter)?)?;
+        {
+            let staker_fixed: Ref<InsuranceStaker> = insurance_staker.get_fixed()?;
+            require!(
+                staker_fixed.get_market() == market.info.key
+                    && staker_fixed.get_staker() == payer.key,
+                ManifestError::IncorrectAccount,
+                "Insurance staker account does not belong to this market and signer",
+            )?;
+        }
+
+        let trader_token: TokenAccountInfo = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_mint,
+            payer.key,
+        )?;
+
+        let vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
+        let vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+            vault_info,
+            &quote_mint,
+            market.info.key,
             &expected_vault_address,
         )?;
 
@@ -307,6 +607,7 @@ impl<'a, 'info> WithdrawContext<'a, 'info> {
         Ok(Self {
             payer,
             market,
+            insurance_staker,
             trader_token,
             vault,
             token_program,
@@ -315,6 +616,43 @@ impl<'a, 'info> WithdrawContext<'a, 'info> {
     }
 }
 
+/// InsuranceRequestWithdraw account infos
+pub(crate) struct InsuranceRequestWithdrawContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub insurance_staker: ManifestAccountInfo<'a, 'info, InsuranceStaker>,
+}
+
+impl<'a, 'info> InsuranceRequestWithdrawContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &AccountInfo = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+
+        let insurance_staker: ManifestAccountInfo<InsuranceStaker> =
+            ManifestAccountInfo::<InsuranceStaker>::new(next_account_info(account_iter)?)?;
+        {
+            let staker_fixed: Ref<InsuranceStaker> = insurance_staker.get_fixed()?;
+            require!(
+                staker_fixed.get_market() == market.info.key
+                    && staker_fixed.get_staker() == payer.key,
+                ManifestError::IncorrectAccount,
+                "Insurance staker account does not belong to this market and signer",
+            )?;
+        }
+
+        Ok(Self {
+            payer,
+            market,
+            insurance_staker,
+        })
+    }
+}
+
 /// Swap account infos (perps: only quote vault/token needed)
 pub(crate) struct SwapContext<'a, 'info> {
     pub payer: AccountInfo<'info>,
@@ -323,8 +661,13 @@ pub(crate) struct SwapContext<'a, 'info> {
     pub trader_quote: TokenAccountInfo<'a, 'info>,
     pub quote_vault: TokenAccountInfo<'a, 'info>,
     pub token_program_quote: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
     pub quote_mint: Option<MintAccountInfo<'a, 'info>>,
     pub session_token: Option<&'a AccountInfo<'info>>,
+    pub session_scope: Option<&'a AccountInfo<'info>>,
+    pub trade_delegate: Option<ManifestAccountInfo<'a, 'info, TradeDelegate>>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
+    pub cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>>,
 
     // One for each side. First is base, then is quote.
     pub global_trade_accounts_opts: [Option<GlobalTradeAccounts<'a, 'info>>; 2],
@@ -349,10 +692,9 @@ impl<'a, 'info> SwapContext<'a, 'info> {
                 let market_info: &AccountInfo = next_account_info(account_iter)?;
                 (
                     Signer::new(owner_or_market)?,
-                    ManifestAccountInfo::<MarketFixed>::new(market_info)
-                        .or_else(|_| {
-                            ManifestAccountInfo::<MarketFixed>::new_delegated(market_info)
-                        })?,
+                    ManifestAccountInfo::<MarketFixed>::new(market_info).or_else(|_| {
+                        ManifestAccountInfo::<MarketFixed>::new_delegated(market_info)
+                    })?,
                 )
             }
         };
@@ -360,20 +702,16 @@ impl<'a, 'info> SwapContext<'a, 'info> {
         let _system_program: Program =
             Program::new(next_account_info(account_iter)?, &system_program::id())?;
 
-        // Try to load optional session token (same logic as batch_update)
-        let session_token: Option<&'a AccountInfo<'info>> = {
-            let remaining_accounts = account_iter.as_slice();
-            if !remaining_accounts.is_empty() {
-                let next_account = &remaining_accounts[0];
-                if next_account.data_len() == crate::state::SESSION_TOKEN_SIZE {
-                    Some(next_account_info(account_iter)?)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        };
+        let (session_token, session_scope) = load_session_accounts_opt(account_iter)?;
+
+        let trade_delegate: Option<ManifestAccountInfo<'a, 'info, TradeDelegate>> =
+            load_trade_delegate_opt(account_iter)?;
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
+        // Cross-margin seats pass their portfolio accounts next.
+        let cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>> =
+            CrossMarginAccounts::load_opt(account_iter, market.info.key)?;
 
         let market_fixed: Ref<MarketFixed> = market.get_fixed()?;
         let quote_mint_key: Pubkey = *market_fixed.get_quote_mint();
@@ -382,13 +720,14 @@ impl<'a, 'info> SwapContext<'a, 'info> {
             TokenAccountInfo::new(next_account_info(account_iter)?, &quote_mint_key)?;
 
         let quote_vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
-        let quote_vault: TokenAccountInfo = TokenAccountInfo::new(quote_vault_info, &quote_mint_key)?;
+        let quote_vault: TokenAccountInfo =
+            TokenAccountInfo::new(quote_vault_info, &quote_mint_key)?;
         drop(market_fixed);
 
-        let token_program_quote: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let token_program_quote: TokenProgram =
+            TokenProgram::new(next_account_info(account_iter)?)?;
         let mut quote_mint: Option<MintAccountInfo> = None;
-        let global_trade_accounts_opts: [Option<GlobalTradeAccounts<'a, 'info>>; 2] =
-            [None, None];
+        let global_trade_accounts_opts: [Option<GlobalTradeAccounts<'a, 'info>>; 2] = [None, None];
 
         let current_account_info_or: Result<&AccountInfo<'info>, ProgramError> =
             next_account_info(account_iter);
@@ -412,28 +751,141 @@ impl<'a, 'info> SwapContext<'a, 'info> {
             token_program_quote,
             quote_mint,
             session_token,
+            session_scope,
+            trade_delegate,
+            seat_extension,
+            cross_margin_accounts,
             global_trade_accounts_opts,
         })
     }
 
-    /// Validates session or authority and returns the trader authority.
-    /// This eliminates duplicate validation code across processors.
+    /// Validates session, trade delegate or authority and returns the trader
+    /// authority, with the session's scope if it has one. This eliminates
+    /// duplicate validation code across processors.
     pub fn validate_and_get_trader_authority(
         &self,
         program_id: &Pubkey,
-    ) -> Result<Pubkey, ProgramError> {
-        use crate::state::SESSION_KEYS_PROGRAM_ID;
-        use crate::validation::validate_session_or_authority;
-
+        sub_account_id: u8,
+    ) -> Result<(Pubkey, Option<SessionScope>), ProgramError> {
+        if let Some(trade_delegate) = &self.trade_delegate {
+            require!(
+                self.session_token.is_none(),
+                ManifestError::InvalidTradeDelegate,
+                "Cannot use a session token and a trade delegate together",
+            )?;
+            let trader: Pubkey = validate_trade_delegate(
+                &self.owner,
+                trade_delegate,
+                self.market.info.key,
+                sub_account_id,
+            )?;
+            return Ok((trader, None));
+        }
         validate_session_or_authority(
             self.owner.info,
             self.session_token,
+            self.session_scope,
+            self.market.info.key,
+            ManifestInstruction::Swap,
             &SESSION_KEYS_PROGRAM_ID,
             program_id,
         )
     }
 }
 
+/// Load a session token and the session's scope account if a session token is
+/// the next account. Every instruction that accepts one takes it right after
+/// the market, or after the system program when the instruction has one. It
+/// is told apart from the accounts that may follow by its size. The scope
+/// account is required after the token even when no scope was set, so a
+/// session cannot escape its limits by leaving it out.
+#[allow(clippy::type_complexity)]
+fn load_session_accounts_opt<'a, 'info>(
+    account_iter: &mut Iter<'a, AccountInfo<'info>>,
+) -> Result<
+    (
+        Option<&'a AccountInfo<'info>>,
+        Option<&'a AccountInfo<'info>>,
+    ),
+    ProgramError,
+> {
+    let remaining_accounts: &[AccountInfo<'info>] = account_iter.as_slice();
+    if remaining_accounts.is_empty() || remaining_accounts[0].data_len() != SESSION_TOKEN_SIZE {
+        return Ok((None, None));
+    }
+    let session_token: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+    let expected_session_scope: Pubkey = {
+        let session_data = session_token.try_borrow_data()?;
+        let session: &SessionToken = bytemuck::try_from_bytes::<SessionToken>(
+            &session_data[SESSION_TOKEN_DISCRIMINATOR_SIZE..SESSION_TOKEN_SIZE],
+        )
+        .map_err(|_| ProgramError::from(ManifestError::InvalidSession))?;
+        get_session_scope_address(&session.authority, &session.session_signer).0
+    };
+    let session_scope: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+    require!(
+        session_scope.key == &expected_session_scope,
+        ManifestError::InvalidSession,
+        "Session token must be followed by its scope account {}",
+        expected_session_scope
+    )?;
+    Ok((Some(session_token), Some(session_scope)))
+}
+
+/// Load the seat's trade delegate record if it is the next account. It is
+/// told apart from the accounts that may follow by its size.
+fn load_trade_delegate_opt<'a, 'info>(
+    account_iter: &mut Iter<'a, AccountInfo<'info>>,
+) -> Result<Option<ManifestAccountInfo<'a, 'info, TradeDelegate>>, ProgramError> {
+    let remaining_accounts: &[AccountInfo<'info>] = account_iter.as_slice();
+    if remaining_accounts.is_empty() || remaining_accounts[0].data_len() != TRADE_DELEGATE_SIZE {
+        return Ok(None);
+    }
+    Ok(Some(ManifestAccountInfo::<TradeDelegate>::new(
+        next_account_info(account_iter)?,
+    )?))
+}
+
+/// Load the seat's extension if it is the next account. It is told apart from
+/// the accounts that may follow by its size. Clients may pass the extension
+/// PDA before it is created, so an empty system account in its place is
+/// skipped. Whether the extension belongs to the seat is checked once the
+/// seat is known.
+pub(crate) fn load_seat_extension_opt<'a, 'info>(
+    account_iter: &mut Iter<'a, AccountInfo<'info>>,
+) -> Result<Option<ManifestAccountInfo<'a, 'info, SeatExtension>>, ProgramError> {
+    let remaining_accounts: &[AccountInfo<'info>] = account_iter.as_slice();
+    if remaining_accounts.is_empty() {
+        return Ok(None);
+    }
+    if remaining_accounts[0].data_len() == 0 && remaining_accounts[0].owner == &system_program::id()
+    {
+        next_account_info(account_iter)?;
+        return Ok(None);
+    }
+    if remaining_accounts[0].data_len() != SEAT_EXTENSION_SIZE {
+        return Ok(None);
+    }
+    Ok(Some(ManifestAccountInfo::<SeatExtension>::new(
+        next_account_info(account_iter)?,
+    )?))
+}
+
+/// Load the trader's withdraw allowlist if it is the next account. It is told
+/// apart from the cross-margin accounts that may follow by its size.
+fn load_withdraw_allowlist_opt<'a, 'info>(
+    account_iter: &mut Iter<'a, AccountInfo<'info>>,
+) -> Result<Option<ManifestAccountInfo<'a, 'info, WithdrawAllowlist>>, ProgramError> {
+    let remaining_accounts: &[AccountInfo<'info>] = account_iter.as_slice();
+    if remaining_accounts.is_empty() || remaining_accounts[0].data_len() != WITHDRAW_ALLOWLIST_SIZE
+    {
+        return Ok(None);
+    }
+    Ok(Some(ManifestAccountInfo::<WithdrawAllowlist>::new(
+        next_account_info(account_iter)?,
+    )?))
+}
+
 /// Accounts needed to make a global trade. Scope is beyond just crate so
 /// clients can place orders on markets in testing.
 pub struct GlobalTradeAccounts<'a, 'info> {
@@ -462,6 +914,10 @@ pub(crate) struct BatchUpdateContext<'a, 'info> {
     pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
     pub _system_program: Program<'a, 'info>,
     pub session_token: Option<&'a AccountInfo<'info>>,
+    pub session_scope: Option<&'a AccountInfo<'info>>,
+    pub trade_delegate: Option<ManifestAccountInfo<'a, 'info, TradeDelegate>>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
+    pub cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>>,
 
     // One for each side. First is base, then is quote.
     pub global_trade_accounts_opts: [Option<GlobalTradeAccounts<'a, 'info>>; 2],
@@ -481,24 +937,17 @@ impl<'a, 'info> BatchUpdateContext<'a, 'info> {
         let system_program: Program =
             Program::new(next_account_info(account_iter)?, &system_program::id())?;
 
-        // Try to load optional session token (after system_program, before global accounts)
-        // Session token is identified by data length matching SessionToken size (128 bytes)
-        let session_token: Option<&'a AccountInfo<'info>> = {
-            // Peek at the next account without consuming it
-            let remaining_accounts = account_iter.as_slice();
-            if !remaining_accounts.is_empty() {
-                let next_account = &remaining_accounts[0];
-                // Check if it looks like a session token
-                if next_account.data_len() == crate::state::SESSION_TOKEN_SIZE {
-                    // Consume it from the iterator
-                    Some(next_account_info(account_iter)?)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        };
+        // Optional session accounts (after system_program, before global accounts)
+        let (session_token, session_scope) = load_session_accounts_opt(account_iter)?;
+
+        let trade_delegate: Option<ManifestAccountInfo<'a, 'info, TradeDelegate>> =
+            load_trade_delegate_opt(account_iter)?;
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
+        // Cross-margin seats pass their portfolio accounts next.
+        let cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>> =
+            CrossMarginAccounts::load_opt(account_iter, market.info.key)?;
 
         // Certora version is not mutable.
         #[cfg(feature = "certora")]
@@ -597,22 +1046,42 @@ impl<'a, 'info> BatchUpdateContext<'a, 'info> {
             market,
             _system_program: system_program,
             session_token,
+            session_scope,
+            trade_delegate,
+            seat_extension,
+            cross_margin_accounts,
             global_trade_accounts_opts,
         })
     }
 
-    /// Validates session or authority and returns the trader authority.
-    /// This eliminates duplicate validation code across processors.
+    /// Validates session, trade delegate or authority and returns the trader
+    /// authority, with the session's scope if it has one. This eliminates
+    /// duplicate validation code across processors.
     pub fn validate_and_get_trader_authority(
         &self,
         program_id: &Pubkey,
-    ) -> Result<Pubkey, ProgramError> {
-        use crate::state::SESSION_KEYS_PROGRAM_ID;
-        use crate::validation::validate_session_or_authority;
-
+        sub_account_id: u8,
+    ) -> Result<(Pubkey, Option<SessionScope>), ProgramError> {
+        if let Some(trade_delegate) = &self.trade_delegate {
+            require!(
+                self.session_token.is_none(),
+                ManifestError::InvalidTradeDelegate,
+                "Cannot use a session token and a trade delegate together",
+            )?;
+            let trader: Pubkey = validate_trade_delegate(
+                &self.payer,
+                trade_delegate,
+                self.market.info.key,
+                sub_account_id,
+            )?;
+            return Ok((trader, None));
+        }
         validate_session_or_authority(
             self.payer.info,
             self.session_token,
+            self.session_scope,
+            self.market.info.key,
+            ManifestInstruction::BatchUpdate,
             &SESSION_KEYS_PROGRAM_ID,
             program_id,
         )
@@ -910,7 +1379,10 @@ impl<'a, 'info> GlobalCleanContext<'a, 'info> {
 pub(crate) struct CrankFundingContext<'a, 'info> {
     pub payer: Signer<'a, 'info>,
     pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
-    pub pyth_price_feed: &'a AccountInfo<'info>,
+    /// One account per active oracle source, in the market's source order
+    pub oracle_feeds: Vec<&'a AccountInfo<'info>>,
+    /// Market's funding history, if the cranker passed it
+    pub funding_history: Option<ManifestAccountInfo<'a, 'info, FundingHistory>>,
 }
 
 impl<'a, 'info> CrankFundingContext<'a, 'info> {
@@ -923,35 +1395,117 @@ impl<'a, 'info> CrankFundingContext<'a, 'info> {
             ManifestAccountInfo::<MarketFixed>::new(market_info)
                 .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
 
-        let pyth_price_feed: &'a AccountInfo<'info> = next_account_info(account_iter)?;
-
-        // Validate pyth feed matches the one stored on the market
+        // Every configured oracle source has to be passed, so a cranker can
+        // not leave out the sources that disagree with the one it wants.
+        let mut oracle_feeds: Vec<&'a AccountInfo<'info>> = Vec::new();
         {
             let market_fixed: std::cell::Ref<MarketFixed> = market.get_fixed()?;
-            require!(
-                *pyth_price_feed.key == *market_fixed.get_pyth_feed(),
-                ManifestError::IncorrectAccount,
-                "Pyth feed account does not match market's oracle",
-            )?;
             require!(
                 *market_fixed.get_pyth_feed() != Pubkey::default(),
                 ManifestError::InvalidPerpsOperation,
                 "Market has no oracle configured",
             )?;
+            for source in market_fixed
+                .get_oracle_sources()
+                .iter()
+                .filter(|source| source.is_active())
+            {
+                let oracle_feed: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+                require!(
+                    *oracle_feed.key == source.feed,
+                    ManifestError::IncorrectAccount,
+                    "Oracle account {} does not match market's oracle {}",
+                    oracle_feed.key,
+                    source.feed,
+                )?;
+                oracle_feeds.push(oracle_feed);
+            }
         }
 
-        Ok(Self {
+        // The funding history account is optional and follows the oracles.
+        let funding_history: Option<ManifestAccountInfo<FundingHistory>> =
+            match next_account_info(account_iter) {
+                Ok(funding_history_info) => {
+                    let (expected_funding_history, _) =
+                        get_funding_history_address(market.info.key);
+                    require!(
+                        expected_funding_history == *funding_history_info.key,
+                        ManifestError::IncorrectAccount,
+                        "Incorrect funding history account",
+                    )?;
+                    Some(
+                        ManifestAccountInfo::<FundingHistory>::new(funding_history_info).or_else(
+                            |_| {
+                                ManifestAccountInfo::<FundingHistory>::new_delegated(
+                                    funding_history_info,
+                                )
+                            },
+                        )?,
+                    )
+                }
+                Err(_) => None,
+            };
+
+        Ok(Self {
             payer,
             market,
-            pyth_price_feed,
+            oracle_feeds,
+            funding_history,
         })
     }
 }
 
-/// Liquidate account infos
+/// CreateFundingHistory account infos
+pub(crate) struct CreateFundingHistoryContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub funding_history: EmptyAccount<'a, 'info>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> CreateFundingHistoryContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(next_account_info(account_iter)?)?;
+
+        let funding_history: EmptyAccount = EmptyAccount::new(next_account_info(account_iter)?)?;
+        let (expected_funding_history, _) = get_funding_history_address(market.info.key);
+        require!(
+            expected_funding_history == *funding_history.info.key,
+            ManifestError::IncorrectAccount,
+            "Incorrect funding history account",
+        )?;
+
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            market,
+            funding_history,
+            system_program,
+        })
+    }
+}
+
+/// Liquidate account infos. A cross-margin trader is only liquidated when
+/// their portfolio accounts follow the system program and optional session
+/// token, with the accounts to move their collateral into the market vault
+/// after them. Seat extensions go last.
 pub(crate) struct LiquidateContext<'a, 'info> {
     pub liquidator: Signer<'a, 'info>,
     pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub session_token: Option<&'a AccountInfo<'info>>,
+    pub session_scope: Option<&'a AccountInfo<'info>>,
+    pub cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>>,
+    pub cross_margin_vault: Option<TokenAccountInfo<'a, 'info>>,
+    pub market_vault: Option<TokenAccountInfo<'a, 'info>>,
+    pub token_program: Option<TokenProgram<'a, 'info>>,
+    /// Extensions of the liquidator's and the traders' seats, in any order.
+    pub seat_extensions: Vec<ManifestAccountInfo<'a, 'info, SeatExtension>>,
 }
 
 impl<'a, 'info> LiquidateContext<'a, 'info> {
@@ -966,10 +1520,665 @@ impl<'a, 'info> LiquidateContext<'a, 'info> {
         // system_program is optional, just consume it
         let _system_program = next_account_info(account_iter).ok();
 
+        let (session_token, session_scope) = load_session_accounts_opt(account_iter)?;
+
+        let cross_margin_accounts: Option<CrossMarginAccounts<'a, 'info>> =
+            CrossMarginAccounts::load_opt(account_iter, market.info.key)?;
+        let (cross_margin_vault, market_vault, token_program) = match &cross_margin_accounts {
+            None => (None, None, None),
+            Some(CrossMarginAccounts { cross_margin, .. }) => {
+                let quote_mint: Pubkey = *cross_margin.get_fixed()?.get_quote_mint();
+                let (expected_cross_margin_vault, _) =
+                    get_vault_address(cross_margin.info.key, &quote_mint);
+                let (expected_market_vault, _) = get_vault_address(market.info.key, &quote_mint);
+                let cross_margin_vault: TokenAccountInfo =
+                    TokenAccountInfo::new_with_owner_and_key(
+                        next_account_info(account_iter)?,
+                        &quote_mint,
+                        cross_margin.info.key,
+                        &expected_cross_margin_vault,
+                    )?;
+                let market_vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+                    next_account_info(account_iter)?,
+                    &quote_mint,
+                    market.info.key,
+                    &expected_market_vault,
+                )?;
+                let token_program: TokenProgram =
+                    TokenProgram::new(next_account_info(account_iter)?)?;
+                (
+                    Some(cross_margin_vault),
+                    Some(market_vault),
+                    Some(token_program),
+                )
+            }
+        };
+
+        let mut seat_extensions: Vec<ManifestAccountInfo<SeatExtension>> = Vec::new();
+        loop {
+            let num_remaining: usize = account_iter.len();
+            match load_seat_extension_opt(account_iter)? {
+                Some(seat_extension) => seat_extensions.push(seat_extension),
+                // An extension not created yet was skipped.
+                None if account_iter.len() < num_remaining => {}
+                None => break,
+            }
+        }
+
         Ok(Self {
             liquidator,
             market,
+            session_token,
+            session_scope,
+            cross_margin_accounts,
+            cross_margin_vault,
+            market_vault,
+            token_program,
+            seat_extensions,
+        })
+    }
+
+    /// Validates session or authority and returns the liquidator whose seat
+    /// receives the reward or the taken-over position.
+    pub fn validate_and_get_liquidator_authority(
+        &self,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, ProgramError> {
+        let (liquidator, _) = validate_session_or_authority(
+            self.liquidator.info,
+            self.session_token,
+            self.session_scope,
+            self.market.info.key,
+            ManifestInstruction::Liquidate,
+            &SESSION_KEYS_PROGRAM_ID,
+            program_id,
+        )?;
+        Ok(liquidator)
+    }
+}
+
+/// SettlePnl account infos
+pub(crate) struct SettlePnlContext<'a, 'info> {
+    #[allow(dead_code)]
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
+}
+
+impl<'a, 'info> SettlePnlContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
+        Ok(Self {
+            payer,
+            market,
+            seat_extension,
+        })
+    }
+}
+
+/// UpdateMarket account infos
+pub(crate) struct UpdateMarketContext<'a, 'info> {
+    #[allow(dead_code)]
+    pub authority: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+}
+
+impl<'a, 'info> UpdateMarketContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let authority: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+
+        {
+            let market_fixed: Ref<MarketFixed> = market.get_fixed()?;
+            require!(
+                *authority.key == *market_fixed.get_market_authority(),
+                ManifestError::InvalidMarketAuthority,
+                "Signer {} is not the market authority {}",
+                authority.key,
+                market_fixed.get_market_authority(),
+            )?;
+        }
+
+        Ok(Self { authority, market })
+    }
+}
+
+/// Accounts a cross-margin seat passes so its whole portfolio can be valued:
+/// the cross-margin account followed by every other market it links, in link
+/// order. Optional on instructions that check margin, where it goes at a
+/// fixed position and is recognized by the cross-margin account size.
+pub(crate) struct CrossMarginAccounts<'a, 'info> {
+    pub cross_margin: ManifestAccountInfo<'a, 'info, CrossMarginAccount>,
+    /// Linked markets other than the one the instruction is for.
+    pub markets: Vec<ManifestAccountInfo<'a, 'info, MarketFixed>>,
+}
+
+impl<'a, 'info> CrossMarginAccounts<'a, 'info> {
+    /// Load the block if the next account is a cross-margin account. `market`
+    /// is the market the instruction is for, which must be linked.
+    pub fn load_opt(
+        account_iter: &mut Iter<'a, AccountInfo<'info>>,
+        market: &Pubkey,
+    ) -> Result<Option<Self>, ProgramError> {
+        let remaining_accounts: &[AccountInfo<'info>] = account_iter.as_slice();
+        if remaining_accounts.is_empty()
+            || remaining_accounts[0].data_len() != CROSS_MARGIN_ACCOUNT_SIZE
+        {
+            return Ok(None);
+        }
+        let cross_margin: ManifestAccountInfo<CrossMarginAccount> =
+            ManifestAccountInfo::<CrossMarginAccount>::new(next_account_info(account_iter)?)?;
+        let linked_markets: Vec<Pubkey> = {
+            let cross_margin_fixed: Ref<CrossMarginAccount> = cross_margin.get_fixed()?;
+            require!(
+                cross_margin_fixed.is_linked(market),
+                ManifestError::IncorrectAccount,
+                "Market {} is not linked to the cross-margin account",
+                market,
+            )?;
+            cross_margin_fixed
+                .get_markets()
+                .iter()
+                .filter(|linked_market| *linked_market != market)
+                .copied()
+                .collect()
+        };
+        let markets: Vec<ManifestAccountInfo<MarketFixed>> =
+            Self::load_markets(account_iter, &linked_markets)?;
+        Ok(Some(Self {
+            cross_margin,
+            markets,
+        }))
+    }
+
+    /// Load `linked_markets` in order from the account iterator.
+    pub fn load_markets(
+        account_iter: &mut Iter<'a, AccountInfo<'info>>,
+        linked_markets: &[Pubkey],
+    ) -> Result<Vec<ManifestAccountInfo<'a, 'info, MarketFixed>>, ProgramError> {
+        linked_markets
+            .iter()
+            .map(|linked_market| {
+                let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+                require!(
+                    market_info.key == linked_market,
+                    ManifestError::IncorrectAccount,
+                    "Expected linked market {} got {}",
+                    linked_market,
+                    market_info.key,
+                )?;
+                ManifestAccountInfo::<MarketFixed>::new(market_info)
+                    .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))
+            })
+            .collect()
+    }
+}
+
+/// CreateCrossMarginAccount account infos
+pub(crate) struct CreateCrossMarginAccountContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub cross_margin: EmptyAccount<'a, 'info>,
+    pub quote_mint: MintAccountInfo<'a, 'info>,
+    pub vault: EmptyAccount<'a, 'info>,
+    pub token_program: TokenProgram<'a, 'info>,
+    pub associated_token_program: Program<'a, 'info>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> CreateCrossMarginAccountContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let cross_margin: EmptyAccount = EmptyAccount::new(next_account_info(account_iter)?)?;
+        let quote_mint: MintAccountInfo = MintAccountInfo::new(next_account_info(account_iter)?)?;
+        let (expected_cross_margin, _) = get_cross_margin_address(payer.key, quote_mint.info.key);
+        require!(
+            expected_cross_margin == *cross_margin.info.key,
+            ManifestError::IncorrectAccount,
+            "Incorrect cross-margin account",
+        )?;
+
+        let vault: EmptyAccount = EmptyAccount::new(next_account_info(account_iter)?)?;
+        let (expected_vault, _) = get_vault_address(cross_margin.info.key, quote_mint.info.key);
+        require!(
+            expected_vault == *vault.info.key,
+            ManifestError::IncorrectAccount,
+            "Incorrect cross-margin vault account (expected ATA of cross-margin account)",
+        )?;
+
+        let token_program: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let associated_token_program: Program = Program::new(
+            next_account_info(account_iter)?,
+            &spl_associated_token_account::id(),
+        )?;
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            cross_margin,
+            quote_mint,
+            vault,
+            token_program,
+            associated_token_program,
+            system_program,
+        })
+    }
+}
+
+/// CrossMarginDeposit account infos
+pub(crate) struct CrossMarginDepositContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub cross_margin: ManifestAccountInfo<'a, 'info, CrossMarginAccount>,
+    pub trader_token: TokenAccountInfo<'a, 'info>,
+    pub vault: TokenAccountInfo<'a, 'info>,
+    pub token_program: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
+    pub mint: Option<MintAccountInfo<'a, 'info>>,
+}
+
+impl<'a, 'info> CrossMarginDepositContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let cross_margin: ManifestAccountInfo<CrossMarginAccount> =
+            ManifestAccountInfo::<CrossMarginAccount>::new(next_account_info(account_iter)?)?;
+
+        let cross_margin_fixed: Ref<CrossMarginAccount> = cross_margin.get_fixed()?;
+        require!(
+            cross_margin_fixed.get_owner() == payer.key,
+            ManifestError::IncorrectAccount,
+            "Cross-margin account does not belong to the signer",
+        )?;
+        let quote_mint: Pubkey = *cross_margin_fixed.get_quote_mint();
+        let (expected_vault_address, _) = get_vault_address(cross_margin.info.key, &quote_mint);
+
+        let trader_token: TokenAccountInfo = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_mint,
+            payer.key,
+        )?;
+
+        let vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
+        let vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+            vault_info,
+            &quote_mint,
+            cross_margin.info.key,
+            &expected_vault_address,
+        )?;
+
+        let token_program: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let mint: Option<MintAccountInfo> =
+            Some(MintAccountInfo::new(next_account_info(account_iter)?)?);
+
+        drop(cross_margin_fixed);
+        Ok(Self {
+            payer,
+            cross_margin,
+            trader_token,
+            vault,
+            token_program,
+            mint,
+        })
+    }
+}
+
+/// CrossMarginWithdraw account infos. Every linked market follows, in link
+/// order, so the portfolio can be valued after the withdrawal.
+pub(crate) struct CrossMarginWithdrawContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub cross_margin: ManifestAccountInfo<'a, 'info, CrossMarginAccount>,
+    pub trader_token: TokenAccountInfo<'a, 'info>,
+    pub vault: TokenAccountInfo<'a, 'info>,
+    pub token_program: TokenProgram<'a, 'info>,
+    #[allow(dead_code)]
+    pub mint: Option<MintAccountInfo<'a, 'info>>,
+    pub markets: Vec<ManifestAccountInfo<'a, 'info, MarketFixed>>,
+}
+
+impl<'a, 'info> CrossMarginWithdrawContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let cross_margin: ManifestAccountInfo<CrossMarginAccount> =
+            ManifestAccountInfo::<CrossMarginAccount>::new(next_account_info(account_iter)?)?;
+
+        let cross_margin_fixed: Ref<CrossMarginAccount> = cross_margin.get_fixed()?;
+        require!(
+            cross_margin_fixed.get_owner() == payer.key,
+            ManifestError::IncorrectAccount,
+            "Cross-margin account does not belong to the signer",
+        )?;
+        let quote_mint: Pubkey = *cross_margin_fixed.get_quote_mint();
+        let (expected_vault_address, _) = get_vault_address(cross_margin.info.key, &quote_mint);
+        let linked_markets: Vec<Pubkey> = cross_margin_fixed.get_markets().to_vec();
+
+        let trader_token: TokenAccountInfo = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_mint,
+            payer.key,
+        )?;
+
+        let vault_info: &AccountInfo<'info> = next_account_info(account_iter)?;
+        let vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+            vault_info,
+            &quote_mint,
+            cross_margin.info.key,
+            &expected_vault_address,
+        )?;
+
+        let token_program: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let mint: Option<MintAccountInfo> =
+            Some(MintAccountInfo::new(next_account_info(account_iter)?)?);
+        let markets: Vec<ManifestAccountInfo<MarketFixed>> =
+            CrossMarginAccounts::load_markets(account_iter, &linked_markets)?;
+
+        drop(cross_margin_fixed);
+        Ok(Self {
+            payer,
+            cross_margin,
+            trader_token,
+            vault,
+            token_program,
+            mint,
+            markets,
+        })
+    }
+}
+
+/// CrossMarginLink account infos
+pub(crate) struct CrossMarginLinkContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub cross_margin: ManifestAccountInfo<'a, 'info, CrossMarginAccount>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
+}
+
+impl<'a, 'info> CrossMarginLinkContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+        let cross_margin: ManifestAccountInfo<CrossMarginAccount> =
+            ManifestAccountInfo::<CrossMarginAccount>::new(next_account_info(account_iter)?)?;
+
+        {
+            let market_fixed: Ref<MarketFixed> = market.get_fixed()?;
+            let cross_margin_fixed: Ref<CrossMarginAccount> = cross_margin.get_fixed()?;
+            require!(
+                cross_margin_fixed.get_owner() == payer.key,
+                ManifestError::IncorrectAccount,
+                "Cross-margin account does not belong to the signer",
+            )?;
+            require!(
+                cross_margin_fixed.get_quote_mint() == market_fixed.get_quote_mint(),
+                ManifestError::IncorrectAccount,
+                "Cross-margin account quote mint does not match the market",
+            )?;
+        }
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
+        Ok(Self {
+            payer,
+            market,
+            cross_margin,
+            seat_extension,
+        })
+    }
+}
+
+/// CreateSeatExtension account infos
+pub(crate) struct CreateSeatExtensionContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    /// Either empty or the seat's existing extension. Its address depends on
+    /// the sub-account, so the processor checks it.
+    pub seat_extension: &'a AccountInfo<'info>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> CreateSeatExtensionContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+        let seat_extension: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            market,
+            seat_extension,
+            system_program,
         })
     }
 }
 
+/// CrossMarginTransfer account infos
+pub(crate) struct CrossMarginTransferContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub cross_margin: ManifestAccountInfo<'a, 'info, CrossMarginAccount>,
+    pub market_vault: TokenAccountInfo<'a, 'info>,
+    pub cross_margin_vault: TokenAccountInfo<'a, 'info>,
+    pub token_program: TokenProgram<'a, 'info>,
+    /// Linked markets other than `market`, in link order.
+    pub markets: Vec<ManifestAccountInfo<'a, 'info, MarketFixed>>,
+    pub seat_extension: Option<ManifestAccountInfo<'a, 'info, SeatExtension>>,
+}
+
+impl<'a, 'info> CrossMarginTransferContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+        let cross_margin: ManifestAccountInfo<CrossMarginAccount> =
+            ManifestAccountInfo::<CrossMarginAccount>::new(next_account_info(account_iter)?)?;
+
+        let cross_margin_fixed: Ref<CrossMarginAccount> = cross_margin.get_fixed()?;
+        require!(
+            cross_margin_fixed.get_owner() == payer.key,
+            ManifestError::IncorrectAccount,
+            "Cross-margin account does not belong to the signer",
+        )?;
+        let quote_mint: Pubkey = *cross_margin_fixed.get_quote_mint();
+        require!(
+            *market.get_fixed()?.get_quote_mint() == quote_mint,
+            ManifestError::IncorrectAccount,
+            "Cross-margin account quote mint does not match the market",
+        )?;
+        let linked_markets: Vec<Pubkey> = cross_margin_fixed
+            .get_markets()
+            .iter()
+            .filter(|linked_market| *linked_market != market.info.key)
+            .copied()
+            .collect();
+
+        let (expected_market_vault, _) = get_vault_address(market.info.key, &quote_mint);
+        let market_vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &quote_mint,
+            market.info.key,
+            &expected_market_vault,
+        )?;
+        let (expected_cross_margin_vault, _) =
+            get_vault_address(cross_margin.info.key, &quote_mint);
+        let cross_margin_vault: TokenAccountInfo = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &quote_mint,
+            cross_margin.info.key,
+            &expected_cross_margin_vault,
+        )?;
+
+        let token_program: TokenProgram = TokenProgram::new(next_account_info(account_iter)?)?;
+        let markets: Vec<ManifestAccountInfo<MarketFixed>> =
+            CrossMarginAccounts::load_markets(account_iter, &linked_markets)?;
+        let seat_extension: Option<ManifestAccountInfo<SeatExtension>> =
+            load_seat_extension_opt(account_iter)?;
+
+        drop(cross_margin_fixed);
+        Ok(Self {
+            payer,
+            market,
+            cross_margin,
+            market_vault,
+            cross_margin_vault,
+            token_program,
+            markets,
+            seat_extension,
+        })
+    }
+}
+
+/// SetTradeDelegate account infos
+pub(crate) struct SetTradeDelegateContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    /// Either empty or the seat's existing record. Its address depends on the
+    /// sub-account, so the processor checks it.
+    pub trade_delegate: &'a AccountInfo<'info>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> SetTradeDelegateContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+        let trade_delegate: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            market,
+            trade_delegate,
+            system_program,
+        })
+    }
+}
+
+/// RevokeTradeDelegate account infos
+pub(crate) struct RevokeTradeDelegateContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    pub market: ManifestAccountInfo<'a, 'info, MarketFixed>,
+    pub trade_delegate: ManifestAccountInfo<'a, 'info, TradeDelegate>,
+}
+
+impl<'a, 'info> RevokeTradeDelegateContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new(next_account_info(account_iter)?)?;
+        let market_info: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let market: ManifestAccountInfo<MarketFixed> =
+            ManifestAccountInfo::<MarketFixed>::new(market_info)
+                .or_else(|_| ManifestAccountInfo::<MarketFixed>::new_delegated(market_info))?;
+        let trade_delegate: ManifestAccountInfo<TradeDelegate> =
+            ManifestAccountInfo::<TradeDelegate>::new(next_account_info(account_iter)?)?;
+
+        {
+            let record: Ref<TradeDelegate> = trade_delegate.get_fixed()?;
+            let (expected_trade_delegate, _) =
+                get_trade_delegate_address(market.info.key, payer.key, record.get_sub_account_id());
+            require!(
+                expected_trade_delegate == *trade_delegate.info.key,
+                ManifestError::IncorrectAccount,
+                "Trade delegate record does not belong to the signer on this market",
+            )?;
+        }
+
+        Ok(Self {
+            payer,
+            market,
+            trade_delegate,
+        })
+    }
+}
+
+/// SetSessionScope account infos
+pub(crate) struct SetSessionScopeContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    /// Either empty or the existing scope. Its address depends on the session
+    /// signer, so the processor checks it.
+    pub session_scope: &'a AccountInfo<'info>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> SetSessionScopeContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let session_scope: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            session_scope,
+            system_program,
+        })
+    }
+}
+
+/// SetWithdrawAllowlist account infos
+pub(crate) struct SetWithdrawAllowlistContext<'a, 'info> {
+    pub payer: Signer<'a, 'info>,
+    /// Either empty or the existing allowlist. The processor checks its
+    /// address.
+    pub withdraw_allowlist: &'a AccountInfo<'info>,
+    pub system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> SetWithdrawAllowlistContext<'a, 'info> {
+    pub fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_iter: &mut Iter<AccountInfo<'info>> = &mut accounts.iter();
+
+        let payer: Signer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let withdraw_allowlist: &'a AccountInfo<'info> = next_account_info(account_iter)?;
+        let system_program: Program =
+            Program::new(next_account_info(account_iter)?, &system_program::id())?;
+
+        Ok(Self {
+            payer,
+            withdraw_allowlist,
+            system_program,
+        })
+    }
+}
@@ -27,6 +27,13 @@ impl<'a, 'info, T: ManifestAccount + Get + Clone> ManifestAccountInfo<'a, 'info,
         verify_owned_by_manifest(info.owner)?;
 
         let bytes: Ref<&mut [u8]> = info.try_borrow_data()?;
+        require!(
+            bytes.len() >= size_of::<T>(),
+            ProgramError::InvalidAccountData,
+            "Account data {} bytes is shorter than its {} byte header",
+            bytes.len(),
+            size_of::<T>()
+        )?;
         let (header_bytes, _) = bytes.split_at(size_of::<T>());
         let header: &T = get_helper::<T>(header_bytes, 0_u32);
         header.verify_discriminant()?;
@@ -44,6 +51,13 @@ impl<'a, 'info, T: ManifestAccount + Get + Clone> ManifestAccountInfo<'a, 'info,
         info: &'a AccountInfo<'info>,
     ) -> Result<ManifestAccountInfo<'a, 'info, T>, ProgramError> {
         let bytes: Ref<&mut [u8]> = info.try_borrow_data()?;
+        require!(
+            bytes.len() >= size_of::<T>(),
+            ProgramError::InvalidAccountData,
+            "Account data {} bytes is shorter than its {} byte header",
+            bytes.len(),
+            size_of::<T>()
+        )?;
         let (header_bytes, _) = bytes.split_at(size_of::<T>());
         let header: &T = get_helper::<T>(header_bytes, 0_u32);
         header.verify_discriminant()?;
@@ -85,7 +99,7 @@ pub trait ManifestAccount {
     fn verify_discriminant(&self) -> ProgramResult;
 }
 
-fn verify_owned_by_manifest(owner: &Pubkey) -> ProgramResult {
+pub(crate) fn verify_owned_by_manifest(owner: &Pubkey) -> ProgramResult {
     require!(
         owner == &crate::ID,
         ProgramError::IllegalOwner,
@@ -122,7 +136,11 @@ fn verify_uninitialized<T: Pod + ManifestAccount>(info: &AccountInfo) -> Program
 #[cfg(test)]
 mod test {
     use crate::state::{
-        GlobalFixed, MarketFixed, GLOBAL_FIXED_DISCRIMINANT, MARKET_FIXED_DISCRIMINANT,
+        CrossMarginAccount, FundingHistory, GlobalFixed, InsuranceStaker, MarketFixed,
+        SeatExtension, SessionScope, TradeDelegate, WithdrawAllowlist,
+        CROSS_MARGIN_ACCOUNT_DISCRIMINANT, FUNDING_HISTORY_DISCRIMINANT, GLOBAL_FIXED_DISCRIMINANT,
+        INSURANCE_STAKER_DISCRIMINANT, MARKET_FIXED_DISCRIMINANT, SEAT_EXTENSION_DISCRIMINANT,
+        SESSION_SCOPE_DISCRIMINANT, TRADE_DELEGATE_DISCRIMINANT, WITHDRAW_ALLOWLIST_DISCRIMINANT,
     };
 
     #[test]
@@ -136,6 +154,48 @@ mod test {
         let discriminant: u64 = crate::utils::get_discriminant::<GlobalFixed>().unwrap();
         assert_eq!(discriminant, GLOBAL_FIXED_DISCRIMINANT);
     }
+
+    #[test]
+    fn test_insurance_staker_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<InsuranceStaker>().unwrap();
+        assert_eq!(discriminant, INSURANCE_STAKER_DISCRIMINANT);
+    }
+
+    #[test]
+    fn test_funding_history_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<FundingHistory>().unwrap();
+        assert_eq!(discriminant, FUNDING_HISTORY_DISCRIMINANT);
+    }
+
+    #[test]
+    fn test_seat_extension_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<SeatExtension>().unwrap();
+        assert_eq!(discriminant, SEAT_EXTENSION_DISCRIMINANT);
+    }
+
+    #[test]
+    fn test_cross_margin_account_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<CrossMarginAccount>().unwrap();
+        assert_eq!(discriminant, CROSS_MARGIN_ACCOUNT_DISCRIMINANT);
+    }
+
+    #[test]
+    fn test_trade_delegate_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<TradeDelegate>().unwrap();
+        assert_eq!(discriminant, TRADE_DELEGATE_DISCRIMINANT);
+    }
+
+    #[test]
+    fn test_session_scope_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<SessionScope>().unwrap();
+        assert_eq!(discriminant, SESSION_SCOPE_DISCRIMINANT);
+    }
+
+    #[test]
+    fn test_withdraw_allowlist_discriminant() {
+        let discriminant: u64 = crate::utils::get_discriminant::<WithdrawAllowlist>().unwrap();
+        assert_eq!(discriminant, WITHDRAW_ALLOWLIST_DISCRIMINANT);
+    }
 }
 
 macro_rules! global_seeds {
@@ -164,10 +224,125 @@ macro_rules! market_seeds {
 #[macro_export]
 macro_rules! market_seeds_with_bump {
     ( $base_mint_index:expr, $quote_mint:expr, $bump:expr ) => {
-        &[&[b"market", &[$base_mint_index], $quote_mint.as_ref(), &[$bump]]]
+        &[&[
+            b"market",
+            &[$base_mint_index],
+            $quote_mint.as_ref(),
+            &[$bump],
+        ]]
     };
 }
 
 pub fn get_market_address(base_mint_index: u8, quote_mint: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(market_seeds!(base_mint_index, quote_mint), &crate::ID)
 }
+
+macro_rules! insurance_staker_seeds {
+    ( $market:expr, $staker:expr ) => {
+        &[b"insurance-staker", $market.as_ref(), $staker.as_ref()]
+    };
+}
+
+pub fn get_insurance_staker_address(market: &Pubkey, staker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(insurance_staker_seeds!(market, staker), &crate::ID)
+}
+
+macro_rules! funding_history_seeds {
+    ( $market:expr ) => {
+        &[b"funding-history", $market.as_ref()]
+    };
+}
+
+pub fn get_funding_history_address(market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(funding_history_seeds!(market), &crate::ID)
+}
+
+macro_rules! seat_extension_seeds {
+    ( $market:expr, $trader:expr, $sub_account_id:expr ) => {
+        &[
+            b"seat-extension",
+            $market.as_ref(),
+            $trader.as_ref(),
+            &[$sub_account_id],
+        ]
+    };
+}
+
+pub fn get_seat_extension_address(
+    market: &Pubkey,
+    trader: &Pubkey,
+    sub_account_id: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        seat_extension_seeds!(market, trader, sub_account_id),
+        &crate::ID,
+    )
+}
+
+macro_rules! cross_margin_seeds {
+    ( $owner:expr, $quote_mint:expr ) => {
+        &[b"cross-margin", $owner.as_ref(), $quote_mint.as_ref()]
+    };
+}
+
+#[macro_export]
+macro_rules! cross_margin_seeds_with_bump {
+    ( $owner:expr, $quote_mint:expr, $bump:expr ) => {
+        &[&[
+            b"cross-margin",
+            $owner.as_ref(),
+            $quote_mint.as_ref(),
+            &[$bump],
+        ]]
+    };
+}
+
+pub fn get_cross_margin_address(owner: &Pubkey, quote_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(cross_margin_seeds!(owner, quote_mint), &crate::ID)
+}
+
+macro_rules! trade_delegate_seeds {
+    ( $market:expr, $trader:expr, $sub_account_id:expr ) => {
+        &[
+            b"trade-delegate",
+            $market.as_ref(),
+            $trader.as_ref(),
+            &[$sub_account_id],
+        ]
+    };
+}
+
+pub fn get_trade_delegate_address(
+    market: &Pubkey,
+    trader: &Pubkey,
+    sub_account_id: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        trade_delegate_seeds!(market, trader, sub_account_id),
+        &crate::ID,
+    )
+}
+
+macro_rules! session_scope_seeds {
+    ( $authority:expr, $session_signer:expr ) => {
+        &[
+            b"session-scope",
+            $authority.as_ref(),
+            $session_signer.as_ref(),
+        ]
+    };
+}
+
+pub fn get_session_scope_address(authority: &Pubkey, session_signer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(session_scope_seeds!(authority, session_signer), &crate::ID)
+}
+
+macro_rules! withdraw_allowlist_seeds {
+    ( $trader:expr ) => {
+        &[b"withdraw-allowlist", $trader.as_ref()]
+    };
+}
+
+pub fn get_withdraw_allowlist_address(trader: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(withdraw_allowlist_seeds!(trader), &crate::ID)
+}
@@ -3,8 +3,10 @@ pub mod manifest_checker;
 pub mod session_validator;
 pub mod solana_checkers;
 pub mod token_checkers;
+pub mod trade_delegate_validator;
 
 pub use manifest_checker::*;
 pub use session_validator::*;
 pub use solana_checkers::*;
 pub use token_checkers::*;
+pub use trade_delegate_validator::*;
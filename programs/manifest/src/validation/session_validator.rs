@@ -4,9 +4,10 @@ use solana_program::{
 };
 
 use crate::{
-    program::error::ManifestError,
+    program::{error::ManifestError, ManifestInstruction},
     require,
-    state::{SessionToken, SESSION_TOKEN_DISCRIMINATOR_SIZE},
+    state::{SessionScope, SessionToken, SESSION_TOKEN_DISCRIMINATOR_SIZE},
+    validation::{get_session_scope_address, ManifestAccountInfo},
 };
 
 /// Validates that the signer is authorized either directly as the authority,
@@ -17,18 +18,27 @@ use crate::{
 /// # Arguments
 /// * `signer` - The account that signed the transaction
 /// * `session_token` - Optional session token account
+/// * `session_scope` - The session's scope account, required with a session
+///   token. Empty when the authority has not limited the session.
+/// * `market` - Market the instruction acts on
+/// * `instruction` - Instruction being authorized
 /// * `session_keys_program_id` - The session-keys program ID
 /// * `manifest_program_id` - The Manifest program ID (target program)
 ///
 /// # Returns
-/// * `Ok(Pubkey)` - The trader authority (from session or direct signer)
+/// * `Ok((Pubkey, Option<SessionScope>))` - The trader authority (from session
+///   or direct signer) and the session's scope, for the caller to check order
+///   size and position against
 /// * `Err(ProgramError)` if validation fails
 pub fn validate_session_or_authority<'a>(
     signer: &AccountInfo<'a>,
     session_token: Option<&AccountInfo<'a>>,
+    session_scope: Option<&AccountInfo<'a>>,
+    market: &Pubkey,
+    instruction: ManifestInstruction,
     session_keys_program_id: &Pubkey,
     manifest_program_id: &Pubkey,
-) -> Result<Pubkey, ProgramError> {
+) -> Result<(Pubkey, Option<SessionScope>), ProgramError> {
     // If no session token provided, signer must be the authority
     if session_token.is_none() {
         require!(
@@ -36,7 +46,7 @@ pub fn validate_session_or_authority<'a>(
             ProgramError::MissingRequiredSignature,
             "Authority must sign transaction"
         )?;
-        return Ok(*signer.key);
+        return Ok((*signer.key, None));
     }
 
     // Session token provided - validate it
@@ -70,7 +80,8 @@ pub fn validate_session_or_authority<'a>(
 
     // Skip the 8-byte Anchor discriminator
     let session = bytemuck::try_from_bytes::<SessionToken>(
-        &session_data[SESSION_TOKEN_DISCRIMINATOR_SIZE..SESSION_TOKEN_DISCRIMINATOR_SIZE + struct_size],
+        &session_data
+            [SESSION_TOKEN_DISCRIMINATOR_SIZE..SESSION_TOKEN_DISCRIMINATOR_SIZE + struct_size],
     )
     .map_err(|_| ProgramError::from(ManifestError::InvalidSession))?;
 
@@ -79,13 +90,12 @@ pub fn validate_session_or_authority<'a>(
     // The PDA derivation uses the authority (from deserialized data) and the signer.
     // Even if someone crafted malicious data, the PDA wouldn't match unless the
     // session-keys program explicitly created a session for this authority+signer pair.
-    let expected_seeds: &[&[u8]] = &[
-        b"session_token_v2",
-        manifest_program_id.as_ref(),
-        signer.key.as_ref(),
-        session.authority.as_ref(),
-    ];
-    let (expected_pda, _bump) = Pubkey::find_program_address(expected_seeds, session_keys_program_id);
+    let (expected_pda, _bump) = SessionToken::get_address(
+        manifest_program_id,
+        &session.authority,
+        signer.key,
+        session_keys_program_id,
+    );
 
     require!(
         session_token_info.key == &expected_pda,
@@ -115,8 +125,35 @@ pub fn validate_session_or_authority<'a>(
         "Session has expired"
     )?;
 
+    // The scope account is the PDA of this authority and signer, so a session
+    // cannot dodge its limits by passing another account.
+    let session_scope_info = session_scope.ok_or(ManifestError::InvalidSession)?;
+    let (expected_session_scope, _) = get_session_scope_address(&session.authority, signer.key);
+    require!(
+        session_scope_info.key == &expected_session_scope,
+        ProgramError::from(ManifestError::InvalidSession),
+        "Session scope account does not match the session"
+    )?;
+    if session_scope_info.data_is_empty() {
+        return Ok((session.authority, None));
+    }
+    let scope: SessionScope =
+        *ManifestAccountInfo::<SessionScope>::new(session_scope_info)?.get_fixed()?;
+    require!(
+        scope.allows_market(market),
+        ProgramError::from(ManifestError::SessionScopeExceeded),
+        "Session may not act on market {}",
+        market
+    )?;
+    require!(
+        scope.allows_instruction(instruction),
+        ProgramError::from(ManifestError::SessionScopeExceeded),
+        "Session may not sign {:?}",
+        instruction
+    )?;
+
     // Return the trader authority from the session
-    Ok(session.authority)
+    Ok((session.authority, Some(scope)))
 }
 
 #[cfg(test)]
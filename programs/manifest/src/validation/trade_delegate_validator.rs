@@ -0,0 +1,41 @@
+use std::cell::Ref;
+
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    program::error::ManifestError,
+    require,
+    state::TradeDelegate,
+    validation::{get_trade_delegate_address, ManifestAccountInfo, Signer},
+};
+
+/// Validates that the signer is the trade delegate of a seat and returns the
+/// trader the seat belongs to.
+///
+/// The record must be the seat's PDA on this market for `sub_account_id`, so
+/// a delegate cannot act on another market or sub-account of the trader.
+pub fn validate_trade_delegate(
+    signer: &Signer,
+    trade_delegate: &ManifestAccountInfo<TradeDelegate>,
+    market: &Pubkey,
+    sub_account_id: u8,
+) -> Result<Pubkey, ProgramError> {
+    let record: Ref<TradeDelegate> = trade_delegate.get_fixed()?;
+    let trader: Pubkey = *record.get_trader();
+
+    let (expected_trade_delegate, _) = get_trade_delegate_address(market, &trader, sub_account_id);
+    require!(
+        expected_trade_delegate == *trade_delegate.info.key,
+        ManifestError::InvalidTradeDelegate,
+        "Trade delegate record is not for this market and sub-account",
+    )?;
+    require!(
+        !record.is_revoked() && record.get_delegate() == signer.key,
+        ManifestError::InvalidTradeDelegate,
+        "Signer {} is not the trade delegate of {}",
+        signer.key,
+        trader,
+    )?;
+
+    Ok(trader)
+}
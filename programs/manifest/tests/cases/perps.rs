@@ -1,10 +1,20 @@
+use std::rc::Rc;
+
 use solana_program::pubkey::Pubkey;
 use solana_program_test::tokio;
-use solana_sdk::signature::Signer;
+use solana_sdk::signature::{Keypair, Signer};
 
-use manifest::state::OrderType;
+use manifest::{
+    program::{
+        batch_update::PlaceOrderParams, update_market::UpdateMarketParams, ManifestInstruction,
+    },
+    state::{OracleSource, OracleType, OrderType, RestingOrder, RiskTier},
+};
 
-use crate::{build_mock_pyth_data, Side, TestFixture, Token, USDC_UNIT_SIZE};
+use crate::{
+    build_mock_pyth_data, build_mock_pyth_v3_data, build_mock_switchboard_data, expand_market,
+    Side, TestFixture, Token, TokenAccountFixture, USDC_UNIT_SIZE,
+};
 
 /// Price encoding: mantissa=1, exponent=-2 = 0.01 quote atoms per base atom
 /// With base_decimals=9, quote_decimals=6:
@@ -53,10 +63,7 @@ async fn test_open_long_position() -> anyhow::Result<()> {
         .get_trader_position(&second_keypair.pubkey())
         .await;
     assert_eq!(second_pos, SOL as i64, "Second trader should be LONG 1 SOL");
-    assert_eq!(
-        second_cost, TEN_USDC,
-        "Second cost basis should be 10 USDC"
-    );
+    assert_eq!(second_cost, TEN_USDC, "Second cost basis should be 10 USDC");
 
     Ok(())
 }
@@ -98,11 +105,7 @@ async fn test_open_short_position() -> anyhow::Result<()> {
         .market_fixture
         .get_trader_position(&second_keypair.pubkey())
         .await;
-    assert_eq!(
-        second_pos,
-        -(SOL as i64),
-        "Second should be SHORT 1 SOL"
-    );
+    assert_eq!(second_pos, -(SOL as i64), "Second should be SHORT 1 SOL");
 
     Ok(())
 }
@@ -117,16 +120,13 @@ async fn test_initial_margin_reject() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 12000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 12000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
 
     test_fixture.claim_seat().await?;
     test_fixture.deposit(Token::USDC, USDC_UNIT_SIZE).await?; // 1 USDC
 
-    test_fixture
-        .claim_seat_for_keypair(&second_keypair)
-        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -168,16 +168,15 @@ async fn test_initial_margin_accept() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 12000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 12000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 13 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 13 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -218,18 +217,17 @@ async fn test_liquidation_happy_path() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     // Crank funding to cache oracle price at 10 USDC/SOL
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 6 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 6 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -253,31 +251,22 @@ async fn test_liquidation_happy_path() -> anyhow::Result<()> {
     // Payer sells 1 SOL → short at 10 USDC, equity = 6 USDC
     test_fixture.swap(SOL, 0, true, true).await?;
 
-    let (pos, _) = test_fixture.market_fixture.get_trader_position(&payer).await;
+    let (pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
     assert_eq!(pos, -(SOL as i64), "Payer should be SHORT 1 SOL");
 
+    // Advance time so funding crank accepts
+    test_fixture.advance_time_seconds(3600).await;
+
     // Now update oracle to 1 USDC/SOL (price crash from short perspective: SHORT profits)
     // Actually for SHORT to be underwater, price needs to RISE, not fall.
     // Short PnL = cost_basis - current_value. If price rises, current_value > cost_basis → loss.
     // Let's set oracle to 20 USDC/SOL
-    let new_pyth_data = build_mock_pyth_data(20_0000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
-
-    // Advance time so funding crank accepts
-    test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 20_0000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     // Now oracle = 20 USDC/SOL. Payer is SHORT 1 SOL.
@@ -293,7 +282,10 @@ async fn test_liquidation_happy_path() -> anyhow::Result<()> {
         .await?;
 
     // Verify position is closed
-    let (pos_after, _) = test_fixture.market_fixture.get_trader_position(&payer).await;
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
     assert_eq!(pos_after, 0, "Position should be closed after liquidation");
 
     // Verify liquidator received reward
@@ -319,8 +311,7 @@ async fn test_liquidation_reject_healthy() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
@@ -329,9 +320,7 @@ async fn test_liquidation_reject_healthy() -> anyhow::Result<()> {
         .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
         .await?;
 
-    test_fixture
-        .claim_seat_for_keypair(&second_keypair)
-        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -373,17 +362,16 @@ async fn test_liquidation_cancels_orders() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 6 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 6 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -425,22 +413,10 @@ async fn test_liquidation_cancels_orders() -> anyhow::Result<()> {
     let payer_orders_before = orders_before.len(); // includes all traders' orders
 
     // Tank the price to make payer underwater
-    let new_pyth_data = build_mock_pyth_data(20_0000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
     test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 20_0000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     // Liquidate
@@ -449,7 +425,10 @@ async fn test_liquidation_cancels_orders() -> anyhow::Result<()> {
         .await?;
 
     // Verify position is closed
-    let (pos, _) = test_fixture.market_fixture.get_trader_position(&payer).await;
+    let (pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
     assert_eq!(pos, 0, "Position should be closed");
 
     // Verify payer's orders were cancelled (should have fewer total orders now)
@@ -470,8 +449,7 @@ async fn test_funding_rate_application() -> anyhow::Result<()> {
     // Oracle at 10 USDC/SOL
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
@@ -480,9 +458,7 @@ async fn test_funding_rate_application() -> anyhow::Result<()> {
         .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
         .await?;
 
-    test_fixture
-        .claim_seat_for_keypair(&second_keypair)
-        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -516,27 +492,15 @@ async fn test_funding_rate_application() -> anyhow::Result<()> {
         .get_quote_balance_atoms(&second_keypair.pubkey())
         .await;
 
-    // Now set oracle to 8 USDC/SOL (below orderbook mark of ~10)
-    // Mark > Oracle → positive funding rate → longs pay shorts
-    let new_pyth_data = build_mock_pyth_data(8_0000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
-
     // Advance 1 hour for full funding period
     test_fixture.advance_time_seconds(3600).await;
 
+    // Now set oracle to 8 USDC/SOL (below orderbook mark of ~10)
+    // Mark > Oracle → positive funding rate → longs pay shorts
+    test_fixture
+        .set_pyth_price(&pyth_key, 8_0000_0000, -8, 100_000)
+        .await;
+
     // Crank funding — updates global cumulative rate only (lazy settlement).
     test_fixture.crank_funding(&pyth_key).await?;
 
@@ -601,17 +565,16 @@ async fn test_partial_liquidation() -> anyhow::Result<()> {
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
     // 10% initial margin, 5% maintenance, 2% liquidation buffer (default)
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 2 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -633,28 +596,19 @@ async fn test_partial_liquidation() -> anyhow::Result<()> {
     // Payer sells 1 SOL → short, margin = 2 USDC (deposit only)
     test_fixture.swap(SOL, 0, true, true).await?;
 
-    let (pos, _) = test_fixture.market_fixture.get_trader_position(&payer).await;
+    let (pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
     assert_eq!(pos, -(SOL as i64));
 
     // Set oracle to 11.5 USDC and do the first-ever crank.
     // Since last_funding_ts == 0, crank just caches oracle — no funding applied.
     // equity = 2 + (10 - 11.5) = 0.5 USDC, maintenance = 11.5 * 5% = 0.575 → liquidatable
     // target_bps = 700, equity_bps = 434, f = 266/450 ≈ 0.59 → partial liquidation
-    let new_pyth_data = build_mock_pyth_data(11_5000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     // Liquidate
@@ -663,7 +617,10 @@ async fn test_partial_liquidation() -> anyhow::Result<()> {
         .await?;
 
     // Position should be PARTIALLY closed (not zero)
-    let (pos_after, _) = test_fixture.market_fixture.get_trader_position(&payer).await;
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
     assert!(
         pos_after < 0 && pos_after > -(SOL as i64),
         "Position should be partially closed: got {}",
@@ -681,17 +638,16 @@ async fn test_partial_liquidation_cost_basis() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 2 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -712,33 +668,30 @@ async fn test_partial_liquidation_cost_basis() -> anyhow::Result<()> {
 
     test_fixture.swap(SOL, 0, true, true).await?;
 
-    let (_, cost_before) = test_fixture.market_fixture.get_trader_position(&payer).await;
-    assert_eq!(cost_before, TEN_USDC, "Cost basis should be 10 USDC initially");
+    let (_, cost_before) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert_eq!(
+        cost_before, TEN_USDC,
+        "Cost basis should be 10 USDC initially"
+    );
 
     // First-ever crank at 11.5 — just caches oracle, no funding
     // equity = 2 + (10 - 11.5) = 0.5, maintenance = 0.575 → liquidatable, partial
-    let new_pyth_data = build_mock_pyth_data(11_5000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     test_fixture
         .liquidate_for_keypair(&payer, &second_keypair)
         .await?;
 
-    let (pos_after, cost_after) = test_fixture.market_fixture.get_trader_position(&payer).await;
+    let (pos_after, cost_after) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
 
     // Partial liquidation expected — cost basis should be reduced proportionally
     assert!(pos_after != 0, "Should be partial, not full liquidation");
@@ -762,17 +715,16 @@ async fn test_full_liquidation_deeply_underwater() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 2 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -796,22 +748,10 @@ async fn test_full_liquidation_deeply_underwater() -> anyhow::Result<()> {
 
     // Price jumps to 100 USDC → hugely underwater
     // equity = 2 + (10 - 100) = 2 - 90 = -88 (before funding, even more negative after)
-    let new_pyth_data = build_mock_pyth_data(100_0000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
     test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 100_0000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     test_fixture
@@ -819,9 +759,18 @@ async fn test_full_liquidation_deeply_underwater() -> anyhow::Result<()> {
         .await?;
 
     // Should be fully liquidated (not partial)
-    let (pos_after, cost_after) = test_fixture.market_fixture.get_trader_position(&payer).await;
-    assert_eq!(pos_after, 0, "Position should be fully closed when deeply underwater");
-    assert_eq!(cost_after, 0, "Cost basis should be zero after full liquidation");
+    let (pos_after, cost_after) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert_eq!(
+        pos_after, 0,
+        "Position should be fully closed when deeply underwater"
+    );
+    assert_eq!(
+        cost_after, 0,
+        "Cost basis should be zero after full liquidation"
+    );
 
     Ok(())
 }
@@ -834,16 +783,13 @@ async fn test_insurance_fund_covers_bad_debt() -> anyhow::Result<()> {
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
     // Use fees to build up insurance fund, then test bad debt coverage
-    let mut test_fixture =
-        TestFixture::new_with_pyth_and_fees(
-            pyth_key,
-            pyth_data,
-            1000,  // 10% initial margin
-            500,   // 5% maintenance
-            500,   // 5% taker fee (high to build fund quickly)
-            200,   // 2% liquidation buffer
-        )
-        .await;
+    let mut test_fixture = TestFixture::new_with_pyth_and_fees(
+        pyth_key, pyth_data, 1000, // 10% initial margin
+        500,  // 5% maintenance
+        500,  // 5% taker fee (high to build fund quickly)
+        200,  // 2% liquidation buffer
+    )
+    .await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
@@ -852,9 +798,7 @@ async fn test_insurance_fund_covers_bad_debt() -> anyhow::Result<()> {
         .deposit(Token::USDC, 10 * USDC_UNIT_SIZE)
         .await?;
 
-    test_fixture
-        .claim_seat_for_keypair(&second_keypair)
-        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -889,22 +833,10 @@ async fn test_insurance_fund_covers_bad_debt() -> anyhow::Result<()> {
     );
 
     // Now crash the price → bad debt
-    let new_pyth_data = build_mock_pyth_data(100_0000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
     test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 100_0000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     let fund_before = test_fixture
@@ -939,17 +871,16 @@ async fn test_insurance_fund_insufficient() -> anyhow::Result<()> {
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
     // No taker fee → insurance fund stays at 0
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 2 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -978,22 +909,10 @@ async fn test_insurance_fund_insufficient() -> anyhow::Result<()> {
     assert_eq!(fund_before, 0, "Insurance fund should be empty");
 
     // Crash price hugely
-    let new_pyth_data = build_mock_pyth_data(100_0000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
     test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 100_0000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     let second_balance_before = test_fixture
@@ -1011,7 +930,10 @@ async fn test_insurance_fund_insufficient() -> anyhow::Result<()> {
         .market_fixture
         .get_quote_balance_atoms(&payer)
         .await;
-    assert_eq!(payer_balance, 0, "Trader margin should be 0 after bad debt liquidation");
+    assert_eq!(
+        payer_balance, 0,
+        "Trader margin should be 0 after bad debt liquidation"
+    );
 
     // Liquidator should still get some reward (possibly reduced)
     let second_balance_after = test_fixture
@@ -1036,16 +958,13 @@ async fn test_taker_fee_collection() -> anyhow::Result<()> {
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
     // 1% taker fee
-    let mut test_fixture =
-        TestFixture::new_with_pyth_and_fees(
-            pyth_key,
-            pyth_data,
-            1000,  // 10% initial
-            500,   // 5% maintenance
-            100,   // 1% taker fee
-            200,   // 2% buffer
-        )
-        .await;
+    let mut test_fixture = TestFixture::new_with_pyth_and_fees(
+        pyth_key, pyth_data, 1000, // 10% initial
+        500,  // 5% maintenance
+        100,  // 1% taker fee
+        200,  // 2% buffer
+    )
+    .await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
 
     test_fixture.claim_seat().await?;
@@ -1053,9 +972,7 @@ async fn test_taker_fee_collection() -> anyhow::Result<()> {
         .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
         .await?;
 
-    test_fixture
-        .claim_seat_for_keypair(&second_keypair)
-        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -1110,17 +1027,16 @@ async fn test_liquidator_reward_on_notional() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 2 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -1149,21 +1065,9 @@ async fn test_liquidator_reward_on_notional() -> anyhow::Result<()> {
 
     // First-ever crank at 11.5 — just caches oracle, no funding
     // equity = 2 + (10 - 11.5) = 0.5, maintenance = 0.575 → liquidatable, partial
-    let new_pyth_data = build_mock_pyth_data(11_5000_0000, -8, 100_000);
-    {
-        let mut ctx = test_fixture.context.borrow_mut();
-        ctx.set_account(
-            &pyth_key,
-            &solana_sdk::account::Account {
-                lamports: u32::MAX as u64,
-                data: new_pyth_data,
-                owner: Pubkey::new_unique(),
-                executable: false,
-                rent_epoch: 0,
-            }
-            .into(),
-        );
-    }
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
     test_fixture.crank_funding(&pyth_key).await?;
 
     test_fixture
@@ -1204,7 +1108,9 @@ async fn test_withdraw_no_position() -> anyhow::Result<()> {
     assert_eq!(balance_before, 100 * USDC_UNIT_SIZE);
 
     // Withdraw 50 USDC — no position open, should succeed
-    test_fixture.withdraw(Token::USDC, 50 * USDC_UNIT_SIZE).await?;
+    test_fixture
+        .withdraw(Token::USDC, 50 * USDC_UNIT_SIZE)
+        .await?;
 
     let balance_after = test_fixture
         .market_fixture
@@ -1227,17 +1133,16 @@ async fn test_withdraw_with_position_healthy() -> anyhow::Result<()> {
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
     // 10% initial margin, 5% maintenance
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 10 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 10 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -1264,7 +1169,9 @@ async fn test_withdraw_with_position_healthy() -> anyhow::Result<()> {
 
     // Withdraw 8 USDC → remaining margin = 2, equity = 2 + 0 = 2 USDC
     // maintenance = 0.5 → still healthy, should succeed
-    test_fixture.withdraw(Token::USDC, 8 * USDC_UNIT_SIZE).await?;
+    test_fixture
+        .withdraw(Token::USDC, 8 * USDC_UNIT_SIZE)
+        .await?;
 
     let balance_after = test_fixture
         .market_fixture
@@ -1287,16 +1194,15 @@ async fn test_withdraw_rejected_insufficient_margin() -> anyhow::Result<()> {
     let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
 
     // 10% initial margin, 5% maintenance
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
 
     test_fixture.claim_seat().await?;
-    test_fixture.deposit(Token::USDC, 2 * USDC_UNIT_SIZE).await?;
-
     test_fixture
-        .claim_seat_for_keypair(&second_keypair)
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
         .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -1322,9 +1228,7 @@ async fn test_withdraw_rejected_insufficient_margin() -> anyhow::Result<()> {
 
     // Try to withdraw 1.6 USDC → remaining margin = 0.4, equity = 0.4
     // maintenance = 10 * 5% = 0.5 → equity < maintenance → FAIL
-    let result = test_fixture
-        .withdraw(Token::USDC, 1_600_000)
-        .await;
+    let result = test_fixture.withdraw(Token::USDC, 1_600_000).await;
     assert!(
         result.is_err(),
         "Withdrawal should fail: equity would drop below maintenance margin"
@@ -1342,11 +1246,11 @@ async fn test_withdraw_rejected_insufficient_margin() -> anyhow::Result<()> {
 #[tokio::test]
 async fn test_10x_leverage_position() -> anyhow::Result<()> {
     let pyth_key = Pubkey::new_unique();
-    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+    // Zero confidence: the margin check values the short at exactly 10.
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 0);
 
     // 10% initial margin, 5% maintenance
-    let mut test_fixture =
-        TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
     let second_keypair = test_fixture.second_keypair.insecure_clone();
     let payer = test_fixture.payer();
 
@@ -1355,9 +1259,7 @@ async fn test_10x_leverage_position() -> anyhow::Result<()> {
         .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
         .await?;
 
-    test_fixture
-        .claim_seat_for_keypair(&second_keypair)
-        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
     test_fixture
         .deposit_for_keypair(Token::USDC, 2100 * USDC_UNIT_SIZE, &second_keypair)
         .await?;
@@ -1388,11 +1290,7 @@ async fn test_10x_leverage_position() -> anyhow::Result<()> {
         .market_fixture
         .get_trader_position(&payer)
         .await;
-    assert_eq!(
-        pos,
-        -((100 * SOL) as i64),
-        "Payer should be SHORT 100 SOL"
-    );
+    assert_eq!(pos, -((100 * SOL) as i64), "Payer should be SHORT 100 SOL");
     assert_eq!(
         cost,
         1000 * USDC_UNIT_SIZE,
@@ -1412,3 +1310,2955 @@ async fn test_10x_leverage_position() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// ─── Test 20: Uncovered bad debt is socialized on next interaction ──
+
+#[tokio::test]
+async fn test_socialized_loss_charged_on_next_interaction() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    // No taker fee → insurance fund stays at 0, so all bad debt is socialized
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // Payer goes SHORT 1 SOL against second's bid, second goes LONG 1 SOL.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_cumulative_loss_per_unit()
+            .await,
+        0,
+        "Nothing should be socialized before any bad debt"
+    );
+
+    // Price goes 10x, the short is deeply underwater.
+    test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 100_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    test_fixture
+        .liquidate_for_keypair(&payer, &second_keypair)
+        .await?;
+
+    // The deficit could not be covered by the empty insurance fund, so it is
+    // spread over the remaining open interest (second's 1 SOL long).
+    let loss_per_unit: u64 = test_fixture
+        .market_fixture
+        .get_cumulative_loss_per_unit()
+        .await;
+    assert!(loss_per_unit > 0, "Uncovered deficit should be socialized");
+
+    // The charge is lazy: second's margin is untouched until they interact.
+    let second_balance_before = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&second_keypair.pubkey())
+        .await;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    let second_balance_after = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&second_keypair.pubkey())
+        .await;
+
+    let expected_charge: u64 =
+        ((SOL as u128 * loss_per_unit as u128 / 1_000_000_000) as u64).min(second_balance_before);
+    assert!(expected_charge > 0, "Second should owe a share of the loss");
+    assert_eq!(
+        second_balance_after,
+        second_balance_before + USDC_UNIT_SIZE - expected_charge,
+        "Socialized loss should be charged on the next interaction"
+    );
+
+    // Charged only once.
+    test_fixture
+        .deposit_for_keypair(Token::USDC, USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&second_keypair.pubkey())
+            .await,
+        second_balance_after + USDC_UNIT_SIZE,
+        "Socialized loss should not be charged twice"
+    );
+
+    Ok(())
+}
+
+// ─── Test 21: Insurance stakers earn fees, withdraw after requesting and cooldown ──
+
+#[tokio::test]
+async fn test_insurance_stake_earns_fees_after_cooldown() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    // 1% taker fee
+    let mut test_fixture = TestFixture::new_with_pyth_and_fees(
+        pyth_key, pyth_data, 1000, // 10% initial
+        500,  // 5% maintenance
+        100,  // 1% taker fee
+        200,  // 2% buffer
+    )
+    .await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+
+    // Stake into an empty fund: shares are minted 1:1.
+    test_fixture.insurance_deposit(10 * USDC_UNIT_SIZE).await?;
+    assert_eq!(
+        test_fixture.get_insurance_shares().await,
+        10 * USDC_UNIT_SIZE
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_insurance_total_shares()
+            .await,
+        10 * USDC_UNIT_SIZE
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_insurance_fund_balance()
+            .await,
+        10 * USDC_UNIT_SIZE
+    );
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    // Fee = 1% of 10 USDC, all of it owned by the only staker.
+    test_fixture.swap(SOL, 0, true, true).await?;
+    let expected_fee: u64 = TEN_USDC / 100;
+
+    // Nothing can be withdrawn without a request.
+    test_fixture.advance_time_seconds(7 * 24 * 60 * 60).await;
+    assert!(
+        test_fixture
+            .insurance_withdraw(10 * USDC_UNIT_SIZE)
+            .await
+            .is_err(),
+        "Withdraw should fail without a request"
+    );
+
+    assert!(
+        test_fixture
+            .insurance_request_withdraw(11 * USDC_UNIT_SIZE)
+            .await
+            .is_err(),
+        "Cannot request more shares than held"
+    );
+    test_fixture
+        .insurance_request_withdraw(10 * USDC_UNIT_SIZE)
+        .await?;
+
+    // Locked during the cooldown, which runs from the request.
+    assert!(
+        test_fixture
+            .insurance_withdraw(10 * USDC_UNIT_SIZE)
+            .await
+            .is_err(),
+        "Withdraw should fail before the cooldown elapses"
+    );
+
+    test_fixture.advance_time_seconds(7 * 24 * 60 * 60).await;
+
+    let wallet_before: u64 = test_fixture.payer_usdc_fixture.balance_atoms().await;
+    test_fixture.insurance_withdraw(10 * USDC_UNIT_SIZE).await?;
+    let wallet_after: u64 = test_fixture.payer_usdc_fixture.balance_atoms().await;
+
+    assert_eq!(
+        wallet_after - wallet_before,
+        10 * USDC_UNIT_SIZE + expected_fee,
+        "Staker should redeem their stake plus the fees earned"
+    );
+    assert_eq!(test_fixture.get_insurance_shares().await, 0);
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_insurance_fund_balance()
+            .await,
+        0
+    );
+
+    Ok(())
+}
+
+// ─── Test 22: Orderbook liquidation closes against resting liquidity ──
+
+#[tokio::test]
+async fn test_orderbook_liquidation_fills_against_book() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Same funding-free setup as test 9: payer short 1 SOL at 10.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    // Oracle to 11.5, short is below maintenance.
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // No asks to buy the short back from.
+    assert!(
+        test_fixture
+            .liquidate_on_orderbook_for_keypair(&payer, &second_keypair)
+            .await
+            .is_err(),
+        "Orderbook liquidation should fail without liquidity"
+    );
+
+    // Second offers 1 SOL at 11.5 (mantissa 115, exponent -4).
+    test_fixture
+        .place_order_for_keypair(
+            Side::Ask,
+            SOL,
+            115,
+            -4,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+
+    let margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    test_fixture
+        .liquidate_on_orderbook_for_keypair(&payer, &second_keypair)
+        .await?;
+
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    let (maker_pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&second_keypair.pubkey())
+        .await;
+    let closed: i64 = pos_after + SOL as i64;
+    assert!(
+        closed > 0 && pos_after <= 0,
+        "Short should be reduced by the fill: got {}",
+        pos_after,
+    );
+    assert_eq!(
+        maker_pos_after,
+        SOL as i64 - closed,
+        "Maker's long should shrink by the amount filled"
+    );
+
+    // Loss of 1.5 USDC per SOL realized at the fill price, plus the reward.
+    let margin_after: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    let realized_loss: u64 = closed as u64 * 15 / 10_000;
+    assert!(
+        margin_after <= margin_before - realized_loss,
+        "Realized loss should come out of margin: {} -> {}",
+        margin_before,
+        margin_after,
+    );
+
+    Ok(())
+}
+
+// ─── Test 23: A partial liquidation's bad debt is recovered in full ──
+
+#[tokio::test]
+async fn test_partial_liquidation_socializes_whole_deficit() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    // No taker fee and no liquidation buffer, so the insurance fund stays
+    // empty and a small enough equity only closes part of the position.
+    let mut test_fixture =
+        TestFixture::new_with_pyth_and_fees(pyth_key, pyth_data, 1000, 500, 0, 0).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, USDC_UNIT_SIZE * 11 / 10)
+        .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Payer goes short 1 SOL at 10 on close to the minimum margin.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    // At 10.79 the short is below maintenance but still has equity, so only
+    // part of it is closed. The only asks are 7% above mark, and buying back
+    // there loses more than the whole margin.
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_7900_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    test_fixture
+        .place_order_for_keypair(
+            Side::Ask,
+            SOL,
+            1155,
+            -5,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+
+    let margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    test_fixture
+        .liquidate_on_orderbook_for_keypair(&payer, &second_keypair)
+        .await?;
+
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert!(
+        pos_after < 0,
+        "Liquidation should leave part of the short open: got {}",
+        pos_after,
+    );
+    let closed: u64 = SOL - pos_after.unsigned_abs();
+    let (second_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&second_keypair.pubkey())
+        .await;
+    assert_eq!(second_pos, SOL as i64 - closed as i64);
+
+    // Bought back at 11.55 what was sold at 10.
+    let deficit: u64 = (closed * 1155 / 100_000 - closed / 100).saturating_sub(margin_before);
+    assert!(deficit > 0, "The close should leave bad debt");
+
+    // The payer's remaining short was checkpointed past the loss, so the
+    // other side of the open interest owes all of it.
+    let loss_per_unit: u64 = test_fixture
+        .market_fixture
+        .get_cumulative_loss_per_unit()
+        .await;
+    let second_charge: u64 =
+        (second_pos.unsigned_abs() as u128 * loss_per_unit as u128 / 1_000_000_000) as u64;
+    assert!(
+        second_charge + 2 >= deficit && second_charge <= deficit + 2,
+        "Socialized charge {} should cover the deficit {}",
+        second_charge,
+        deficit,
+    );
+
+    Ok(())
+}
+
+// ─── Test 24: Takeover moves the position to the liquidator's seat ──
+
+#[tokio::test]
+async fn test_takeover_liquidation_moves_position() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Same funding-free setup as test 9: payer short 1 SOL at 10.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    let margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    let liquidator_margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&second_keypair.pubkey())
+        .await;
+
+    test_fixture
+        .liquidate_takeover_for_keypair(&payer, &second_keypair)
+        .await?;
+
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    let (liquidator_pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&second_keypair.pubkey())
+        .await;
+    let closed: i64 = pos_after + SOL as i64;
+    assert!(
+        closed > 0 && pos_after < 0,
+        "Short should be partially taken over: got {}",
+        pos_after,
+    );
+    // The liquidator was long 1 SOL, taking over the short nets it down.
+    assert_eq!(liquidator_pos_after, SOL as i64 - closed);
+
+    // Open interest follows both seats.
+    let (total_long, total_short) = test_fixture.market_fixture.get_open_interest().await;
+    assert_eq!(total_long, liquidator_pos_after as u64);
+    assert_eq!(total_short, pos_after.unsigned_abs());
+
+    // Trader pays mark plus the 2.5% discount: 11.5 * 1.025 = 11.7875.
+    let margin_after: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    // Allow a couple of atoms for rounding of the mark notional and discount.
+    let takeover_loss: u64 = closed as u64 * 17875 / 10_000_000;
+    assert!(
+        margin_after <= margin_before - takeover_loss + 2,
+        "Loss at the takeover price should come out of margin: {} -> {}",
+        margin_before,
+        margin_after,
+    );
+
+    // No separate reward on a takeover. The takeover closes part of the
+    // liquidator's long at a profit, which the PnL pool pays out of the loss
+    // just collected from the trader.
+    let liquidator_margin_after: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&second_keypair.pubkey())
+        .await;
+    let trader_loss: u64 = margin_before - margin_after;
+    assert!(
+        liquidator_margin_after <= liquidator_margin_before + trader_loss
+            && liquidator_margin_after + 2 >= liquidator_margin_before + trader_loss,
+        "Liquidator should be paid the trader's loss: {} -> {}, loss {}",
+        liquidator_margin_before,
+        liquidator_margin_after,
+        trader_loss,
+    );
+
+    Ok(())
+}
+
+// ─── Test 25: Market authority updates liquidation parameters ──
+
+#[tokio::test]
+async fn test_update_market_liquidation_params() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    assert_eq!(
+        test_fixture.market_fixture.get_liquidation_params().await,
+        (250, 1000)
+    );
+
+    // Only the creator may update.
+    assert!(
+        test_fixture
+            .update_market_for_keypair(UpdateMarketParams::new(Some(100), None), &second_keypair,)
+            .await
+            .is_err(),
+        "Non-authority should not update the market"
+    );
+
+    // Reward must stay below the 5% maintenance margin.
+    assert!(
+        test_fixture
+            .update_market_for_keypair(UpdateMarketParams::new(Some(500), None), &payer_keypair,)
+            .await
+            .is_err(),
+        "Reward at maintenance margin should be rejected"
+    );
+
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams::new(Some(100), Some(1_000_000)),
+            &payer_keypair,
+        )
+        .await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_liquidation_params().await,
+        (100, 1_000_000)
+    );
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(UpdateMarketParams::new(None, Some(0)), &payer_keypair)
+            .await
+            .is_err(),
+        "Min position size must be positive"
+    );
+
+    // Unset fields are left alone.
+    test_fixture
+        .update_market_for_keypair(UpdateMarketParams::new(None, Some(5000)), &payer_keypair)
+        .await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_liquidation_params().await,
+        (100, 5000)
+    );
+
+    Ok(())
+}
+
+// ─── Test 26: The protocol's cut of taker fees buys insurance shares ──
+
+#[tokio::test]
+async fn test_insurance_protocol_fee_buys_shares() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    // 1% taker fee
+    let mut test_fixture =
+        TestFixture::new_with_pyth_and_fees(pyth_key, pyth_data, 1000, 500, 100, 200).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(
+                UpdateMarketParams {
+                    insurance_protocol_fee_bps: Some(10_001),
+                    ..Default::default()
+                },
+                &payer_keypair,
+            )
+            .await
+            .is_err(),
+        "Protocol fee cannot exceed the whole fee"
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                insurance_protocol_fee_bps: Some(5_000),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+
+    test_fixture.insurance_deposit(10 * USDC_UNIT_SIZE).await?;
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    // Half of the 0.1 USDC fee buys shares at the pre-fee price of 1.
+    let protocol_shares: u64 = TEN_USDC / 100 / 2;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_insurance_protocol_shares()
+            .await,
+        protocol_shares
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_insurance_total_shares()
+            .await,
+        10 * USDC_UNIT_SIZE + protocol_shares
+    );
+
+    test_fixture
+        .insurance_request_withdraw(10 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.advance_time_seconds(7 * 24 * 60 * 60).await;
+    let wallet_before: u64 = test_fixture.payer_usdc_fixture.balance_atoms().await;
+    test_fixture.insurance_withdraw(10 * USDC_UNIT_SIZE).await?;
+    let wallet_after: u64 = test_fixture.payer_usdc_fixture.balance_atoms().await;
+
+    // The staker keeps their pro-rata part of the fee; the protocol's shares
+    // stay in the fund.
+    let fund_balance: u64 = 10 * USDC_UNIT_SIZE + TEN_USDC / 100;
+    let staker_payout: u64 = (10 * USDC_UNIT_SIZE as u128 * fund_balance as u128
+        / (10 * USDC_UNIT_SIZE + protocol_shares) as u128) as u64;
+    assert_eq!(wallet_after - wallet_before, staker_payout);
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_insurance_fund_balance()
+            .await,
+        fund_balance - staker_payout
+    );
+
+    Ok(())
+}
+
+// ─── Test 27: One instruction liquidates every underwater seat ──
+
+#[tokio::test]
+async fn test_batch_liquidation_scans_all_seats() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let third_keypair = Keypair::new();
+    let payer = test_fixture.payer();
+    test_fixture.context.borrow_mut().set_account(
+        &third_keypair.pubkey(),
+        &solana_sdk::account::Account::new(u32::MAX as u64, 0, &solana_sdk::system_program::id())
+            .into(),
+    );
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&third_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 2 * USDC_UNIT_SIZE, &third_keypair)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Payer and the third trader each go short 1 SOL at 10 against the
+    // second trader, who stays long and acts as liquidator.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            3 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+    test_fixture
+        .place_order_for_keypair(
+            Side::Ask,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::ImmediateOrCancel,
+            &third_keypair,
+        )
+        .await?;
+
+    // At 11.5 both shorts are below the 5% maintenance margin.
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    let liquidator_margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&second_keypair.pubkey())
+        .await;
+
+    // An empty list scans every seat.
+    test_fixture
+        .liquidate_batch_for_keypair(vec![], &second_keypair)
+        .await?;
+
+    for trader in [payer, third_keypair.pubkey()] {
+        let (pos_after, _) = test_fixture
+            .market_fixture
+            .get_trader_position(&trader)
+            .await;
+        assert!(
+            pos_after > -(SOL as i64),
+            "Short of {} should be reduced: got {}",
+            trader,
+            pos_after,
+        );
+    }
+    let (liquidator_pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&second_keypair.pubkey())
+        .await;
+    assert_eq!(
+        liquidator_pos_after,
+        2 * SOL as i64,
+        "Liquidator is skipped"
+    );
+    assert!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&second_keypair.pubkey())
+            .await
+            > liquidator_margin_before,
+        "Liquidator should be paid a reward"
+    );
+
+    // Both are back above maintenance, so another pass finds nobody.
+    assert!(
+        test_fixture
+            .liquidate_batch_for_keypair(vec![payer, third_keypair.pubkey()], &second_keypair)
+            .await
+            .is_err(),
+        "Healthy traders should not be liquidated again"
+    );
+
+    Ok(())
+}
+
+// ─── Test 28: Seat scans resume from a given seat and stop at a limit ──
+
+#[tokio::test]
+async fn test_liquidation_scans_in_pages() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let third_keypair = Keypair::new();
+    let fourth_keypair = Keypair::new();
+    let payer = test_fixture.payer();
+    for keypair in [&third_keypair, &fourth_keypair] {
+        test_fixture.context.borrow_mut().set_account(
+            &keypair.pubkey(),
+            &solana_sdk::account::Account::new(
+                u32::MAX as u64,
+                0,
+                &solana_sdk::system_program::id(),
+            )
+            .into(),
+        );
+    }
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+    for keypair in [&third_keypair, &fourth_keypair] {
+        test_fixture.claim_seat_for_keypair(keypair).await?;
+        test_fixture
+            .deposit_for_keypair(Token::USDC, 2 * USDC_UNIT_SIZE, keypair)
+            .await?;
+    }
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // The payer, third and fourth traders each go short 1 SOL at 10.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            3 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+    for keypair in [&third_keypair, &fourth_keypair] {
+        test_fixture
+            .place_order_for_keypair(
+                Side::Ask,
+                SOL,
+                PRICE_10_MANTISSA,
+                PRICE_10_EXPONENT,
+                0,
+                OrderType::ImmediateOrCancel,
+                keypair,
+            )
+            .await?;
+    }
+
+    // At 11.5 all three are below maintenance.
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    assert!(
+        test_fixture
+            .liquidate_scan_for_keypair(1, 0, &second_keypair)
+            .await
+            .is_err(),
+        "Scan must start from a claimed seat"
+    );
+
+    // A scan limited to one seat from the payer's leaves the others alone.
+    let payer_index = test_fixture.market_fixture.get_trader_index(&payer).await;
+    test_fixture
+        .liquidate_scan_for_keypair(payer_index, 1, &second_keypair)
+        .await?;
+    let (payer_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert!(payer_pos > -(SOL as i64), "Payer should be liquidated");
+    for keypair in [&third_keypair, &fourth_keypair] {
+        let (pos, _) = test_fixture
+            .market_fixture
+            .get_trader_position(&keypair.pubkey())
+            .await;
+        assert_eq!(pos, -(SOL as i64), "Seats past the limit are not checked");
+    }
+
+    let fourth_index = test_fixture
+        .market_fixture
+        .get_trader_index(&fourth_keypair.pubkey())
+        .await;
+    test_fixture
+        .liquidate_scan_for_keypair(fourth_index, 1, &second_keypair)
+        .await?;
+    let (fourth_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&fourth_keypair.pubkey())
+        .await;
+    assert!(fourth_pos > -(SOL as i64), "Fourth should be liquidated");
+
+    Ok(())
+}
+
+// ─── Test 29: Orderbook liquidation slippage is a market parameter ──
+
+#[tokio::test]
+async fn test_orderbook_liquidation_slippage_and_skip() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let third_keypair = Keypair::new();
+    let payer_keypair = test_fixture.payer_keypair();
+    let payer = test_fixture.payer();
+    test_fixture.context.borrow_mut().set_account(
+        &third_keypair.pubkey(),
+        &solana_sdk::account::Account::new(u32::MAX as u64, 0, &solana_sdk::system_program::id())
+            .into(),
+    );
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&third_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 2 * USDC_UNIT_SIZE, &third_keypair)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Payer and the third trader each go short 1 SOL at 10.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            3 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+    test_fixture
+        .place_order_for_keypair(
+            Side::Ask,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::ImmediateOrCancel,
+            &third_keypair,
+        )
+        .await?;
+
+    // At 11.5 both shorts are below maintenance. The only ask, 0.5 SOL at
+    // 11.7, is about 1.7% above mark.
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    test_fixture
+        .place_order_for_keypair(
+            Side::Ask,
+            SOL / 2,
+            117,
+            -4,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(
+                UpdateMarketParams {
+                    max_liquidation_slippage_bps: Some(10_000),
+                    ..Default::default()
+                },
+                &payer_keypair,
+            )
+            .await
+            .is_err(),
+        "Slippage must stay below 100%"
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                max_liquidation_slippage_bps: Some(100),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+    assert!(
+        test_fixture
+            .liquidate_batch_on_orderbook_for_keypair(
+                vec![payer, third_keypair.pubkey()],
+                &second_keypair,
+            )
+            .await
+            .is_err(),
+        "Nothing fills within 1% of mark"
+    );
+
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                max_liquidation_slippage_bps: Some(500),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+    // The payer's close takes all of the ask, so the third trader finds no
+    // liquidity and is skipped instead of failing the batch.
+    test_fixture
+        .liquidate_batch_on_orderbook_for_keypair(
+            vec![payer, third_keypair.pubkey()],
+            &second_keypair,
+        )
+        .await?;
+    let (payer_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert_eq!(payer_pos, -(SOL as i64) / 2);
+    let (third_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&third_keypair.pubkey())
+        .await;
+    assert_eq!(third_pos, -(SOL as i64));
+
+    Ok(())
+}
+
+// ─── Test 30: Risk tiers raise margin for larger positions ──
+
+#[tokio::test]
+async fn test_risk_tiers_apply_to_margin_and_liquidation() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Payer goes short 1 SOL at 10 on 2 USDC, fine at the base 10% / 5%.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert!(
+        test_fixture
+            .liquidate_for_keypair(&payer, &second_keypair)
+            .await
+            .is_err(),
+        "Position is healthy without tiers"
+    );
+
+    // From 5 USDC of notional, require 50% initial and 25% maintenance.
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                risk_tiers: Some(vec![RiskTier::new(5 * USDC_UNIT_SIZE, 5000, 2500)]),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+
+    // Increasing the short now needs 5.5 USDC of initial margin.
+    assert!(
+        test_fixture.swap(SOL / 10, 0, true, true).await.is_err(),
+        "Initial margin should use the tier"
+    );
+
+    // And 2 USDC of equity is below the 2.5 USDC tier maintenance margin.
+    test_fixture
+        .liquidate_for_keypair(&payer, &second_keypair)
+        .await?;
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert!(
+        pos_after > -(SOL as i64),
+        "Short should be reduced under the tier: got {}",
+        pos_after,
+    );
+
+    Ok(())
+}
+
+// ─── Test 31: Oracle confidence is bounded and priced in ──
+
+#[tokio::test]
+async fn test_oracle_confidence_limits_crank_and_prices_margin() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 2 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // Payer goes short 1 SOL at 10 on 2 USDC.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    // Oracle still at 10, but only confident to +/- 1.5 (1500 bps).
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 1_5000_0000)
+        .await;
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "Default max confidence should reject a 1500 bps interval"
+    );
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(
+                UpdateMarketParams {
+                    max_oracle_conf_bps: Some(0),
+                    ..Default::default()
+                },
+                &payer_keypair,
+            )
+            .await
+            .is_err(),
+        "Max confidence must be positive"
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                max_oracle_conf_bps: Some(2000),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_confidence().await,
+        (1_5000_0000, 2000)
+    );
+
+    // At 10 the short is healthy, but it is valued at 10 + 1.5 = 11.5:
+    // equity = 2 + (10 - 11.5) = 0.5 < maintenance = 11.5 * 5% = 0.575.
+    test_fixture
+        .liquidate_for_keypair(&payer, &second_keypair)
+        .await?;
+    let (pos_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert!(
+        pos_after > -(SOL as i64),
+        "Short should be reduced at the conservative price: got {}",
+        pos_after,
+    );
+
+    Ok(())
+}
+
+// ─── Test 32: Stale oracle prices are rejected by publish time ──
+
+#[tokio::test]
+async fn test_stale_oracle_blocks_crank_and_margin_checks() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 10 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(
+                UpdateMarketParams {
+                    max_oracle_staleness_secs: Some(0),
+                    ..Default::default()
+                },
+                &payer_keypair,
+            )
+            .await
+            .is_err(),
+        "Max staleness must be positive"
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                max_oracle_staleness_secs: Some(60),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+
+    // The feed stops publishing. Cranking its old price does not help.
+    test_fixture.advance_time_seconds(120).await;
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "Crank should reject a price published 120s ago"
+    );
+    assert!(
+        test_fixture
+            .withdraw(Token::USDC, USDC_UNIT_SIZE)
+            .await
+            .is_err(),
+        "Withdraw with a position should need a fresh oracle"
+    );
+    assert!(
+        test_fixture.swap(SOL / 10, 0, true, true).await.is_err(),
+        "Swap should need a fresh oracle"
+    );
+
+    // Once a fresh price is published and cranked, both work again.
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    test_fixture.withdraw(Token::USDC, USDC_UNIT_SIZE).await?;
+    test_fixture.swap(SOL / 10, 0, true, true).await?;
+
+    Ok(())
+}
+
+// ─── Test 33: PriceUpdateV3 accounts must match the market's feed id ──
+
+#[tokio::test]
+async fn test_pyth_v3_requires_feed_id_and_full_verification() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let payer_keypair = test_fixture.payer_keypair();
+    let feed_id: [u8; 32] = [7; 32];
+    let now: i64 = test_fixture.get_unix_timestamp().await;
+
+    // The oracle account is re-posted as a PriceUpdateV3 pull update.
+    test_fixture.set_pyth_data(
+        &pyth_key,
+        build_mock_pyth_v3_data(feed_id, 10_0000_0000, -8, 100_000, now, true),
+    );
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "PriceUpdateV3 needs a feed id configured on the market"
+    );
+
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                pyth_feed_id: Some(feed_id),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+
+    test_fixture.set_pyth_data(
+        &pyth_key,
+        build_mock_pyth_v3_data([8; 32], 10_0000_0000, -8, 100_000, now, true),
+    );
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "An update for another feed should be rejected"
+    );
+
+    test_fixture.set_pyth_data(
+        &pyth_key,
+        build_mock_pyth_v3_data(feed_id, 10_0000_0000, -8, 100_000, now, false),
+    );
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "A partially verified update should be rejected"
+    );
+
+    test_fixture.set_pyth_data(
+        &pyth_key,
+        build_mock_pyth_v3_data(feed_id, 10_0000_0000, -8, 100_000, now, true),
+    );
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_confidence().await,
+        (100_000, 200)
+    );
+
+    Ok(())
+}
+
+// ─── Test 34: Markets can read a Switchboard On-Demand pull feed ──
+
+#[tokio::test]
+async fn test_switchboard_oracle_feeds_crank_funding() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let payer_keypair = test_fixture.payer_keypair();
+
+    // 10.00 with a 0.01 standard deviation, in 18 decimals.
+    let switchboard_key = Pubkey::new_unique();
+    test_fixture
+        .set_switchboard_price(&switchboard_key, 10 * 10i128.pow(18), 10i128.pow(16))
+        .await;
+    assert!(
+        test_fixture.crank_funding(&switchboard_key).await.is_err(),
+        "Only the market's oracle account can be cranked"
+    );
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(
+                UpdateMarketParams {
+                    oracle_type: Some(OracleType::SwitchboardOnDemand),
+                    ..Default::default()
+                },
+                &payer_keypair,
+            )
+            .await
+            .is_err(),
+        "A new oracle type needs a feed account of that type"
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                oracle_type: Some(OracleType::SwitchboardOnDemand),
+                oracle_feed_account: Some(switchboard_key),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+    // The Pyth price is not kept for the Switchboard feed.
+    assert_eq!(test_fixture.market_fixture.get_oracle_price().await, (0, 0));
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "The old Pyth account is no longer the market's oracle"
+    );
+
+    test_fixture.crank_funding(&switchboard_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_price().await,
+        (10_000_000_000, -9)
+    );
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_confidence().await,
+        (10_000_000, 200)
+    );
+
+    // A feed without samples, or Pyth data at the feed address, is rejected.
+    let now: i64 = test_fixture.get_unix_timestamp().await;
+    let mut no_samples = build_mock_switchboard_data(10 * 10i128.pow(18), 0, now);
+    no_samples[2360] = 0;
+    test_fixture.set_pyth_data(&switchboard_key, no_samples);
+    assert!(
+        test_fixture.crank_funding(&switchboard_key).await.is_err(),
+        "A feed without samples should be rejected"
+    );
+    test_fixture
+        .set_pyth_price(&switchboard_key, 10_0000_0000, -8, 100_000)
+        .await;
+    assert!(
+        test_fixture.crank_funding(&switchboard_key).await.is_err(),
+        "Pyth data should not parse as a Switchboard feed"
+    );
+
+    Ok(())
+}
+
+// ─── Test 35: Extra oracle sources are combined by median ──
+
+#[tokio::test]
+async fn test_median_of_multiple_oracle_sources() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let payer_keypair = test_fixture.payer_keypair();
+
+    // A Switchboard feed at 10.20 and a second Pyth account at 50.00.
+    let switchboard_key = Pubkey::new_unique();
+    let second_pyth_key = Pubkey::new_unique();
+    test_fixture
+        .set_switchboard_price(&switchboard_key, 102 * 10i128.pow(17), 10i128.pow(16))
+        .await;
+    test_fixture
+        .set_pyth_price(&second_pyth_key, 50_0000_0000, -8, 100_000)
+        .await;
+
+    assert!(
+        test_fixture
+            .update_market_for_keypair(
+                UpdateMarketParams {
+                    extra_oracle_sources: Some(vec![OracleSource::new(pyth_key, OracleType::Pyth)]),
+                    ..Default::default()
+                },
+                &payer_keypair,
+            )
+            .await
+            .is_err(),
+        "An extra source cannot repeat the primary oracle account"
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                extra_oracle_sources: Some(vec![
+                    OracleSource::new(switchboard_key, OracleType::SwitchboardOnDemand),
+                    OracleSource::new(second_pyth_key, OracleType::Pyth),
+                ]),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+
+    assert!(
+        test_fixture.crank_funding(&pyth_key).await.is_err(),
+        "Every configured oracle source has to be passed"
+    );
+
+    // The 50.00 outlier is outvoted; the median is the Switchboard price.
+    let oracle_feeds = [pyth_key, switchboard_key, second_pyth_key];
+    test_fixture
+        .crank_funding_with_oracles(&oracle_feeds)
+        .await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_price().await,
+        (10_200_000_000, -9)
+    );
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_confidence().await,
+        (10_000_000, 200)
+    );
+
+    // Once the outlier's confidence is too wide it is filtered out, and the
+    // two remaining prices are averaged with an interval covering both.
+    test_fixture.advance_time_seconds(60).await;
+    test_fixture
+        .set_pyth_price(&second_pyth_key, 50_0000_0000, -8, 5_0000_0000)
+        .await;
+    test_fixture
+        .crank_funding_with_oracles(&oracle_feeds)
+        .await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_price().await,
+        (10_100_000_000, -9)
+    );
+    assert_eq!(
+        test_fixture.market_fixture.get_oracle_confidence().await,
+        (110_000_000, 200)
+    );
+
+    Ok(())
+}
+
+// ─── Test 36: Mark price follows the moving average book premium ──
+
+#[tokio::test]
+async fn test_ema_premium_moves_mark_price() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    assert_eq!(
+        test_fixture.market_fixture.get_ema_premium().await,
+        (0, 1800)
+    );
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                mark_ema_window_secs: Some(600),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 12 * USDC_UNIT_SIZE / 10)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // Book at 10 bid / 12 ask: the mid is 10% over the oracle.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture
+        .place_order_for_keypair(Side::Ask, SOL, 12, -3, 0, OrderType::Limit, &second_keypair)
+        .await?;
+
+    // Payer shorts 1 SOL at 10 with 1.2 USDC. With no premium built up yet
+    // the mark stays at the oracle price and the position is healthy.
+    test_fixture.swap(SOL, 0, true, true).await?;
+    assert!(
+        test_fixture
+            .liquidate_for_keypair(&payer, &second_keypair)
+            .await
+            .is_err(),
+        "A fresh book premium should not move the mark yet"
+    );
+
+    // Half a window at 10% premium: the average is 5% and the mark is the
+    // median of 10, 10.5 and 11, so 10.5. Equity 0.7 stays above 0.525.
+    test_fixture.advance_time_seconds(300).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_ema_premium().await,
+        (50_000_000, 600)
+    );
+    assert!(
+        test_fixture
+            .liquidate_for_keypair(&payer, &second_keypair)
+            .await
+            .is_err(),
+        "Short should still be healthy at a 10.5 mark"
+    );
+
+    // A full window later the average has caught up with the book, the mark
+    // is 11 and the short is liquidatable with the oracle unchanged.
+    test_fixture.advance_time_seconds(600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_ema_premium().await,
+        (100_000_000, 600)
+    );
+    test_fixture
+        .liquidate_for_keypair(&payer, &second_keypair)
+        .await?;
+    let (position_after, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert!(
+        position_after > -(SOL as i64),
+        "Liquidation should reduce the short"
+    );
+
+    Ok(())
+}
+
+// ─── Test 37: Funding uses the premium TWAP, not the crank snapshot ──
+
+#[tokio::test]
+async fn test_funding_uses_time_weighted_premium() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // The book trades at the oracle price for most of the hour.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(SOL, 0, true, true).await?;
+
+    // A minute before the crank a bid at 12 lifts the book 20% over the
+    // oracle.
+    test_fixture.advance_time_seconds(3540).await;
+    test_fixture
+        .place_order_for_keypair(Side::Bid, SOL, 12, -3, 0, OrderType::Limit, &second_keypair)
+        .await?;
+    test_fixture.advance_time_seconds(60).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // A snapshot would have charged the 1% cap. The hour's TWAP premium is
+    // 20% * 60 / 3600 = 0.333%, so longs pay 0.0333 USDC per SOL.
+    assert_eq!(
+        test_fixture.market_fixture.get_cumulative_funding().await,
+        33_333
+    );
+
+    Ok(())
+}
+
+// ─── Test 38: Per-market funding period, cap and interest rate ──
+
+#[tokio::test]
+async fn test_per_market_funding_params() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    assert_eq!(
+        test_fixture.market_fixture.get_funding_params().await,
+        (3600, 10_000_000, 0)
+    );
+
+    // The interest rate has to fit under the cap.
+    assert!(test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                funding_interest_rate: Some(20_000_000),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await
+        .is_err());
+
+    // 10 minute periods, capped at 0.1%, with 0.01% interest per period.
+    test_fixture
+        .update_market_for_keypair(
+            UpdateMarketParams {
+                funding_period_secs: Some(600),
+                max_funding_rate: Some(1_000_000),
+                funding_interest_rate: Some(100_000),
+                ..Default::default()
+            },
+            &payer_keypair,
+        )
+        .await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_funding_params().await,
+        (600, 1_000_000, 100_000)
+    );
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // With the book at the oracle only interest is charged, and a crank
+    // charges at most one period: 0.01% of 10 USDC per SOL.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.advance_time_seconds(1200).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_cumulative_funding().await,
+        1_000
+    );
+
+    // A book 20% over the oracle is held to the market's 0.1% cap.
+    test_fixture
+        .place_order_for_keypair(Side::Bid, SOL, 12, -3, 0, OrderType::Limit, &second_keypair)
+        .await?;
+    test_fixture.advance_time_seconds(600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+    assert_eq!(
+        test_fixture.market_fixture.get_cumulative_funding().await,
+        11_000
+    );
+
+    Ok(())
+}
+
+// ─── Test 39: Funding cranks are recorded in the funding history ──
+
+#[tokio::test]
+async fn test_funding_history_records_cranks() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+
+    test_fixture.create_funding_history().await?;
+    assert!(test_fixture.create_funding_history().await.is_err());
+    assert!(test_fixture.get_funding_history().await.is_empty());
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // The first crank only starts the funding clock, so nothing is recorded.
+    test_fixture.crank_funding_with_history(&[pyth_key]).await?;
+    assert!(test_fixture.get_funding_history().await.is_empty());
+
+    // Half an hour with the book at the oracle charges nothing.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.advance_time_seconds(1800).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding_with_history(&[pyth_key]).await?;
+
+    // A crank without the history account still charges funding but is not
+    // recorded.
+    test_fixture
+        .place_order_for_keypair(Side::Bid, SOL, 12, -3, 0, OrderType::Limit, &second_keypair)
+        .await?;
+    test_fixture.advance_time_seconds(1800).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // An hour 20% over the oracle is held to the 1% cap.
+    test_fixture.advance_time_seconds(3600).await;
+    test_fixture
+        .set_pyth_price(&pyth_key, 10_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding_with_history(&[pyth_key]).await?;
+
+    let history = test_fixture.get_funding_history().await;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].timestamp - history[0].timestamp, 5400);
+    assert_eq!(history[0].funding_rate, 0);
+    assert_eq!(history[0].elapsed_secs, 1800);
+    assert_eq!(history[1].funding_rate, 10_000_000);
+    assert_eq!(history[1].elapsed_secs, 3600);
+    for entry in history {
+        // 10 USDC per SOL is 10_000_000 quote atoms per 1e9 base atoms, and
+        // with a one-sided book the mark stays at the oracle.
+        assert_eq!(entry.oracle_price, 10_000_000);
+        assert_eq!(entry.mark_price, 10_000_000);
+    }
+
+    Ok(())
+}
+
+// ─── Test 40: Seats track realized PnL, funding and fees ──────────
+
+#[tokio::test]
+async fn test_lifetime_pnl_funding_and_fees() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    // 1% taker fee
+    let mut test_fixture =
+        TestFixture::new_with_pyth_and_fees(pyth_key, pyth_data, 1000, 500, 100, 200).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture
+        .create_seat_extension_for_keypair(&payer_keypair)
+        .await?;
+    test_fixture
+        .create_seat_extension_for_keypair(&second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // Payer opens a 1 SOL short at 10 against second's bid.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture
+        .place_order(
+            Side::Ask,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+        )
+        .await?;
+
+    // Opening realizes nothing, the taker pays 1% of 10 USDC.
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_lifetime_stats(&payer)
+            .await,
+        (0, 0, TEN_USDC / 100)
+    );
+
+    // Payer buys back at 8 from second, who sells out of their long.
+    test_fixture
+        .place_order_for_keypair(Side::Ask, SOL, 8, -3, 0, OrderType::Limit, &second_keypair)
+        .await?;
+    test_fixture
+        .place_order(Side::Bid, SOL, 8, -3, 0, OrderType::Limit)
+        .await?;
+
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_position(&payer)
+            .await,
+        (0, 0)
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_lifetime_stats(&payer)
+            .await,
+        (
+            2 * USDC_UNIT_SIZE as i64,
+            0,
+            TEN_USDC / 100 + 8 * USDC_UNIT_SIZE / 100
+        )
+    );
+    // The maker realizes the opposite loss without its extension, which picks
+    // it up the next time it is passed.
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_lifetime_stats(&second_keypair.pubkey())
+            .await,
+        (0, 0, 0)
+    );
+    test_fixture
+        .settle_pnl_for_keypair(&second_keypair.pubkey(), &payer_keypair)
+        .await?;
+    // The maker pays no fee.
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_lifetime_stats(&second_keypair.pubkey())
+            .await,
+        (-2 * USDC_UNIT_SIZE as i64, 0, 0)
+    );
+
+    Ok(())
+}
+
+// ─── Test 41: Realized PnL is settled into margin through the PnL pool ──
+
+#[tokio::test]
+async fn test_settle_pnl_moves_realized_pnl_into_margin() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+    let payer_keypair = test_fixture.payer_keypair();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // Payer opens a 1 SOL short at 10 against second's bid.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture
+        .place_order(
+            Side::Ask,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+        )
+        .await?;
+
+    let payer_margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    let second_margin_before: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&second_keypair.pubkey())
+        .await;
+
+    // Payer buys back at 8 from second, who sells out of their long.
+    test_fixture
+        .place_order_for_keypair(Side::Ask, SOL, 8, -3, 0, OrderType::Limit, &second_keypair)
+        .await?;
+    test_fixture
+        .place_order(Side::Bid, SOL, 8, -3, 0, OrderType::Limit)
+        .await?;
+
+    // Both sides realized 2 USDC. Nobody has paid the payer's profit yet, so
+    // it waits as unsettled PnL next to the maker's unsettled loss.
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_unsettled_pnl(&payer)
+            .await,
+        2 * USDC_UNIT_SIZE as i64
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_unsettled_pnl(&second_keypair.pubkey())
+            .await,
+        -2 * USDC_UNIT_SIZE as i64
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        payer_margin_before
+    );
+    assert_eq!(test_fixture.market_fixture.get_pnl_pool_balance().await, 0);
+
+    // Anyone can settle the maker, which moves the loss into the pool.
+    test_fixture
+        .settle_pnl_for_keypair(&second_keypair.pubkey(), &payer_keypair)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&second_keypair.pubkey())
+            .await,
+        second_margin_before - 2 * USDC_UNIT_SIZE
+    );
+    assert_eq!(
+        test_fixture.market_fixture.get_pnl_pool_balance().await,
+        2 * USDC_UNIT_SIZE
+    );
+
+    // Now the pool can pay the payer's profit.
+    test_fixture
+        .settle_pnl_for_keypair(&payer, &payer_keypair)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        payer_margin_before + 2 * USDC_UNIT_SIZE
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_trader_unsettled_pnl(&payer)
+            .await,
+        0
+    );
+    assert_eq!(test_fixture.market_fixture.get_pnl_pool_balance().await, 0);
+
+    // Payer shorts again at 10 and the price halves, a 5 USDC unrealized profit.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture
+        .place_order(
+            Side::Ask,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+        )
+        .await?;
+    test_fixture
+        .set_pyth_price(&pyth_key, 5_0000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // The unrealized profit would cover maintenance, but it cannot back a
+    // withdrawal of the whole margin.
+    let payer_margin: u64 = test_fixture
+        .market_fixture
+        .get_quote_balance_atoms(&payer)
+        .await;
+    assert!(
+        test_fixture
+            .withdraw(Token::USDC, payer_margin)
+            .await
+            .is_err(),
+        "Unrealized profit should not back a withdrawal"
+    );
+    test_fixture
+        .withdraw(Token::USDC, payer_margin - USDC_UNIT_SIZE)
+        .await?;
+
+    Ok(())
+}
+
+// ─── Test 42: A full batch fits the default compute budget ──
+
+/// Orders clients send in one BatchUpdate at most.
+const MAX_BATCH_ORDERS: u32 = 20;
+/// Compute units an instruction gets without a compute budget instruction.
+const DEFAULT_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+#[tokio::test]
+async fn test_full_batch_update_compute_units() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    // A taker fee so every fill collects one.
+    let mut test_fixture =
+        TestFixture::new_with_pyth_and_fees(pyth_key, pyth_data, 1000, 500, 100, 200).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer_keypair = test_fixture.payer_keypair();
+    expand_market(
+        Rc::clone(&test_fixture.context),
+        &test_fixture.market_fixture.key,
+        2 * MAX_BATCH_ORDERS,
+    )
+    .await?;
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture
+        .create_seat_extension_for_keypair(&payer_keypair)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // Second rests asks at 10..19 USDC.
+    let num_fills: u32 = MAX_BATCH_ORDERS / 2;
+    for i in 0..num_fills {
+        test_fixture
+            .place_order_for_keypair(
+                Side::Ask,
+                SOL / 100,
+                10 + i,
+                -3,
+                0,
+                OrderType::Limit,
+                &second_keypair,
+            )
+            .await?;
+    }
+
+    // Half of the batch takes one ask each, the other half rests below.
+    let orders: Vec<PlaceOrderParams> = (0..MAX_BATCH_ORDERS)
+        .map(|i| {
+            let price_mantissa: u32 = if i < num_fills {
+                10 + i
+            } else {
+                i - num_fills + 1
+            };
+            PlaceOrderParams::new(SOL / 100, price_mantissa, -3, true, OrderType::Limit, 0)
+        })
+        .collect();
+    let units_consumed: u64 = test_fixture
+        .batch_update_compute_units_for_keypair(vec![], orders, &payer_keypair)
+        .await?;
+    assert!(
+        units_consumed < DEFAULT_COMPUTE_UNIT_LIMIT,
+        "A batch of {} orders used {} compute units",
+        MAX_BATCH_ORDERS,
+        units_consumed
+    );
+
+    let (payer_position, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer_keypair.pubkey())
+        .await;
+    assert_eq!(payer_position, (num_fills as u64 * SOL / 100) as i64);
+
+    Ok(())
+}
+
+// ─── Test 43: Cross-margin collateral backs a linked seat ─────────
+
+#[tokio::test]
+async fn test_cross_margin_collateral_backs_linked_seat() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    // Zero confidence: the margin check values the short at exactly 10.
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 0);
+
+    // 10% initial margin, 5% maintenance
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 50 * USDC_UNIT_SIZE)
+        .await?;
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 2100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            200 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+
+    // Short 100 SOL at 10 needs 100 USDC of initial margin, the seat alone
+    // only has 50.
+    assert!(
+        test_fixture.swap(100 * SOL, 0, true, true).await.is_err(),
+        "Isolated seat should not open the position"
+    );
+
+    test_fixture.create_cross_margin_account().await?;
+    test_fixture
+        .cross_margin_deposit(50 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.cross_margin_link(true).await?;
+    assert_eq!(
+        test_fixture.get_cross_margin_collateral().await,
+        50 * USDC_UNIT_SIZE
+    );
+
+    // Seat margin 50 + collateral 50 = 100 covers the initial margin.
+    test_fixture
+        .swap_cross_margin(100 * SOL, 0, true, true)
+        .await?;
+    let (pos, _cost) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert_eq!(pos, -((100 * SOL) as i64), "Payer should be SHORT 100 SOL");
+
+    // Maintenance is 50 USDC: taking 60 out leaves 40, taking 40 leaves 60.
+    assert!(
+        test_fixture
+            .cross_margin_withdraw(60 * USDC_UNIT_SIZE)
+            .await
+            .is_err(),
+        "Collateral backing the position should stay"
+    );
+    test_fixture
+        .cross_margin_withdraw(40 * USDC_UNIT_SIZE)
+        .await?;
+    assert_eq!(
+        test_fixture.get_cross_margin_collateral().await,
+        10 * USDC_UNIT_SIZE
+    );
+
+    // An open position keeps the seat linked.
+    assert!(
+        test_fixture.cross_margin_link(false).await.is_err(),
+        "Seat with an open position should not unlink"
+    );
+
+    Ok(())
+}
+
+// ─── Test 44: Collateral moves between an isolated seat and cross margin ──
+
+#[tokio::test]
+async fn test_cross_margin_transfer_keeps_isolated_seat_margined() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    // Zero confidence: the margin check values the short at exactly 10.
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 0);
+
+    // 10% initial margin, 5% maintenance
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_seat().await?;
+    test_fixture
+        .deposit(Token::USDC, 100 * USDC_UNIT_SIZE)
+        .await?;
+    test_fixture.create_cross_margin_account().await?;
+
+    // Nothing is open yet, so the seat can give up any of its margin.
+    test_fixture
+        .cross_margin_transfer(40 * USDC_UNIT_SIZE, true)
+        .await?;
+    assert!(
+        test_fixture
+            .cross_margin_transfer(50 * USDC_UNIT_SIZE, false)
+            .await
+            .is_err(),
+        "Only 40 USDC of collateral is there to move back"
+    );
+    test_fixture
+        .cross_margin_transfer(40 * USDC_UNIT_SIZE, false)
+        .await?;
+    assert_eq!(test_fixture.get_cross_margin_collateral().await, 0);
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        100 * USDC_UNIT_SIZE
+    );
+
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 2100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+    test_fixture.crank_funding(&pyth_key).await?;
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            200 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap(100 * SOL, 0, true, true).await?;
+
+    // The isolated short needs 50 USDC of maintenance margin on the seat
+    // itself: moving 60 out leaves 40, moving 50 leaves exactly 50.
+    assert!(
+        test_fixture
+            .cross_margin_transfer(60 * USDC_UNIT_SIZE, true)
+            .await
+            .is_err(),
+        "Isolated seat should keep its maintenance margin"
+    );
+    test_fixture
+        .cross_margin_transfer(50 * USDC_UNIT_SIZE, true)
+        .await?;
+    assert_eq!(
+        test_fixture.get_cross_margin_collateral().await,
+        50 * USDC_UNIT_SIZE
+    );
+
+    Ok(())
+}
+
+// ─── Test 45: Liquidation skips cross seats without their accounts ──
+
+#[tokio::test]
+async fn test_liquidation_skips_cross_seats_without_accounts() -> anyhow::Result<()> {
+    let pyth_key = Pubkey::new_unique();
+    let pyth_data = build_mock_pyth_data(10_0000_0000, -8, 100_000);
+
+    let mut test_fixture = TestFixture::new_with_pyth(pyth_key, pyth_data, 1000, 500).await;
+    let second_keypair = test_fixture.second_keypair.insecure_clone();
+    let third_keypair = Keypair::new();
+    let payer = test_fixture.payer();
+    test_fixture.context.borrow_mut().set_account(
+        &third_keypair.pubkey(),
+        &solana_sdk::account::Account::new(u32::MAX as u64, 0, &solana_sdk::system_program::id())
+            .into(),
+    );
+
+    test_fixture.claim_seat().await?;
+    test_fixture.deposit(Token::USDC, USDC_UNIT_SIZE).await?;
+    test_fixture.create_cross_margin_account().await?;
+    test_fixture.cross_margin_deposit(USDC_UNIT_SIZE).await?;
+    test_fixture.cross_margin_link(true).await?;
+    test_fixture.claim_seat_for_keypair(&third_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 2 * USDC_UNIT_SIZE, &third_keypair)
+        .await?;
+    test_fixture.claim_seat_for_keypair(&second_keypair).await?;
+    test_fixture
+        .deposit_for_keypair(Token::USDC, 100 * USDC_UNIT_SIZE, &second_keypair)
+        .await?;
+
+    // The payer, on cross margin, and the isolated third trader each go
+    // short 1 SOL at 10.
+    test_fixture
+        .place_order_for_keypair(
+            Side::Bid,
+            2 * SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::Limit,
+            &second_keypair,
+        )
+        .await?;
+    test_fixture.swap_cross_margin(SOL, 0, true, true).await?;
+    test_fixture
+        .place_order_for_keypair(
+            Side::Ask,
+            SOL,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            0,
+            OrderType::ImmediateOrCancel,
+            &third_keypair,
+        )
+        .await?;
+
+    // At 11.5 both are below maintenance.
+    test_fixture
+        .set_pyth_price(&pyth_key, 11_5000_0000, -8, 100_000)
+        .await;
+    test_fixture.crank_funding(&pyth_key).await?;
+
+    // The payer's cross-margin accounts are not passed, so the payer is
+    // skipped and the batch goes on to the third trader.
+    test_fixture
+        .liquidate_batch_for_keypair(vec![payer, third_keypair.pubkey()], &second_keypair)
+        .await?;
+    let (payer_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&payer)
+        .await;
+    assert_eq!(payer_pos, -(SOL as i64), "Cross seat should be skipped");
+    let (third_pos, _) = test_fixture
+        .market_fixture
+        .get_trader_position(&third_keypair.pubkey())
+        .await;
+    assert_eq!(third_pos, 0);
+
+    Ok(())
+}
+
+// ─── Test 46: Sub-accounts keep separate balances on one market ──
+
+#[tokio::test]
+async fn test_sub_accounts_hold_separate_balances() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let payer = test_fixture.payer();
+
+    // The fixture already claimed and funded the payer's default seat.
+    test_fixture.claim_sub_account_seat(1).await?;
+    assert!(
+        test_fixture.claim_sub_account_seat(1).await.is_err(),
+        "Sub-account 1 is already claimed"
+    );
+
+    test_fixture
+        .deposit_to_sub_account(30 * USDC_UNIT_SIZE, 1)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_sub_account_quote_balance_atoms(&payer, 1)
+            .await,
+        30 * USDC_UNIT_SIZE
+    );
+
+    // Each sub-account only withdraws its own margin.
+    assert!(
+        test_fixture
+            .withdraw_from_sub_account(40 * USDC_UNIT_SIZE, 1)
+            .await
+            .is_err(),
+        "Sub-account 1 only holds 30 USDC"
+    );
+    test_fixture
+        .withdraw_from_sub_account(30 * USDC_UNIT_SIZE, 1)
+        .await?;
+    test_fixture.release_sub_account_seat(1).await?;
+
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        100 * USDC_UNIT_SIZE
+    );
+
+    Ok(())
+}
+
+// ─── Test 47: Cross margin links and funds a chosen sub-account seat ──
+
+#[tokio::test]
+async fn test_cross_margin_sub_account_seat() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let payer = test_fixture.payer();
+
+    test_fixture.claim_sub_account_seat(1).await?;
+    test_fixture
+        .deposit_to_sub_account(30 * USDC_UNIT_SIZE, 1)
+        .await?;
+    test_fixture.create_cross_margin_account().await?;
+
+    test_fixture.cross_margin_link_sub_account(true, 1).await?;
+    assert!(
+        test_fixture
+            .cross_margin_link_sub_account(true, 1)
+            .await
+            .is_err(),
+        "Sub-account 1 is already linked"
+    );
+
+    // Only sub-account 1 pays into cross margin, the default seat is
+    // untouched.
+    test_fixture
+        .cross_margin_transfer_sub_account(10 * USDC_UNIT_SIZE, true, 1)
+        .await?;
+    assert_eq!(
+        test_fixture.get_cross_margin_collateral().await,
+        10 * USDC_UNIT_SIZE
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_sub_account_quote_balance_atoms(&payer, 1)
+            .await,
+        20 * USDC_UNIT_SIZE
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        100 * USDC_UNIT_SIZE
+    );
+
+    // Linking the default seat too keeps the market linked when
+    // sub-account 1 unlinks.
+    test_fixture.cross_margin_link(true).await?;
+    test_fixture.cross_margin_link_sub_account(false, 1).await?;
+    test_fixture
+        .cross_margin_transfer(10 * USDC_UNIT_SIZE, false)
+        .await?;
+    assert_eq!(test_fixture.get_cross_margin_collateral().await, 0);
+
+    Ok(())
+}
+
+// ─── Test 48: A trade delegate can place orders until revoked ──
+
+#[tokio::test]
+async fn test_trade_delegate_places_orders_until_revoked() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let delegate = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+
+    assert!(
+        test_fixture
+            .place_order_as_trade_delegate(
+                Side::Bid,
+                SOL / 10,
+                PRICE_10_MANTISSA,
+                PRICE_10_EXPONENT,
+                &delegate,
+            )
+            .await
+            .is_err(),
+        "No delegate has been set yet"
+    );
+
+    test_fixture.set_trade_delegate(&delegate.pubkey()).await?;
+    test_fixture
+        .place_order_as_trade_delegate(
+            Side::Bid,
+            SOL / 10,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            &delegate,
+        )
+        .await?;
+
+    // The order rests on the payer's seat, not the delegate's.
+    test_fixture.market_fixture.reload().await;
+    let payer_index = test_fixture.market_fixture.market.get_trader_index(&payer);
+    let orders: Vec<RestingOrder> = test_fixture.market_fixture.get_resting_orders().await;
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].get_trader_index(), payer_index);
+
+    test_fixture.revoke_trade_delegate().await?;
+    assert!(
+        test_fixture
+            .place_order_as_trade_delegate(
+                Side::Bid,
+                SOL / 10,
+                PRICE_10_MANTISSA,
+                PRICE_10_EXPONENT,
+                &delegate,
+            )
+            .await
+            .is_err(),
+        "Revoked delegate can no longer trade"
+    );
+
+    Ok(())
+}
+
+// ─── Test 49: A session withdraws only to the authority's own account ──
+
+#[tokio::test]
+async fn test_session_withdraw_pays_only_the_authority() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let session_signer = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+    test_fixture
+        .create_session_token(&session_signer.pubkey())
+        .await;
+
+    let session_signer_usdc: TokenAccountFixture = TokenAccountFixture::new(
+        Rc::clone(&test_fixture.context),
+        &test_fixture.usdc_mint_fixture.key,
+        &session_signer.pubkey(),
+    )
+    .await;
+    assert!(
+        test_fixture
+            .withdraw_with_session(TEN_USDC, &session_signer, &session_signer_usdc.key)
+            .await
+            .is_err(),
+        "Session withdrawals cannot pay the session signer"
+    );
+
+    let payer_usdc: Pubkey = test_fixture.payer_usdc_fixture.key;
+    test_fixture
+        .withdraw_with_session(TEN_USDC, &session_signer, &payer_usdc)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        100 * USDC_UNIT_SIZE - TEN_USDC
+    );
+
+    Ok(())
+}
+
+// ─── Test 50: A session deposits only from the signer or the authority ──
+
+#[tokio::test]
+async fn test_session_deposit_source_owner() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let session_signer = test_fixture.second_keypair.insecure_clone();
+    let payer = test_fixture.payer();
+    let payer_keypair = test_fixture.payer_keypair();
+    test_fixture
+        .create_session_token(&session_signer.pubkey())
+        .await;
+
+    // Someone else's account is refused even with the session signer as
+    // its delegate.
+    let stranger: Keypair = Keypair::new();
+    let stranger_usdc: TokenAccountFixture = TokenAccountFixture::new(
+        Rc::clone(&test_fixture.context),
+        &test_fixture.usdc_mint_fixture.key,
+        &stranger.pubkey(),
+    )
+    .await;
+    test_fixture
+        .usdc_mint_fixture
+        .mint_to(&stranger_usdc.key, TEN_USDC)
+        .await;
+    test_fixture
+        .approve_token_delegate(
+            &stranger,
+            &stranger_usdc.key,
+            &session_signer.pubkey(),
+            TEN_USDC,
+        )
+        .await?;
+    assert!(
+        test_fixture
+            .deposit_with_session(TEN_USDC, &session_signer, &stranger_usdc.key)
+            .await
+            .is_err(),
+        "Session deposits cannot spend a third party's tokens"
+    );
+
+    // The authority's account works once the session signer is approved.
+    let payer_usdc: Pubkey = test_fixture.payer_usdc_fixture.key;
+    test_fixture
+        .usdc_mint_fixture
+        .mint_to(&payer_usdc, TEN_USDC)
+        .await;
+    test_fixture
+        .approve_token_delegate(
+            &payer_keypair,
+            &payer_usdc,
+            &session_signer.pubkey(),
+            TEN_USDC,
+        )
+        .await?;
+    test_fixture
+        .deposit_with_session(TEN_USDC, &session_signer, &payer_usdc)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        100 * USDC_UNIT_SIZE + TEN_USDC
+    );
+
+    Ok(())
+}
+
+// ─── Test 51: A session scope limits order size, position and instructions ──
+
+#[tokio::test]
+async fn test_session_scope_limits_session() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let session_signer = test_fixture.second_keypair.insecure_clone();
+    test_fixture
+        .create_session_token(&session_signer.pubkey())
+        .await;
+    test_fixture
+        .set_session_scope(
+            &session_signer.pubkey(),
+            5 * USDC_UNIT_SIZE,
+            SOL / 10,
+            &[ManifestInstruction::BatchUpdate],
+        )
+        .await?;
+
+    // 0.1 SOL at 10 USDC is 1 USDC of notional and a 0.1 SOL position.
+    test_fixture
+        .place_order_with_session(
+            Side::Bid,
+            SOL / 10,
+            PRICE_10_MANTISSA,
+            PRICE_10_EXPONENT,
+            &session_signer,
+        )
+        .await?;
+    assert!(
+        test_fixture
+            .place_order_with_session(
+                Side::Bid,
+                SOL,
+                PRICE_10_MANTISSA,
+                PRICE_10_EXPONENT,
+                &session_signer,
+            )
+            .await
+            .is_err(),
+        "10 USDC of notional is over the order limit"
+    );
+    assert!(
+        test_fixture
+            .place_order_with_session(
+                Side::Bid,
+                SOL / 5,
+                PRICE_10_MANTISSA,
+                PRICE_10_EXPONENT,
+                &session_signer,
+            )
+            .await
+            .is_err(),
+        "A 0.2 SOL position is over the position limit"
+    );
+    assert!(
+        test_fixture
+            .place_order_with_session_token_only(
+                Side::Bid,
+                SOL / 100,
+                PRICE_10_MANTISSA,
+                PRICE_10_EXPONENT,
+                &session_signer,
+            )
+            .await
+            .is_err(),
+        "A session cannot leave its scope account out"
+    );
+
+    let payer_usdc: Pubkey = test_fixture.payer_usdc_fixture.key;
+    assert!(
+        test_fixture
+            .withdraw_with_session(TEN_USDC, &session_signer, &payer_usdc)
+            .await
+            .is_err(),
+        "The scope does not allow Withdraw"
+    );
+
+    Ok(())
+}
+
+// ─── Test 52: Deposit for another trader and withdraw to an allowlisted wallet ──
+
+#[tokio::test]
+async fn test_deposit_for_beneficiary_and_withdraw_to_allowlisted_wallet() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let payer = test_fixture.payer();
+    let beneficiary = test_fixture.second_keypair.pubkey();
+
+    // The payer's tokens fund the beneficiary's seat.
+    test_fixture
+        .deposit_for_beneficiary(TEN_USDC, &beneficiary)
+        .await?;
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&beneficiary)
+            .await,
+        100 * USDC_UNIT_SIZE + TEN_USDC
+    );
+    assert_eq!(
+        test_fixture
+            .market_fixture
+            .get_quote_balance_atoms(&payer)
+            .await,
+        100 * USDC_UNIT_SIZE
+    );
+
+    let cold_wallet: Pubkey = Pubkey::new_unique();
+    let cold_wallet_usdc: TokenAccountFixture = TokenAccountFixture::new(
+        Rc::clone(&test_fixture.context),
+        &test_fixture.usdc_mint_fixture.key,
+        &cold_wallet,
+    )
+    .await;
+    assert!(
+        test_fixture
+            .withdraw_to_recipient(TEN_USDC, &cold_wallet_usdc.key)
+            .await
+            .is_err(),
+        "The cold wallet is not allowlisted yet"
+    );
+
+    test_fixture
+        .set_withdraw_allowlist(vec![cold_wallet])
+        .await?;
+    test_fixture
+        .withdraw_to_recipient(TEN_USDC, &cold_wallet_usdc.key)
+        .await?;
+    assert_eq!(cold_wallet_usdc.balance_atoms().await, TEN_USDC);
+
+    test_fixture.set_withdraw_allowlist(vec![]).await?;
+    assert!(
+        test_fixture
+            .withdraw_to_recipient(TEN_USDC, &cold_wallet_usdc.key)
+            .await
+            .is_err(),
+        "Clearing the allowlist stops withdrawals to the cold wallet"
+    );
+
+    Ok(())
+}
+
+// ─── Test 53: A session cannot withdraw to an allowlisted wallet ──
+
+#[tokio::test]
+async fn test_session_withdraw_ignores_allowlist() -> anyhow::Result<()> {
+    let mut test_fixture = TestFixture::try_new_for_perps_test(100 * USDC_UNIT_SIZE).await?;
+    let session_signer = test_fixture.second_keypair.insecure_clone();
+    test_fixture
+        .create_session_token(&session_signer.pubkey())
+        .await;
+
+    let cold_wallet: Pubkey = Pubkey::new_unique();
+    let cold_wallet_usdc: TokenAccountFixture = TokenAccountFixture::new(
+        Rc::clone(&test_fixture.context),
+        &test_fixture.usdc_mint_fixture.key,
+        &cold_wallet,
+    )
+    .await;
+    test_fixture
+        .set_withdraw_allowlist(vec![cold_wallet])
+        .await?;
+
+    assert!(
+        test_fixture
+            .withdraw_to_recipient_with_session(TEN_USDC, &session_signer, &cold_wallet_usdc.key)
+            .await
+            .is_err(),
+        "Session withdrawals pay only the authority"
+    );
+    test_fixture
+        .withdraw_to_recipient(TEN_USDC, &cold_wallet_usdc.key)
+        .await?;
+    assert_eq!(cold_wallet_usdc.balance_atoms().await, TEN_USDC);
+
+    Ok(())
+}
@@ -10,16 +10,38 @@ use manifest::{
     program::{
         batch_update::{CancelOrderParams, PlaceOrderParams},
         batch_update_instruction,
-        claim_seat_instruction::claim_seat_instruction,
-        create_market_instructions, deposit_instruction, get_dynamic_value,
-        global_add_trader_instruction,
+        claim_seat_instruction::{claim_seat_for_sub_account_instruction, claim_seat_instruction},
+        create_cross_margin_account_instruction, create_funding_history_instruction,
+        create_market_instructions, create_seat_extension_instruction, cross_margin_account_metas,
+        cross_margin_deposit_instruction, cross_margin_link_for_sub_account_instruction,
+        cross_margin_link_instruction, cross_margin_transfer_for_sub_account_instruction,
+        cross_margin_transfer_instruction, cross_margin_withdraw_instruction,
+        deposit_for_beneficiary_instruction, deposit_for_sub_account_instruction,
+        deposit_instruction, get_dynamic_value, global_add_trader_instruction,
         global_create_instruction::create_global_instruction,
-        global_deposit_instruction, global_withdraw_instruction, swap_instruction,
-        swap_v2_instruction, withdraw_instruction,
+        global_deposit_instruction, global_withdraw_instruction, insurance_deposit_instruction,
+        insurance_request_withdraw_instruction, insurance_withdraw_instruction,
+        liquidate::LiquidationMode,
+        release_seat_for_sub_account_instruction, revoke_trade_delegate_instruction,
+        seat_extension_account_meta, session_account_metas, set_session_scope_instruction,
+        set_trade_delegate_instruction, set_withdraw_allowlist_instruction, swap_instruction,
+        swap_v2_instruction, trade_delegate_account_meta,
+        update_market::UpdateMarketParams,
+        update_market_instruction, withdraw_for_sub_account_instruction, withdraw_instruction,
+        withdraw_to_recipient_instruction, ManifestInstruction,
     },
     quantities::WrapperU64,
-    state::{GlobalFixed, GlobalValue, MarketFixed, MarketValue, OrderType, RestingOrder},
-    validation::{get_global_address, get_market_address, get_vault_address, MintAccountInfo},
+    state::{
+        CrossMarginAccount, FundingHistory, FundingHistoryEntry, GlobalFixed, GlobalValue,
+        InsuranceStaker, MarketFixed, MarketValue, OrderType, RestingOrder, SeatExtension,
+        SessionToken, DEFAULT_FUNDING_PERIOD_SECS, DEFAULT_MAX_FUNDING_RATE,
+        SESSION_KEYS_PROGRAM_ID, SESSION_TOKEN_DISCRIMINATOR_SIZE,
+    },
+    validation::{
+        get_cross_margin_address, get_funding_history_address, get_global_address,
+        get_insurance_staker_address, get_market_address, get_seat_extension_address,
+        get_vault_address, get_withdraw_allowlist_address, MintAccountInfo,
+    },
 };
 use solana_program::{hash::Hash, pubkey::Pubkey, rent::Rent};
 use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
@@ -237,9 +259,7 @@ impl TestFixture {
         test_fixture.claim_seat().await?;
         test_fixture.deposit(Token::USDC, usdc_amount).await?;
 
-        test_fixture
-            .claim_seat_for_keypair(&second_keypair)
-            .await?;
+        test_fixture.claim_seat_for_keypair(&second_keypair).await?;
         test_fixture
             .deposit_for_keypair(Token::USDC, usdc_amount, &second_keypair)
             .await?;
@@ -253,7 +273,15 @@ impl TestFixture {
         initial_margin_bps: u64,
         maintenance_margin_bps: u64,
     ) -> TestFixture {
-        Self::new_with_pyth_and_fees(pyth_key, pyth_data, initial_margin_bps, maintenance_margin_bps, 0, 200).await
+        Self::new_with_pyth_and_fees(
+            pyth_key,
+            pyth_data,
+            initial_margin_bps,
+            maintenance_margin_bps,
+            0,
+            200,
+        )
+        .await
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -307,22 +335,31 @@ impl TestFixture {
             );
         }
 
-        // Inject mock Pyth oracle account
-        program.add_account(
-            pyth_key,
-            solana_sdk::account::Account {
+        let context: Rc<RefCell<ProgramTestContext>> =
+            Rc::new(RefCell::new(program.start_with_context().await));
+        solana_logger::setup_with_default(RUST_LOG_DEFAULT);
+
+        // Inject mock Pyth oracle account, published at the starting clock
+        let clock: Clock = context
+            .borrow_mut()
+            .banks_client
+            .get_sysvar()
+            .await
+            .unwrap();
+        let mut pyth_data: Vec<u8> = pyth_data;
+        pyth_data[96..104].copy_from_slice(&clock.unix_timestamp.to_le_bytes());
+        context.borrow_mut().set_account(
+            &pyth_key,
+            &solana_sdk::account::Account {
                 lamports: u32::MAX as u64,
                 data: pyth_data,
                 owner: Pubkey::new_unique(), // Pyth owner doesn't matter for our tests
                 executable: false,
                 rent_epoch: 0,
-            },
+            }
+            .into(),
         );
 
-        let context: Rc<RefCell<ProgramTestContext>> =
-            Rc::new(RefCell::new(program.start_with_context().await));
-        solana_logger::setup_with_default(RUST_LOG_DEFAULT);
-
         let usdc_mint_f: MintFixture = MintFixture::new(Rc::clone(&context), Some(6)).await;
         let sol_mint_f: MintFixture = MintFixture::new(Rc::clone(&context), Some(9)).await;
         let mut market_fixture: MarketFixture = MarketFixture::new_with_pyth(
@@ -395,6 +432,171 @@ impl TestFixture {
         .await
     }
 
+    /// Send a settle_pnl instruction for a trader, signed by the given keypair.
+    pub async fn settle_pnl_for_keypair(
+        &mut self,
+        trader: &Pubkey,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        use manifest::program::settle_pnl_instruction::settle_pnl_instruction;
+        let mut ix = settle_pnl_instruction(&self.market_fixture.key, &keypair.pubkey(), trader);
+        ix.accounts.push(seat_extension_account_meta(
+            &self.market_fixture.key,
+            trader,
+            0,
+        ));
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await
+    }
+
+    /// Send a liquidate instruction that closes the position against the book.
+    pub async fn liquidate_on_orderbook_for_keypair(
+        &mut self,
+        trader_to_liquidate: &Pubkey,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.liquidate_with_mode_for_keypair(
+            trader_to_liquidate,
+            keypair,
+            LiquidationMode::Orderbook,
+        )
+        .await
+    }
+
+    /// Send a liquidate instruction that moves the position to the liquidator.
+    pub async fn liquidate_takeover_for_keypair(
+        &mut self,
+        trader_to_liquidate: &Pubkey,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.liquidate_with_mode_for_keypair(
+            trader_to_liquidate,
+            keypair,
+            LiquidationMode::Takeover,
+        )
+        .await
+    }
+
+    /// Send one liquidate instruction covering `traders_to_liquidate`. An
+    /// empty list lets the program scan every seat.
+    pub async fn liquidate_batch_for_keypair(
+        &mut self,
+        traders_to_liquidate: Vec<Pubkey>,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.liquidate_batch_with_mode_for_keypair(
+            traders_to_liquidate,
+            keypair,
+            LiquidationMode::MarkPrice,
+        )
+        .await
+    }
+
+    /// Send one liquidate instruction that closes each of
+    /// `traders_to_liquidate` against the book.
+    pub async fn liquidate_batch_on_orderbook_for_keypair(
+        &mut self,
+        traders_to_liquidate: Vec<Pubkey>,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.liquidate_batch_with_mode_for_keypair(
+            traders_to_liquidate,
+            keypair,
+            LiquidationMode::Orderbook,
+        )
+        .await
+    }
+
+    async fn liquidate_batch_with_mode_for_keypair(
+        &mut self,
+        traders_to_liquidate: Vec<Pubkey>,
+        keypair: &Keypair,
+        mode: LiquidationMode,
+    ) -> anyhow::Result<(), BanksClientError> {
+        use manifest::program::liquidate_instruction::liquidate_batch_instruction;
+        let ix = liquidate_batch_instruction(
+            &self.market_fixture.key,
+            &keypair.pubkey(),
+            traders_to_liquidate,
+            mode,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await
+    }
+
+    /// Send one liquidate instruction that scans at most `scan_limit` seats
+    /// with a position, starting from `scan_start_index`.
+    pub async fn liquidate_scan_for_keypair(
+        &mut self,
+        scan_start_index: DataIndex,
+        scan_limit: u32,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        use manifest::program::liquidate_instruction::liquidate_scan_instruction;
+        let ix = liquidate_scan_instruction(
+            &self.market_fixture.key,
+            &keypair.pubkey(),
+            LiquidationMode::MarkPrice,
+            scan_start_index,
+            scan_limit,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await
+    }
+
+    async fn liquidate_with_mode_for_keypair(
+        &mut self,
+        trader_to_liquidate: &Pubkey,
+        keypair: &Keypair,
+        mode: LiquidationMode,
+    ) -> anyhow::Result<(), BanksClientError> {
+        use manifest::program::liquidate_instruction::liquidate_instruction_with_mode;
+        let ix = liquidate_instruction_with_mode(
+            &self.market_fixture.key,
+            &keypair.pubkey(),
+            trader_to_liquidate,
+            mode,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await
+    }
+
+    /// Send an update_market instruction signed by `keypair`.
+    pub async fn update_market_for_keypair(
+        &mut self,
+        params: UpdateMarketParams,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = update_market_instruction(&self.market_fixture.key, &keypair.pubkey(), params);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await
+    }
+
     /// Send a crank_funding instruction.
     pub async fn crank_funding(
         &mut self,
@@ -413,6 +615,74 @@ impl TestFixture {
         .await
     }
 
+    /// Send a crank_funding instruction with the primary oracle account
+    /// followed by the market's extra ones.
+    pub async fn crank_funding_with_oracles(
+        &mut self,
+        oracle_feeds: &[Pubkey],
+    ) -> anyhow::Result<(), BanksClientError> {
+        use manifest::program::crank_funding_instruction::crank_funding_with_oracles_instruction;
+        let payer = self.payer();
+        let payer_keypair = self.payer_keypair();
+        let ix =
+            crank_funding_with_oracles_instruction(&self.market_fixture.key, &payer, oracle_feeds);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&payer),
+            &[&payer_keypair],
+        )
+        .await
+    }
+
+    /// Send a crank_funding instruction that also records the crank in the
+    /// market's funding history.
+    pub async fn crank_funding_with_history(
+        &mut self,
+        oracle_feeds: &[Pubkey],
+    ) -> anyhow::Result<(), BanksClientError> {
+        use manifest::program::crank_funding_instruction::crank_funding_with_history_instruction;
+        let payer = self.payer();
+        let payer_keypair = self.payer_keypair();
+        let ix =
+            crank_funding_with_history_instruction(&self.market_fixture.key, &payer, oracle_feeds);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&payer),
+            &[&payer_keypair],
+        )
+        .await
+    }
+
+    /// Create the market's funding history account, paid by the payer.
+    pub async fn create_funding_history(&mut self) -> anyhow::Result<(), BanksClientError> {
+        let payer = self.payer();
+        let payer_keypair = self.payer_keypair();
+        let ix = create_funding_history_instruction(&self.market_fixture.key, &payer);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[ix],
+            Some(&payer),
+            &[&payer_keypair],
+        )
+        .await
+    }
+
+    /// Entries in the market's funding history, oldest first.
+    pub async fn get_funding_history(&self) -> Vec<FundingHistoryEntry> {
+        let (funding_history, _) = get_funding_history_address(&self.market_fixture.key);
+        let account: Account = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_account(funding_history)
+            .await
+            .unwrap()
+            .unwrap();
+        bytemuck::from_bytes::<FundingHistory>(&account.data).get_entries()
+    }
+
     pub async fn try_load(
         &self,
         address: &Pubkey,
@@ -445,6 +715,48 @@ impl TestFixture {
         self.context.borrow_mut().set_sysvar(&clock);
     }
 
+    /// Replace the mock Pyth price account with a price published now.
+    pub async fn set_pyth_price(&self, pyth_key: &Pubkey, price: i64, expo: i32, confidence: u64) {
+        let now: i64 = self.get_unix_timestamp().await;
+        self.set_pyth_data(
+            pyth_key,
+            build_mock_pyth_data_at(price, expo, confidence, now),
+        );
+    }
+
+    /// Replace a mock Switchboard pull feed with a result updated now.
+    /// `value` and `std_dev` are fixed point with 18 decimals.
+    pub async fn set_switchboard_price(&self, feed_key: &Pubkey, value: i128, std_dev: i128) {
+        let now: i64 = self.get_unix_timestamp().await;
+        self.set_pyth_data(feed_key, build_mock_switchboard_data(value, std_dev, now));
+    }
+
+    /// Replace the data of the mock Pyth price account.
+    pub fn set_pyth_data(&self, pyth_key: &Pubkey, data: Vec<u8>) {
+        self.context.borrow_mut().set_account(
+            pyth_key,
+            &solana_sdk::account::Account {
+                lamports: u32::MAX as u64,
+                data,
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            }
+            .into(),
+        );
+    }
+
+    pub async fn get_unix_timestamp(&self) -> i64 {
+        let clock: Clock = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_sysvar()
+            .await
+            .unwrap();
+        clock.unix_timestamp
+    }
+
     pub async fn create_new_market(
         &self,
         base_mint_index: u8,
@@ -455,18 +767,22 @@ impl TestFixture {
         let payer: Pubkey = self.context.borrow().payer.pubkey();
         let payer_keypair: Keypair = self.context.borrow().payer.insecure_clone();
 
-        let create_market_ixs: Vec<Instruction> =
-            create_market_instructions(
-                base_mint_index,
-                base_mint_decimals,
-                quote_mint,
-                &payer,
-                1000,
-                500,
-                Pubkey::default(),
-                0,   // taker_fee_bps
-                200, // liquidation_buffer_bps
-            );
+        let create_market_ixs: Vec<Instruction> = create_market_instructions(
+            base_mint_index,
+            base_mint_decimals,
+            quote_mint,
+            &payer,
+            1000,
+            500,
+            Pubkey::default(),
+            0,    // taker_fee_bps
+            200,  // liquidation_buffer_bps
+            250,  // liquidator_reward_bps
+            1000, // min_position_size_atoms
+            DEFAULT_FUNDING_PERIOD_SECS,
+            DEFAULT_MAX_FUNDING_RATE,
+            0, // funding_interest_rate
+        );
 
         send_tx_with_retry(
             Rc::clone(&self.context),
@@ -497,65 +813,168 @@ impl TestFixture {
         .await
     }
 
-    pub async fn global_add_trader(&self) -> anyhow::Result<(), BanksClientError> {
-        self.global_add_trader_for_keypair(&self.payer_keypair())
-            .await
-    }
-
-    pub async fn global_add_trader_for_keypair(
+    /// Create the extension of the keypair's seat.
+    pub async fn create_seat_extension_for_keypair(
         &self,
         keypair: &Keypair,
     ) -> anyhow::Result<(), BanksClientError> {
+        let create_seat_extension_ix: Instruction =
+            create_seat_extension_instruction(&self.market_fixture.key, &keypair.pubkey(), 0);
         send_tx_with_retry(
             Rc::clone(&self.context),
-            &[global_add_trader_instruction(
-                &self.global_fixture.key,
-                &keypair.pubkey(),
-            )],
+            &[create_seat_extension_ix],
             Some(&keypair.pubkey()),
-            &[&keypair],
+            &[keypair],
         )
         .await
     }
 
-    pub async fn global_deposit(&mut self, num_atoms: u64) -> anyhow::Result<(), BanksClientError> {
-        self.global_deposit_for_keypair(&self.payer_keypair(), num_atoms)
-            .await
-    }
-
-    pub async fn global_deposit_for_keypair(
-        &mut self,
-        keypair: &Keypair,
-        num_atoms: u64,
+    pub async fn claim_sub_account_seat(
+        &self,
+        sub_account_id: u8,
     ) -> anyhow::Result<(), BanksClientError> {
-        // Make a throw away token account
-        let token_account_keypair: Keypair = Keypair::new();
-        let token_account_fixture: TokenAccountFixture = TokenAccountFixture::new_with_keypair(
-            Rc::clone(&self.context),
-            &self.global_fixture.mint_key,
-            &keypair.pubkey(),
-            &token_account_keypair,
-        )
-        .await;
-        self.usdc_mint_fixture
-            .mint_to(&token_account_fixture.key, num_atoms)
-            .await;
+        let claim_seat_ix: Instruction = claim_seat_for_sub_account_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            sub_account_id,
+        );
         send_tx_with_retry(
             Rc::clone(&self.context),
-            &[global_deposit_instruction(
-                &self.global_fixture.mint_key,
-                &keypair.pubkey(),
-                &token_account_fixture.key,
-                &spl_token::id(),
-                num_atoms,
-            )],
-            Some(&keypair.pubkey()),
-            &[&keypair],
+            &[claim_seat_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
         )
         .await
     }
 
-    pub async fn global_withdraw(
+    pub async fn release_sub_account_seat(
+        &self,
+        sub_account_id: u8,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let release_seat_ix: Instruction = release_seat_for_sub_account_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            sub_account_id,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[release_seat_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Deposit USDC from the payer into one of their sub-account seats.
+    pub async fn deposit_to_sub_account(
+        &mut self,
+        num_atoms: u64,
+        sub_account_id: u8,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.usdc_mint_fixture
+            .mint_to(&self.payer_usdc_fixture.key, num_atoms)
+            .await;
+        let deposit_ix: Instruction = deposit_for_sub_account_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+            sub_account_id,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[deposit_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Withdraw USDC from one of the payer's sub-account seats.
+    pub async fn withdraw_from_sub_account(
+        &mut self,
+        num_atoms: u64,
+        sub_account_id: u8,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let withdraw_ix: Instruction = withdraw_for_sub_account_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+            sub_account_id,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[withdraw_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    pub async fn global_add_trader(&self) -> anyhow::Result<(), BanksClientError> {
+        self.global_add_trader_for_keypair(&self.payer_keypair())
+            .await
+    }
+
+    pub async fn global_add_trader_for_keypair(
+        &self,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[global_add_trader_instruction(
+                &self.global_fixture.key,
+                &keypair.pubkey(),
+            )],
+            Some(&keypair.pubkey()),
+            &[&keypair],
+        )
+        .await
+    }
+
+    pub async fn global_deposit(&mut self, num_atoms: u64) -> anyhow::Result<(), BanksClientError> {
+        self.global_deposit_for_keypair(&self.payer_keypair(), num_atoms)
+            .await
+    }
+
+    pub async fn global_deposit_for_keypair(
+        &mut self,
+        keypair: &Keypair,
+        num_atoms: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        // Make a throw away token account
+        let token_account_keypair: Keypair = Keypair::new();
+        let token_account_fixture: TokenAccountFixture = TokenAccountFixture::new_with_keypair(
+            Rc::clone(&self.context),
+            &self.global_fixture.mint_key,
+            &keypair.pubkey(),
+            &token_account_keypair,
+        )
+        .await;
+        self.usdc_mint_fixture
+            .mint_to(&token_account_fixture.key, num_atoms)
+            .await;
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[global_deposit_instruction(
+                &self.global_fixture.mint_key,
+                &keypair.pubkey(),
+                &token_account_fixture.key,
+                &spl_token::id(),
+                num_atoms,
+            )],
+            Some(&keypair.pubkey()),
+            &[&keypair],
+        )
+        .await
+    }
+
+    pub async fn global_withdraw(
         &mut self,
         num_atoms: u64,
     ) -> anyhow::Result<(), BanksClientError> {
@@ -654,7 +1073,7 @@ impl TestFixture {
             (&self.usdc_mint_fixture.key, trader_token_account)
         };
 
-        let deposit_ix: Instruction = deposit_instruction(
+        let mut deposit_ix: Instruction = deposit_instruction(
             &self.market_fixture.key,
             &keypair.pubkey(),
             mint,
@@ -663,6 +1082,11 @@ impl TestFixture {
             spl_token::id(),
             None,
         );
+        // The seat extension is skipped while it has not been created.
+        deposit_ix.accounts.insert(
+            2,
+            seat_extension_account_meta(&self.market_fixture.key, &keypair.pubkey(), 0),
+        );
 
         send_tx_with_retry(
             Rc::clone(&self.context),
@@ -673,73 +1097,685 @@ impl TestFixture {
         .await
     }
 
-    pub async fn withdraw(
+    pub async fn withdraw(
+        &mut self,
+        token: Token,
+        num_atoms: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.withdraw_for_keypair(token, num_atoms, &self.payer_keypair())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn withdraw_for_keypair(
+        &mut self,
+        token: Token,
+        num_atoms: u64,
+        keypair: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let is_base: bool = token == Token::SOL;
+        let (mint, trader_token_account) = if is_base {
+            let trader_token_account: Pubkey = if keypair.pubkey() == self.payer() {
+                self.payer_sol_fixture.key
+            } else {
+                // Make a new token account
+                let token_account_keypair: Keypair = Keypair::new();
+                let token_account_fixture: TokenAccountFixture =
+                    TokenAccountFixture::new_with_keypair(
+                        Rc::clone(&self.context),
+                        &self.sol_mint_fixture.key,
+                        &keypair.pubkey(),
+                        &token_account_keypair,
+                    )
+                    .await;
+                token_account_fixture.key
+            };
+            (&self.sol_mint_fixture.key, trader_token_account)
+        } else {
+            let trader_token_account: Pubkey = if keypair.pubkey() == self.payer() {
+                self.payer_usdc_fixture.key
+            } else {
+                // Make a new token account
+                let token_account_keypair: Keypair = Keypair::new();
+                let token_account_fixture: TokenAccountFixture =
+                    TokenAccountFixture::new_with_keypair(
+                        Rc::clone(&self.context),
+                        &self.usdc_mint_fixture.key,
+                        &keypair.pubkey(),
+                        &token_account_keypair,
+                    )
+                    .await;
+                token_account_fixture.key
+            };
+            (&self.usdc_mint_fixture.key, trader_token_account)
+        };
+
+        let withdraw_ix: Instruction = withdraw_instruction(
+            &self.market_fixture.key,
+            &keypair.pubkey(),
+            mint,
+            num_atoms,
+            &trader_token_account,
+            spl_token::id(),
+            None,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[withdraw_ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await
+    }
+
+    /// Write a session token letting `session_signer` act for the payer, as
+    /// the session-keys program would.
+    pub async fn create_session_token(&self, session_signer: &Pubkey) {
+        let session: SessionToken = SessionToken::new(
+            self.payer(),
+            manifest::id(),
+            *session_signer,
+            i64::MAX,
+            self.payer(),
+        );
+        let (session_token, _) = SessionToken::get_address(
+            &manifest::id(),
+            &self.payer(),
+            session_signer,
+            &SESSION_KEYS_PROGRAM_ID,
+        );
+        self.context.borrow_mut().set_account(
+            &session_token,
+            &solana_sdk::account::Account {
+                lamports: u32::MAX as u64,
+                data: [
+                    vec![0; SESSION_TOKEN_DISCRIMINATOR_SIZE],
+                    bytemuck::bytes_of(&session).to_vec(),
+                ]
+                .concat(),
+                owner: SESSION_KEYS_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            }
+            .into(),
+        );
+    }
+
+    /// Deposit USDC from `source_token_account` into the payer's seat, signed
+    /// by a session signer.
+    pub async fn deposit_with_session(
+        &mut self,
+        num_atoms: u64,
+        session_signer: &Keypair,
+        source_token_account: &Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let mut deposit_ix: Instruction = deposit_instruction(
+            &self.market_fixture.key,
+            &session_signer.pubkey(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            source_token_account,
+            spl_token::id(),
+            None,
+        );
+        deposit_ix.accounts.splice(
+            2..2,
+            session_account_metas(&self.payer(), &session_signer.pubkey()),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[deposit_ix],
+            Some(&session_signer.pubkey()),
+            &[session_signer],
+        )
+        .await
+    }
+
+    /// Let `delegate` spend `num_atoms` from `owner`'s token account.
+    pub async fn approve_token_delegate(
+        &mut self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        delegate: &Pubkey,
+        num_atoms: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let approve_ix: Instruction = spl_token::instruction::approve(
+            &spl_token::id(),
+            token_account,
+            delegate,
+            &owner.pubkey(),
+            &[],
+            num_atoms,
+        )
+        .unwrap();
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[approve_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair(), owner],
+        )
+        .await
+    }
+
+    /// Withdraw USDC from the payer's seat, signed by a session signer.
+    pub async fn withdraw_with_session(
+        &mut self,
+        num_atoms: u64,
+        session_signer: &Keypair,
+        trader_token_account: &Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let mut withdraw_ix: Instruction = withdraw_instruction(
+            &self.market_fixture.key,
+            &session_signer.pubkey(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            trader_token_account,
+            spl_token::id(),
+            None,
+        );
+        withdraw_ix.accounts.splice(
+            2..2,
+            session_account_metas(&self.payer(), &session_signer.pubkey()),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[withdraw_ix],
+            Some(&session_signer.pubkey()),
+            &[session_signer],
+        )
+        .await
+    }
+
+    /// Fund `beneficiary`'s seat with USDC minted to the payer.
+    pub async fn deposit_for_beneficiary(
+        &mut self,
+        num_atoms: u64,
+        beneficiary: &Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.usdc_mint_fixture
+            .mint_to(&self.payer_usdc_fixture.key, num_atoms)
+            .await;
+        let deposit_ix: Instruction = deposit_for_beneficiary_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+            beneficiary,
+            0,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[deposit_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Replace the wallets the payer's withdrawals may pay.
+    pub async fn set_withdraw_allowlist(
+        &mut self,
+        recipients: Vec<Pubkey>,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let set_withdraw_allowlist_ix: Instruction =
+            set_withdraw_allowlist_instruction(&self.payer(), recipients);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[set_withdraw_allowlist_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Withdraw the payer's USDC to a token account of an allowlisted wallet.
+    pub async fn withdraw_to_recipient(
+        &mut self,
+        num_atoms: u64,
+        recipient_token_account: &Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let withdraw_ix: Instruction = withdraw_to_recipient_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            recipient_token_account,
+            spl_token::id(),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[withdraw_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Withdraw USDC from the payer's seat to `recipient_token_account`,
+    /// signed by a session signer and passing the payer's withdraw allowlist.
+    pub async fn withdraw_to_recipient_with_session(
+        &mut self,
+        num_atoms: u64,
+        session_signer: &Keypair,
+        recipient_token_account: &Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let mut withdraw_ix: Instruction = withdraw_instruction(
+            &self.market_fixture.key,
+            &session_signer.pubkey(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            recipient_token_account,
+            spl_token::id(),
+            None,
+        );
+        let (withdraw_allowlist, _) = get_withdraw_allowlist_address(&self.payer());
+        withdraw_ix
+            .accounts
+            .push(AccountMeta::new_readonly(withdraw_allowlist, false));
+        withdraw_ix.accounts.splice(
+            2..2,
+            session_account_metas(&self.payer(), &session_signer.pubkey()),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[withdraw_ix],
+            Some(&session_signer.pubkey()),
+            &[session_signer],
+        )
+        .await
+    }
+
+    /// Limit what the payer's session signer may do.
+    pub async fn set_session_scope(
+        &mut self,
+        session_signer: &Pubkey,
+        max_order_notional_atoms: u64,
+        max_position_atoms: u64,
+        allowed_instructions: &[ManifestInstruction],
+    ) -> anyhow::Result<(), BanksClientError> {
+        let set_session_scope_ix: Instruction = set_session_scope_instruction(
+            &self.payer(),
+            session_signer,
+            vec![self.market_fixture.key],
+            max_order_notional_atoms,
+            max_position_atoms,
+            allowed_instructions,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[set_session_scope_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Place a limit order for the payer, signed by their session signer.
+    pub async fn place_order_with_session(
+        &mut self,
+        side: Side,
+        base_atoms: u64,
+        price_mantissa: u32,
+        price_exponent: i8,
+        session_signer: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let mut place_order_ix: Instruction = batch_update_instruction(
+            &self.market_fixture.key,
+            &session_signer.pubkey(),
+            None,
+            vec![],
+            vec![PlaceOrderParams::new(
+                base_atoms,
+                price_mantissa,
+                price_exponent,
+                side == Side::Bid,
+                OrderType::Limit,
+                0,
+            )],
+            None,
+            None,
+            None,
+            None,
+        );
+        // The session accounts go right after the system program.
+        place_order_ix.accounts.splice(
+            3..3,
+            session_account_metas(&self.payer(), &session_signer.pubkey()),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[place_order_ix],
+            Some(&session_signer.pubkey()),
+            &[session_signer],
+        )
+        .await
+    }
+
+    /// Place an order on the payer's seat, signed by a session signer that
+    /// leaves out the session's scope account.
+    pub async fn place_order_with_session_token_only(
+        &mut self,
+        side: Side,
+        base_atoms: u64,
+        price_mantissa: u32,
+        price_exponent: i8,
+        session_signer: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let mut place_order_ix: Instruction = batch_update_instruction(
+            &self.market_fixture.key,
+            &session_signer.pubkey(),
+            None,
+            vec![],
+            vec![PlaceOrderParams::new(
+                base_atoms,
+                price_mantissa,
+                price_exponent,
+                side == Side::Bid,
+                OrderType::Limit,
+                0,
+            )],
+            None,
+            None,
+            None,
+            None,
+        );
+        let mut session_metas: Vec<AccountMeta> =
+            session_account_metas(&self.payer(), &session_signer.pubkey());
+        session_metas.truncate(1);
+        place_order_ix.accounts.splice(3..3, session_metas);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[place_order_ix],
+            Some(&session_signer.pubkey()),
+            &[session_signer],
+        )
+        .await
+    }
+
+    /// Stake USDC from the payer into the market insurance fund.
+    pub async fn insurance_deposit(
+        &mut self,
+        num_atoms: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.usdc_mint_fixture
+            .mint_to(&self.payer_usdc_fixture.key, num_atoms)
+            .await;
+        let insurance_deposit_ix: Instruction = insurance_deposit_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[insurance_deposit_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Ask to withdraw the payer's insurance shares, starting the cooldown.
+    pub async fn insurance_request_withdraw(
+        &mut self,
+        shares: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let insurance_request_withdraw_ix: Instruction =
+            insurance_request_withdraw_instruction(&self.market_fixture.key, &self.payer(), shares);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[insurance_request_withdraw_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Redeem the payer's requested insurance shares into their USDC account.
+    pub async fn insurance_withdraw(
+        &mut self,
+        shares: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let insurance_withdraw_ix: Instruction = insurance_withdraw_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            shares,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[insurance_withdraw_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Insurance shares held by the payer, zero if they never staked.
+    pub async fn get_insurance_shares(&self) -> u64 {
+        let (insurance_staker, _) =
+            get_insurance_staker_address(&self.market_fixture.key, &self.payer());
+        let account: Option<Account> = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_account(insurance_staker)
+            .await
+            .unwrap();
+        match account {
+            Some(account) => bytemuck::from_bytes::<InsuranceStaker>(&account.data).get_shares(),
+            None => 0,
+        }
+    }
+
+    /// Create the payer's cross-margin account for USDC.
+    pub async fn create_cross_margin_account(&mut self) -> anyhow::Result<(), BanksClientError> {
+        let create_cross_margin_account_ix: Instruction =
+            create_cross_margin_account_instruction(&self.payer(), &self.usdc_mint_fixture.key);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[create_cross_margin_account_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Move USDC from the payer into their cross-margin account.
+    pub async fn cross_margin_deposit(
+        &mut self,
+        num_atoms: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.usdc_mint_fixture
+            .mint_to(&self.payer_usdc_fixture.key, num_atoms)
+            .await;
+        let cross_margin_deposit_ix: Instruction = cross_margin_deposit_instruction(
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[cross_margin_deposit_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Take USDC out of the payer's cross-margin account, which links only
+    /// the fixture market.
+    pub async fn cross_margin_withdraw(
+        &mut self,
+        num_atoms: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let cross_margin_withdraw_ix: Instruction = cross_margin_withdraw_instruction(
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            &self.payer_usdc_fixture.key,
+            spl_token::id(),
+            &[self.market_fixture.key],
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[cross_margin_withdraw_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Link or unlink the payer's seat on the fixture market.
+    pub async fn cross_margin_link(&mut self, link: bool) -> anyhow::Result<(), BanksClientError> {
+        let cross_margin_link_ix: Instruction = cross_margin_link_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            link,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[cross_margin_link_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Move USDC between the payer's seat margin on the fixture market and
+    /// their cross-margin account, which links no other market.
+    pub async fn cross_margin_transfer(
+        &mut self,
+        num_atoms: u64,
+        to_cross_margin: bool,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let cross_margin_transfer_ix: Instruction = cross_margin_transfer_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            num_atoms,
+            to_cross_margin,
+            spl_token::id(),
+            &[],
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[cross_margin_transfer_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Link or unlink one of the payer's sub-account seats on the fixture
+    /// market.
+    pub async fn cross_margin_link_sub_account(
+        &mut self,
+        link: bool,
+        sub_account_id: u8,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let cross_margin_link_ix: Instruction = cross_margin_link_for_sub_account_instruction(
+            &self.market_fixture.key,
+            &self.payer(),
+            &self.usdc_mint_fixture.key,
+            link,
+            sub_account_id,
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[cross_margin_link_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Move USDC between one of the payer's sub-account seats on the fixture
+    /// market and their cross-margin account, which links no other market.
+    pub async fn cross_margin_transfer_sub_account(
         &mut self,
-        token: Token,
         num_atoms: u64,
+        to_cross_margin: bool,
+        sub_account_id: u8,
     ) -> anyhow::Result<(), BanksClientError> {
-        self.withdraw_for_keypair(token, num_atoms, &self.payer_keypair())
-            .await?;
-        Ok(())
+        let cross_margin_transfer_ix: Instruction =
+            cross_margin_transfer_for_sub_account_instruction(
+                &self.market_fixture.key,
+                &self.payer(),
+                &self.usdc_mint_fixture.key,
+                num_atoms,
+                to_cross_margin,
+                spl_token::id(),
+                &[],
+                sub_account_id,
+            );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[cross_margin_transfer_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
     }
 
-    pub async fn withdraw_for_keypair(
+    /// Collateral in the payer's cross-margin account.
+    pub async fn get_cross_margin_collateral(&self) -> u64 {
+        let (cross_margin, _) =
+            get_cross_margin_address(&self.payer(), &self.usdc_mint_fixture.key);
+        let account: Account = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_account(cross_margin)
+            .await
+            .unwrap()
+            .unwrap();
+        bytemuck::from_bytes::<CrossMarginAccount>(&account.data).get_collateral_atoms()
+    }
+
+    /// Swap for the payer with their cross-margin account attached, for a
+    /// seat linked on the fixture market only.
+    pub async fn swap_cross_margin(
         &mut self,
-        token: Token,
-        num_atoms: u64,
-        keypair: &Keypair,
+        in_atoms: u64,
+        out_atoms: u64,
+        is_base_in: bool,
+        is_exact_in: bool,
     ) -> anyhow::Result<(), BanksClientError> {
-        let is_base: bool = token == Token::SOL;
-        let (mint, trader_token_account) = if is_base {
-            let trader_token_account: Pubkey = if keypair.pubkey() == self.payer() {
-                self.payer_sol_fixture.key
-            } else {
-                // Make a new token account
-                let token_account_keypair: Keypair = Keypair::new();
-                let token_account_fixture: TokenAccountFixture =
-                    TokenAccountFixture::new_with_keypair(
-                        Rc::clone(&self.context),
-                        &self.sol_mint_fixture.key,
-                        &keypair.pubkey(),
-                        &token_account_keypair,
-                    )
-                    .await;
-                token_account_fixture.key
-            };
-            (&self.sol_mint_fixture.key, trader_token_account)
-        } else {
-            let trader_token_account: Pubkey = if keypair.pubkey() == self.payer() {
-                self.payer_usdc_fixture.key
-            } else {
-                // Make a new token account
-                let token_account_keypair: Keypair = Keypair::new();
-                let token_account_fixture: TokenAccountFixture =
-                    TokenAccountFixture::new_with_keypair(
-                        Rc::clone(&self.context),
-                        &self.usdc_mint_fixture.key,
-                        &keypair.pubkey(),
-                        &token_account_keypair,
-                    )
-                    .await;
-                token_account_fixture.key
-            };
-            (&self.usdc_mint_fixture.key, trader_token_account)
-        };
-
-        let withdraw_ix: Instruction = withdraw_instruction(
+        let mut swap_ix: Instruction = swap_instruction(
             &self.market_fixture.key,
-            &keypair.pubkey(),
-            mint,
-            num_atoms,
-            &trader_token_account,
+            &self.payer(),
+            &self.sol_mint_fixture.key,
+            &self.usdc_mint_fixture.key,
+            &self.payer_sol_fixture.key,
+            &self.payer_usdc_fixture.key,
+            in_atoms,
+            out_atoms,
+            is_base_in,
+            is_exact_in,
             spl_token::id(),
-            None,
+            spl_token::id(),
+            false,
+        );
+        // The cross-margin block goes right after the system program.
+        swap_ix.accounts.splice(
+            3..3,
+            cross_margin_account_metas(&self.payer(), &self.usdc_mint_fixture.key, &[]),
         );
         send_tx_with_retry(
             Rc::clone(&self.context),
-            &[withdraw_ix],
-            Some(&keypair.pubkey()),
-            &[keypair],
+            &[swap_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
         )
         .await
     }
@@ -777,7 +1813,7 @@ impl TestFixture {
         keypair: &Keypair,
     ) -> anyhow::Result<(), BanksClientError> {
         let is_bid: bool = side == Side::Bid;
-        let place_order_ix: Instruction = batch_update_instruction(
+        let mut place_order_ix: Instruction = batch_update_instruction(
             &self.market_fixture.key,
             &keypair.pubkey(),
             None,
@@ -795,6 +1831,11 @@ impl TestFixture {
             None,
             None,
         );
+        // The seat extension is skipped while it has not been created.
+        place_order_ix.accounts.insert(
+            3,
+            seat_extension_account_meta(&self.market_fixture.key, &keypair.pubkey(), 0),
+        );
         send_tx_with_retry(
             Rc::clone(&self.context),
             &[place_order_ix],
@@ -806,6 +1847,76 @@ impl TestFixture {
 
     // Similar to swap, but the second_keypair is the gas/rent payer and normal
     // keypair owns the token accounts.
+    /// Name `delegate` as the trade delegate of the payer's default seat.
+    pub async fn set_trade_delegate(
+        &self,
+        delegate: &Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let set_trade_delegate_ix: Instruction =
+            set_trade_delegate_instruction(&self.market_fixture.key, &self.payer(), delegate, 0);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[set_trade_delegate_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    pub async fn revoke_trade_delegate(&self) -> anyhow::Result<(), BanksClientError> {
+        let revoke_trade_delegate_ix: Instruction =
+            revoke_trade_delegate_instruction(&self.market_fixture.key, &self.payer(), 0);
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[revoke_trade_delegate_ix],
+            Some(&self.payer()),
+            &[&self.payer_keypair()],
+        )
+        .await
+    }
+
+    /// Place an order on the payer's default seat, signed by its trade
+    /// delegate.
+    pub async fn place_order_as_trade_delegate(
+        &mut self,
+        side: Side,
+        base_atoms: u64,
+        price_mantissa: u32,
+        price_exponent: i8,
+        delegate: &Keypair,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let mut place_order_ix: Instruction = batch_update_instruction(
+            &self.market_fixture.key,
+            &delegate.pubkey(),
+            None,
+            vec![],
+            vec![PlaceOrderParams::new(
+                base_atoms,
+                price_mantissa,
+                price_exponent,
+                side == Side::Bid,
+                OrderType::Limit,
+                0,
+            )],
+            None,
+            None,
+            None,
+            None,
+        );
+        // The trade delegate record goes right after the system program.
+        place_order_ix.accounts.insert(
+            3,
+            trade_delegate_account_meta(&self.market_fixture.key, &self.payer(), 0),
+        );
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[place_order_ix],
+            Some(&delegate.pubkey()),
+            &[delegate],
+        )
+        .await
+    }
+
     pub async fn swap_v2(
         &mut self,
         in_atoms: u64,
@@ -891,14 +2002,13 @@ impl TestFixture {
         } else {
             // Create a new USDC token account for this keypair
             let token_account_keypair: Keypair = Keypair::new();
-            let token_account_fixture: TokenAccountFixture =
-                TokenAccountFixture::new_with_keypair(
-                    Rc::clone(&self.context),
-                    &self.usdc_mint_fixture.key,
-                    &keypair.pubkey(),
-                    &token_account_keypair,
-                )
-                .await;
+            let token_account_fixture: TokenAccountFixture = TokenAccountFixture::new_with_keypair(
+                Rc::clone(&self.context),
+                &self.usdc_mint_fixture.key,
+                &keypair.pubkey(),
+                &token_account_keypair,
+            )
+            .await;
             // For going long (is_base_in=false), need USDC in this account
             if !is_base_in {
                 self.usdc_mint_fixture
@@ -1018,6 +2128,53 @@ impl TestFixture {
         .await
     }
 
+    /// Send a batch update and return the compute units it consumed.
+    pub async fn batch_update_compute_units_for_keypair(
+        &mut self,
+        cancels: Vec<CancelOrderParams>,
+        orders: Vec<PlaceOrderParams>,
+        keypair: &Keypair,
+    ) -> anyhow::Result<u64, BanksClientError> {
+        let mut batch_update_ix: Instruction = batch_update_instruction(
+            &self.market_fixture.key,
+            &keypair.pubkey(),
+            None,
+            cancels,
+            orders,
+            None,
+            None,
+            None,
+            None,
+        );
+        batch_update_ix.accounts.insert(
+            3,
+            seat_extension_account_meta(&self.market_fixture.key, &keypair.pubkey(), 0),
+        );
+        let units_consumed: u64 = {
+            let mut context: RefMut<ProgramTestContext> = self.context.borrow_mut();
+            let blockhash: Hash = context.get_new_latest_blockhash().await?;
+            let tx: Transaction = Transaction::new_signed_with_payer(
+                &[batch_update_ix.clone()],
+                Some(&keypair.pubkey()),
+                &[keypair],
+                blockhash,
+            );
+            let simulation = context.banks_client.simulate_transaction(tx).await?;
+            if let Some(Err(error)) = simulation.result {
+                return Err(BanksClientError::TransactionError(error));
+            }
+            simulation.simulation_details.unwrap().units_consumed
+        };
+        send_tx_with_retry(
+            Rc::clone(&self.context),
+            &[batch_update_ix],
+            Some(&keypair.pubkey()),
+            &[keypair],
+        )
+        .await?;
+        Ok(units_consumed)
+    }
+
     pub async fn batch_update_with_global_for_keypair(
         &mut self,
         trader_index_hint: Option<DataIndex>,
@@ -1064,18 +2221,22 @@ impl MarketFixture {
         let (market_key, _) = get_market_address(base_mint_index, quote_mint);
         let payer: Pubkey = context.borrow().payer.pubkey();
         let payer_keypair: Keypair = context.borrow().payer.insecure_clone();
-        let create_market_ixs: Vec<Instruction> =
-            create_market_instructions(
-                base_mint_index,
-                base_mint_decimals,
-                quote_mint,
-                &payer,
-                1000, // initial_margin_bps (10%)
-                500,  // maintenance_margin_bps (5%)
-                Pubkey::default(), // pyth_feed_account
-                0,    // taker_fee_bps
-                200,  // liquidation_buffer_bps
-            );
+        let create_market_ixs: Vec<Instruction> = create_market_instructions(
+            base_mint_index,
+            base_mint_decimals,
+            quote_mint,
+            &payer,
+            1000,              // initial_margin_bps (10%)
+            500,               // maintenance_margin_bps (5%)
+            Pubkey::default(), // pyth_feed_account
+            0,                 // taker_fee_bps
+            200,               // liquidation_buffer_bps
+            250,               // liquidator_reward_bps
+            1000,              // min_position_size_atoms
+            DEFAULT_FUNDING_PERIOD_SECS,
+            DEFAULT_MAX_FUNDING_RATE,
+            0, // funding_interest_rate
+        );
 
         send_tx_with_retry(
             Rc::clone(&context),
@@ -1148,23 +2309,154 @@ impl MarketFixture {
         self.market.get_trader_balance(trader).1.as_u64()
     }
 
+    pub async fn get_sub_account_quote_balance_atoms(
+        &mut self,
+        trader: &Pubkey,
+        sub_account_id: u8,
+    ) -> u64 {
+        self.reload().await;
+        let trader_index: DataIndex = self.market.get_sub_account_index(trader, sub_account_id);
+        self.market
+            .get_trader_balance_by_index(trader_index)
+            .1
+            .as_u64()
+    }
+
     pub async fn get_quote_volume(&mut self, trader: &Pubkey) -> u64 {
         self.reload().await;
         self.market.get_trader_voume(trader).as_u64()
     }
 
+    /// Get the index of the trader's seat in the market.
+    pub async fn get_trader_index(&mut self, trader: &Pubkey) -> DataIndex {
+        self.reload().await;
+        self.market.get_trader_index(trader)
+    }
+
     /// Get the trader's perps position: (position_size, quote_cost_basis)
     pub async fn get_trader_position(&mut self, trader: &Pubkey) -> (i64, u64) {
         self.reload().await;
         self.market.get_trader_position(trader)
     }
 
+    /// Get the trader's lifetime stats from their seat extension:
+    /// (realized_pnl, funding_paid, fees_paid)
+    pub async fn get_trader_lifetime_stats(&mut self, trader: &Pubkey) -> (i64, i64, u64) {
+        let (seat_extension, _) = get_seat_extension_address(&self.key, trader, 0);
+        let account: Account = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_account(seat_extension)
+            .await
+            .unwrap()
+            .unwrap();
+        let seat_extension: &SeatExtension = bytemuck::from_bytes(&account.data);
+        (
+            seat_extension.get_realized_pnl(),
+            seat_extension.get_cumulative_funding_paid(),
+            seat_extension.get_fees_paid(),
+        )
+    }
+
+    /// Get the trader's realized PnL not yet settled into their margin.
+    pub async fn get_trader_unsettled_pnl(&mut self, trader: &Pubkey) -> i64 {
+        self.reload().await;
+        self.market.get_trader_unsettled_pnl(trader)
+    }
+
+    /// Get the PnL pool balance from the market.
+    pub async fn get_pnl_pool_balance(&mut self) -> u64 {
+        self.reload().await;
+        self.market.fixed.get_pnl_pool_balance()
+    }
+
     /// Get the insurance fund balance from the market.
     pub async fn get_insurance_fund_balance(&mut self) -> u64 {
         self.reload().await;
         self.market.fixed.get_insurance_fund_balance()
     }
 
+    /// Get the total insurance shares outstanding from the market.
+    pub async fn get_insurance_total_shares(&mut self) -> u64 {
+        self.reload().await;
+        self.market.fixed.get_insurance_total_shares()
+    }
+
+    /// Get the insurance shares owned by the protocol from the market.
+    pub async fn get_insurance_protocol_shares(&mut self) -> u64 {
+        self.reload().await;
+        self.market.fixed.get_insurance_protocol_shares()
+    }
+
+    /// Get the cumulative socialized loss per unit (scaled by 1e9) from the market.
+    pub async fn get_cumulative_loss_per_unit(&mut self) -> u64 {
+        self.reload().await;
+        self.market.fixed.get_cumulative_loss_per_unit()
+    }
+
+    /// Get `(liquidator_reward_bps, min_position_size_atoms)` from the market.
+    pub async fn get_liquidation_params(&mut self) -> (u64, u64) {
+        self.reload().await;
+        (
+            self.market.fixed.get_liquidator_reward_bps(),
+            self.market.fixed.get_min_position_size_atoms(),
+        )
+    }
+
+    /// Get `(oracle_conf, max_oracle_conf_bps)` from the market.
+    pub async fn get_oracle_confidence(&mut self) -> (u64, u64) {
+        self.reload().await;
+        (
+            self.market.fixed.get_oracle_conf(),
+            self.market.fixed.get_max_oracle_conf_bps(),
+        )
+    }
+
+    /// Get the cached `(oracle_price_mantissa, oracle_price_expo)` from the market.
+    pub async fn get_oracle_price(&mut self) -> (u64, i32) {
+        self.reload().await;
+        (
+            self.market.fixed.get_oracle_price_mantissa(),
+            self.market.fixed.get_oracle_price_expo(),
+        )
+    }
+
+    /// Get the global cumulative funding (scaled by 1e9) from the market.
+    pub async fn get_cumulative_funding(&mut self) -> i64 {
+        self.reload().await;
+        self.market.fixed.get_cumulative_funding()
+    }
+
+    /// Get `(ema_premium_rate, mark_ema_window_secs)` from the market.
+    pub async fn get_ema_premium(&mut self) -> (i64, u64) {
+        self.reload().await;
+        (
+            self.market.fixed.get_ema_premium_rate(),
+            self.market.fixed.get_mark_ema_window_secs(),
+        )
+    }
+
+    /// Get `(funding_period_secs, max_funding_rate, funding_interest_rate)`
+    /// from the market.
+    pub async fn get_funding_params(&mut self) -> (u64, u64, i64) {
+        self.reload().await;
+        (
+            self.market.fixed.get_funding_period_secs(),
+            self.market.fixed.get_max_funding_rate(),
+            self.market.fixed.get_funding_interest_rate(),
+        )
+    }
+
+    /// Get `(total_long_base_atoms, total_short_base_atoms)` from the market.
+    pub async fn get_open_interest(&mut self) -> (u64, u64) {
+        self.reload().await;
+        (
+            self.market.fixed.get_total_long_base_atoms(),
+            self.market.fixed.get_total_short_base_atoms(),
+        )
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn new_with_pyth(
         context: Rc<RefCell<ProgramTestContext>>,
@@ -1180,18 +2472,22 @@ impl MarketFixture {
         let (market_key, _) = get_market_address(base_mint_index, quote_mint);
         let payer: Pubkey = context.borrow().payer.pubkey();
         let payer_keypair: Keypair = context.borrow().payer.insecure_clone();
-        let create_market_ixs: Vec<Instruction> =
-            create_market_instructions(
-                base_mint_index,
-                base_mint_decimals,
-                quote_mint,
-                &payer,
-                initial_margin_bps,
-                maintenance_margin_bps,
-                pyth_feed,
-                taker_fee_bps,
-                liquidation_buffer_bps,
-            );
+        let create_market_ixs: Vec<Instruction> = create_market_instructions(
+            base_mint_index,
+            base_mint_decimals,
+            quote_mint,
+            &payer,
+            initial_margin_bps,
+            maintenance_margin_bps,
+            pyth_feed,
+            taker_fee_bps,
+            liquidation_buffer_bps,
+            250,  // liquidator_reward_bps
+            1000, // min_position_size_atoms
+            DEFAULT_FUNDING_PERIOD_SECS,
+            DEFAULT_MAX_FUNDING_RATE,
+            0, // funding_interest_rate
+        );
 
         send_tx_with_retry(
             Rc::clone(&context),
@@ -1368,11 +2664,23 @@ impl MarketFixture {
 /// Build mock Pyth V2 price account data for testing.
 /// Returns a 240-byte buffer with the correct layout.
 pub fn build_mock_pyth_data(price: i64, expo: i32, confidence: u64) -> Vec<u8> {
+    build_mock_pyth_data_at(price, expo, confidence, 0)
+}
+
+/// Same as `build_mock_pyth_data`, published at `publish_time`.
+pub fn build_mock_pyth_data_at(
+    price: i64,
+    expo: i32,
+    confidence: u64,
+    publish_time: i64,
+) -> Vec<u8> {
     let mut data = vec![0u8; 240];
     // Magic number at offset 0
     data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
     // Exponent (i32) at offset 20
     data[20..24].copy_from_slice(&expo.to_le_bytes());
+    // Publish timestamp (i64) at offset 96
+    data[96..104].copy_from_slice(&publish_time.to_le_bytes());
     // Aggregate price (i64) at offset 208
     data[208..216].copy_from_slice(&price.to_le_bytes());
     // Aggregate confidence (u64) at offset 216
@@ -1382,6 +2690,56 @@ pub fn build_mock_pyth_data(price: i64, expo: i32, confidence: u64) -> Vec<u8> {
     data
 }
 
+/// Build mock Pyth PriceUpdateV3 account data for testing. Fully verified
+/// updates put the message at offset 41, partially verified ones carry a
+/// signature count first and put it at 42.
+pub fn build_mock_pyth_v3_data(
+    feed_id: [u8; 32],
+    price: i64,
+    expo: i32,
+    confidence: u64,
+    publish_time: i64,
+    fully_verified: bool,
+) -> Vec<u8> {
+    let mut data = vec![0u8; 134];
+    let msg_start: usize = if fully_verified {
+        data[40] = 1;
+        41
+    } else {
+        data[40] = 0;
+        data[41] = 5;
+        42
+    };
+    data[msg_start..msg_start + 32].copy_from_slice(&feed_id);
+    data[msg_start + 32..msg_start + 40].copy_from_slice(&price.to_le_bytes());
+    data[msg_start + 40..msg_start + 48].copy_from_slice(&confidence.to_le_bytes());
+    data[msg_start + 48..msg_start + 52].copy_from_slice(&expo.to_le_bytes());
+    data[msg_start + 52..msg_start + 60].copy_from_slice(&publish_time.to_le_bytes());
+    data
+}
+
+/// Build mock Switchboard On-Demand PullFeedAccountData for testing, with a
+/// single-sample current result. `value` and `std_dev` are fixed point with
+/// 18 decimals.
+pub fn build_mock_switchboard_data(
+    value: i128,
+    std_dev: i128,
+    last_update_timestamp: i64,
+) -> Vec<u8> {
+    let mut data = vec![0u8; 3208];
+    // Anchor discriminator at offset 0
+    data[0..8].copy_from_slice(&[196, 27, 108, 196, 10, 215, 219, 40]);
+    // last_update_timestamp (i64) at offset 2216
+    data[2216..2224].copy_from_slice(&last_update_timestamp.to_le_bytes());
+    // Current result value (i128) at offset 2264
+    data[2264..2280].copy_from_slice(&value.to_le_bytes());
+    // Current result std_dev (i128) at offset 2280
+    data[2280..2296].copy_from_slice(&std_dev.to_le_bytes());
+    // Current result num_samples (u8) at offset 2360
+    data[2360] = 1;
+    data
+}
+
 #[derive(Clone)]
 pub struct GlobalFixture {
     pub context: Rc<RefCell<ProgramTestContext>>,
@@ -2113,18 +3471,22 @@ pub async fn create_market_with_mints(
     let payer_keypair = context.borrow().payer.insecure_clone();
     let payer = payer_keypair.pubkey();
 
-    let create_market_ixs: Vec<Instruction> =
-        create_market_instructions(
-            base_mint_index,
-            base_mint_decimals,
-            quote_mint,
-            &payer,
-            1000,
-            500,
-            Pubkey::default(),
-            0,   // taker_fee_bps
-            200, // liquidation_buffer_bps
-        );
+    let create_market_ixs: Vec<Instruction> = create_market_instructions(
+        base_mint_index,
+        base_mint_decimals,
+        quote_mint,
+        &payer,
+        1000,
+        500,
+        Pubkey::default(),
+        0,    // taker_fee_bps
+        200,  // liquidation_buffer_bps
+        250,  // liquidator_reward_bps
+        1000, // min_position_size_atoms
+        DEFAULT_FUNDING_PERIOD_SECS,
+        DEFAULT_MAX_FUNDING_RATE,
+        0, // funding_interest_rate
+    );
 
     send_tx_with_retry(
         Rc::clone(&context),
@@ -2,29 +2,39 @@ use anyhow::Result;
 use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
-    instruction::Instruction,
-    signature::Keypair,
-    signer::Signer,
-    transaction::Transaction,
+    commitment_config::CommitmentConfig, instruction::Instruction, signature::Keypair,
+    signer::Signer, transaction::Transaction,
 };
 use spl_associated_token_account::get_associated_token_address;
 
 use manifest::program::{
     batch_update::{CancelOrderParams, PlaceOrderParams},
-    batch_update_instruction, claim_seat_instruction::claim_seat_instruction,
-    create_market_instructions, crank_funding_instruction, deposit_instruction,
-    deposit_instruction_with_vault, expand_market_instruction, liquidate_instruction,
-    release_seat_instruction, swap_instruction::swap_instruction_with_vaults,
-    withdraw_instruction, withdraw_instruction_with_vault,
+    batch_update_instruction,
+    claim_seat_instruction::claim_seat_instruction,
+    crank_funding_instruction, crank_funding_with_history_instruction,
+    crank_funding_with_oracles_instruction, create_funding_history_instruction,
+    create_market_instructions,
+    create_seat_extension_instruction::{
+        create_seat_extension_instruction, seat_extension_account_meta,
+    },
+    deposit_instruction, deposit_instruction_with_vault, expand_market_instruction,
+    insurance_deposit_instruction, insurance_withdraw_instruction,
+    liquidate::LiquidationMode,
+    liquidate_batch_instruction, liquidate_instruction, liquidate_instruction_with_mode,
+    release_seat_instruction, settle_pnl_instruction,
+    swap_instruction::swap_instruction_with_vaults,
+    update_market::UpdateMarketParams,
+    update_market_instruction, withdraw_instruction, withdraw_instruction_with_vault,
 };
 use manifest::validation::get_market_address;
 
 use crate::config::ManifestConfig;
 use crate::ephemeral;
+use crate::funding_history::{FundingHistoryState, FundingRecord};
 use crate::market::MarketState;
 use crate::oracle;
 use crate::position::PositionInfo;
+use crate::seat_extension::{SeatExtensionState, SeatStats};
 
 /// Parameters for creating a new perps market.
 pub struct CreateMarketParams {
@@ -36,6 +46,15 @@ pub struct CreateMarketParams {
     pub pyth_feed: Pubkey,
     pub taker_fee_bps: u64,
     pub liquidation_buffer_bps: u64,
+    pub liquidator_reward_bps: u64,
+    pub min_position_size_atoms: u64,
+    /// Length of a funding period in seconds.
+    pub funding_period_secs: u64,
+    /// Largest funding rate per period, scaled by `FUNDING_SCALE`.
+    pub max_funding_rate: u64,
+    /// Fixed interest rate per period added to the premium, scaled by
+    /// `FUNDING_SCALE`. Zero for none.
+    pub funding_interest_rate: i64,
     pub num_blocks: u32,
 }
 
@@ -75,19 +94,14 @@ pub struct ManifestClient {
 impl ManifestClient {
     /// Initialize with a config. Connects to the ER URL from config.
     pub fn init(config: ManifestConfig) -> Self {
-        let rpc = RpcClient::new_with_commitment(
-            config.er_url.clone(),
-            CommitmentConfig::confirmed(),
-        );
+        let rpc =
+            RpcClient::new_with_commitment(config.er_url.clone(), CommitmentConfig::confirmed());
         Self { rpc, config }
     }
 
     /// Initialize with a config and explicit RPC URL override (e.g. base chain).
     pub fn init_with_url(config: ManifestConfig, url: &str) -> Self {
-        let rpc = RpcClient::new_with_commitment(
-            url.to_string(),
-            CommitmentConfig::confirmed(),
-        );
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
         Self { rpc, config }
     }
 
@@ -124,7 +138,24 @@ impl ManifestClient {
         Ok(PositionInfo::compute(&state, trader))
     }
 
-    /// Fetch oracle price. Tries Pyth V2, then falls back to V3.
+    /// Fetch a market's recorded funding cranks, oldest first, with rates
+    /// annualized.
+    pub fn fetch_funding_history(&self, market: &Pubkey) -> Result<Vec<FundingRecord>> {
+        let state = self.fetch_market(market)?;
+        let history = FundingHistoryState::fetch(&self.rpc, market)?;
+        Ok(history.annualized_history(&state))
+    }
+
+    /// Fetch a trader's lifetime accounting on a market. Zero if the trader
+    /// never created a seat extension.
+    pub fn fetch_seat_stats(&self, market: &Pubkey, trader: &Pubkey) -> Result<SeatStats> {
+        Ok(SeatExtensionState::fetch(&self.rpc, market, trader, 0)?
+            .map(|extension| extension.stats)
+            .unwrap_or_default())
+    }
+
+    /// Fetch oracle price. Tries Pyth V2, then Switchboard, then falls back
+    /// to V3.
     pub fn fetch_oracle_price(
         &self,
         feed: &Pubkey,
@@ -153,18 +184,40 @@ impl ManifestClient {
             params.pyth_feed,
             params.taker_fee_bps,
             params.liquidation_buffer_bps,
+            params.liquidator_reward_bps,
+            params.min_position_size_atoms,
+            params.funding_period_secs,
+            params.max_funding_rate,
+            params.funding_interest_rate,
             params.num_blocks,
         );
         let sig = self.send(&ixs, &[payer])?;
         Ok((market, sig))
     }
 
+    /// Update market parameters. `authority` must be the market authority.
+    pub fn update_market(
+        &self,
+        authority: &Keypair,
+        market: &Pubkey,
+        params: UpdateMarketParams,
+    ) -> Result<String> {
+        let ix = update_market_instruction(market, &authority.pubkey(), params);
+        self.send(&[ix], &[authority])
+    }
+
     /// Claim a trading seat on a market.
     pub fn claim_seat(&self, payer: &Keypair, market: &Pubkey) -> Result<String> {
         let ix = claim_seat_instruction(market, &payer.pubkey());
         self.send(&[ix], &[payer])
     }
 
+    /// Create the account that tracks a seat's lifetime statistics.
+    pub fn create_seat_extension(&self, payer: &Keypair, market: &Pubkey) -> Result<String> {
+        let ix = create_seat_extension_instruction(market, &payer.pubkey(), 0);
+        self.send(&[ix], &[payer])
+    }
+
     /// Release a trading seat.
     pub fn release_seat(&self, payer: &Keypair, market: &Pubkey) -> Result<String> {
         let ix = release_seat_instruction(market, &payer.pubkey());
@@ -219,6 +272,48 @@ impl ManifestClient {
         self.send(&[ix], &[payer])
     }
 
+    /// Stake USDC into the market insurance fund (on base chain, using
+    /// standard SPL ATAs).
+    pub fn insurance_deposit(
+        &self,
+        payer: &Keypair,
+        market: &Pubkey,
+        quote_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<String> {
+        let ata = get_associated_token_address(&payer.pubkey(), quote_mint);
+        let ix = insurance_deposit_instruction(
+            market,
+            &payer.pubkey(),
+            quote_mint,
+            amount,
+            &ata,
+            spl_token::id(),
+        );
+        self.send(&[ix], &[payer])
+    }
+
+    /// Redeem insurance fund shares for USDC once the withdrawal cooldown has
+    /// elapsed.
+    pub fn insurance_withdraw(
+        &self,
+        payer: &Keypair,
+        market: &Pubkey,
+        quote_mint: &Pubkey,
+        shares: u64,
+    ) -> Result<String> {
+        let ata = get_associated_token_address(&payer.pubkey(), quote_mint);
+        let ix = insurance_withdraw_instruction(
+            market,
+            &payer.pubkey(),
+            quote_mint,
+            shares,
+            &ata,
+            spl_token::id(),
+        );
+        self.send(&[ix], &[payer])
+    }
+
     /// Place a single order via BatchUpdate.
     pub fn place_order(
         &self,
@@ -266,8 +361,7 @@ impl ManifestClient {
     pub fn swap(&self, payer: &Keypair, market: &Pubkey, params: SwapParams) -> Result<String> {
         let (trader_ata, _) =
             ephemeral::get_ephemeral_ata(&self.config, &payer.pubkey(), &params.quote_mint);
-        let (vault_ata, _) =
-            ephemeral::get_ephemeral_ata(&self.config, market, &params.quote_mint);
+        let (vault_ata, _) = ephemeral::get_ephemeral_ata(&self.config, market, &params.quote_mint);
 
         let ix = swap_instruction_with_vaults(
             market,
@@ -300,6 +394,63 @@ impl ManifestClient {
         self.send(&[ix], &[liquidator])
     }
 
+    /// Liquidate an underwater trader by closing their position against the
+    /// orderbook instead of at the mark price.
+    pub fn liquidate_on_orderbook(
+        &self,
+        liquidator: &Keypair,
+        market: &Pubkey,
+        trader: &Pubkey,
+    ) -> Result<String> {
+        let ix = liquidate_instruction_with_mode(
+            market,
+            &liquidator.pubkey(),
+            trader,
+            LiquidationMode::Orderbook,
+        );
+        self.send(&[ix], &[liquidator])
+    }
+
+    /// Liquidate an underwater trader by taking the closed portion of their
+    /// position onto the liquidator's own seat at a discount to mark.
+    pub fn liquidate_takeover(
+        &self,
+        liquidator: &Keypair,
+        market: &Pubkey,
+        trader: &Pubkey,
+    ) -> Result<String> {
+        let ix = liquidate_instruction_with_mode(
+            market,
+            &liquidator.pubkey(),
+            trader,
+            LiquidationMode::Takeover,
+        );
+        self.send(&[ix], &[liquidator])
+    }
+
+    /// Liquidate several traders at one mark price in a single instruction.
+    /// An empty `traders` list liquidates every underwater seat, stopping
+    /// early if the compute budget runs low.
+    pub fn liquidate_batch(
+        &self,
+        liquidator: &Keypair,
+        market: &Pubkey,
+        traders: Vec<Pubkey>,
+        mode: LiquidationMode,
+    ) -> Result<String> {
+        let ix = liquidate_batch_instruction(market, &liquidator.pubkey(), traders, mode);
+        self.send(&[ix], &[liquidator])
+    }
+
+    /// Settle a trader's realized PnL into their margin. Anyone can pay for
+    /// it, so makers can be settled without placing an order.
+    pub fn settle_pnl(&self, payer: &Keypair, market: &Pubkey, trader: &Pubkey) -> Result<String> {
+        let mut ix = settle_pnl_instruction(market, &payer.pubkey(), trader);
+        ix.accounts
+            .push(seat_extension_account_meta(market, trader, 0));
+        self.send(&[ix], &[payer])
+    }
+
     /// Crank the funding rate (update oracle cache + global cumulative funding).
     pub fn crank_funding(
         &self,
@@ -311,6 +462,37 @@ impl ManifestClient {
         self.send(&[ix], &[payer])
     }
 
+    /// Crank the funding rate on a market with extra oracle sources.
+    /// `oracle_feeds` is the primary oracle account followed by the extra
+    /// ones, as returned by `MarketState::oracle_sources`.
+    pub fn crank_funding_with_oracles(
+        &self,
+        payer: &Keypair,
+        market: &Pubkey,
+        oracle_feeds: &[Pubkey],
+    ) -> Result<String> {
+        let ix = crank_funding_with_oracles_instruction(market, &payer.pubkey(), oracle_feeds);
+        self.send(&[ix], &[payer])
+    }
+
+    /// Crank the funding rate and record it in the market's funding
+    /// history. `oracle_feeds` is as for `crank_funding_with_oracles`.
+    pub fn crank_funding_with_history(
+        &self,
+        payer: &Keypair,
+        market: &Pubkey,
+        oracle_feeds: &[Pubkey],
+    ) -> Result<String> {
+        let ix = crank_funding_with_history_instruction(market, &payer.pubkey(), oracle_feeds);
+        self.send(&[ix], &[payer])
+    }
+
+    /// Create a market's funding history account. Anyone can pay for it.
+    pub fn create_funding_history(&self, payer: &Keypair, market: &Pubkey) -> Result<String> {
+        let ix = create_funding_history_instruction(market, &payer.pubkey());
+        self.send(&[ix], &[payer])
+    }
+
     // ── Ephemeral ER operations ─────────────────────────────────────────
 
     /// Delegate a market account to the MagicBlock ER.
@@ -377,12 +559,8 @@ impl ManifestClient {
     /// Sign and send a transaction. Returns the signature string.
     pub fn send(&self, ixs: &[Instruction], signers: &[&Keypair]) -> Result<String> {
         let blockhash = self.rpc.get_latest_blockhash()?;
-        let tx = Transaction::new_signed_with_payer(
-            ixs,
-            Some(&signers[0].pubkey()),
-            signers,
-            blockhash,
-        );
+        let tx =
+            Transaction::new_signed_with_payer(ixs, Some(&signers[0].pubkey()), signers, blockhash);
         let sig = self
             .rpc
             .send_and_confirm_transaction_with_spinner_and_config(
@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+use manifest::state::{
+    FundingHistory, FundingHistoryEntry, FUNDING_HISTORY_SIZE, FUNDING_REFERENCE_BASE_ATOMS,
+    FUNDING_SCALE,
+};
+use manifest::validation::get_funding_history_address;
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+
+use crate::market::MarketState;
+
+/// Seconds in a 365 day year, used to annualize funding rates.
+pub const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+/// One recorded funding crank in human-readable units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingRecord {
+    /// Unix timestamp of the crank.
+    pub timestamp: i64,
+    /// Seconds of funding the crank charged.
+    pub elapsed_secs: u64,
+    /// Funding rate charged over `elapsed_secs`, as a fraction of position
+    /// value (0.01 = 1%). Positive means longs paid shorts.
+    pub funding_rate: f64,
+    /// `funding_rate` extended to a year.
+    pub annualized_rate: f64,
+    /// Oracle price in USD per base unit.
+    pub oracle_price: f64,
+    /// Mark price in USD per base unit.
+    pub mark_price: f64,
+}
+
+/// Parsed funding history account of a market.
+pub struct FundingHistoryState {
+    pub key: Pubkey,
+    pub market: Pubkey,
+    /// Recorded cranks, oldest first.
+    pub entries: Vec<FundingHistoryEntry>,
+}
+
+impl FundingHistoryState {
+    /// Fetch and parse the funding history account of a market.
+    pub fn fetch(client: &RpcClient, market_key: &Pubkey) -> Result<Self> {
+        let (key, _) = get_funding_history_address(market_key);
+        let account = client.get_account(&key)?;
+        Self::from_account_data(key, &account.data)
+    }
+
+    /// Parse from raw account data (no RPC needed).
+    pub fn from_account_data(key: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < FUNDING_HISTORY_SIZE {
+            return Err(anyhow!(
+                "Account data too small for FundingHistory ({} < {})",
+                data.len(),
+                FUNDING_HISTORY_SIZE
+            ));
+        }
+        let history: FundingHistory = bytemuck::pod_read_unaligned(&data[..FUNDING_HISTORY_SIZE]);
+        Ok(Self {
+            key,
+            market: *history.get_market(),
+            entries: history.get_entries(),
+        })
+    }
+
+    /// Recorded cranks with rates annualized and prices in USD, oldest
+    /// first. `market` supplies the token decimals.
+    pub fn annualized_history(&self, market: &MarketState) -> Vec<FundingRecord> {
+        // Prices are stored in quote atoms per FUNDING_REFERENCE_BASE_ATOMS.
+        let price_scale = 10f64.powi(market.base_decimals() as i32)
+            / (10f64.powi(market.quote_decimals() as i32) * FUNDING_REFERENCE_BASE_ATOMS as f64);
+        self.entries
+            .iter()
+            .map(|entry| {
+                let funding_rate = entry.funding_rate as f64 / FUNDING_SCALE as f64;
+                let annualized_rate = if entry.elapsed_secs == 0 {
+                    0.0
+                } else {
+                    funding_rate * SECONDS_PER_YEAR / entry.elapsed_secs as f64
+                };
+                FundingRecord {
+                    timestamp: entry.timestamp,
+                    elapsed_secs: entry.elapsed_secs,
+                    funding_rate,
+                    annualized_rate,
+                    oracle_price: entry.oracle_price as f64 * price_scale,
+                    mark_price: entry.mark_price as f64 * price_scale,
+                }
+            })
+            .collect()
+    }
+}
@@ -23,26 +23,32 @@ pub mod client;
 pub mod config;
 pub mod ephemeral;
 pub mod error;
+pub mod funding_history;
 pub mod market;
 pub mod oracle;
 pub mod position;
+pub mod seat_extension;
 
 // ── Re-exports from manifest-dex ────────────────────────────────────────────
 
 /// Instruction builders for constructing on-chain transactions.
 pub mod instructions {
     pub use manifest::program::{
-        batch_update_instruction,
         batch_update::{CancelOrderParams, PlaceOrderParams},
+        batch_update_instruction,
         claim_seat_instruction::claim_seat_instruction,
+        crank_funding_instruction, crank_funding_with_history_instruction,
+        crank_funding_with_oracles_instruction, create_funding_history_instruction,
         create_market_instructions,
-        crank_funding_instruction,
-        deposit_instruction, deposit_instruction_with_vault,
-        expand_market_instruction, expand_market_n_instruction,
-        liquidate_instruction,
+        create_seat_extension_instruction::create_seat_extension_instruction,
+        deposit_instruction, deposit_instruction_with_vault, expand_market_instruction,
+        expand_market_n_instruction, insurance_deposit_instruction, insurance_withdraw_instruction,
+        liquidate::LiquidationMode,
+        liquidate_batch_instruction, liquidate_instruction, liquidate_instruction_with_mode,
         release_seat_instruction,
         swap_instruction::{swap_instruction, swap_instruction_with_vaults},
-        withdraw_instruction, withdraw_instruction_with_vault,
+        update_market::UpdateMarketParams,
+        update_market_instruction, withdraw_instruction, withdraw_instruction_with_vault,
         ManifestInstruction,
     };
 }
@@ -50,7 +56,9 @@ pub mod instructions {
 /// On-chain state types.
 pub mod state {
     pub use manifest::state::{
-        MarketFixed, MarketValue, OrderType, RestingOrder, MARKET_FIXED_SIZE, MARKET_BLOCK_SIZE,
+        FundingHistory, FundingHistoryEntry, InsuranceStaker, MarketFixed, MarketValue,
+        OracleSource, OracleType, OrderType, RestingOrder, RiskTier, SeatExtension,
+        MARKET_BLOCK_SIZE, MARKET_FIXED_SIZE, MAX_ORACLE_SOURCES, MAX_RISK_TIERS,
     };
 }
 
@@ -61,7 +69,10 @@ pub mod quantities {
 
 /// PDA derivation helpers.
 pub mod validation {
-    pub use manifest::validation::{get_market_address, get_vault_address};
+    pub use manifest::validation::{
+        get_funding_history_address, get_market_address, get_seat_extension_address,
+        get_vault_address,
+    };
 }
 
 /// The Manifest program ID.
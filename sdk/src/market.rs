@@ -1,8 +1,11 @@
 use anyhow::{anyhow, Result};
 use hypertree::HyperTreeValueIteratorTrait;
-use manifest::quantities::WrapperU64;
+use manifest::quantities::{BaseAtoms, WrapperU64};
 use manifest::state::market::MarketFixed;
-use manifest::state::{MarketValue, RestingOrder, MARKET_FIXED_SIZE};
+use manifest::state::{
+    MarketValue, OracleSource, OracleType, RestingOrder, RiskTier, FUNDING_SCALE,
+    MARKET_FIXED_SIZE, PREMIUM_RATE_SCALE,
+};
 use solana_client::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
 
@@ -50,12 +53,126 @@ impl MarketState {
         mantissa as f64 * 10f64.powi(expo)
     }
 
+    /// Oracle confidence interval as a human-readable f64 (USD).
+    pub fn oracle_confidence(&self) -> f64 {
+        let conf = self.fixed.get_oracle_conf();
+        let expo = self.fixed.get_oracle_price_expo();
+        conf as f64 * 10f64.powi(expo)
+    }
+
+    /// Largest oracle confidence interval the funding crank accepts, in basis
+    /// points of the oracle price.
+    pub fn max_oracle_conf_bps(&self) -> u64 {
+        self.fixed.get_max_oracle_conf_bps()
+    }
+
+    /// Unix timestamp the cached oracle price was published at.
+    pub fn oracle_publish_time(&self) -> i64 {
+        self.fixed.get_oracle_publish_time()
+    }
+
+    /// Oldest oracle price, in seconds since publication, the market accepts.
+    pub fn max_oracle_staleness_secs(&self) -> u64 {
+        self.fixed.get_max_oracle_staleness_secs()
+    }
+
+    /// Whether the cached oracle price is too old at `now` (unix seconds) for
+    /// margin checks and liquidations. Those fail until funding is cranked
+    /// with a fresh price.
+    pub fn is_oracle_stale(&self, now: i64) -> bool {
+        now.saturating_sub(self.oracle_publish_time()) > self.max_oracle_staleness_secs() as i64
+    }
+
+    /// Format of the market's oracle account.
+    pub fn oracle_type(&self) -> OracleType {
+        self.fixed.get_oracle_type()
+    }
+
+    /// Active oracle sources, primary first. These are the oracle accounts
+    /// `CrankFunding` expects, in order.
+    pub fn oracle_sources(&self) -> Vec<OracleSource> {
+        self.fixed
+            .get_oracle_sources()
+            .into_iter()
+            .filter(OracleSource::is_active)
+            .collect()
+    }
+
+    /// Moving average premium of the orderbook mid over the oracle price, as
+    /// a fraction (0.01 = 1%).
+    pub fn ema_premium(&self) -> f64 {
+        self.fixed.get_ema_premium_rate() as f64 / PREMIUM_RATE_SCALE as f64
+    }
+
+    /// Time constant of the premium average, in seconds.
+    pub fn mark_ema_window_secs(&self) -> u64 {
+        self.fixed.get_mark_ema_window_secs()
+    }
+
+    /// Mid of the best bid and ask in USD, or `None` unless both sides of
+    /// the book have orders.
+    pub fn orderbook_mid_price(&self) -> Option<f64> {
+        let best_bid = self
+            .get_resting_bids()
+            .first()
+            .map(|order| self.order_price(order))?;
+        let best_ask = self
+            .get_resting_asks()
+            .first()
+            .map(|order| self.order_price(order))?;
+        Some((best_bid + best_ask) / 2.0)
+    }
+
+    /// Mark price margin checks and liquidations value positions at: the
+    /// median of the oracle price, the oracle price moved by the average
+    /// premium, and the orderbook mid (the oracle price without a two-sided
+    /// book). Before the oracle price is set this is the orderbook mid.
+    pub fn mark_price(&self) -> f64 {
+        let oracle_price = self.oracle_price();
+        if oracle_price == 0.0 {
+            return self.orderbook_mid_price().unwrap_or(0.0);
+        }
+        let mut candidates = [
+            oracle_price,
+            oracle_price * (1.0 + self.ema_premium()),
+            self.orderbook_mid_price().unwrap_or(oracle_price),
+        ];
+        candidates.sort_by(f64::total_cmp);
+        candidates[1]
+    }
+
+    /// Price of a resting order in USD per whole base unit.
+    fn order_price(&self, order: &RestingOrder) -> f64 {
+        let one_base_unit = BaseAtoms::new(10u64.pow(self.base_decimals()));
+        match order
+            .get_price()
+            .checked_quote_for_base(one_base_unit, false)
+        {
+            Ok(quote) => quote.as_u64() as f64 / 10f64.powi(self.quote_decimals() as i32),
+            Err(_) => 0.0,
+        }
+    }
+
+    /// Pyth feed id that PriceUpdateV3 oracle accounts must carry, or `None`
+    /// if the market only accepts the V2 account it was created with.
+    pub fn pyth_feed_id(&self) -> Option<[u8; 32]> {
+        let feed_id = *self.fixed.get_pyth_feed_id();
+        (feed_id != [0; 32]).then_some(feed_id)
+    }
+
     /// Trader position: `(position_size_atoms, cost_basis_atoms)`.
     /// `position_size` is signed (positive = long, negative = short).
     pub fn get_trader_position(&self, trader: &Pubkey) -> (i64, u64) {
         self.market.get_trader_position(trader)
     }
 
+    /// Trader realized PnL in quote atoms that has not been settled into
+    /// margin yet. Losses settle on the trader's next interaction, profits
+    /// once the PnL pool holds enough to pay them.
+    pub fn get_trader_unsettled_pnl(&self, trader: &Pubkey) -> i64 {
+        self.market.get_trader_unsettled_pnl(trader)
+    }
+
     /// Trader quote balance (margin) in quote atoms.
     pub fn get_trader_balance(&self, trader: &Pubkey) -> u64 {
         let (_, quote) = self.market.get_trader_balance(trader);
@@ -99,16 +216,97 @@ impl MarketState {
         self.fixed.get_liquidation_buffer_bps()
     }
 
+    /// Active risk tiers, in increasing notional order.
+    pub fn risk_tiers(&self) -> Vec<RiskTier> {
+        self.fixed
+            .get_risk_tiers()
+            .iter()
+            .take_while(|tier| tier.is_active())
+            .copied()
+            .collect()
+    }
+
+    /// `(initial_margin_bps, maintenance_margin_bps)` for a position of
+    /// `notional_atoms` quote atoms, after applying the risk tiers.
+    pub fn margin_bps_for_notional(&self, notional_atoms: u64) -> (u64, u64) {
+        self.fixed.get_margin_bps_for_notional(notional_atoms)
+    }
+
+    /// Liquidator reward (and takeover discount) in basis points.
+    pub fn liquidator_reward_bps(&self) -> u64 {
+        self.fixed.get_liquidator_reward_bps()
+    }
+
+    /// Smallest position in base atoms left after a partial liquidation.
+    pub fn min_position_size_atoms(&self) -> u64 {
+        self.fixed.get_min_position_size_atoms()
+    }
+
+    /// Signer allowed to update the market parameters.
+    pub fn market_authority(&self) -> Pubkey {
+        *self.fixed.get_market_authority()
+    }
+
     /// Insurance fund balance in quote atoms.
     pub fn insurance_fund_balance(&self) -> u64 {
         self.fixed.get_insurance_fund_balance()
     }
 
+    /// Settled losses not yet paid out as profit, in quote atoms.
+    pub fn pnl_pool_balance(&self) -> u64 {
+        self.fixed.get_pnl_pool_balance()
+    }
+
+    /// Total insurance fund shares outstanding.
+    pub fn insurance_total_shares(&self) -> u64 {
+        self.fixed.get_insurance_total_shares()
+    }
+
+    /// Quote atoms that `shares` of the insurance fund currently redeem for.
+    pub fn insurance_share_value(&self, shares: u64) -> u64 {
+        let total_shares = self.insurance_total_shares();
+        if total_shares == 0 {
+            return 0;
+        }
+        (shares as u128 * self.insurance_fund_balance() as u128 / total_shares as u128) as u64
+    }
+
     /// Global cumulative funding (scaled by 1e9).
     pub fn cumulative_funding(&self) -> i64 {
         self.fixed.get_cumulative_funding()
     }
 
+    /// Length of a funding period, in seconds.
+    pub fn funding_period_secs(&self) -> u64 {
+        self.fixed.get_funding_period_secs()
+    }
+
+    /// Largest funding rate per period, as a fraction (0.01 = 1%).
+    pub fn max_funding_rate(&self) -> f64 {
+        self.fixed.get_max_funding_rate() as f64 / FUNDING_SCALE as f64
+    }
+
+    /// Fixed interest rate per period added to the premium, as a fraction.
+    pub fn funding_interest_rate(&self) -> f64 {
+        self.fixed.get_funding_interest_rate() as f64 / FUNDING_SCALE as f64
+    }
+
+    /// Funding rate the next crank would charge for a full period if the
+    /// book stays where it has been since the last crank: the time-weighted
+    /// book premium plus the interest rate, clamped to the max rate. As a
+    /// fraction of position value per period; positive means longs pay.
+    pub fn estimated_funding_rate(&self) -> f64 {
+        let premium_twap = self.fixed.get_premium_twap() as f64 / PREMIUM_RATE_SCALE as f64;
+        let max_funding_rate = self.max_funding_rate();
+        (premium_twap + self.funding_interest_rate()).clamp(-max_funding_rate, max_funding_rate)
+    }
+
+    /// Cumulative uncovered bad debt socialized per base atom of open
+    /// interest (scaled by 1e9).
+    pub fn cumulative_loss_per_unit(&self) -> u64 {
+        self.fixed.get_cumulative_loss_per_unit()
+    }
+
     /// Get all resting bid orders (sorted highest price first).
     pub fn get_resting_bids(&self) -> Vec<RestingOrder> {
         self.market
@@ -92,6 +92,75 @@ pub fn parse_price_v3(data: &[u8]) -> Result<f64> {
     Ok(price as f64 / 10f64.powi(expo))
 }
 
+/// Read the feed id from a Pyth `PriceUpdateV3` account, e.g. to configure it
+/// on a market with `UpdateMarketParams::pyth_feed_id`.
+pub fn parse_price_v3_feed_id(data: &[u8]) -> Result<[u8; 32]> {
+    if data.len() < 93 {
+        return Err(anyhow!(
+            "PriceUpdateV3 account too small ({} bytes)",
+            data.len()
+        ));
+    }
+    let msg_start: usize = match data[40] {
+        0x01 => 41,
+        0x00 => 42,
+        b => return Err(anyhow!("Unknown VerificationLevel byte: {:#04x}", b)),
+    };
+    Ok(data[msg_start..msg_start + 32].try_into().unwrap())
+}
+
+/// Parse a Switchboard On-Demand pull feed (`PullFeedAccountData`).
+///
+/// Returns `(price_usd, std_dev_usd, last_update_timestamp)` from the feed's
+/// current result.
+pub fn parse_switchboard_pull_feed(data: &[u8]) -> Result<(f64, f64, i64)> {
+    const DISCRIMINATOR: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+    const LAST_UPDATE_OFF: usize = 2216;
+    const VALUE_OFF: usize = 2264;
+    const STD_DEV_OFF: usize = 2280;
+    const NUM_SAMPLES_OFF: usize = 2360;
+    const MIN_LEN: usize = 2392;
+
+    if data.len() < MIN_LEN {
+        return Err(anyhow!(
+            "Switchboard pull feed too small ({} bytes)",
+            data.len()
+        ));
+    }
+    if data[0..8] != DISCRIMINATOR {
+        return Err(anyhow!("Account is not a Switchboard pull feed"));
+    }
+    if data[NUM_SAMPLES_OFF] == 0 {
+        return Err(anyhow!("Switchboard pull feed has no samples"));
+    }
+    let value = i128::from_le_bytes(data[VALUE_OFF..VALUE_OFF + 16].try_into().unwrap());
+    let std_dev = i128::from_le_bytes(data[STD_DEV_OFF..STD_DEV_OFF + 16].try_into().unwrap());
+    let last_update = i64::from_le_bytes(
+        data[LAST_UPDATE_OFF..LAST_UPDATE_OFF + 8]
+            .try_into()
+            .unwrap(),
+    );
+    if value <= 0 {
+        return Err(anyhow!("Switchboard price non-positive: {value}"));
+    }
+    Ok((value as f64 / 1e18, std_dev as f64 / 1e18, last_update))
+}
+
+/// Fetch a price from a Switchboard On-Demand pull feed.
+///
+/// Returns `(mantissa, exponent, price_usd)`.
+pub fn fetch_switchboard_price(
+    client: &RpcClient,
+    feed: &Pubkey,
+    quote_decimals: u8,
+    base_decimals: u8,
+) -> Result<(u32, i8, f64)> {
+    let data = client.get_account_data(feed)?;
+    let (price_usd, _, _) = parse_switchboard_pull_feed(&data)?;
+    let (m, e) = usd_to_order_price(price_usd, quote_decimals, base_decimals);
+    Ok((m, e, price_usd))
+}
+
 /// Fetch a price from the ER oracle (PriceUpdateV3 format).
 ///
 /// Returns `(mantissa, exponent, price_usd)`.
@@ -107,7 +176,8 @@ pub fn fetch_er_price(
     Ok((m, e, price_usd))
 }
 
-/// Fetch price trying V2 first, falling back to V3.
+/// Fetch price trying Pyth V2 first, then a Switchboard pull feed, falling
+/// back to V3.
 ///
 /// Returns `(mantissa, exponent, price_usd)`.
 pub fn fetch_price(
@@ -116,9 +186,9 @@ pub fn fetch_price(
     quote_decimals: u8,
     base_decimals: u8,
 ) -> Result<(u32, i8, f64)> {
-    fetch_pyth_v2_price(client, feed, quote_decimals, base_decimals).or_else(|_| {
-        fetch_er_price(client, feed, quote_decimals, base_decimals)
-    })
+    fetch_pyth_v2_price(client, feed, quote_decimals, base_decimals)
+        .or_else(|_| fetch_switchboard_price(client, feed, quote_decimals, base_decimals))
+        .or_else(|_| fetch_er_price(client, feed, quote_decimals, base_decimals))
 }
 
 /// Convert a human USD price to order `(mantissa, exponent)`.
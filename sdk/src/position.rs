@@ -1,3 +1,4 @@
+use manifest::state::RiskTier;
 use solana_program::pubkey::Pubkey;
 
 use crate::market::MarketState;
@@ -57,14 +58,15 @@ pub struct PositionInfo {
 impl PositionInfo {
     /// Compute full position analytics from a parsed market state and trader pubkey.
     pub fn compute(market: &MarketState, trader: &Pubkey) -> Self {
-        let oracle_price = market.oracle_price();
+        // Positions are margined at the mark price, not the raw oracle price.
+        let mark_price = market.mark_price();
         let (position_atoms, cost_basis_atoms) = market.get_trader_position(trader);
         let margin_atoms = market.get_trader_balance(trader);
         let cumulative_funding = market.cumulative_funding();
         let last_cumulative_funding = market.get_trader_last_cumulative_funding(trader);
 
         Self::from_raw(
-            oracle_price,
+            mark_price,
             position_atoms,
             cost_basis_atoms,
             margin_atoms,
@@ -74,6 +76,7 @@ impl PositionInfo {
             market.quote_decimals(),
             market.initial_margin_bps(),
             market.maintenance_margin_bps(),
+            &market.risk_tiers(),
         )
     }
 
@@ -90,6 +93,7 @@ impl PositionInfo {
         quote_decimals: u32,
         initial_margin_bps: u64,
         maintenance_margin_bps: u64,
+        risk_tiers: &[RiskTier],
     ) -> Self {
         let base_factor = 10f64.powi(base_decimals as i32);
         let quote_factor = 10f64.powi(quote_decimals as i32);
@@ -131,12 +135,38 @@ impl PositionInfo {
             0.0
         };
 
-        // Liquidation price
-        let maint_ratio = maintenance_margin_bps as f64 / 10_000.0;
+        let ranges = margin_ranges(
+            initial_margin_bps,
+            maintenance_margin_bps,
+            risk_tiers,
+            quote_factor,
+        );
+
+        // Liquidation price. Solve in each tier's notional range with that
+        // tier's maintenance ratio. A short only gets less healthy as the
+        // price rises, so take the lowest solution. A long can cross into a
+        // higher tier as the price rises, so only ranges starting at or below
+        // the current price count, and the highest solution is taken.
         let liquidation_price = if is_long {
-            (cost_basis - margin) / (abs_pos * (1.0 - maint_ratio))
+            let numerator = cost_basis - margin;
+            ranges
+                .iter()
+                .filter(|r| r.lo / abs_pos <= oracle_price)
+                .filter_map(|r| {
+                    let p = numerator / (abs_pos * (1.0 - r.maint_ratio));
+                    (p >= r.lo / abs_pos).then(|| p.min(r.hi / abs_pos))
+                })
+                .reduce(f64::max)
+                .unwrap_or(numerator / (abs_pos * (1.0 - ranges[0].maint_ratio)))
         } else if is_short {
-            (margin + cost_basis) / (abs_pos * (1.0 + maint_ratio))
+            let numerator = margin + cost_basis;
+            ranges
+                .iter()
+                .filter_map(|r| {
+                    let p = (numerator / (abs_pos * (1.0 + r.maint_ratio))).max(r.lo / abs_pos);
+                    (p < r.hi / abs_pos).then_some(p)
+                })
+                .fold(f64::INFINITY, f64::min)
         } else {
             0.0
         };
@@ -146,9 +176,16 @@ impl PositionInfo {
             0.0
         };
 
-        // Max position at current equity
-        let max_leverage = 10_000.0 / initial_margin_bps as f64;
-        let max_notional = equity * max_leverage;
+        // Max position at current equity. Each tier's initial margin only
+        // applies within its own notional range.
+        let max_notional = ranges
+            .iter()
+            .filter_map(|r| {
+                let notional = equity / r.init_ratio;
+                (notional >= r.lo).then(|| notional.min(r.hi))
+            })
+            .reduce(f64::max)
+            .unwrap_or(equity / ranges[0].init_ratio);
         let max_position_base = if oracle_price > 0.0 {
             max_notional / oracle_price
         } else {
@@ -183,3 +220,40 @@ impl PositionInfo {
         }
     }
 }
+
+/// A notional range in quote units and the margin ratios that apply in it.
+struct MarginRange {
+    lo: f64,
+    hi: f64,
+    init_ratio: f64,
+    maint_ratio: f64,
+}
+
+/// Split notional into the base margin range and one range per active risk
+/// tier, mirroring `MarketFixed::get_margin_bps_for_notional`.
+fn margin_ranges(
+    initial_margin_bps: u64,
+    maintenance_margin_bps: u64,
+    risk_tiers: &[RiskTier],
+    quote_factor: f64,
+) -> Vec<MarginRange> {
+    let mut ranges = vec![MarginRange {
+        lo: 0.0,
+        hi: f64::INFINITY,
+        init_ratio: initial_margin_bps as f64 / 10_000.0,
+        maint_ratio: maintenance_margin_bps as f64 / 10_000.0,
+    }];
+    for tier in risk_tiers.iter().take_while(|tier| tier.is_active()) {
+        let lo = tier.notional_threshold_atoms as f64 / quote_factor;
+        if let Some(previous) = ranges.last_mut() {
+            previous.hi = lo;
+        }
+        ranges.push(MarginRange {
+            lo,
+            hi: f64::INFINITY,
+            init_ratio: tier.initial_margin_bps as f64 / 10_000.0,
+            maint_ratio: tier.maintenance_margin_bps as f64 / 10_000.0,
+        });
+    }
+    ranges
+}
@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use manifest::state::{SeatExtension, SEAT_EXTENSION_SIZE};
+use manifest::validation::get_seat_extension_address;
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+
+/// Lifetime accounting of a seat, in quote atoms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeatStats {
+    /// PnL realized by reducing or closing the position.
+    pub realized_pnl: i64,
+    /// Funding settled against the seat. Negative when the trader received
+    /// more funding than it paid.
+    pub funding_paid: i64,
+    /// Trading fees paid.
+    pub fees_paid: u64,
+}
+
+/// Parsed seat extension account of a trader.
+pub struct SeatExtensionState {
+    pub key: Pubkey,
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    pub sub_account_id: u8,
+    pub stats: SeatStats,
+}
+
+impl SeatExtensionState {
+    /// Fetch and parse the seat extension of a trader, or `None` if it was
+    /// never created.
+    pub fn fetch(
+        client: &RpcClient,
+        market_key: &Pubkey,
+        trader: &Pubkey,
+        sub_account_id: u8,
+    ) -> Result<Option<Self>> {
+        let (key, _) = get_seat_extension_address(market_key, trader, sub_account_id);
+        let accounts = client.get_multiple_accounts(&[key])?;
+        match accounts.into_iter().next().flatten() {
+            Some(account) if !account.data.is_empty() => {
+                Self::from_account_data(key, &account.data).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse from raw account data (no RPC needed).
+    pub fn from_account_data(key: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < SEAT_EXTENSION_SIZE {
+            return Err(anyhow!(
+                "Account data too small for SeatExtension ({} < {})",
+                data.len(),
+                SEAT_EXTENSION_SIZE
+            ));
+        }
+        let extension: SeatExtension = bytemuck::pod_read_unaligned(&data[..SEAT_EXTENSION_SIZE]);
+        Ok(Self {
+            key,
+            market: *extension.get_market(),
+            trader: *extension.get_trader(),
+            sub_account_id: extension.get_sub_account_id(),
+            stats: SeatStats {
+                realized_pnl: extension.get_realized_pnl(),
+                funding_paid: extension.get_cumulative_funding_paid(),
+                fees_paid: extension.get_fees_paid(),
+            },
+        })
+    }
+}